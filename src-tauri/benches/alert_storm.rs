@@ -0,0 +1,80 @@
+//! Ráfagas de alarmas: parseo -> actualización del store -> side effects -> eventos hacia la UI.
+//!
+//! `cargo bench --features e2e --bench alert_storm`; conviene correrlo también en la placa destino.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use nxt_hmi_lib::e2e::{alarm_burst, pipeline};
+
+const BURST: usize = 1_000;
+const TOPIC: &str = "v1/devices/me/rpc/request/1";
+
+fn alert_storm(c: &mut Criterion) {
+    let pipeline = pipeline();
+    let active = alarm_burst("storm", BURST, "ACTIVE_UNACK");
+    let cleared = alarm_burst("storm", BURST, "CLEARED_UNACK");
+
+    let mut group = c.benchmark_group("alert_storm");
+    group.throughput(Throughput::Elements(BURST as u64));
+    group.sample_size(10);
+
+    group.bench_function("parse", |b| {
+        b.iter(|| {
+            active
+                .iter()
+                .filter(|payload| pipeline.parse(payload))
+                .count()
+        })
+    });
+
+    group.bench_function("new_alarms", |b| {
+        b.iter_batched(
+            || pipeline.reset(),
+            |()| {
+                for payload in &active {
+                    pipeline.ingest(TOPIC, payload);
+                }
+                pipeline.event_count()
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    group.bench_function("updates", |b| {
+        b.iter_batched(
+            || {
+                pipeline.reset();
+                for payload in &active {
+                    pipeline.ingest(TOPIC, payload);
+                }
+            },
+            |()| {
+                for payload in &active {
+                    pipeline.ingest(TOPIC, payload);
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    group.bench_function("clear", |b| {
+        b.iter_batched(
+            || {
+                pipeline.reset();
+                for payload in &active {
+                    pipeline.ingest(TOPIC, payload);
+                }
+            },
+            |()| {
+                for payload in &cleared {
+                    pipeline.ingest(TOPIC, payload);
+                }
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, alert_storm);
+criterion_main!(benches);
@@ -1,3 +1,61 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FRONTEND_DIST: &str = "../dist";
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Hash del bundle del frontend embebido, para reportarlo como atributo del dispositivo.
+fn frontend_bundle_hash() -> String {
+    let root = Path::new(FRONTEND_DIST);
+    let mut files = Vec::new();
+    collect_files(root, &mut files);
+    if files.is_empty() {
+        return "unknown".to_string();
+    }
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update(fs::read(&file).unwrap_or_default());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Genera el código del contrato gRPC con el `protoc` vendorizado, sin depender del sistema.
+#[cfg(feature = "grpc")]
+fn compile_grpc_contract() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc vendorizado no disponible");
+    std::env::set_var("PROTOC", protoc);
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/nxt_hmi.proto"], &["proto"])
+        .expect("No se pudo compilar proto/nxt_hmi.proto");
+}
+
 fn main() {
+    println!("cargo:rerun-if-changed={}", FRONTEND_DIST);
+    #[cfg(feature = "grpc")]
+    compile_grpc_contract();
+    println!("cargo:rustc-env=FRONTEND_BUNDLE_HASH={}", frontend_bundle_hash());
     tauri_build::build()
 }
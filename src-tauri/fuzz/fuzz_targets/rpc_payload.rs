@@ -0,0 +1,8 @@
+//! `cargo +nightly fuzz run rpc_payload` desde `src-tauri/`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = nxt_hmi_lib::fuzzing::rpc_payload(data);
+});
@@ -0,0 +1,143 @@
+//! Lectores de credenciales USB HID (código de barras o RFID) que se presentan como teclado.
+//!
+//! Las teclas llegan por evdev (`/dev/input/by-id/...-event-kbd`) o reenviadas por el frontend
+//! (modo cuña de teclado). Un lector "teclea" el código mucho más rápido que una persona y lo
+//! termina con Enter: `ScanBuffer` sólo acepta ráfagas así y descarta lo que escribe el operador.
+
+use std::time::{Duration, Instant};
+
+pub const EV_KEY: u16 = 1;
+const KEY_ENTER: u16 = 28;
+const KEY_KPENTER: u16 = 96;
+const KEY_LEFTSHIFT: u16 = 42;
+const KEY_RIGHTSHIFT: u16 = 54;
+
+/// `struct input_event`: `timeval` de dos `__kernel_ulong_t` (ancho de palabra), tipo, código y valor.
+pub const INPUT_EVENT_SIZE: usize = 2 * std::mem::size_of::<usize>() + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub kind: u16,
+    pub code: u16,
+    /// 1 = presionada, 0 = soltada, 2 = repetición.
+    pub value: i32,
+}
+
+/// Decodifica un evento en el orden de bytes nativo; `None` si `bytes` es más corto.
+pub fn decode_event(bytes: &[u8]) -> Option<InputEvent> {
+    let offset = INPUT_EVENT_SIZE - 8;
+    let bytes = bytes.get(offset..INPUT_EVENT_SIZE)?;
+    Some(InputEvent {
+        kind: u16::from_ne_bytes([bytes[0], bytes[1]]),
+        code: u16::from_ne_bytes([bytes[2], bytes[3]]),
+        value: i32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanKey {
+    Char(char),
+    Enter,
+}
+
+impl ScanKey {
+    /// Nombre de tecla del DOM (`KeyboardEvent.key`): `Enter` o un único carácter.
+    pub fn from_dom(key: &str) -> Option<Self> {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            _ if key == "Enter" => Some(ScanKey::Enter),
+            (Some(c), None) if !c.is_control() => Some(ScanKey::Char(c)),
+            _ => None,
+        }
+    }
+}
+
+/// Traduce códigos de tecla evdev a caracteres con distribución US, la que emulan los lectores.
+#[derive(Debug, Default)]
+pub struct KeyDecoder {
+    shift: bool,
+}
+
+impl KeyDecoder {
+    pub fn feed(&mut self, event: &InputEvent) -> Option<ScanKey> {
+        if event.kind != EV_KEY {
+            return None;
+        }
+        if matches!(event.code, KEY_LEFTSHIFT | KEY_RIGHTSHIFT) {
+            self.shift = event.value != 0;
+            return None;
+        }
+        if event.value != 1 {
+            return None;
+        }
+        match event.code {
+            KEY_ENTER | KEY_KPENTER => Some(ScanKey::Enter),
+            code => keycode_char(code, self.shift).map(ScanKey::Char),
+        }
+    }
+}
+
+/// Dígitos, letras y los signos que usan los códigos de barras habituales.
+pub fn keycode_char(code: u16, shift: bool) -> Option<char> {
+    const DIGITS: &str = "1234567890";
+    const ROWS: [(u16, &str); 3] = [(16, "qwertyuiop"), (30, "asdfghjkl"), (44, "zxcvbnm")];
+    let c = match code {
+        2..=11 => DIGITS.chars().nth(usize::from(code - 2))?,
+        12 => '-',
+        13 => '=',
+        52 => '.',
+        53 => '/',
+        57 => ' ',
+        _ => ROWS.iter().find_map(|(first, row)| {
+            code.checked_sub(*first)
+                .and_then(|index| row.chars().nth(usize::from(index)))
+        })?,
+    };
+    Some(match (shift, c) {
+        (true, '-') => '_',
+        (true, c) => c.to_ascii_uppercase(),
+        (false, c) => c,
+    })
+}
+
+/// Junta las teclas de una lectura: se reinicia si pasa más de `max_gap` entre dos teclas.
+#[derive(Debug)]
+pub struct ScanBuffer {
+    max_gap: Duration,
+    min_len: usize,
+    code: String,
+    last: Option<Instant>,
+}
+
+impl ScanBuffer {
+    pub fn new(max_gap: Duration, min_len: usize) -> Self {
+        Self {
+            max_gap,
+            min_len: min_len.max(1),
+            code: String::new(),
+            last: None,
+        }
+    }
+
+    /// Devuelve el código al llegar Enter si la ráfaga fue continua y alcanza `min_len`.
+    pub fn push(&mut self, key: ScanKey, at: Instant) -> Option<String> {
+        if self
+            .last
+            .is_some_and(|last| at.saturating_duration_since(last) > self.max_gap)
+        {
+            self.code.clear();
+        }
+        self.last = Some(at);
+        match key {
+            ScanKey::Char(c) => {
+                self.code.push(c);
+                None
+            }
+            ScanKey::Enter => {
+                self.last = None;
+                let code = std::mem::take(&mut self.code);
+                (code.chars().count() >= self.min_len).then_some(code)
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Lectura de planillas CSV (RFC 4180) para la puesta en marcha.
+//!
+//! El separador se detecta en la cabecera: Excel con configuración regional en español exporta con
+//! `;` porque la coma es el separador decimal. Se aceptan campos entre comillas con separadores,
+//! comillas dobladas y saltos de línea dentro, y el BOM UTF-8 inicial.
+
+/// Tabla con cabecera; cada fila guarda la línea del archivo donde empieza, para los reportes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<CsvRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRow {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+impl CsvTable {
+    /// Cabeceras sin distinguir mayúsculas ni espacios alrededor.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+    }
+
+    /// Valor recortado de `name` en `row`; vacío si la columna no existe o la fila es más corta.
+    pub fn get<'a>(&self, row: &'a CsvRow, name: &str) -> &'a str {
+        self.column(name)
+            .and_then(|index| row.fields.get(index))
+            .map_or("", |value| value.trim())
+    }
+}
+
+/// Separa `text` en filas; las filas vacías se omiten. Error si queda una comilla sin cerrar.
+pub fn parse(text: &str) -> Result<CsvTable, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let delimiter = detect_delimiter(text);
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            '\n' if quoted => {
+                line += 1;
+                field.push(c);
+            }
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                push_record(&mut records, record_line, std::mem::take(&mut fields));
+                line += 1;
+                record_line = line;
+            }
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!(
+            "Comilla sin cerrar en la fila de la línea {}",
+            record_line
+        ));
+    }
+    fields.push(field);
+    push_record(&mut records, record_line, fields);
+
+    let mut rows = records.into_iter();
+    let headers = rows
+        .next()
+        .ok_or("El CSV está vacío")?
+        .fields
+        .into_iter()
+        .map(|header| header.trim().to_string())
+        .collect();
+    Ok(CsvTable {
+        headers,
+        rows: rows.collect(),
+    })
+}
+
+fn push_record(records: &mut Vec<CsvRow>, line: usize, fields: Vec<String>) {
+    if fields.iter().any(|field| !field.trim().is_empty()) {
+        records.push(CsvRow { line, fields });
+    }
+}
+
+/// `;` si la primera línea tiene más que comas; si no, `,`.
+fn detect_delimiter(text: &str) -> char {
+    let header = text.lines().next().unwrap_or_default();
+    if header.matches(';').count() > header.matches(',').count() {
+        ';'
+    } else {
+        ','
+    }
+}
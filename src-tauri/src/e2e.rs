@@ -0,0 +1,432 @@
+//! Harness de integración: broker MQTT embebido que alimenta el loop de conexión real.
+//!
+//! Sólo se compila con la feature `e2e` (`cargo test --features e2e`). La configuración es
+//! global al proceso, así que cada binario de test comparte un único [`Harness`] o [`Pipeline`].
+
+use crate::{
+    alerts_since as diff, app_config, command_schema as schema, handle_rpc_payload,
+    mute_alerts_internal, parse_rpc_payload, register_default_side_effects,
+    registered_commands as commands, snapshot_mute_state, start_mqtt_loop, with_alert_store, Alert,
+    AppConfig, EventSink, APP_CONFIG, APP_EVENTS, MQTT_CONNECTED,
+};
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+static HARNESS: OnceLock<Harness> = OnceLock::new();
+static PIPELINE: OnceLock<Pipeline> = OnceLock::new();
+
+/// Evento emitido por el backend hacia la UI, tal como lo habría recibido el frontend.
+#[derive(Debug, Clone)]
+pub struct RecordedEvent {
+    pub name: String,
+    pub payload: serde_json::Value,
+}
+
+pub(crate) type EventLog = Arc<Mutex<Vec<RecordedEvent>>>;
+
+/// Topic y payload de un mensaje publicado por el panel.
+pub type Message = (String, Vec<u8>);
+
+pub(crate) fn record_event<S: Serialize>(log: &EventLog, name: &str, payload: &S) {
+    let payload = serde_json::to_value(payload).unwrap_or(serde_json::Value::Null);
+    lock(log).push(RecordedEvent {
+        name: name.to_string(),
+        payload,
+    });
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Backend real (loop MQTT y side effects) conectado a un broker en memoria.
+pub struct Harness {
+    pub broker: Broker,
+    events: EventLog,
+}
+
+/// Arranca el harness la primera vez; las llamadas siguientes devuelven el mismo.
+pub fn harness() -> &'static Harness {
+    HARNESS.get_or_init(Harness::start)
+}
+
+impl Harness {
+    fn start() -> Harness {
+        let broker = Broker::start();
+        install_config(broker.port());
+        let events = EventLog::default();
+        register_default_side_effects();
+        start_mqtt_loop(EventSink::Recorder(events.clone()));
+        let harness = Harness { broker, events };
+        assert!(
+            harness.wait_until(Duration::from_secs(10), || {
+                MQTT_CONNECTED.load(Ordering::SeqCst) && harness.broker.has_subscriber()
+            }),
+            "El loop MQTT no se conectó al broker embebido en {}:{}",
+            app_config().mqtt_server,
+            app_config().mqtt_port
+        );
+        harness
+    }
+
+    /// Entrega `payload` al panel como si viniera de la plataforma.
+    pub fn publish(&self, topic: &str, payload: &[u8]) {
+        self.broker.publish(topic, payload);
+    }
+
+    pub fn events(&self) -> Vec<RecordedEvent> {
+        lock(&self.events).clone()
+    }
+
+    /// Espera el primer evento `name` que cumpla `matches`, incluidos los ya registrados.
+    pub fn wait_for_event<F>(
+        &self,
+        name: &str,
+        timeout: Duration,
+        matches: F,
+    ) -> Option<RecordedEvent>
+    where
+        F: Fn(&serde_json::Value) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let found = lock(&self.events)
+                .iter()
+                .find(|event| event.name == name && matches(&event.payload))
+                .cloned();
+            if found.is_some() || Instant::now() >= deadline {
+                return found;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Silencia el panel como el botón de la UI.
+    pub fn mute(&self) {
+        mute_alerts_internal(&EventSink::Recorder(self.events.clone()));
+    }
+
+    pub fn muted(&self) -> bool {
+        snapshot_mute_state().muted
+    }
+
+    pub fn active_alerts(&self) -> Vec<Alert> {
+        with_alert_store(|store| store.values().cloned().collect())
+    }
+
+    pub fn wait_until<F: Fn() -> bool>(&self, timeout: Duration, condition: F) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !condition() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        true
+    }
+}
+
+/// Configuración de prueba (sin TLS ni buzzer, datos en un directorio temporal) fijada una sola vez.
+fn install_config(mqtt_port: u16) {
+    let data_dir = std::env::temp_dir().join(format!("nxt-hmi-e2e-{}", std::process::id()));
+    let cfg = AppConfig {
+        mqtt_server: "127.0.0.1".to_string(),
+        mqtt_port,
+        mqtt_use_secure_client: false,
+        mqtt_client_id: "nxt-hmi-e2e".to_string(),
+        buzzer_enabled: false,
+        data_dir: data_dir.to_string_lossy().into_owned(),
+        ..AppConfig::default()
+    };
+    if APP_CONFIG.set(cfg).is_err() {
+        panic!("La configuración ya estaba cargada antes de iniciar el harness");
+    }
+}
+
+/// Camino parse -> store -> side effects -> eventos sin broker ni loop MQTT, para benchmarks.
+pub struct Pipeline {
+    sink: EventSink,
+    events: EventLog,
+}
+
+pub fn pipeline() -> &'static Pipeline {
+    PIPELINE.get_or_init(|| {
+        install_config(0);
+        register_default_side_effects();
+        let events = EventLog::default();
+        Pipeline {
+            sink: EventSink::Recorder(events.clone()),
+            events,
+        }
+    })
+}
+
+impl Pipeline {
+    /// Procesa el payload exactamente como si hubiera llegado por `topic`.
+    pub fn ingest(&self, topic: &str, payload: &[u8]) {
+        handle_rpc_payload(topic, payload, &self.sink);
+    }
+
+    /// Sólo el parseo, sin tocar el store.
+    pub fn parse(&self, payload: &[u8]) -> bool {
+        parse_rpc_payload(payload).is_ok()
+    }
+
+    pub fn event_count(&self) -> usize {
+        lock(&self.events).len()
+    }
+
+    /// Vacía el store de alertas y los eventos registrados entre iteraciones.
+    pub fn reset(&self) {
+        with_alert_store(|store| store.clear());
+        lock(&self.events).clear();
+    }
+}
+
+/// `count` alarmas distintas (`<prefix>-0`…) con severidades alternadas.
+pub fn alarm_burst(prefix: &str, count: usize, status: &str) -> Vec<Vec<u8>> {
+    const SEVERITIES: [&str; 4] = ["CRITICAL", "MAJOR", "MINOR", "WARNING"];
+    (0..count)
+        .map(|index| {
+            alarm_rpc(
+                &format!("{}-{}", prefix, index),
+                &format!("Cámara {}", index % 40),
+                SEVERITIES[index % SEVERITIES.len()],
+                status,
+            )
+        })
+        .collect()
+}
+
+/// Solicitud RPC `ALARM` con el formato de ThingsBoard (ver `payload_format.json`).
+pub fn alarm_rpc(id: &str, device: &str, severity: &str, status: &str) -> Vec<u8> {
+    let now_ms = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or_default();
+    serde_json::json!({
+        "method": "ALARM",
+        "params": {
+            "id": { "entityType": "ALARM", "id": id },
+            "createdTime": now_ms,
+            "type": "Temperature out of range",
+            "originatorName": device,
+            "severity": severity,
+            "acknowledged": status.ends_with("_ACK"),
+            "status": status,
+            "details": { "data": "Temperatura actual = 4.79" },
+        }
+    })
+    .to_string()
+    .into_bytes()
+}
+
+/// Lo que devuelve `get_command_schema`, tal como lo recibe el frontend.
+pub fn command_schema() -> serde_json::Value {
+    serde_json::to_value(schema()).unwrap_or_default()
+}
+
+/// Comandos que recibe `generate_handler!`, en su orden.
+pub fn registered_commands() -> Vec<&'static str> {
+    commands()
+}
+
+/// Eventos declarados en `app_events!`, en su orden.
+pub fn registered_events() -> Vec<&'static str> {
+    APP_EVENTS.iter().map(|&(name, _)| name).collect()
+}
+
+/// Lo que devuelve `get_alerts_since`, tal como lo recibe un visor remoto.
+pub fn alerts_since(cursor: Option<&str>) -> serde_json::Value {
+    serde_json::to_value(diff(cursor)).unwrap_or_default()
+}
+
+/// Broker MQTT 3.1.1 mínimo: CONNECT, SUBSCRIBE, PUBLISH QoS 0/1 y PING, sin sesiones ni retain.
+pub struct Broker {
+    port: u16,
+    clients: Arc<Mutex<Vec<BrokerClient>>>,
+    received: Arc<Mutex<Vec<Message>>>,
+}
+
+struct BrokerClient {
+    stream: TcpStream,
+    filters: Vec<String>,
+}
+
+impl Broker {
+    fn start() -> Broker {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").expect("No se pudo abrir el broker embebido");
+        let port = listener
+            .local_addr()
+            .map(|addr| addr.port())
+            .unwrap_or_default();
+        let broker = Broker {
+            port,
+            clients: Arc::default(),
+            received: Arc::default(),
+        };
+        let clients = broker.clients.clone();
+        let received = broker.received.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let clients = clients.clone();
+                let received = received.clone();
+                thread::spawn(move || serve_client(stream, &clients, &received));
+            }
+        });
+        broker
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn has_subscriber(&self) -> bool {
+        lock(&self.clients)
+            .iter()
+            .any(|client| !client.filters.is_empty())
+    }
+
+    /// Reenvía a los clientes suscritos con QoS 0.
+    pub fn publish(&self, topic: &str, payload: &[u8]) {
+        let packet = publish_packet(topic, payload);
+        let mut clients = lock(&self.clients);
+        for client in clients.iter_mut() {
+            if client
+                .filters
+                .iter()
+                .any(|filter| rumqttc::matches(topic, filter))
+            {
+                let _ = client.stream.write_all(&packet);
+            }
+        }
+    }
+
+    /// Mensajes publicados por el panel (respuestas RPC, telemetría, atributos…).
+    pub fn received(&self, topic_prefix: &str) -> Vec<Message> {
+        lock(&self.received)
+            .iter()
+            .filter(|(topic, _)| topic.starts_with(topic_prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+fn serve_client(
+    mut stream: TcpStream,
+    clients: &Mutex<Vec<BrokerClient>>,
+    received: &Mutex<Vec<Message>>,
+) {
+    let Ok(writer) = stream.try_clone() else {
+        return;
+    };
+    let peer = writer.peer_addr().ok();
+    lock(clients).push(BrokerClient {
+        stream: writer,
+        filters: Vec::new(),
+    });
+    let reply = |bytes: &[u8]| {
+        let mut clients = lock(clients);
+        if let Some(client) = clients
+            .iter_mut()
+            .find(|client| client.stream.peer_addr().ok() == peer)
+        {
+            let _ = client.stream.write_all(bytes);
+        }
+    };
+
+    while let Some((header, body)) = read_packet(&mut stream) {
+        match header >> 4 {
+            1 => reply(&[0x20, 0x02, 0x00, 0x00]),
+            3 => {
+                let qos = (header >> 1) & 0x03;
+                let Some((topic, mut rest)) = read_string(&body) else {
+                    break;
+                };
+                if qos > 0 && rest.len() >= 2 {
+                    reply(&[0x40, 0x02, rest[0], rest[1]]);
+                    rest = &rest[2..];
+                }
+                lock(received).push((topic, rest.to_vec()));
+            }
+            8 if body.len() >= 2 => {
+                let mut filters = Vec::new();
+                let mut rest = &body[2..];
+                while let Some((filter, tail)) = read_string(rest) {
+                    filters.push(filter);
+                    rest = tail.get(1..).unwrap_or_default();
+                }
+                let mut ack = vec![0x90, 2 + filters.len() as u8, body[0], body[1]];
+                ack.extend(filters.iter().map(|_| 0x01));
+                if let Some(client) = lock(clients)
+                    .iter_mut()
+                    .find(|client| client.stream.peer_addr().ok() == peer)
+                {
+                    client.filters.extend(filters);
+                }
+                reply(&ack);
+            }
+            10 if body.len() >= 2 => reply(&[0xB0, 0x02, body[0], body[1]]),
+            12 => reply(&[0xD0, 0x00]),
+            14 => break,
+            _ => {}
+        }
+    }
+    lock(clients).retain(|client| client.stream.peer_addr().ok() != peer);
+}
+
+fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let mut byte = [0u8; 1];
+    stream.read_exact(&mut byte).ok()?;
+    let header = byte[0];
+    let mut length = 0usize;
+    for shift in (0..28).step_by(7) {
+        stream.read_exact(&mut byte).ok()?;
+        length |= usize::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).ok()?;
+    Some((header, body))
+}
+
+fn read_string(bytes: &[u8]) -> Option<(String, &[u8])> {
+    let length = usize::from(u16::from_be_bytes([*bytes.first()?, *bytes.get(1)?]));
+    let text = bytes.get(2..2 + length)?;
+    Some((
+        String::from_utf8_lossy(text).into_owned(),
+        &bytes[2 + length..],
+    ))
+}
+
+fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut length = 2 + topic.len() + payload.len();
+    let mut packet = vec![0x30];
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+    packet.extend_from_slice(topic.as_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
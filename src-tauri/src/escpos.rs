@@ -0,0 +1,138 @@
+//! Tickets para impresoras térmicas ESC/POS.
+//!
+//! Sólo se usan comandos que soporta cualquier impresora compatible: inicializar, página de códigos
+//! PC850 (tiene las letras del español), negrita, doble tamaño, alineación, avance y corte parcial.
+//! El texto se parte por palabras al ancho del papel, en caracteres de la fuente normal.
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+/// `ESC t 2`: PC850 (Multilingual).
+const CODEPAGE_PC850: u8 = 2;
+/// Líneas en blanco antes del corte, para que el texto salga de la cuchilla.
+const FEED_BEFORE_CUT: u8 = 4;
+
+#[derive(Debug, Clone)]
+pub struct Ticket {
+    bytes: Vec<u8>,
+    width: usize,
+}
+
+impl Ticket {
+    /// `width` en caracteres: 32 en papel de 58 mm, 42 o 48 en 80 mm.
+    pub fn new(width: usize) -> Self {
+        Self {
+            bytes: vec![ESC, b'@', ESC, b't', CODEPAGE_PC850],
+            width: width.max(1),
+        }
+    }
+
+    /// Centrado, en negrita y a doble tamaño (ocupa el doble de ancho).
+    pub fn title(&mut self, text: &str) -> &mut Self {
+        self.bytes
+            .extend_from_slice(&[ESC, b'a', 1, ESC, b'E', 1, GS, b'!', 0x11]);
+        self.wrapped(text, self.width / 2);
+        self.bytes
+            .extend_from_slice(&[GS, b'!', 0, ESC, b'E', 0, ESC, b'a', 0]);
+        self
+    }
+
+    pub fn bold(&mut self, text: &str) -> &mut Self {
+        self.bytes.extend_from_slice(&[ESC, b'E', 1]);
+        self.wrapped(text, self.width);
+        self.bytes.extend_from_slice(&[ESC, b'E', 0]);
+        self
+    }
+
+    /// Texto de una o varias líneas.
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        self.wrapped(text, self.width);
+        self
+    }
+
+    pub fn separator(&mut self) -> &mut Self {
+        self.bytes.extend(std::iter::repeat_n(b'-', self.width));
+        self.bytes.push(b'\n');
+        self
+    }
+
+    /// Avanza el papel y hace un corte parcial.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.bytes
+            .extend_from_slice(&[ESC, b'd', FEED_BEFORE_CUT, GS, b'V', 1]);
+        self.bytes
+    }
+
+    fn wrapped(&mut self, text: &str, width: usize) {
+        for line in text.lines().flat_map(|line| wrap(line, width)) {
+            self.bytes.extend(encode_pc850(&line));
+            self.bytes.push(b'\n');
+        }
+    }
+}
+
+/// Parte `line` por palabras en renglones de hasta `width` caracteres; las palabras más largas que
+/// el renglón se cortan. Una línea vacía da un renglón vacío.
+pub fn wrap(line: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        let used = current.chars().count();
+        if used > 0 && used + 1 + word.len() <= width {
+            current.push(' ');
+            current.extend(word);
+            continue;
+        }
+        if used > 0 {
+            lines.push(std::mem::take(&mut current));
+        }
+        while word.len() > width {
+            lines.push(word.drain(..width).collect());
+        }
+        current.extend(word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Texto en PC850; lo que la página no tiene sale como `?` y los caracteres de control se omiten.
+pub fn encode_pc850(text: &str) -> Vec<u8> {
+    text.chars()
+        .filter_map(|c| match c {
+            '\t' => Some(b' '),
+            c if c.is_control() => None,
+            c if c.is_ascii() => Some(c as u8),
+            c => Some(pc850_byte(c).unwrap_or(b'?')),
+        })
+        .collect()
+}
+
+fn pc850_byte(c: char) -> Option<u8> {
+    Some(match c {
+        'Ç' => 0x80,
+        'ü' => 0x81,
+        'é' => 0x82,
+        'ç' => 0x87,
+        'É' => 0x90,
+        'Ü' => 0x9a,
+        'á' => 0xa0,
+        'í' => 0xa1,
+        'ó' => 0xa2,
+        'ú' => 0xa3,
+        'ñ' => 0xa4,
+        'Ñ' => 0xa5,
+        'ª' => 0xa6,
+        'º' => 0xa7,
+        '¿' => 0xa8,
+        '¡' => 0xad,
+        'Á' => 0xb5,
+        'Í' => 0xd6,
+        'Ó' => 0xe0,
+        'Ú' => 0xe9,
+        '°' => 0xf8,
+        _ => return None,
+    })
+}
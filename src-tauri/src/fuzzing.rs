@@ -0,0 +1,27 @@
+//! Entradas para proptest y cargo-fuzz sobre los payloads que llegan de la plataforma.
+//!
+//! Sólo se compila con la feature `fuzzing`; no toca configuración ni estado global.
+
+use crate::{map_alarm, parse_rpc_payload, parse_site_pack, AlertIdentityMode, RpcRequest};
+
+/// Parsea y mapea una solicitud RPC como el loop MQTT y devuelve el tipo reconocido.
+///
+/// Un `Err` es un rechazo controlado (el loop lo manda a dead letters); nunca debe haber pánico.
+pub fn rpc_payload(data: &[u8]) -> Result<&'static str, String> {
+    let (_, request) = parse_rpc_payload(data)?;
+    if let RpcRequest::Alarm(params) = &request {
+        let alert = map_alarm(params, AlertIdentityMode::AlarmId);
+        if alert.id != params.id.value {
+            return Err(format!("id mapeado {} != {}", alert.id, params.id.value));
+        }
+    }
+    Ok(request.kind())
+}
+
+/// Lee un site pack como la importación desde USB y devuelve el panel de origen.
+///
+/// Un pack sin firma, firmado con otra clave o alterado debe terminar en `Err`.
+pub fn site_pack(text: &str, secret: &str) -> Result<String, String> {
+    let (file, _) = parse_site_pack(text, secret)?;
+    Ok(file.source_panel)
+}
@@ -0,0 +1,291 @@
+//! Servidor gRPC opcional (feature `grpc`) para integradores MES: mismas consultas de alertas,
+//! reconocimiento y silencio que los comandos Tauri, con el contrato de `proto/nxt_hmi.proto`.
+//!
+//! Corre en su propio hilo con un runtime tokio de un solo hilo; las operaciones que tocan el
+//! store o la base se despachan a `spawn_blocking` como hacen los comandos. Si `GRPC.token` está
+//! definido, cada llamada debe traer `authorization: Bearer <token>`.
+
+use super::{
+    app_config, is_shutting_down, register_side_effect, remove_alert_from, snapshot_alerts,
+    snapshot_mute_state, toggle_alerts_mute_blocking, with_display, Alert, AlertSeverity,
+    AlertType, DomainEvent, EventSink, MuteStatePayload, PanelRole,
+};
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+use std::net::{SocketAddr, TcpListener};
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{BroadcastStream, TcpListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+pub mod pb {
+    tonic::include_proto!("nxt_hmi.v1");
+}
+
+use pb::nxt_hmi_server::{NxtHmi, NxtHmiServer};
+
+/// Eventos sin leer por suscriptor; un cliente más lento pierde los más viejos (y se le avisa).
+const WATCH_BUFFER: usize = 256;
+const SHUTDOWN_POLL: Duration = Duration::from_secs(1);
+
+static WATCHERS: OnceLock<broadcast::Sender<pb::AlertEvent>> = OnceLock::new();
+
+fn watchers() -> &'static broadcast::Sender<pb::AlertEvent> {
+    WATCHERS.get_or_init(|| broadcast::channel(WATCH_BUFFER).0)
+}
+
+impl From<&Alert> for pb::Alert {
+    fn from(alert: &Alert) -> Self {
+        let alert_type = match alert.alert_type {
+            AlertType::Disconnect => pb::AlertType::Disconnect,
+            AlertType::TempUp => pb::AlertType::TempUp,
+            AlertType::TempDown => pb::AlertType::TempDown,
+        };
+        let severity = match alert.severity {
+            AlertSeverity::Critical => pb::AlertSeverity::Critical,
+            AlertSeverity::Major => pb::AlertSeverity::Major,
+            AlertSeverity::Minor => pb::AlertSeverity::Minor,
+            AlertSeverity::Warning => pb::AlertSeverity::Warning,
+            AlertSeverity::Indeterminate => pb::AlertSeverity::Indeterminate,
+        };
+        let details = alert.details.as_ref();
+        Self {
+            id: alert.id.clone(),
+            date_time: alert.date_time.clone(),
+            created_at_iso: alert.created_at_iso.clone(),
+            created_at_ms: alert.created_at_ms,
+            r#type: alert_type as i32,
+            device: alert.device.clone(),
+            description: alert.description.clone(),
+            severity: severity as i32,
+            acknowledged: alert.acknowledged,
+            current_value: details.and_then(|details| details.current_value),
+            threshold: details.and_then(|details| details.threshold),
+            unit: details.and_then(|details| details.unit.clone()),
+            eta_to_limit_secs: alert.eta_to_limit,
+            defrost: alert.defrost,
+            pin_order: alert
+                .pin_order
+                .map(|order| u32::try_from(order).unwrap_or(u32::MAX)),
+            display: alert.display.as_ref().map(|display| pb::AlertDisplay {
+                color: display.color.clone(),
+                icon: display.icon.clone(),
+                label: display.label.clone(),
+                priority: u32::from(display.priority),
+                clock_warning: display.clock_warning,
+            }),
+            alarm_id: alert.alarm_id.clone(),
+            snapshot_url: details.and_then(|details| details.snapshot_url.clone()),
+        }
+    }
+}
+
+impl From<MuteStatePayload> for pb::MuteState {
+    fn from(payload: MuteStatePayload) -> Self {
+        Self {
+            muted: payload.muted,
+            expires_at: payload.expires_at.unwrap_or_default(),
+            expires_at_ms: payload.expires_at_ms,
+        }
+    }
+}
+
+/// Reenvía los eventos de dominio a los `WatchAlerts` abiertos; sin suscriptores no hace nada.
+fn watch_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    let sender = watchers();
+    if sender.receiver_count() == 0 {
+        return;
+    }
+    let event = match event {
+        DomainEvent::AlertAdded(alert) => {
+            pb::alert_event::Event::Added(pb::Alert::from(&with_display(alert)))
+        }
+        DomainEvent::AlertUpdated(alert) => {
+            pb::alert_event::Event::Updated(pb::Alert::from(&with_display(alert)))
+        }
+        DomainEvent::AlertRemoved(alert) => pb::alert_event::Event::RemovedId(alert.id.clone()),
+        DomainEvent::MuteChanged(payload) => pb::alert_event::Event::Mute(payload.clone().into()),
+    };
+    let _ = sender.send(pb::AlertEvent { event: Some(event) });
+}
+
+/// Un panel espectador no reconoce ni silencia, tampoco por gRPC.
+fn check_write_access() -> Result<(), Status> {
+    if app_config().panel_role == PanelRole::Spectator {
+        warn!("[GRPC] Operación de escritura rechazada: panel en modo espectador");
+        return Err(Status::permission_denied(
+            "Panel en modo solo lectura: no puede modificar alertas ni silencio",
+        ));
+    }
+    Ok(())
+}
+
+async fn blocking<T, F>(task: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(task).await.map_err(|err| {
+        error!("[GRPC] Tarea bloqueante fallida: {:?}", err);
+        Status::internal("Error interno del panel")
+    })
+}
+
+struct PanelService {
+    sink: EventSink,
+}
+
+type WatchStream = Pin<Box<dyn Stream<Item = Result<pb::AlertEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl NxtHmi for PanelService {
+    async fn list_alerts(
+        &self,
+        _request: Request<pb::ListAlertsRequest>,
+    ) -> Result<Response<pb::ListAlertsResponse>, Status> {
+        let alerts = blocking(snapshot_alerts).await?;
+        Ok(Response::new(pb::ListAlertsResponse {
+            alerts: alerts.iter().map(pb::Alert::from).collect(),
+        }))
+    }
+
+    async fn acknowledge_alert(
+        &self,
+        request: Request<pb::AcknowledgeAlertRequest>,
+    ) -> Result<Response<pb::AcknowledgeAlertResponse>, Status> {
+        check_write_access()?;
+        let pb::AcknowledgeAlertRequest { id, reason } = request.into_inner();
+        if id.trim().is_empty() {
+            return Err(Status::invalid_argument("Falta el id de la alerta"));
+        }
+        let sink = self.sink.clone();
+        let removed = blocking(move || {
+            let reason = Some(reason.as_str()).filter(|reason| !reason.trim().is_empty());
+            remove_alert_from(&sink, &id, "grpc", reason)
+        })
+        .await?
+        .map_err(Status::failed_precondition)?;
+        Ok(Response::new(pb::AcknowledgeAlertResponse { removed }))
+    }
+
+    async fn get_mute_state(
+        &self,
+        _request: Request<pb::GetMuteStateRequest>,
+    ) -> Result<Response<pb::MuteState>, Status> {
+        let payload = blocking(snapshot_mute_state).await?;
+        Ok(Response::new(payload.into()))
+    }
+
+    async fn toggle_mute(
+        &self,
+        _request: Request<pb::ToggleMuteRequest>,
+    ) -> Result<Response<pb::MuteState>, Status> {
+        check_write_access()?;
+        let sink = self.sink.clone();
+        let payload = blocking(move || toggle_alerts_mute_blocking(&sink, "grpc")).await?;
+        Ok(Response::new(payload.into()))
+    }
+
+    type WatchAlertsStream = WatchStream;
+
+    async fn watch_alerts(
+        &self,
+        _request: Request<pb::WatchAlertsRequest>,
+    ) -> Result<Response<Self::WatchAlertsStream>, Status> {
+        let stream = BroadcastStream::new(watchers().subscribe()).map(|event| {
+            event.map_err(|err| {
+                warn!("[GRPC] Suscriptor lento: {}", err);
+                Status::data_loss(format!("Se perdieron eventos ({}); vuelva a listar", err))
+            })
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Compara los resúmenes SHA-256 para que el tiempo no dependa de cuántos bytes coinciden.
+fn token_matches(expected: &str, presented: &str) -> bool {
+    Sha256::digest(expected.as_bytes()) == Sha256::digest(presented.as_bytes())
+}
+
+fn check_token(expected: &str, request: &Request<()>) -> Result<(), Status> {
+    if expected.is_empty() {
+        return Ok(());
+    }
+    let presented = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| Status::unauthenticated("Falta authorization: Bearer <token>"))?;
+    if token_matches(expected, presented.trim()) {
+        Ok(())
+    } else {
+        Err(Status::unauthenticated("Token inválido"))
+    }
+}
+
+/// Abre el puerto en el arranque (así un `bind` ocupado o inválido falla el paso) y atiende en
+/// un hilo propio hasta el apagado.
+pub(crate) fn start(sink: EventSink) -> Result<(), String> {
+    let cfg = app_config().grpc.clone();
+    if !cfg.enabled {
+        return Ok(());
+    }
+    let addr: SocketAddr = cfg
+        .bind
+        .parse()
+        .map_err(|err| format!("bind inválido {:?}: {}", cfg.bind, err))?;
+    let listener =
+        TcpListener::bind(addr).map_err(|err| format!("No se pudo abrir {}: {}", addr, err))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|err| format!("No se pudo configurar {}: {}", addr, err))?;
+    register_side_effect("grpc", watch_side_effect);
+
+    let token = cfg.token;
+    let service = NxtHmiServer::with_interceptor(PanelService { sink }, move |request| {
+        check_token(&token, &request).map(|_| request)
+    });
+    thread::Builder::new()
+        .name("grpc".to_string())
+        .spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(err) => {
+                    error!("[GRPC] No se pudo crear el runtime: {}", err);
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                let listener = match tokio::net::TcpListener::from_std(listener) {
+                    Ok(listener) => listener,
+                    Err(err) => {
+                        error!("[GRPC] No se pudo registrar el puerto {}: {}", addr, err);
+                        return;
+                    }
+                };
+                let shutdown = async {
+                    while !is_shutting_down() {
+                        tokio::time::sleep(SHUTDOWN_POLL).await;
+                    }
+                };
+                if let Err(err) = tonic::transport::Server::builder()
+                    .add_service(service)
+                    .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown)
+                    .await
+                {
+                    error!("[GRPC] Servidor detenido: {}", err);
+                }
+            });
+        })
+        .map_err(|err| format!("No se pudo crear el hilo gRPC: {}", err))?;
+    info!("[GRPC] Escuchando en {}", addr);
+    Ok(())
+}
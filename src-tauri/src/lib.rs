@@ -1,38 +1,349 @@
 use anyhow::Result;
-use chrono::{DateTime, FixedOffset, Local, SecondsFormat, Utc};
-use log::{debug, error, info, warn};
-use rumqttc::{Client, Event, MqttOptions, Packet, QoS, TlsConfiguration, Transport};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveTime, SecondsFormat, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport as _};
+use log::{debug, error, info, trace, warn, LevelFilter};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use network::IpPreference;
+use rumqttc::{
+    v5, AsyncClient, Client, Event, EventLoop, MqttOptions, Outgoing, Packet, QoS,
+    TlsConfiguration, Transport,
+};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use signing::{
+    canonical_json, from_hex, sign_action_token, sign_json, to_hex, verify_json, ActionClaims,
+    NonceCache,
+};
+use startup::{Step, StepState};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::io::Write;
-use std::net::TcpStream;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use supabase_realtime_rs::{
     PostgresChangeEvent, PostgresChangesFilter, RealtimeClient, RealtimeClientOptions,
 };
 use tauri::async_runtime::{self, JoinHandle};
-use tauri::{Emitter, WindowEvent};
+use tauri::{Emitter, Manager, WindowEvent};
+
+pub mod badge;
+pub mod csv;
+#[cfg(feature = "e2e")]
+pub mod e2e;
+pub mod escpos;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(feature = "grpc")]
+mod grpc;
+pub mod lockout;
+pub mod network;
+pub mod schedule;
+pub mod signing;
+pub mod startup;
+
+/// Eventos hacia el frontend con el tipo de su payload; cada nombre se declara sólo aquí para que
+/// `get_command_schema` los liste todos.
+macro_rules! app_events {
+    ($($name:ident = $event:literal => $payload:literal;)*) => {
+        $(const $name: &str = $event;)*
+        const APP_EVENTS: &[(&str, &str)] = &[$(($event, $payload)),*];
+    };
+}
+
+app_events! {
+    ALERT_ADDED_EVENT = "alerts://added" => "Alert";
+    ALERT_UPDATED_EVENT = "alerts://updated" => "Alert";
+    ALERT_REMOVED_EVENT = "alerts://removed" => "AlertRemovalEvent";
+    ALERT_FOCUS_EVENT = "alerts://focus" => "AlertFocus";
+    SNAPSHOT_READY_EVENT = "alerts://snapshot_ready" => "{ id: string, url: string }";
+    MUTE_CHANGED_EVENT = "alerts://mute_changed" => "MuteStatePayload";
+    VISUAL_ALARM_EVENT = "visual://alarm_changed" => "VisualAlarmState";
+    BUZZER_INHIBIT_EVENT = "buzzer://inhibit_changed" => "BuzzerInhibitStatus";
+    AUDIO_PROFILE_EVENT = "audio://profile_changed" => "AudioProfileStatus";
+    HARDWARE_STATUS_EVENT = "hardware://status_changed" => "HardwareStatus";
+    DEVICE_STATUS_EVENT = "device://status_changed" => "DeviceStatusUpdate";
+    MAINTENANCE_EVENT = "maintenance://completed" => "MaintenanceReport";
+    OPERATOR_SESSION_EVENT = "operator://session_changed" => "OperatorSession | null";
+    SESSION_IDLE_WARNING_EVENT = "session://idle_warning" => "SessionIdleWarningPayload";
+    SESSION_EXPIRED_EVENT = "session://expired" => "SessionExpiredPayload";
+    PANEL_LOCK_EVENT = "operator://lockout_changed" => "PanelLockStatus";
+    PRESENCE_EVENT = "presence://changed" => "PresenceStatus";
+    FLOORPLAN_OVERLAY_EVENT = "floorplan://overlay_changed" => "FloorplanOverlay";
+    PLAYBACK_FRAME_EVENT = "playback://frame" => "PlaybackFrame";
+    PLAYBACK_STATE_EVENT = "playback://state" => "PlaybackState";
+    MQTT_STATUS_EVENT = "mqtt://status" => "MqttStatus";
+    CONNECTIVITY_EVENT = "network://connectivity_changed" => "ConnectivityStatus";
+    CLOCK_SKEW_EVENT = "clock://skew_changed" => "ClockSkewStatus";
+    DEGRADATION_EVENT = "system://degradation_changed" => "DegradationStatus";
+    STARTUP_EVENT = "system://startup" => "StartupStatus";
+    SYSTEM_FAULT_EVENT = "system://fault" => "SystemFaultStatus";
+    CERT_EXPIRING_EVENT = "certs://expiring" => "CertExpiryStatus";
+    WIZARD_PROGRESS_EVENT = "wizard://progress" => "WizardStepResult";
+}
 
 static ALERT_STORE: OnceLock<Mutex<HashMap<String, Alert>>> = OnceLock::new();
-const ALERT_ADDED_EVENT: &str = "alerts://added";
-const ALERT_REMOVED_EVENT: &str = "alerts://removed";
-static BUZZER_CONTROLLER: OnceLock<Mutex<BuzzerController>> = OnceLock::new();
+static ALERT_JOURNAL: OnceLock<Mutex<AlertJournal>> = OnceLock::new();
+/// Cambios recordados para `get_alerts_since`; un cursor más viejo recibe la lista completa.
+const ALERT_JOURNAL_CAPACITY: usize = 2000;
+static PINNED_ALERTS: OnceLock<Mutex<Vec<String>>> = OnceLock::new();
+const PINNED_ALERTS_FILE: &str = "pinned_alerts.json";
+static HISTORY_DB: OnceLock<Mutex<Option<Connection>>> = OnceLock::new();
+const HISTORY_DB_FILE: &str = "history.db";
+const HISTORY_SEARCH_LIMIT: i64 = 500;
+const HISTORY_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS alert_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    alert_id TEXT NOT NULL,
+    event TEXT NOT NULL,
+    ts_ms INTEGER NOT NULL,
+    alert_type TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    acknowledged INTEGER NOT NULL DEFAULT 0,
+    device TEXT NOT NULL,
+    description TEXT NOT NULL,
+    notes TEXT NOT NULL DEFAULT '',
+    alarm_id TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX IF NOT EXISTS idx_alert_history_ts ON alert_history(ts_ms);
+CREATE INDEX IF NOT EXISTS idx_alert_history_alert ON alert_history(alert_id);
+CREATE VIRTUAL TABLE IF NOT EXISTS alert_history_fts USING fts5(
+    description, device, notes, content='alert_history', content_rowid='id'
+);
+CREATE TRIGGER IF NOT EXISTS alert_history_ai AFTER INSERT ON alert_history BEGIN
+    INSERT INTO alert_history_fts(rowid, description, device, notes)
+    VALUES (new.id, new.description, new.device, new.notes);
+END;
+CREATE TRIGGER IF NOT EXISTS alert_history_ad AFTER DELETE ON alert_history BEGIN
+    INSERT INTO alert_history_fts(alert_history_fts, rowid, description, device, notes)
+    VALUES ('delete', old.id, old.description, old.device, old.notes);
+END;
+CREATE TRIGGER IF NOT EXISTS alert_history_au AFTER UPDATE ON alert_history BEGIN
+    INSERT INTO alert_history_fts(alert_history_fts, rowid, description, device, notes)
+    VALUES ('delete', old.id, old.description, old.device, old.notes);
+    INSERT INTO alert_history_fts(rowid, description, device, notes)
+    VALUES (new.id, new.description, new.device, new.notes);
+END;
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ts_ms INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL DEFAULT '',
+    detail TEXT NOT NULL DEFAULT '',
+    operator TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX IF NOT EXISTS idx_audit_log_ts ON audit_log(ts_ms);
+";
+const REPORTS_DIR: &str = "reports";
+const OPERATOR_SESSION_TICK: Duration = Duration::from_secs(5);
+const BADGE_REOPEN_DELAY: Duration = Duration::from_secs(10);
+static OPERATOR_SESSION: OnceLock<Mutex<Option<OperatorSession>>> = OnceLock::new();
+/// Teclas reenviadas por el frontend en modo cuña de teclado.
+static BADGE_WEDGE_BUFFER: OnceLock<Mutex<badge::ScanBuffer>> = OnceLock::new();
+static PANEL_LOCKOUT: Mutex<lockout::Lockout> = Mutex::new(lockout::Lockout {
+    failures: 0,
+    lockouts: 0,
+    locked_until: None,
+});
+const SMTP_TIMEOUT: Duration = Duration::from_secs(20);
+static EMAIL_QUEUE: OnceLock<Mutex<Vec<QueuedEmail>>> = OnceLock::new();
+const EMAIL_QUEUE_FILE: &str = "email_queue.json";
+static EMAIL_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+const EMAIL_QUEUE_LIMIT: usize = 500;
+const EMAIL_QUEUE_TICK: Duration = Duration::from_secs(10);
+const EMAIL_RETRY_BASE: Duration = Duration::from_secs(30);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+static OPEN_INCIDENTS: OnceLock<Mutex<HashMap<String, AlertSeverity>>> = OnceLock::new();
+const STATISTICS_DEFAULT_DAYS: i64 = 30;
+const PRESENCE_CHECK_TICK: Duration = Duration::from_secs(5);
+static PRESENCE_CHECK: OnceLock<Mutex<PresenceCheck>> = OnceLock::new();
+const ON_CALL_SCHEDULE_ATTRIBUTE: &str = "onCallSchedule";
+const ZONES_ATTRIBUTE: &str = "zones";
+const NOTIFICATION_ROUTES_ATTRIBUTE: &str = "notificationRoutes";
+static NOTIFICATION_ROUTES: OnceLock<Mutex<Vec<NotificationRoute>>> = OnceLock::new();
+static PLAYBACK_SESSION: AtomicU64 = AtomicU64::new(0);
+const PLAYBACK_MAX_SPEED: f64 = 3600.0;
+/// Tope de espera real entre dos cuadros: los tramos sin cambios no frenan la revisión.
+const PLAYBACK_MAX_GAP: Duration = Duration::from_secs(5);
+static ZONES: OnceLock<Mutex<Vec<Zone>>> = OnceLock::new();
+/// Despejes en espera con `ALERT_IDENTITY.mode = originator_type`: id del panel → id de la alarma despejada.
+static PENDING_CLEARS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+const ESCALATION_CHECK_TICK: Duration = Duration::from_secs(30);
+static ON_CALL_SCHEDULE: OnceLock<Mutex<Vec<OnCallShift>>> = OnceLock::new();
+static ESCALATIONS: OnceLock<Mutex<HashMap<String, Escalation>>> = OnceLock::new();
+static NOTIFICATION_THROTTLE: OnceLock<Mutex<HashMap<(String, String), NotificationThrottle>>> =
+    OnceLock::new();
+const NOTIFICATION_DIGEST_TICK: Duration = Duration::from_secs(60);
+static BUZZER_CONTROLLERS: OnceLock<Mutex<HashMap<String, BuzzerController>>> = OnceLock::new();
+static SIGNAL_OUTPUTS: OnceLock<Vec<SignalOutput>> = OnceLock::new();
+const ONBOARD_BUZZER_OUTPUT: &str = "onboard";
+const STROBE_OUTPUT: &str = "strobe";
+const PWM_PERIOD_NS: u64 = 1_000_000;
 static MUTE_CONTROLLER: OnceLock<Mutex<MuteController>> = OnceLock::new();
-const MUTE_CHANGED_EVENT: &str = "alerts://mute_changed";
+const VISUAL_ALARM_DIM_PERCENT: u32 = 10;
+static VISUAL_ALARM: OnceLock<Mutex<VisualAlarmController>> = OnceLock::new();
+static SIDE_EFFECT_HANDLERS: OnceLock<Mutex<Vec<(&'static str, SideEffectHandler)>>> =
+    OnceLock::new();
 static APP_CONFIG: OnceLock<AppConfig> = OnceLock::new();
 static LOGGER_INITIALIZED: OnceLock<()> = OnceLock::new();
+static RECENT_EVENTS: OnceLock<Mutex<VecDeque<BackendEvent>>> = OnceLock::new();
+static RECENT_EVENT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+const RECENT_EVENT_CAPACITY: usize = 500;
+const RECENT_EVENT_MESSAGE_LIMIT: usize = 1024;
+const RECENT_EVENTS_DEFAULT: usize = 100;
+static LOG_FORWARD: OnceLock<Mutex<LogForwardBuffer>> = OnceLock::new();
+const LOG_FORWARD_TICK: Duration = Duration::from_secs(2);
+const LOG_FORWARD_BATCH: usize = 500;
+const LOG_FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+const LOG_FORWARD_APP_NAME: &str = "nxt-hmi";
+/// local0: así rsyslog puede separar el log del panel con una sola regla.
+const SYSLOG_FACILITY: u8 = 16;
 const CONFIG_PATH: &str = "config/config.yaml";
+const SITE_PACK_FORMAT: &str = "nxt-hmi-site-pack";
+const SITE_PACK_VERSION: u32 = 1;
+const MQTT_CA_PATH: &str = "certs/emqxsl-ca.crt";
+const CERT_EXPIRY_WARNING_DAYS: i64 = 30;
+const CERT_EXPIRY_ALERT_ID: &str = "cert-expiry";
+const CERT_EXPIRY_TICK: Duration = Duration::from_secs(60);
+static CERT_STATUS: Mutex<Option<CertExpiryStatus>> = Mutex::new(None);
+/// Pide revisar los certificados en el próximo tick (p. ej. tras importar uno).
+static CERT_RECHECK: AtomicBool = AtomicBool::new(true);
+const CERT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15);
+const DEVICE_CSV_MAX_BYTES: u64 = 4 * 1024 * 1024;
+/// En las celdas de la planilla de equipos borra el valor actual (vacío lo conserva).
+const DEVICE_CSV_CLEAR: &str = "-";
+const CERT_MAX_BYTES: u64 = 64 * 1024;
 
 static MQTT_CONNECTED: AtomicBool = AtomicBool::new(false);
+/// Intentos de conexión desde la última vez que se estuvo conectado.
+static MQTT_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+static MQTT_LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
 const MQTT_RETRY_DELAY: Duration = Duration::from_secs(5);
 const MQTT_MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
 pub const MQTT_RPC_REQUEST_TOPIC: &str = "v1/devices/me/rpc/request/+";
+pub const MQTT_TELEMETRY_PUBLISH_TOPIC: &str = "v1/devices/me/telemetry";
+pub const MQTT_ATTRIBUTES_TOPIC: &str = "v1/devices/me/attributes";
+const MQTT_RPC_REQUEST_PREFIX: &str = "v1/devices/me/rpc/request/";
+const MQTT_RPC_RESPONSE_PREFIX: &str = "v1/devices/me/rpc/response/";
+const GET_STATE_RPC_METHOD: &str = "GET_STATE";
+const DEVICE_TREE_MODEL_PATH: &str = "/proc/device-tree/model";
+const DEVICE_TREE_COMPATIBLE_PATH: &str = "/proc/device-tree/compatible";
+const BOARD_EEPROM_READ_BYTES: u64 = 256;
+const BOARD_EEPROM_MIN_STRING: usize = 4;
+const DEFAULT_HARDWARE_PROFILE: &str = "default";
+static HARDWARE_PROFILE: OnceLock<HardwareProfile> = OnceLock::new();
+static MQTT_RECONNECTS: AtomicU64 = AtomicU64::new(0);
+/// Reintentos MQTT postergados porque el monitor de red no veía enlace ni ruta por defecto.
+static MQTT_DEFERRED_RETRIES: AtomicU64 = AtomicU64::new(0);
+const NETWORK_MONITOR_INTERVAL: Duration = Duration::from_secs(2);
+const NETWORK_ROUTE_PATH: &str = "/proc/net/route";
+const NETWORK_CLASS_DIR: &str = "/sys/class/net";
+const CONNECTIVITY_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const BROKER_RACE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Menor que el latido del monitor de red por `TASK_STALL_FACTOR`.
+const CAPTIVE_PORTAL_TIMEOUT: Duration = Duration::from_secs(4);
+const CAPTIVE_PORTAL_ALERT_ID: &str = "captive-portal";
+static CONNECTIVITY: OnceLock<Mutex<ConnectivityStatus>> = OnceLock::new();
+static MODEM_STATUS: OnceLock<Mutex<Option<ModemStatus>>> = OnceLock::new();
+/// Alertas ya avisadas por SMS; se liberan cuando la alerta se retira.
+static SMS_NOTIFIED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+const SMS_MAX_CHARS: usize = 300;
+/// Enlace y ruta por defecto según el monitor; se asume disponible hasta la primera lectura.
+static NETWORK_LINK_UP: AtomicBool = AtomicBool::new(true);
+/// Subidas de enlace observadas; cortan la espera entre reintentos MQTT.
+static NETWORK_LINK_UPS: AtomicU64 = AtomicU64::new(0);
+const BUZZER_INHIBIT_RPC_METHOD: &str = "setBuzzerInhibit";
+const NOTIFICATION_ACTION_RPC_METHOD: &str = "notificationAction";
+const UNLOCK_PANEL_RPC_METHOD: &str = "unlockPanel";
+const BUZZER_INHIBIT_ATTRIBUTE: &str = "buzzerInhibit";
+const AUDIO_PROFILE_RPC_METHOD: &str = "setAudioProfile";
+const AUDIO_PROFILE_ATTRIBUTE: &str = "audioProfile";
+/// Volumen mínimo aceptado: el perfil no sirve para silenciar alertas.
+const AUDIO_MIN_VOLUME: u8 = 10;
+const AUDIO_CHIME_ON: Duration = Duration::from_millis(150);
+const AUDIO_CHIME_PERIOD: Duration = Duration::from_secs(15);
+static AUDIO_PROFILE_STATE: Mutex<AudioProfileState> = Mutex::new(AudioProfileState {
+    remote: None,
+    local: None,
+});
+static BUZZER_INHIBIT: OnceLock<Mutex<Option<BuzzerInhibit>>> = OnceLock::new();
+static BUZZER_INHIBIT_GENERATION: AtomicU64 = AtomicU64::new(0);
+static INTERACTION_METRICS: OnceLock<Mutex<InteractionMetrics>> = OnceLock::new();
+static MQTT_CLIENT: OnceLock<Mutex<Option<MqttClient>>> = OnceLock::new();
+/// Credenciales cargadas con `set_mqtt_credentials`; rigen hasta el reinicio, que las lee del YAML.
+static MQTT_CREDENTIALS: Mutex<Option<MqttCredentials>> = Mutex::new(None);
+static MQTT_CREDENTIALS_GENERATION: AtomicU64 = AtomicU64::new(0);
+static BRIDGE_CLIENT: OnceLock<Mutex<Option<Client>>> = OnceLock::new();
+static BRIDGE_FORWARDED: AtomicU64 = AtomicU64::new(0);
+static DEAD_LETTERS: OnceLock<Mutex<VecDeque<DeadLetter>>> = OnceLock::new();
+const DEAD_LETTER_CAPACITY: usize = 200;
+const DEAD_LETTER_PAYLOAD_LIMIT: usize = 4096;
+static TASKS: OnceLock<Mutex<BTreeMap<String, TaskEntry>>> = OnceLock::new();
+static TASK_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+static EVENT_LOOP_LAG_MS: AtomicU64 = AtomicU64::new(0);
+static EVENT_LOOP_MAX_LAG_MS: AtomicU64 = AtomicU64::new(0);
+const RUNTIME_HEALTH_TICK: Duration = Duration::from_secs(1);
+const RUNTIME_HEALTH_ALERT_ID: &str = "runtime-health";
+static DEGRADATION: Mutex<BTreeMap<DegradedMode, DegradedEntry>> = Mutex::new(BTreeMap::new());
+/// Último error de almacenamiento de la base de historial; se limpia con la siguiente operación correcta.
+static PERSISTENCE_ERROR: Mutex<Option<String>> = Mutex::new(None);
+/// Resultado del último control de espacio en `DATA_DIR`: `Some` con el detalle si falta espacio.
+static DISK_FULL: Mutex<Option<String>> = Mutex::new(None);
+static DISK_CHECKED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+static STARTUP_STATUS: Mutex<Option<StartupStatus>> = Mutex::new(None);
+const STARTUP_RETRY_DELAY: Duration = Duration::from_secs(2);
+static SYSTEM_FAULT: Mutex<SystemFaultState> = Mutex::new(SystemFaultState {
+    pending_since: None,
+    active: None,
+});
+static HARDWARE_HEALTH: OnceLock<Mutex<HardwareHealth>> = OnceLock::new();
+static FAULT_INJECTION: OnceLock<Mutex<FaultInjectionConfig>> = OnceLock::new();
+static FAULT_INJECTION_STATE: AtomicU64 = AtomicU64::new(0);
+/// Un ticket a la vez: dos escrituras simultáneas al mismo puerto se intercalan en el papel.
+static PRINTER_LOCK: Mutex<()> = Mutex::new(());
+/// Última lectura de comprobación por salida, para no releer en cada destello.
+static OUTPUT_VERIFIED_AT: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+const HARDWARE_FAULT_ALERT_ID: &str = "hardware-fault";
+const BACKLIGHT_OUTPUT: &str = "backlight";
+/// Un servicio sin latido durante este múltiplo de su intervalo se considera colgado.
+const TASK_STALL_FACTOR: u32 = 3;
+const SUPERVISOR_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const SUPERVISOR_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Tras correr este tiempo sin fallar, el siguiente reinicio vuelve al backoff mínimo.
+const SUPERVISOR_STABLE_RUN: Duration = Duration::from_secs(120);
+static MQTT_TOKEN: OnceLock<Mutex<Option<BrokerToken>>> = OnceLock::new();
+const MQTT_TOKEN_CHECK_TICK: Duration = Duration::from_secs(15);
+const MQTT_TOKEN_DEFAULT_TTL: Duration = Duration::from_secs(300);
+static RPC_NONCES: OnceLock<Mutex<NonceCache>> = OnceLock::new();
+/// Tope de nonces recordados; sin secreto cualquiera puede enviar RPC y llenar el conjunto.
+const RPC_NONCE_LIMIT: usize = 4096;
+/// Nonces de tokens de acción ya usados, con su vencimiento en ms.
+static ACTION_NONCES: OnceLock<Mutex<HashMap<String, i64>>> = OnceLock::new();
+static PAYLOAD_SCHEMA_CACHE: OnceLock<Vec<Option<serde_json::Value>>> = OnceLock::new();
+static MQTT_PING_COUNT: AtomicU64 = AtomicU64::new(0);
+const MQTT_PING_LOG_EVERY: u64 = 20;
+static DISPLAY_LATENCY: OnceLock<Mutex<DisplayLatency>> = OnceLock::new();
+const DISPLAY_LATENCY_SAMPLES: usize = 1000;
+/// Alarmas recibidas que todavía no llegaron al frontend; acota fugas si nunca se emiten.
+const DISPLAY_LATENCY_PENDING_LIMIT: usize = 1000;
+const DISPLAY_LATENCY_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+/// Hora (corregida, ms) del último CONNACK: lo creado antes es reenvío o sincronización inicial.
+static MQTT_SESSION_START_MS: AtomicI64 = AtomicI64::new(0);
 
 static SUPABASE_CONNECTED: AtomicBool = AtomicBool::new(false);
 const SUPABASE_RETRY_DELAY: Duration = Duration::from_secs(5);
@@ -40,12 +351,37 @@ const SUPABASE_MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
 const SUPABASE_CHANNEL_NAME: &str = "schema-db-changes";
 const SUPABASE_DB_SCHEMA: &str = "public";
 const BINARY_ARRAY_SIZE: usize = 6;
-const DEVICE_STATUS_EVENT: &str = "device://status_changed";
+static PEER_SYNC_CLOCK: OnceLock<Mutex<HashMap<String, i64>>> = OnceLock::new();
+const PEER_SYNC_MUTE_KEY: &str = "mute";
+static MDNS_DAEMON: OnceLock<Mutex<Option<ServiceDaemon>>> = OnceLock::new();
+const MDNS_SERVICE_TYPE: &str = "_nxt-hmi._tcp.local.";
 
 static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_NOTIFY: tokio::sync::Notify = tokio::sync::Notify::const_new();
+static CLI_MODE: AtomicBool = AtomicBool::new(false);
+const CLI_USAGE: &str = "Uso: nxt-hmi [--headless]\n       nxt-hmi alerts list\n       nxt-hmi mqtt test\n       nxt-hmi buzzer test\n       nxt-hmi config validate";
+/// Socket en `data_dir` por el que la CLI consulta al panel en marcha.
+#[cfg(unix)]
+const CONTROL_SOCKET_FILE: &str = "nxt-hmi.sock";
+#[cfg(unix)]
+const CONTROL_SOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+#[cfg(unix)]
+const CONTROL_MAX_REQUEST: u64 = 256;
+const MQTT_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BUZZER_TEST_DURATION: Duration = Duration::from_secs(3);
+const AUDIBLE_TEST_ALERT_ID: &str = "audible-test";
+/// Con una alarma sonando la prueba programada se pospone hasta que se silencie.
+const AUDIBLE_TEST_RETRY: Duration = Duration::from_secs(600);
+/// Tiempo para que la realimentación refleje el apagado antes de leerla.
+const AUDIBLE_FEEDBACK_SETTLE: Duration = Duration::from_millis(500);
+static LAST_AUDIBLE_TEST: OnceLock<Mutex<Option<AudibleTestResult>>> = OnceLock::new();
+const USB_MOUNT_ROOTS: [&str; 3] = ["/media", "/run/media", "/mnt"];
+const USB_SCAN_DEPTH: usize = 3;
+/// Lo que ve el asistente en lugar de un secreto guardado.
+const SECRET_SET_MARKER: &str = "********";
 const BUZZER_FAILURE_LIMIT: u8 = 5;
 const SLEEP_CHUNK: Duration = Duration::from_millis(200);
-static BUZZER_GPIO_CACHE: OnceLock<Mutex<Option<(String, String)>>> = OnceLock::new();
+static BUZZER_GPIO_CACHE: OnceLock<Mutex<HashMap<String, (String, String)>>> = OnceLock::new();
 
 const REFRIGERATOR_NAMES: [&str; 6] = [
     "Bodega - microbiología refri 2",
@@ -57,8 +393,44 @@ const REFRIGERATOR_NAMES: [&str; 6] = [
 ];
 const TEMPERATURE_ALARM_TYPE: &str = "Temperature out of range";
 const TEMPERATURE_ALARM_DESCRIPTION: &str = "Temperatura fuera de rango 2 - 8 °C";
+const TEMPERATURE_UNIT: &str = "°C";
+const DETAIL_CURRENT_KEYS: [&str; 4] = ["currentValue", "value", "temperature", "current"];
+const DETAIL_THRESHOLD_KEYS: [&str; 4] = ["threshold", "thresholdValue", "limit", "limitValue"];
+const DETAIL_UNIT_KEYS: [&str; 2] = ["unit", "units"];
+const DETAIL_SNAPSHOT_KEYS: [&str; 4] = ["snapshotUrl", "imageUrl", "snapshot", "image"];
+const SNAPSHOT_DIR: &str = "snapshots";
+const DEEP_LINK_SCHEME: &str = "nxthmi";
+static PENDING_ALERT_FOCUS: OnceLock<Mutex<Option<AlertFocus>>> = OnceLock::new();
+const SNAPSHOT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15);
+const SNAPSHOT_TYPES: [(&str, &str); 4] = [
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+    ("image/gif", "gif"),
+];
+static SNAPSHOT_DOWNLOADS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
 static REFRIGERATOR_ALARM_STATE: OnceLock<Mutex<Vec<u8>>> = OnceLock::new();
 
+static CLOCK_SKEW_MS: AtomicI64 = AtomicI64::new(0);
+static PLANT_TIMEZONE: OnceLock<Tz> = OnceLock::new();
+static HOLIDAY_CALENDAR: OnceLock<schedule::HolidayCalendar> = OnceLock::new();
+static CLOCK_SKEW_EXCEEDED: AtomicBool = AtomicBool::new(false);
+const COMMAND_SCHEMA_VERSION: u32 = 1;
+/// Respuestas a los RPC que inicia el panel (hora del servidor).
+const MQTT_RPC_RESPONSE_TOPIC: &str = "v1/devices/me/rpc/response/+";
+const SERVER_TIME_TICK: Duration = Duration::from_secs(5);
+/// Ids propios lejos de los del servidor, por si el broker devuelve nuestras propias respuestas.
+static SERVER_TIME_REQUEST_ID: AtomicU64 = AtomicU64::new(1_000_000);
+static SERVER_TIME_SYNC: OnceLock<Mutex<ServerTimeSync>> = OnceLock::new();
+/// Un desfase mayor es una marca mal interpretada, no un reloj atrasado: no se aplica.
+const MAX_CLOCK_SKEW_MS: i64 = 24 * 60 * 60 * 1000;
+
+static TELEMETRY_BUFFER: OnceLock<Mutex<HashMap<String, VecDeque<TelemetrySample>>>> =
+    OnceLock::new();
+const TELEMETRY_BUFFER_CAPACITY: usize = 256;
+const TREND_STABLE_RATE_PER_MINUTE: f64 = 0.05;
+const PROJECTION_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 struct AppConfig {
@@ -75,1186 +447,17727 @@ struct AppConfig {
     supabase_url: String,
     #[serde(default)]
     supabase_anon_key: String,
+    #[serde(default = "default_clock_skew_threshold_secs")]
+    clock_skew_threshold_secs: u64,
+    #[serde(default)]
+    server_time: ServerTimeConfig,
+    #[serde(default = "default_trend_window_minutes")]
+    trend_window_minutes: u64,
+    #[serde(default)]
+    critical_temperature_high: Option<f64>,
+    #[serde(default)]
+    critical_temperature_low: Option<f64>,
+    #[serde(default)]
+    mqtt_telemetry_topic: String,
+    #[serde(default)]
+    rate_of_change_rules: Vec<RateOfChangeRule>,
+    #[serde(default)]
+    defrost_schedules: Vec<DefrostSchedule>,
+    #[serde(default)]
+    devices: Vec<DeviceEntry>,
+    #[serde(default)]
+    operators: Vec<OperatorEntry>,
+    #[serde(default)]
+    badge_reader: BadgeReaderConfig,
+    #[serde(default)]
+    pin_lockout: PinLockoutConfig,
+    #[serde(default)]
+    session_idle: SessionIdleConfig,
+    #[serde(default)]
+    panel_id: String,
+    #[serde(default = "default_network_monitor_enabled")]
+    network_monitor_enabled: bool,
+    #[serde(default)]
+    network: NetworkConfig,
+    #[serde(default)]
+    modem: ModemConfig,
+    #[serde(default)]
+    peer_sync_enabled: bool,
+    #[serde(default = "default_peer_sync_topic")]
+    peer_sync_topic: String,
+    #[serde(default)]
+    mdns_enabled: bool,
+    #[serde(default)]
+    mdns_port: u16,
+    #[serde(default)]
+    mdns_endpoints: HashMap<String, String>,
+    #[serde(default = "default_data_dir")]
+    data_dir: String,
+    /// Por debajo de este espacio libre en `DATA_DIR` el panel entra en modo degradado `diskFull`.
+    #[serde(default = "default_disk_min_free_mb")]
+    disk_min_free_mb: u64,
+    #[serde(default)]
+    mqtt_client_key: String,
+    /// Certificado de cliente para mTLS; vacío usa el importado (`<DATA_DIR>/certs/client.crt`).
+    #[serde(default)]
+    mqtt_client_cert: String,
+    #[serde(default)]
+    panel_role: PanelRole,
+    #[serde(default)]
+    spectator_windows: Vec<String>,
+    #[serde(default)]
+    alert_display_rules: Vec<AlertDisplayRule>,
+    #[serde(default = "default_history_enabled")]
+    history_enabled: bool,
+    #[serde(default)]
+    retention: RetentionConfig,
+    #[serde(default = "default_maintenance_hour")]
+    maintenance_hour: u32,
+    /// Zona IANA de la planta para turnos, guardias, descongelamiento y mantenimiento; vacío = la del sistema.
+    #[serde(default)]
+    timezone: String,
+    #[serde(default)]
+    holidays: HolidayConfig,
+    #[serde(default)]
+    log_dir: String,
+    #[serde(default)]
+    metrics_enabled: bool,
+    #[serde(default = "default_metrics_interval_minutes")]
+    metrics_interval_minutes: u64,
+    #[serde(default = "default_shift_start_hours")]
+    shift_start_hours: Vec<u32>,
+    #[serde(default)]
+    remote_buzzer_inhibit_enabled: bool,
+    #[serde(default = "default_buzzer_inhibit_max_minutes")]
+    buzzer_inhibit_max_minutes: u64,
+    #[serde(default)]
+    hardware_revision: String,
+    #[serde(default)]
+    hardware_profiles: Vec<HardwareProfile>,
+    #[serde(default = "default_hardware_profile_name")]
+    hardware_profile: String,
+    #[serde(default)]
+    board_eeprom_path: String,
+    #[serde(default)]
+    audible_outputs: Vec<SignalOutput>,
+    /// Una alerta CRITICAL suena en todas las salidas aunque tengan `zones`.
+    #[serde(default = "default_output_enabled")]
+    critical_sounds_all_zones: bool,
+    #[serde(default)]
+    audio_profile: AudioProfileConfig,
+    #[serde(default)]
+    audible_test: AudibleTestConfig,
+    #[serde(default)]
+    ack_policy: AckPolicyConfig,
+    #[serde(default)]
+    alert_identity: AlertIdentityConfig,
+    #[serde(default)]
+    zones: ZonesConfig,
+    #[serde(default)]
+    floorplan: FloorplanConfig,
+    /// Clave HMAC compartida por los paneles que exportan e importan site packs.
+    #[serde(default)]
+    site_pack_secret: String,
+    #[serde(default = "default_output_enabled")]
+    strobe_enabled: bool,
+    #[serde(default)]
+    visual_alarm_enabled: bool,
+    #[serde(default)]
+    visual_alarm_backlight_pulse: bool,
+    #[serde(default)]
+    shifts: Vec<ShiftDefinition>,
+    #[serde(default)]
+    smtp: SmtpConfig,
+    #[serde(default)]
+    handover_email_to: Vec<String>,
+    #[serde(default)]
+    printer: PrinterConfig,
+    #[serde(default)]
+    email_templates: HashMap<String, EmailTemplate>,
+    #[serde(default = "default_email_footer")]
+    email_footer: String,
+    #[serde(default)]
+    webhooks: Vec<WebhookTarget>,
+    #[serde(default)]
+    pagerduty: PagerDutyConfig,
+    #[serde(default)]
+    opsgenie: OpsgenieConfig,
+    #[serde(default)]
+    notifications: NotificationConfig,
+    #[serde(default)]
+    presence_check: PresenceCheckConfig,
+    #[serde(default)]
+    on_call: OnCallConfig,
+    #[serde(default)]
+    notification_routing: NotificationRoutingConfig,
+    #[serde(default)]
+    mqtt_bridge: MqttBridgeConfig,
+    #[serde(default)]
+    payload_mappings: Vec<PayloadMapping>,
+    #[serde(default)]
+    payload_schemas: Vec<PayloadSchema>,
+    #[serde(default)]
+    rpc_security: RpcSecurityConfig,
+    #[serde(default)]
+    mqtt_auth: MqttAuthConfig,
+    #[serde(default)]
+    mqtt_protocol: MqttProtocol,
+    #[serde(default)]
+    mqtt_v5: MqttV5Config,
+    #[serde(default)]
+    mqtt_transport: MqttTransport,
+    #[serde(default)]
+    mqtt_ca_source: MqttCaSource,
+    /// Ruta del endpoint WebSocket del broker (EMQX: `/mqtt`); sólo con `MQTT_TRANSPORT: wss`.
+    #[serde(default = "default_mqtt_ws_path")]
+    mqtt_ws_path: String,
+    #[serde(default)]
+    hardware_fault_injection: FaultInjectionConfig,
+    #[serde(default)]
+    system_fault: SystemFaultConfig,
+    #[serde(default)]
+    cert_expiry: CertExpiryConfig,
+    #[serde(default)]
+    output_verification: OutputVerificationConfig,
+    #[serde(default)]
+    log_forwarding: LogForwardingConfig,
+    #[serde(default)]
+    alert_snapshots: SnapshotConfig,
+    #[serde(default)]
+    otel: OtelConfig,
+    #[serde(default)]
+    grpc: GrpcConfig,
+    /// Tiempo máximo contractual entre `createdTime` de la alarma y su aparición en pantalla.
+    #[serde(default = "default_display_latency_slo_ms")]
+    display_latency_slo_ms: u64,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            mqtt_server: "j0661b06.ala.us-east-1.emqxsl.com".to_string(),
-            mqtt_use_secure_client: true,
-            mqtt_port: 8883,
-            mqtt_client_id: "hmi-cli".to_string(),
-            mqtt_username: "test".to_string(),
-            mqtt_password: "test".to_string(),
-            mute_duration: 600,
-            buzzer_enabled: default_buzzer_enabled(),
-            supabase_url: String::new(),
-            supabase_anon_key: String::new(),
-        }
-    }
+/// Feriados de la planta: fechas `AAAA-MM-DD` (una vez) o `MM-DD` (todos los años) y/o un archivo iCal.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct HolidayConfig {
+    #[serde(default)]
+    dates: Vec<String>,
+    #[serde(default)]
+    ical_path: String,
 }
 
-fn default_buzzer_enabled() -> bool {
-    true
+/// Áreas de la planta: cada equipo (el `device` de la alarma) pertenece a una zona y las zonas al sitio.
+/// Con `sync_from_platform` las zonas llegan de los assets de ThingsBoard en el atributo compartido `zones`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct ZonesConfig {
+    /// Nombre del sitio; vacío = `PANEL_ID`.
+    #[serde(default)]
+    site: String,
+    #[serde(default)]
+    zones: Vec<Zone>,
+    #[serde(default)]
+    sync_from_platform: bool,
 }
 
-fn init_logging() {
-    LOGGER_INITIALIZED.get_or_init(|| {
-        let env = env_logger::Env::default().default_filter_or("info");
-        if let Err(err) = env_logger::Builder::from_env(env)
-            .format(|buf, record| {
-                writeln!(
-                    buf,
-                    "[{}][{}] {}",
-                    buf.timestamp_millis(),
-                    record.level(),
-                    record.args()
-                )
-            })
-            .try_init()
-        {
-            eprintln!("[LOG] No se pudo inicializar logger: {:?}", err);
-        }
-    });
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Zone {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    devices: Vec<String>,
 }
 
-fn app_config() -> &'static AppConfig {
-    APP_CONFIG.get_or_init(load_or_create_config)
+/// Plano del sitio: imagen (ruta absoluta o relativa a `DATA_DIR`) y posición de cada equipo.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct FloorplanConfig {
+    #[serde(default)]
+    image_path: String,
+    #[serde(default)]
+    markers: Vec<FloorplanMarker>,
 }
 
-fn load_or_create_config() -> AppConfig {
-    let path = Path::new(CONFIG_PATH);
-    match fs::read_to_string(path) {
-        Ok(contents) if !contents.trim().is_empty() => match serde_yaml::from_str(&contents) {
-            Ok(cfg) => cfg,
-            Err(err) => {
-                error!("[CONFIG] Error al parsear {}: {:?}", CONFIG_PATH, err);
-                persist_default_config(path)
-            }
-        },
-        _ => persist_default_config(path),
-    }
+/// `x`/`y` normalizados (0–1 desde la esquina superior izquierda) para no depender de la resolución.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct FloorplanMarker {
+    device: String,
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    label: String,
 }
 
-fn persist_default_config(path: &Path) -> AppConfig {
-    let default_cfg = AppConfig::default();
-    if let Some(parent) = path.parent() {
-        if let Err(err) = fs::create_dir_all(parent) {
-            error!("[CONFIG] No se pudo crear carpeta {:?}: {:?}", parent, err);
-            return default_cfg;
-        }
-    }
+/// Guardias en orden de prioridad; sin acuse en `ACK_TIMEOUT_MINUTES` se escala al siguiente contacto.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OnCallConfig {
+    #[serde(default)]
+    schedule: Vec<OnCallShift>,
+    #[serde(default = "default_on_call_ack_timeout_minutes")]
+    ack_timeout_minutes: u64,
+    #[serde(default)]
+    sync_from_platform: bool,
+}
 
-    match serde_yaml::to_string(&default_cfg) {
-        Ok(yaml) => {
-            if let Err(err) = fs::write(path, yaml) {
-                error!("[CONFIG] No se pudo escribir {:?}: {:?}", path, err);
-            }
+impl Default for OnCallConfig {
+    fn default() -> Self {
+        Self {
+            schedule: Vec::new(),
+            ack_timeout_minutes: default_on_call_ack_timeout_minutes(),
+            sync_from_platform: false,
         }
-        Err(err) => error!("[CONFIG] No se pudo serializar config por defecto: {:?}", err),
     }
-
-    default_cfg
 }
 
-fn mute_duration() -> Duration {
-    Duration::from_secs(app_config().mute_duration.max(1))
+/// `days` usa 1 = lunes … 7 = domingo (vacío = todos) y se refiere al día en que empieza la guardia;
+/// `start`/`end` en HH:MM, vacíos = todo el día. `holidays` decide si la guardia aplica en feriados.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OnCallShift {
+    name: String,
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    days: Vec<u32>,
+    #[serde(default)]
+    start: String,
+    #[serde(default)]
+    end: String,
+    #[serde(default)]
+    holidays: HolidayRule,
 }
 
-fn is_buzzer_enabled() -> bool {
-    app_config().buzzer_enabled
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum HolidayRule {
+    /// Igual que cualquier otro día.
+    #[default]
+    Any,
+    /// Sólo en feriados (planta sin personal).
+    Only,
+    /// Nunca en feriados.
+    Skip,
 }
 
-fn buzzer_gpio_cache() -> &'static Mutex<Option<(String, String)>> {
-    BUZZER_GPIO_CACHE.get_or_init(|| Mutex::new(None))
+impl OnCallShift {
+    fn covers(&self, at: &DateTime<Tz>) -> bool {
+        weekly_window_covers(&self.days, &self.start, &self.end, self.holidays, at)
+    }
 }
 
-fn is_shutting_down() -> bool {
-    SHUTDOWN.load(Ordering::SeqCst)
+/// Franja semanal de guardias y rutas: `days` 1-7 (vacío = todos) cuentan el día en que empieza,
+/// `start`/`end` HH:MM (vacíos = todo el día).
+fn weekly_window_covers(
+    days: &[u32],
+    start: &str,
+    end: &str,
+    holidays: HolidayRule,
+    at: &DateTime<Tz>,
+) -> bool {
+    let start_day = match (schedule::parse_hhmm(start), schedule::parse_hhmm(end)) {
+        (Some(start), Some(end)) => schedule::daily_range_start(at, start, end),
+        _ if start.trim().is_empty() && end.trim().is_empty() => Some(at.date_naive()),
+        _ => None,
+    };
+    start_day.is_some_and(|day| {
+        let holiday_ok = match holidays {
+            HolidayRule::Any => true,
+            HolidayRule::Only => holiday_calendar().contains(day),
+            HolidayRule::Skip => !holiday_calendar().contains(day),
+        };
+        holiday_ok && (days.is_empty() || days.contains(&day.weekday().number_from_monday()))
+    })
 }
 
-fn next_retry_delay(current: Duration) -> Duration {
-    (current * 2).min(MQTT_MAX_RETRY_DELAY)
+/// Matriz severidad × canal × horario que consultan las notificaciones de alertas. La primera ruta
+/// que coincide decide; sin coincidencia se envía (siguen rigiendo los `min_severity` de cada canal).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct NotificationRoutingConfig {
+    #[serde(default)]
+    routes: Vec<NotificationRoute>,
+    #[serde(default)]
+    sync_from_platform: bool,
 }
 
-fn sleep_with_shutdown(total: Duration) {
-    let mut elapsed = Duration::ZERO;
-    while elapsed < total {
-        if is_shutting_down() {
-            break;
-        }
-        let remaining = total.saturating_sub(elapsed);
-        let slice = if remaining < SLEEP_CHUNK {
-            remaining
-        } else {
-            SLEEP_CHUNK
-        };
-        if slice.is_zero() {
-            break;
-        }
-        thread::sleep(slice);
-        elapsed = elapsed.saturating_add(slice);
-    }
+/// `severities` y `channels` vacíos = todos; el horario sigue las reglas de `OnCallShift`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct NotificationRoute {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    severities: Vec<AlertSeverity>,
+    #[serde(default)]
+    channels: Vec<NotificationChannel>,
+    #[serde(default)]
+    days: Vec<u32>,
+    #[serde(default)]
+    start: String,
+    #[serde(default)]
+    end: String,
+    #[serde(default)]
+    holidays: HolidayRule,
+    action: RouteAction,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum AlertType {
-    #[serde(rename = "disconnect")]
-    Disconnect,
-    #[serde(rename = "tempUp")]
-    TempUp,
-    #[serde(rename = "tempDown")]
-    TempDown,
+impl NotificationRoute {
+    fn matches(
+        &self,
+        severity: AlertSeverity,
+        channel: NotificationChannel,
+        at: &DateTime<Tz>,
+    ) -> bool {
+        (self.severities.is_empty() || self.severities.contains(&severity))
+            && (self.channels.is_empty() || self.channels.contains(&channel))
+            && weekly_window_covers(&self.days, &self.start, &self.end, self.holidays, at)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Alert {
-    pub id: String,
-
-    #[serde(rename = "dateTime")]
-    pub date_time: String,
-
-    #[serde(rename = "type")]
-    pub alert_type: AlertType,
-
-    pub device: String,
-    pub description: String,
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum NotificationChannel {
+    Email,
+    Mqtt,
+    Webhook,
+    Pagerduty,
+    Opsgenie,
+    Sms,
 }
 
-#[derive(Debug, Deserialize)]
-struct AlarmRpcEnvelope {
-    method: String,
-    params: AlarmParams,
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum RouteAction {
+    Send,
+    Suppress,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-struct AlarmParams {
-    id: AlarmEntityId,
-    created_time: i64,
-    #[serde(rename = "type")]
-    alarm_type: String,
-    originator_name: String,
-    status: AlarmStatus,
+/// Canales de notificación saliente: correo (vía `SMTP`) y telemetría MQTT hacia la plataforma.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct NotificationConfig {
     #[serde(default)]
-    details: Option<AlarmDetails>,
+    email_to: Vec<String>,
+    #[serde(default = "default_notification_mqtt")]
+    mqtt: bool,
+    /// Notifica alertas nuevas desde esta severidad; sin valor no se notifican alertas.
+    #[serde(default)]
+    min_severity: Option<AlertSeverity>,
+    /// Clave HMAC de los tokens de acción remota; vacía deshabilita reconocer desde la notificación.
+    #[serde(default)]
+    action_secret: String,
+    #[serde(default = "default_action_ttl_minutes")]
+    action_ttl_minutes: u64,
+    /// Máximo de notificaciones por (dispositivo, tipo) dentro de la ventana; 0 sin límite.
+    #[serde(default = "default_throttle_max")]
+    throttle_max: u32,
+    #[serde(default = "default_throttle_window_minutes")]
+    throttle_window_minutes: u64,
+    /// Cada cuánto se envía el resumen de notificaciones suprimidas.
+    #[serde(default = "default_digest_interval_minutes")]
+    digest_interval_minutes: u64,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct AlarmEntityId {
-    #[serde(rename = "id")]
-    value: String,
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            email_to: Vec::new(),
+            mqtt: default_notification_mqtt(),
+            min_severity: None,
+            action_secret: String::new(),
+            action_ttl_minutes: default_action_ttl_minutes(),
+            throttle_max: default_throttle_max(),
+            throttle_window_minutes: default_throttle_window_minutes(),
+            digest_interval_minutes: default_digest_interval_minutes(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct AlarmDetails {
+/// Confirmación periódica de presencia mientras haya alertas CRITICAL activas.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PresenceCheckConfig {
     #[serde(default)]
-    data: Option<String>,
+    enabled: bool,
+    #[serde(default = "default_presence_interval_minutes")]
+    interval_minutes: u64,
+    #[serde(default = "default_presence_grace_minutes")]
+    grace_minutes: u64,
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-enum AlarmStatus {
-    ActiveUnack,
-    ClearedUnack,
-    #[serde(other)]
-    Unknown,
+impl Default for PresenceCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: default_presence_interval_minutes(),
+            grace_minutes: default_presence_grace_minutes(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct SupabaseUpdatePayload {
-    commit_timestamp: String,
-    new: SupabaseNewData,
+/// Turno con nombre; si no hay ninguno se usan las horas de `SHIFT_START_HOURS`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ShiftDefinition {
+    name: String,
+    start: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct SupabaseNewData {
-    message: String,
+/// Impresora térmica ESC/POS junto al panel: imprime un ticket al activarse una alarma desde
+/// `min_severity` y al generar la entrega de turno. `device` es la impresora USB (`/dev/usb/lp0`) o
+/// un puerto serie, que se configura con `baud_rate`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PrinterConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_printer_device")]
+    device: String,
+    #[serde(default)]
+    baud_rate: Option<u32>,
+    /// Caracteres por renglón: 32 en papel de 58 mm, 42 o 48 en 80 mm.
+    #[serde(default = "default_printer_width")]
+    width: usize,
+    /// Sin valor no se imprimen alarmas.
+    #[serde(default = "default_printer_min_severity")]
+    min_severity: Option<AlertSeverity>,
+    #[serde(default = "default_printer_handover")]
+    handover: bool,
 }
 
-#[derive(Debug, Serialize, Clone)]
-struct DeviceStatusUpdate {
-    timestamp: String,
-    status: Vec<u8>,
+impl Default for PrinterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            device: default_printer_device(),
+            baud_rate: None,
+            width: default_printer_width(),
+            min_severity: default_printer_min_severity(),
+            handover: default_printer_handover(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct AlertRemovalEvent {
-    id: String,
+fn default_printer_device() -> String {
+    "/dev/usb/lp0".to_string()
 }
 
-struct BuzzerController {
-    handle: Option<JoinHandle<()>>,
+fn default_printer_width() -> usize {
+    42
 }
 
-impl Default for BuzzerController {
-    fn default() -> Self {
-        Self { handle: None }
+fn default_printer_min_severity() -> Option<AlertSeverity> {
+    Some(AlertSeverity::Critical)
+}
+
+fn default_printer_handover() -> bool {
+    true
+}
+
+/// Servidor de correo saliente; sin `host` el envío queda deshabilitado.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SmtpConfig {
+    #[serde(default)]
+    host: String,
+    #[serde(default = "default_smtp_port")]
+    port: u16,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    from: String,
+    #[serde(default = "default_smtp_starttls")]
+    starttls: bool,
+    /// Tiene prioridad sobre `starttls`, que se mantiene por compatibilidad.
+    #[serde(default)]
+    tls: Option<SmtpTls>,
+    #[serde(default = "default_smtp_max_attempts")]
+    max_attempts: u32,
+    #[serde(default = "default_smtp_retry_max_minutes")]
+    retry_max_minutes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SmtpTls {
+    Starttls,
+    /// TLS desde la conexión (SMTPS, normalmente puerto 465).
+    Implicit,
+    /// Sin cifrar; sólo para relays locales.
+    None,
+}
+
+impl SmtpConfig {
+    fn security(&self) -> SmtpTls {
+        self.tls.unwrap_or(if self.starttls {
+            SmtpTls::Starttls
+        } else {
+            SmtpTls::Implicit
+        })
     }
 }
 
-struct MuteController {
-    muted: bool,
-    deadline: Option<SystemTime>,
-    timer: Option<JoinHandle<()>>,
+/// Origen de la contraseña MQTT: la estática de la config o un JWT de corta duración
+/// obtenido por client credentials (se envía como contraseña con `MQTT_USERNAME`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct MqttAuthConfig {
+    #[serde(default)]
+    mode: MqttAuthMode,
+    #[serde(default)]
+    token_url: String,
+    #[serde(default)]
+    client_id: String,
+    #[serde(default)]
+    client_secret: String,
+    #[serde(default)]
+    scope: String,
+    #[serde(default)]
+    audience: String,
+    /// Antelación con la que se renueva el token y se reconecta antes de que venza.
+    #[serde(default = "default_token_refresh_margin_seconds")]
+    refresh_margin_seconds: u64,
 }
 
-impl Default for MuteController {
+impl Default for MqttAuthConfig {
     fn default() -> Self {
         Self {
-            muted: false,
-            deadline: None,
-            timer: None,
+            mode: MqttAuthMode::default(),
+            token_url: String::new(),
+            client_id: String::new(),
+            client_secret: String::new(),
+            scope: String::new(),
+            audience: String::new(),
+            refresh_margin_seconds: default_token_refresh_margin_seconds(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
-struct MuteStatePayload {
-    muted: bool,
-    #[serde(rename = "expiresAt")]
-    expires_at: Option<String>,
+/// Reenvío del log a un colector central (syslog/rsyslog o Loki), con buffer durante cortes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct LogForwardingConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    target: LogForwardTarget,
+    /// Syslog: `udp://host:514` o `tcp://host:601`; Loki: URL base (`http://loki:3100`).
+    #[serde(default)]
+    endpoint: String,
+    #[serde(default = "default_log_forward_level")]
+    min_level: String,
+    /// Registros retenidos mientras el destino no responde; se descartan los más viejos.
+    #[serde(default = "default_log_forward_buffer")]
+    buffer: usize,
+    /// Etiquetas adicionales del stream de Loki.
+    #[serde(default)]
+    labels: HashMap<String, String>,
 }
 
-fn with_alert_store<F, R>(f: F) -> R
-where
-    F: FnOnce(&mut HashMap<String, Alert>) -> R,
-{
-    let store = ALERT_STORE.get_or_init(|| Mutex::new(HashMap::new()));
-    let mut guard = store
-        .lock()
-        .unwrap_or_else(|poisoned| poisoned.into_inner());
-    f(&mut guard)
+impl Default for LogForwardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: LogForwardTarget::default(),
+            endpoint: String::new(),
+            min_level: default_log_forward_level(),
+            buffer: default_log_forward_buffer(),
+            labels: HashMap::new(),
+        }
+    }
 }
 
-fn with_buzzer_controller<F, R>(f: F) -> R
-where
-    F: FnOnce(&mut BuzzerController) -> R,
-{
-    let controller = BUZZER_CONTROLLER.get_or_init(|| Mutex::new(BuzzerController::default()));
-    let mut guard = controller
-        .lock()
-        .unwrap_or_else(|poisoned| poisoned.into_inner());
-    f(&mut guard)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum LogForwardTarget {
+    #[default]
+    Syslog,
+    Loki,
 }
 
-fn with_mute_controller<F, R>(f: F) -> R
-where
-    F: FnOnce(&mut MuteController) -> R,
-{
-    let controller = MUTE_CONTROLLER.get_or_init(|| Mutex::new(MuteController::default()));
-    let mut guard = controller
-        .lock()
-        .unwrap_or_else(|poisoned| poisoned.into_inner());
-    f(&mut guard)
+fn default_log_forward_level() -> String {
+    "info".to_string()
 }
 
-fn snapshot_mute_state() -> MuteStatePayload {
-    with_mute_controller(|ctrl| MuteStatePayload {
-        muted: ctrl.muted,
-        expires_at: format_deadline(ctrl.deadline),
-    })
+fn default_log_forward_buffer() -> usize {
+    5000
 }
 
-fn format_deadline(deadline: Option<SystemTime>) -> Option<String> {
-    deadline.map(|ts| {
-        let datetime: chrono::DateTime<Utc> = ts.into();
-        datetime.to_rfc3339_opts(SecondsFormat::Secs, true)
-    })
+/// Trazas del pipeline de alarmas exportadas por OTLP/HTTP; requiere compilar con la feature `otel`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OtelConfig {
+    #[serde(default)]
+    enabled: bool,
+    /// URL del colector (`http://collector:4318`); se completa con `/v1/traces` si falta.
+    #[serde(default = "default_otel_endpoint")]
+    endpoint: String,
+    #[serde(default = "default_otel_service_name")]
+    service_name: String,
 }
 
-fn emit_mute_state(app_handle: &tauri::AppHandle, payload: &MuteStatePayload) {
-    if let Err(err) = app_handle.emit(MUTE_CHANGED_EVENT, payload) {
-        warn!("[MUTE] No se pudo emitir estado mute: {:?}", err);
+impl Default for OtelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: default_otel_endpoint(),
+            service_name: default_otel_service_name(),
+        }
     }
 }
 
-fn cancel_mute_timer(ctrl: &mut MuteController) {
-    if let Some(handle) = ctrl.timer.take() {
-        handle.abort();
-    }
+fn default_otel_endpoint() -> String {
+    "http://localhost:4318".to_string()
 }
 
-fn schedule_mute_timer(app_handle: &tauri::AppHandle) -> JoinHandle<()> {
-    let app_handle = app_handle.clone();
-    async_runtime::spawn(async move {
-        tokio::time::sleep(mute_duration()).await;
-        handle_mute_timeout(app_handle);
-    })
+fn default_otel_service_name() -> String {
+    "nxt-hmi".to_string()
 }
 
-fn handle_mute_timeout(app_handle: tauri::AppHandle) {
-    let should_emit = with_mute_controller(|ctrl| {
-        if ctrl.muted {
-            ctrl.muted = false;
-            ctrl.deadline = None;
-            ctrl.timer = None;
-            true
-        } else {
-            ctrl.timer = None;
-            false
-        }
-    });
-
-    if !should_emit {
-        return;
-    }
+/// Servidor gRPC para integradores MES (`proto/nxt_hmi.proto`); requiere compilar con la
+/// feature `grpc`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct GrpcConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_grpc_bind")]
+    bind: String,
+    /// Bearer exigido en `authorization`; vacío sólo se admite escuchando en loopback.
+    #[serde(default)]
+    token: String,
+}
 
-    if has_active_alerts() {
-        set_buzzer_state(true);
-    } else {
-        set_buzzer_state(false);
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_grpc_bind(),
+            token: String::new(),
+        }
     }
+}
 
-    let payload = snapshot_mute_state();
-    emit_mute_state(&app_handle, &payload);
+fn default_grpc_bind() -> String {
+    "127.0.0.1:50051".to_string()
 }
 
-fn has_active_alerts() -> bool {
-    with_alert_store(|store| !store.is_empty())
+/// Fotos de cámara adjuntas a las alarmas: se descargan al llegar y se guardan en `DATA_DIR/snapshots`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SnapshotConfig {
+    #[serde(default = "default_snapshots_enabled")]
+    enabled: bool,
+    #[serde(default = "default_snapshot_max_bytes")]
+    max_bytes: u64,
+    /// Pasado este tiempo se vuelve a descargar; sin red se sigue mostrando la copia vencida.
+    #[serde(default = "default_snapshot_ttl_hours")]
+    ttl_hours: u64,
+    /// Tope del directorio; el mantenimiento borra primero las más viejas.
+    #[serde(default = "default_snapshot_cache_mb")]
+    max_cache_mb: u64,
+    /// Hosts de los que se aceptan fotos, siempre por https; vacío no acepta ninguno.
+    #[serde(default)]
+    allowed_hosts: Vec<String>,
 }
 
-fn force_unmute(app_handle: &tauri::AppHandle) -> Option<MuteStatePayload> {
-    let changed = with_mute_controller(|ctrl| {
-        if ctrl.muted || ctrl.deadline.is_some() || ctrl.timer.is_some() {
-            ctrl.muted = false;
-            ctrl.deadline = None;
-            cancel_mute_timer(ctrl);
-            true
-        } else {
-            false
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_snapshots_enabled(),
+            max_bytes: default_snapshot_max_bytes(),
+            ttl_hours: default_snapshot_ttl_hours(),
+            max_cache_mb: default_snapshot_cache_mb(),
+            allowed_hosts: Vec::new(),
         }
-    });
-
-    if changed {
-        let payload = snapshot_mute_state();
-        emit_mute_state(app_handle, &payload);
-        Some(payload)
-    } else {
-        None
     }
 }
 
-fn mute_alerts_internal(app_handle: &tauri::AppHandle) -> MuteStatePayload {
-    let expires_at = SystemTime::now()
-        .checked_add(mute_duration())
-        .unwrap_or_else(|| SystemTime::now());
-    let timer = schedule_mute_timer(app_handle);
+fn default_snapshots_enabled() -> bool {
+    true
+}
 
-    with_mute_controller(|ctrl| {
-        cancel_mute_timer(ctrl);
-        ctrl.muted = true;
-        ctrl.deadline = Some(expires_at);
-        ctrl.timer = Some(timer);
-    });
+fn default_snapshot_max_bytes() -> u64 {
+    2 * 1024 * 1024
+}
 
-    set_buzzer_state(false);
+fn default_snapshot_ttl_hours() -> u64 {
+    72
+}
 
-    let payload = snapshot_mute_state();
-    emit_mute_state(app_handle, &payload);
-    payload
+fn default_snapshot_cache_mb() -> u64 {
+    100
 }
 
-fn handle_alert_activation_side_effects(app_handle: &tauri::AppHandle) {
-    let mut unmuted = false;
-    with_mute_controller(|ctrl| {
-        if ctrl.muted {
-            ctrl.muted = false;
-            ctrl.deadline = None;
-            cancel_mute_timer(ctrl);
-            unmuted = true;
-        }
-    });
+/// Reconocimiento de un toque salvo para `confirm_severities`, que exigen confirmar con un motivo.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AckPolicyConfig {
+    #[serde(default)]
+    confirm_severities: Vec<AlertSeverity>,
+    #[serde(default = "default_ack_min_reason_chars")]
+    min_reason_chars: usize,
+}
 
-    if unmuted {
-        let payload = snapshot_mute_state();
-        emit_mute_state(app_handle, &payload);
+impl Default for AckPolicyConfig {
+    fn default() -> Self {
+        Self {
+            confirm_severities: Vec::new(),
+            min_reason_chars: default_ack_min_reason_chars(),
+        }
     }
+}
 
-    set_buzzer_state(true);
+fn default_ack_min_reason_chars() -> usize {
+    5
 }
 
-fn handle_no_active_alerts(app_handle: &tauri::AppHandle) {
-    let mut changed = false;
-    with_mute_controller(|ctrl| {
-        if ctrl.muted || ctrl.deadline.is_some() || ctrl.timer.is_some() {
-            ctrl.muted = false;
-            ctrl.deadline = None;
-            cancel_mute_timer(ctrl);
-            changed = true;
-        }
-    });
+/// Identidad de las alertas de la plataforma. Con `originator_type` una alarma que ThingsBoard
+/// despeja y vuelve a crear con otro UUID sigue siendo la misma alerta del panel; el despeje espera
+/// `clear_grace_secs` por si llega la re-creación.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AlertIdentityConfig {
+    #[serde(default)]
+    mode: AlertIdentityMode,
+    #[serde(default = "default_alert_clear_grace_secs")]
+    clear_grace_secs: u64,
+}
 
-    if changed {
-        let payload = snapshot_mute_state();
-        emit_mute_state(app_handle, &payload);
+impl Default for AlertIdentityConfig {
+    fn default() -> Self {
+        Self {
+            mode: AlertIdentityMode::default(),
+            clear_grace_secs: default_alert_clear_grace_secs(),
+        }
     }
+}
 
-    set_buzzer_state(false);
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum AlertIdentityMode {
+    /// Una alerta por UUID de alarma.
+    #[default]
+    AlarmId,
+    /// Una alerta por equipo y tipo de alarma.
+    OriginatorType,
 }
 
-fn snapshot_alerts() -> Vec<Alert> {
-    with_alert_store(|store| store.values().cloned().collect())
+impl AlertIdentityMode {
+    fn alert_id(self, params: &AlarmParams) -> String {
+        match self {
+            AlertIdentityMode::AlarmId => params.id.value.clone(),
+            AlertIdentityMode::OriginatorType => {
+                format!("{}:{}", params.originator_name, params.alarm_type)
+            }
+        }
+    }
 }
 
-fn validate_binary_array(message: &str) -> Result<Vec<u8>> {
-    let values: Vec<u8> = serde_json::from_str(message)
-        .map_err(|e| anyhow::anyhow!("Formato JSON inválido: {}", e))?;
+fn default_alert_clear_grace_secs() -> u64 {
+    15
+}
 
-    if values.len() != BINARY_ARRAY_SIZE {
-        return Err(anyhow::anyhow!(
-            "El array debe tener exactamente {} elementos, pero tiene {}",
-            BINARY_ARRAY_SIZE,
-            values.len()
-        ));
-    }
+/// Prueba semanal de buzzer/baliza que exigen los procedimientos de mantenimiento del sistema de alarma.
+/// `weekday` usa 1 = lunes … 7 = domingo y `time` es HH:MM en hora de la planta.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AudibleTestConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_audible_test_weekday")]
+    weekday: u32,
+    #[serde(default = "default_audible_test_time")]
+    time: String,
+    #[serde(default = "default_audible_test_duration_secs")]
+    duration_secs: u64,
+    /// Nombres de salida (`buzzer`, `strobe` o externas); vacío = todas las habilitadas.
+    #[serde(default)]
+    outputs: Vec<String>,
+    /// Sin entrada de realimentación sólo se comprueba que las salidas se pudieron escribir.
+    #[serde(default)]
+    feedback: Option<AudibleFeedbackConfig>,
+}
 
-    for (index, &value) in values.iter().enumerate() {
-        if value != 0 && value != 1 {
-            return Err(anyhow::anyhow!(
-                "El elemento en posición {} tiene valor {}, debe ser 0 o 1",
-                index, value
-            ));
+impl Default for AudibleTestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            weekday: default_audible_test_weekday(),
+            time: default_audible_test_time(),
+            duration_secs: default_audible_test_duration_secs(),
+            outputs: Vec::new(),
+            feedback: None,
         }
     }
-
-    Ok(values)
 }
 
-fn parse_supabase_timestamp(timestamp: &str) -> String {
-    let guatemala_tz = FixedOffset::west_opt(6 * 3600).unwrap_or_else(|| FixedOffset::west_opt(0).unwrap());
-    
-    match timestamp.parse::<DateTime<Utc>>() {
-        Ok(utc_time) => utc_time
-            .with_timezone(&guatemala_tz)
-            .format("%Y-%m-%d %H:%M:%S")
-            .to_string(),
-        Err(_) => Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-    }
-}
-
-fn cache_alert(alert: &Alert) {
-    let alert_clone = alert.clone();
-    with_alert_store(|store| {
-        store.insert(alert_clone.id.clone(), alert_clone);
-    });
+/// Entrada que confirma que la sirena se energizó: una línea GPIO por nombre (activa en 1) o un
+/// archivo sysfs, como el ADC de un sensor de corriente, cuyo valor debe alcanzar `threshold`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AudibleFeedbackConfig {
+    #[serde(default)]
+    gpio: String,
+    #[serde(default)]
+    path: String,
+    #[serde(default = "default_audible_feedback_threshold")]
+    threshold: f64,
 }
 
-fn remove_alert_by_id(id: &str) -> Option<Alert> {
-    with_alert_store(|store| store.remove(id))
+/// Relectura tras escribir una salida de señalización. Sin `feedback` se relee la propia línea
+/// (`enable` del canal PWM o `gpioget --as-is`, libgpiod 2); sólo una entrada de realimentación
+/// detecta además un transistor de mando pegado. Si no coincide se reescribe una vez y luego se
+/// informa como fallo de hardware.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OutputVerificationConfig {
+    #[serde(default)]
+    enabled: bool,
+    /// Espera entre la escritura y la lectura (relés, filtros del ADC).
+    #[serde(default = "default_output_verification_settle_ms")]
+    settle_ms: u64,
+    /// Entre lecturas de una misma salida; 0 = después de cada escritura.
+    #[serde(default = "default_output_verification_interval_secs")]
+    min_interval_secs: u64,
+    /// Entrada por nombre de salida (`buzzer`, `strobe` o externas).
+    #[serde(default)]
+    feedback: BTreeMap<String, AudibleFeedbackConfig>,
 }
 
-fn format_timestamp_ms(ts_ms: i64) -> String {
-    if let Some(datetime) = chrono::DateTime::<Utc>::from_timestamp_millis(ts_ms) {
-        datetime
-            .with_timezone(&Local)
-            .format("%d/%m/%Y %H:%M:%S")
-            .to_string()
-    } else {
-        Local::now().format("%d/%m/%Y %H:%M:%S").to_string()
+impl Default for OutputVerificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            settle_ms: default_output_verification_settle_ms(),
+            min_interval_secs: default_output_verification_interval_secs(),
+            feedback: BTreeMap::new(),
+        }
     }
 }
 
-fn map_alert_type(source: &str) -> AlertType {
-    match source {
-        "Temperature out of range" => AlertType::TempUp,
-        "Inactivity TimeOut" => AlertType::Disconnect,
-        _ => AlertType::TempUp,
-    }
+fn default_output_verification_settle_ms() -> u64 {
+    20
 }
 
-fn map_description(source: &str, details: Option<&AlarmDetails>) -> String {
-    match source {
-        "Temperature out of range" => details
-            .and_then(|d| d.data.clone())
-            .unwrap_or_else(|| "Temperatura fuera de rango".to_string()),
-        "Inactivity TimeOut" => "Dispositivo desconectado".to_string(),
-        _ => "Detalle no disponible".to_string(),
-    }
+fn default_output_verification_interval_secs() -> u64 {
+    5
 }
 
-fn alert_from_params(params: &AlarmParams) -> Alert {
-    Alert {
-        id: params.id.value.clone(),
-        date_time: format_timestamp_ms(params.created_time),
-        alert_type: map_alert_type(&params.alarm_type),
-        device: params.originator_name.clone(),
-        description: map_description(&params.alarm_type, params.details.as_ref()),
-    }
+fn default_audible_test_weekday() -> u32 {
+    1
 }
 
-fn emit_alert_added(app_handle: &tauri::AppHandle, alert: &Alert) {
-    if let Err(err) = app_handle.emit(ALERT_ADDED_EVENT, alert) {
-        warn!(
-            "[ALERT] No se pudo emitir evento de alerta agregada {}: {:?}",
-            alert.id, err
-        );
-    }
+fn default_audible_test_time() -> String {
+    "10:00".to_string()
 }
 
-fn emit_alert_removed(app_handle: &tauri::AppHandle, id: &str) {
-    let payload = AlertRemovalEvent { id: id.to_string() };
-    if let Err(err) = app_handle.emit(ALERT_REMOVED_EVENT, &payload) {
-        warn!(
-            "[ALERT] No se pudo emitir evento de alerta eliminada {}: {:?}",
-            id, err
-        );
-    }
+fn default_audible_test_duration_secs() -> u64 {
+    5
 }
 
-fn handle_active_alarm(params: AlarmParams, app_handle: &tauri::AppHandle) {
-    let alert = alert_from_params(&params);
-    info!(
-        "[ALERT] ACTIVADA {} tipo={} dispositivo={}",
-        alert.id, params.alarm_type, params.originator_name
-    );
-    cache_alert(&alert);
-    handle_alert_activation_side_effects(app_handle);
-    emit_alert_added(app_handle, &alert);
+fn default_audible_feedback_threshold() -> f64 {
+    1.0
 }
 
-fn handle_cleared_alarm(params: AlarmParams, app_handle: &tauri::AppHandle) {
-    let alert_id = params.id.value;
-    if remove_alert_by_id(&alert_id).is_some() {
-        info!(
-            "[ALERT] LIBERADA {} tipo={} dispositivo={}",
-            alert_id, params.alarm_type, params.originator_name
-        );
-        emit_alert_removed(app_handle, &alert_id);
-        if !has_active_alerts() {
-            handle_no_active_alerts(app_handle);
-        }
-    } else {
-        debug!(
-            "[ALERT] Se recibió CLEAR para {}, pero no existe en cache",
-            alert_id
-        );
-    }
+fn default_system_fault_enabled() -> bool {
+    true
 }
 
-fn handle_rpc_payload(payload: &[u8], app_handle: &tauri::AppHandle) {
-    let envelope: AlarmRpcEnvelope = match serde_json::from_slice(payload) {
-        Ok(data) => data,
-        Err(err) => {
-            warn!("[MQTT] No se pudo parsear payload RPC: {:?}", err);
-            return;
-        }
-    };
+fn default_system_fault_grace_secs() -> u64 {
+    60
+}
 
-    if !envelope.method.eq_ignore_ascii_case("ALARM") {
-        debug!(
-            "[MQTT] Método RPC ignorado: {}",
-            envelope.method
-        );
-        return;
-    }
+fn default_system_fault_on_ms() -> u64 {
+    150
+}
 
-    match envelope.params.status {
-        AlarmStatus::ActiveUnack => handle_active_alarm(envelope.params, app_handle),
-        AlarmStatus::ClearedUnack => handle_cleared_alarm(envelope.params, app_handle),
-        AlarmStatus::Unknown => {
-            warn!("[MQTT] Estado de alarma no manejado, se ignora payload.");
-        }
-    }
+fn default_system_fault_off_ms() -> u64 {
+    350
 }
 
-fn handle_supabase_update(payload: &SupabaseUpdatePayload, app_handle: &tauri::AppHandle) {
-    match validate_binary_array(&payload.new.message) {
-        Ok(binary_array) => {
-            let timestamp = parse_supabase_timestamp(&payload.commit_timestamp);
-            let update = DeviceStatusUpdate {
-                timestamp: timestamp.clone(),
-                status: binary_array.clone(),
-            };
+fn default_cert_warning_days() -> i64 {
+    CERT_EXPIRY_WARNING_DAYS
+}
 
-            info!(
-                "[SUPABASE] Estado actualizado: {:?} en {}",
-                binary_array, timestamp
-            );
+fn default_cert_check_interval_hours() -> u64 {
+    6
+}
 
-            process_refrigerator_alarms(&binary_array, app_handle);
+/// Fallos simulados en buzzer/strobe/backlight para probar cómo se degrada el pipeline de alertas.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct FaultInjectionConfig {
+    #[serde(default)]
+    enabled: bool,
+    /// Probabilidad (0-1) de que falle cada operación.
+    #[serde(default = "default_fault_failure_rate")]
+    failure_rate: f64,
+    /// Salidas afectadas (`onboard`, `strobe`, `backlight`…); vacío afecta a todas.
+    #[serde(default)]
+    outputs: Vec<String>,
+}
 
-            if let Err(err) = app_handle.emit(DEVICE_STATUS_EVENT, &update) {
-                warn!(
-                    "[SUPABASE] No se pudo emitir evento de actualización: {:?}",
-                    err
-                );
-            }
-        }
-        Err(err) => {
-            error!("[SUPABASE] Validación fallida: {}. Mensaje: {}", err, payload.new.message);
+impl Default for FaultInjectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failure_rate: default_fault_failure_rate(),
+            outputs: Vec::new(),
         }
     }
 }
 
-fn process_refrigerator_alarms(binary_array: &[u8], app_handle: &tauri::AppHandle) {
-    let store = REFRIGERATOR_ALARM_STATE.get_or_init(|| Mutex::new(vec![0; BINARY_ARRAY_SIZE]));
-    let mut state_guard = store
-        .lock()
-        .unwrap_or_else(|poisoned| poisoned.into_inner());
-    
-    let previous_state = state_guard.clone();
-    *state_guard = binary_array.to_vec();
-    
-    for (index, (&current_value, &previous_value)) in binary_array.iter().zip(previous_state.iter()).enumerate() {
-        if index >= REFRIGERATOR_NAMES.len() {
-            break;
-        }
-        
-        if current_value == previous_value {
-            continue;
-        }
-        
-        let device_name = REFRIGERATOR_NAMES[index];
-        let alert_id = format!("refrigerator-temp-{}", index);
-        
-        if current_value == 1 && previous_value == 0 {
-            let alert = Alert {
-                id: alert_id.clone(),
-                date_time: Local::now().format("%d/%m/%Y %H:%M:%S").to_string(),
-                alert_type: AlertType::TempUp,
-                device: device_name.to_string(),
-                description: TEMPERATURE_ALARM_DESCRIPTION.to_string(),
-            };
-            
-            info!(
-                "[REFRIGERATOR] ACTIVADA {} tipo={} dispositivo={}",
-                alert.id, TEMPERATURE_ALARM_TYPE, device_name
-            );
-            cache_alert(&alert);
-            handle_alert_activation_side_effects(app_handle);
-            emit_alert_added(app_handle, &alert);
-        } else if current_value == 0 && previous_value == 1 {
-            if remove_alert_by_id(&alert_id).is_some() {
-                info!(
-                    "[REFRIGERATOR] LIBERADA {} tipo={} dispositivo={}",
-                    alert_id, TEMPERATURE_ALARM_TYPE, device_name
-                );
-                emit_alert_removed(app_handle, &alert_id);
-                if !has_active_alerts() {
-                    handle_no_active_alerts(app_handle);
-                }
-            }
+/// Falla del propio pipeline de alarmas (tarea crítica caída, historial que no graba): tras
+/// `grace_secs` las salidas suenan con un patrón propio y se avisa por los canales de
+/// notificación. Un panel mudo es más peligroso que uno ruidoso, así que viene activado.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SystemFaultConfig {
+    #[serde(default = "default_system_fault_enabled")]
+    enabled: bool,
+    #[serde(default = "default_system_fault_enabled")]
+    on_task_failure: bool,
+    #[serde(default = "default_system_fault_enabled")]
+    on_persistence_failure: bool,
+    #[serde(default = "default_system_fault_grace_secs")]
+    grace_secs: u64,
+    #[serde(default = "default_system_fault_on_ms")]
+    on_ms: u64,
+    #[serde(default = "default_system_fault_off_ms")]
+    off_ms: u64,
+}
+
+impl Default for SystemFaultConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_system_fault_enabled(),
+            on_task_failure: default_system_fault_enabled(),
+            on_persistence_failure: default_system_fault_enabled(),
+            grace_secs: default_system_fault_grace_secs(),
+            on_ms: default_system_fault_on_ms(),
+            off_ms: default_system_fault_off_ms(),
         }
     }
 }
 
-#[tauri::command]
-fn get_active_alerts() -> Vec<Alert> {
-    snapshot_alerts()
+/// Vencimiento de los certificados TLS en uso (CA y cliente del broker y del puente): con menos
+/// de `warning_days` días se levanta una alerta local.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct CertExpiryConfig {
+    #[serde(default = "default_cert_warning_days")]
+    warning_days: i64,
+    #[serde(default = "default_cert_check_interval_hours")]
+    check_interval_hours: u64,
 }
 
-#[tauri::command]
-fn remove_alert(app_handle: tauri::AppHandle, id: String) -> bool {
-    if remove_alert_by_id(&id).is_some() {
-        emit_alert_removed(&app_handle, &id);
-        if !has_active_alerts() {
-            handle_no_active_alerts(&app_handle);
+impl Default for CertExpiryConfig {
+    fn default() -> Self {
+        Self {
+            warning_days: default_cert_warning_days(),
+            check_interval_hours: default_cert_check_interval_hours(),
         }
-        true
-    } else {
-        false
     }
 }
 
-#[tauri::command]
-fn check_internet_connection() -> bool {
-    TcpStream::connect_timeout(
-        &"8.8.8.8:53".parse().unwrap(),
-        std::time::Duration::from_secs(2),
-    )
-    .is_ok()
+fn default_fault_failure_rate() -> f64 {
+    0.3
 }
 
-#[tauri::command]
-fn get_mute_status() -> MuteStatePayload {
-    snapshot_mute_state()
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum MqttAuthMode {
+    #[default]
+    Static,
+    Oauth2,
 }
 
-#[tauri::command]
-fn toggle_alerts_mute(app_handle: tauri::AppHandle) -> MuteStatePayload {
-    let currently_muted = with_mute_controller(|ctrl| ctrl.muted);
-
-    if currently_muted {
-        force_unmute(&app_handle);
-        if has_active_alerts() {
-            set_buzzer_state(true);
-        } else {
-            set_buzzer_state(false);
-        }
-        snapshot_mute_state()
-    } else {
-        if !has_active_alerts() {
-            return snapshot_mute_state();
-        }
-        mute_alerts_internal(&app_handle)
-    }
+/// Versión de MQTT con la plataforma; el puente (`MQTT_BRIDGE`) sigue en 3.1.1.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum MqttProtocol {
+    /// MQTT 3.1.1.
+    #[default]
+    V4,
+    V5,
 }
 
-fn invalidate_buzzer_line() {
-    if let Some(cache) = BUZZER_GPIO_CACHE.get() {
-        let mut guard = cache
-            .lock()
-            .unwrap_or_else(|poisoned| poisoned.into_inner());
-        *guard = None;
-    }
+/// Sólo con `MQTT_PROTOCOL: v5`. Con `session_expiry_secs` el broker conserva la sesión
+/// (suscripciones y QoS 1 pendientes) ese tiempo tras un corte; `user_properties` viajan en los
+/// mensajes RPC que publica el panel.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct MqttV5Config {
+    #[serde(default)]
+    session_expiry_secs: u32,
+    #[serde(default)]
+    user_properties: BTreeMap<String, String>,
 }
 
-fn resolve_buzzer_line() -> Option<(String, String)> {
-    if let Some(cache) = BUZZER_GPIO_CACHE.get() {
-        if let Some(pair) = cache
-            .lock()
-            .unwrap_or_else(|poisoned| poisoned.into_inner())
-            .clone()
-        {
-            return Some(pair);
-        }
-    }
-
-    let gpiofind_output = match Command::new("gpiofind").arg("BUZZER_EN").output() {
-        Ok(output) => output,
-        Err(err) => {
-            error!("[BUZZER] No se pudo ejecutar gpiofind: {:?}", err);
-            return None;
-        }
-    };
-
-    if !gpiofind_output.status.success() {
-        error!(
-            "[BUZZER] gpiofind devolvio codigo {:?}: {}",
-            gpiofind_output.status.code(),
-            String::from_utf8_lossy(&gpiofind_output.stderr)
-        );
-        return None;
-    }
+/// Transporte hacia la plataforma. `wss` encapsula MQTT en WebSocket sobre TLS (con la misma CA
+/// y certificado de cliente) para sitios que sólo dejan salir por 443.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum MqttTransport {
+    #[default]
+    Tcp,
+    Wss,
+}
 
-    let location = String::from_utf8_lossy(&gpiofind_output.stdout).to_string();
-    let mut parts = location.split_whitespace();
-    let chip = match parts.next() {
-        Some(chip) => chip.trim().to_string(),
-        None => {
-            error!("[BUZZER] gpiofind no entrego chip valido");
-            return None;
-        }
-    };
-    let line = match parts.next() {
-        Some(line) => line.trim().to_string(),
-        None => {
-            error!("[BUZZER] gpiofind no entrego linea valida");
-            return None;
-        }
-    };
+/// Raíces de confianza para el TLS del broker: la CA del archivo (importada o incluida) o el
+/// almacén del sistema operativo, para brokers con certificados de una CA pública.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum MqttCaSource {
+    #[default]
+    File,
+    System,
+}
 
-    let pair = (chip, line);
-    let cache = buzzer_gpio_cache();
-    let mut guard = cache
-        .lock()
-        .unwrap_or_else(|poisoned| poisoned.into_inner());
-    *guard = Some(pair.clone());
-    Some(pair)
+/// Protección anti-repetición de RPC de control: `nonce` único y `ts` (ms) dentro del margen,
+/// más firma HMAC opcional sobre `método|ts|nonce|params` (params en JSON canónico).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct RpcSecurityConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_rpc_max_skew_seconds")]
+    max_skew_seconds: u64,
+    /// Vacío no exige firma.
+    #[serde(default)]
+    secret: String,
+    /// Métodos protegidos (sin distinguir mayúsculas).
+    #[serde(default = "default_rpc_protected_methods")]
+    methods: Vec<String>,
 }
 
-/// Controla el estado del buzzer. Cuando se enciende, parpadea cada segundo.
-fn set_buzzer_state(on: bool) -> bool {
-    if !is_buzzer_enabled() {
-        debug!("[BUZZER] Cambio de estado ignorado (deshabilitado)");
-        if !on {
-            let _ = stop_buzzer_blinking();
+impl Default for RpcSecurityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_skew_seconds: default_rpc_max_skew_seconds(),
+            secret: String::new(),
+            methods: default_rpc_protected_methods(),
         }
-        return true;
     }
+}
 
-    let result = if on {
-        info!("[BUZZER] Activado");
-        start_buzzer_blinking()
-    } else {
-        info!("[BUZZER] Desactivado");
-        stop_buzzer_blinking()
-    };
+/// JSON Schema (subconjunto: type, properties, required, items, enum, const, límites y
+/// additionalProperties) que deben cumplir los mensajes del topic antes de procesarse.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PayloadSchema {
+    topic: String,
+    #[serde(default)]
+    schema: Option<serde_json::Value>,
+    /// Alternativa a `schema` para esquemas largos guardados en archivo.
+    #[serde(default)]
+    schema_path: Option<String>,
+}
 
-    if !result {
-        error!(
-            "[BUZZER] No se pudo cambiar estado a {}",
-            if on { "ON" } else { "OFF" }
-        );
-    }
+/// Traduce payloads de brokers ajenos a ThingsBoard a telemetría o alarmas internas.
+/// `fields` asigna a cada campo destino una expresión de extracción (ver `eval_expression`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PayloadMapping {
+    topic: String,
+    kind: MappingKind,
+    /// Ruta a un arreglo cuyos elementos se mapean por separado.
+    #[serde(default)]
+    each: Option<String>,
+    #[serde(default)]
+    fields: HashMap<String, String>,
+}
 
-    result
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum MappingKind {
+    Telemetry,
+    Alarm,
 }
 
-fn start_buzzer_blinking() -> bool {
-    if with_buzzer_controller(|ctrl| ctrl.handle.is_some()) {
-        return true;
-    }
+/// Segundo broker al que se republican mensajes recibidos (p. ej. un SCADA heredado).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct MqttBridgeConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    server: String,
+    #[serde(default = "default_bridge_port")]
+    port: u16,
+    /// Vacío usa `<MQTT_CLIENT_ID>-bridge`.
+    #[serde(default)]
+    client_id: String,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+    /// Con CA la conexión usa TLS.
+    #[serde(default)]
+    ca_path: String,
+    /// Certificado y clave PEM para brokers que exigen mTLS; sólo con `ca_path`.
+    #[serde(default)]
+    client_cert: String,
+    #[serde(default)]
+    client_key: String,
+    #[serde(default)]
+    rules: Vec<BridgeRule>,
+}
 
-    if !set_buzzer_gpio(true) {
-        return false;
+impl Default for MqttBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server: String::new(),
+            port: default_bridge_port(),
+            client_id: String::new(),
+            username: String::new(),
+            password: String::new(),
+            ca_path: String::new(),
+            client_cert: String::new(),
+            client_key: String::new(),
+            rules: Vec::new(),
+        }
     }
+}
 
-    let handle = async_runtime::spawn(async move {
-        let mut level = false;
-        let mut consecutive_failures: u8 = 0;
-        loop {
-            tokio::time::sleep(Duration::from_secs(1)).await;
-            if is_shutting_down() {
-                break;
-            }
-            level = !level;
-            if set_buzzer_gpio(level) {
-                consecutive_failures = 0;
-            } else {
-                consecutive_failures = consecutive_failures.saturating_add(1);
-                warn!("[BUZZER] Fallo al alternar nivel {}", level as u8);
-                if consecutive_failures >= BUZZER_FAILURE_LIMIT {
-                    error!(
-                        "[BUZZER] Se desactiva parpadeo tras {} errores consecutivos",
-                        BUZZER_FAILURE_LIMIT
-                    );
-                    break;
-                }
-            }
-        }
+/// `target` y `payload` son plantillas `{{campo}}`: `{{topic}}`, `{{1}}`… (comodines de `source`),
+/// `{{payload}}` y las claves del JSON recibido aplanadas con puntos (`{{values.temperature}}`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct BridgeRule {
+    source: String,
+    target: String,
+    #[serde(default)]
+    qos: u8,
+    #[serde(default)]
+    retain: bool,
+    /// Sin plantilla se reenvía el payload original; los valores se escapan como JSON.
+    #[serde(default)]
+    payload: Option<String>,
+}
 
-        let _ = set_buzzer_gpio(false);
-    });
+/// Destino HTTP de notificaciones; `body` es una plantilla `{{campo}}` con el esquema del receptor.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct WebhookTarget {
+    name: String,
+    url: String,
+    #[serde(default = "default_output_enabled")]
+    enabled: bool,
+    /// Eventos a reenviar (`alert`, `digest`…); vacío reenvía todos.
+    #[serde(default)]
+    events: Vec<String>,
+    #[serde(default)]
+    format: WebhookFormat,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
 
-    let mut handle_slot = Some(handle);
-    let was_set = with_buzzer_controller(|ctrl| {
-        if ctrl.handle.is_none() {
-            ctrl.handle = handle_slot.take();
-            true
-        } else {
-            false
-        }
-    });
+/// PagerDuty Events API v2; sin `routing_key` el canal queda deshabilitado.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PagerDutyConfig {
+    #[serde(default)]
+    routing_key: String,
+    #[serde(default = "default_pagerduty_url")]
+    url: String,
+    #[serde(default)]
+    min_severity: Option<AlertSeverity>,
+}
 
-    if !was_set {
-        if let Some(handle) = handle_slot {
-            handle.abort();
+impl Default for PagerDutyConfig {
+    fn default() -> Self {
+        Self {
+            routing_key: String::new(),
+            url: default_pagerduty_url(),
+            min_severity: None,
         }
     }
+}
 
-    true
+/// Opsgenie Alert API; sin `api_key` el canal queda deshabilitado (`url` para la región EU).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OpsgenieConfig {
+    #[serde(default)]
+    api_key: String,
+    #[serde(default = "default_opsgenie_url")]
+    url: String,
+    #[serde(default)]
+    min_severity: Option<AlertSeverity>,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
-fn stop_buzzer_blinking() -> bool {
-    if let Some(handle) = with_buzzer_controller(|ctrl| ctrl.handle.take()) {
-        handle.abort();
+impl Default for OpsgenieConfig {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            url: default_opsgenie_url(),
+            min_severity: None,
+            tags: Vec::new(),
+        }
     }
-
-    set_buzzer_gpio(false)
 }
 
-fn set_buzzer_gpio(on: bool) -> bool {
-    let level = if on { "1" } else { "0" };
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum WebhookFormat {
+    #[default]
+    Json,
+    Form,
+}
 
-    let (chip, line) = match resolve_buzzer_line() {
-        Some(pair) => pair,
-        None => return false,
-    };
+/// Plantillas `{{campo}}` por evento de notificación (`alert`, `digest`, `handover`…).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct EmailTemplate {
+    #[serde(default)]
+    subject: Option<String>,
+    #[serde(default)]
+    body: Option<String>,
+}
 
-    match Command::new("gpioset")
-        .arg(&chip)
-        .arg(format!("{}={}", line, level))
-        .status()
-    {
-        Ok(status) if status.success() => true,
-        Ok(status) => {
-            error!("[BUZZER] gpioset termino con codigo {:?}", status.code());
-            invalidate_buzzer_line();
-            false
-        }
-        Err(err) => {
-            error!("[BUZZER] No se pudo ejecutar gpioset: {:?}", err);
-            invalidate_buzzer_line();
-            false
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: default_smtp_port(),
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            starttls: default_smtp_starttls(),
+            tls: None,
+            max_attempts: default_smtp_max_attempts(),
+            retry_max_minutes: default_smtp_retry_max_minutes(),
         }
     }
 }
 
-fn request_shutdown() {
-    if !SHUTDOWN.swap(true, Ordering::SeqCst) {
-        info!("[CORE] Shutdown solicitado");
+/// Límite por antigüedad y/o cantidad de filas; `None` no aplica ese límite.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+struct RetentionPolicy {
+    #[serde(default)]
+    days: Option<u32>,
+    #[serde(default)]
+    max_rows: Option<u32>,
+}
+
+impl RetentionPolicy {
+    fn days(days: u32) -> Self {
+        Self {
+            days: Some(days),
+            max_rows: None,
+        }
+    }
+
+    fn cutoff_ms(&self, now_ms: i64) -> Option<i64> {
+        self.days.map(|days| now_ms - i64::from(days) * 86_400_000)
     }
-    MQTT_CONNECTED.store(false, Ordering::SeqCst);
-    SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
-    let _ = stop_buzzer_blinking();
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct RetentionConfig {
+    #[serde(default = "default_history_retention")]
+    alert_history: RetentionPolicy,
+    #[serde(default = "default_audit_retention")]
+    audit_log: RetentionPolicy,
+    #[serde(default = "default_telemetry_retention")]
+    telemetry: RetentionPolicy,
+    #[serde(default = "default_log_retention")]
+    logs: RetentionPolicy,
+}
 
-fn build_mqtt_options() -> Option<MqttOptions> {
-    let cfg = app_config();
-    let mut mqttoptions = MqttOptions::new(
-        cfg.mqtt_client_id.as_str(),
-        cfg.mqtt_server.as_str(),
-        cfg.mqtt_port,
-    );
-    mqttoptions.set_credentials(cfg.mqtt_username.as_str(), cfg.mqtt_password.as_str());
-    mqttoptions.set_keep_alive(Duration::from_secs(60));
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            alert_history: default_history_retention(),
+            audit_log: default_audit_retention(),
+            telemetry: default_telemetry_retention(),
+            logs: default_log_retention(),
+        }
+    }
+}
 
-    if cfg.mqtt_use_secure_client {
-        let ca_path = "certs/emqxsl-ca.crt";
-        let ca_bytes = match fs::read(ca_path) {
-            Ok(b) => b,
-            Err(e) => {
-                error!("[MQTT] No se pudo leer CA en {}: {:?}", ca_path, e);
-                return None;
-            }
-        };
-        let tls_cfg = TlsConfiguration::Simple {
-            ca: ca_bytes,
-            alpn: Some(vec![b"mqtt".to_vec()]),
-            client_auth: None,
-        };
-        mqttoptions.set_transport(Transport::tls_with_config(tls_cfg));
+/// Se elige el primer perfil con algún `match_ids` igual a un compatible del devicetree o texto de la EEPROM.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct HardwareProfile {
+    name: String,
+    #[serde(default)]
+    match_ids: Vec<String>,
+    #[serde(default = "default_buzzer_gpio")]
+    buzzer_gpio: String,
+    #[serde(default)]
+    backlight_path: String,
+    #[serde(default)]
+    pwm_chip: String,
+    #[serde(default)]
+    strobe: Option<StrobeConfig>,
+}
+
+impl Default for HardwareProfile {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_HARDWARE_PROFILE.to_string(),
+            match_ids: Vec::new(),
+            buzzer_gpio: default_buzzer_gpio(),
+            backlight_path: String::new(),
+            pwm_chip: String::new(),
+            strobe: None,
+        }
     }
+}
 
-    Some(mqttoptions)
+/// Baliza por GPIO o canal PWM; con `sync` parpadea en fase con el buzzer, si no con su propio destello.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct StrobeConfig {
+    #[serde(default)]
+    gpio: String,
+    #[serde(default)]
+    pwm_channel: Option<u32>,
+    #[serde(default = "default_strobe_duty_percent")]
+    pwm_duty_percent: u8,
+    #[serde(default = "default_strobe_sync")]
+    sync: bool,
+    #[serde(default = "default_strobe_flash_on_ms")]
+    flash_on_ms: u64,
+    #[serde(default = "default_strobe_flash_off_ms")]
+    flash_off_ms: u64,
+    #[serde(default)]
+    min_severity: Option<AlertSeverity>,
 }
 
-fn start_mqtt_loop(app_handle: tauri::AppHandle) {
-    if let Err(err) = thread::Builder::new()
-        .name("mqtt-loop".to_string())
-        .spawn(move || {
-            let mut retry_delay = MQTT_RETRY_DELAY;
-            while !is_shutting_down() {
-                MQTT_CONNECTED.store(false, Ordering::SeqCst);
+/// Salida de señalización (buzzer, sirena externa o baliza) con su propio umbral y patrones por severidad.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SignalOutput {
+    name: String,
+    #[serde(default)]
+    gpio: String,
+    #[serde(default = "default_output_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    min_severity: Option<AlertSeverity>,
+    #[serde(default)]
+    patterns: Vec<OutputPattern>,
+    /// Arranca en fase con esta salida cuando ambas tienen el mismo patrón.
+    #[serde(default)]
+    sync_with: Option<String>,
+    /// Sólo suena por alertas de equipos de estas zonas (o sin zona); vacío = todas.
+    #[serde(default)]
+    zones: Vec<String>,
+    #[serde(skip)]
+    pwm: Option<PwmLine>,
+}
 
-                let Some(mqttoptions) = build_mqtt_options() else {
-                    error!(
-                        "[MQTT] No se pudieron construir las opciones MQTT. Reintentando en {:?}...",
-                        retry_delay
-                    );
-                    sleep_with_shutdown(retry_delay);
-                    retry_delay = next_retry_delay(retry_delay);
-                    continue;
-                };
+/// Sin `severity` aplica a cualquier severidad que no tenga patrón propio.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OutputPattern {
+    #[serde(default)]
+    severity: Option<AlertSeverity>,
+    on_ms: u64,
+    off_ms: u64,
+}
 
-                let cfg = app_config();
-                info!(
-                    "[MQTT] Intentando conectar ({}) con {}:{} como {}",
-                    if cfg.mqtt_use_secure_client { "TLS" } else { "TCP" },
-                    cfg.mqtt_server.as_str(),
-                    cfg.mqtt_port,
-                    cfg.mqtt_client_id.as_str()
-                );
+/// Cómo anuncian las salidas audibles: patrón por severidad, sonido continuo o un campanazo
+/// corto cada `AUDIO_CHIME_PERIOD`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum AudioMode {
+    #[default]
+    Pattern,
+    Continuous,
+    Chime,
+}
 
-                let (client, mut connection) = Client::new(mqttoptions, 10);
+/// Perfil de audio vigente. `volume` (0-100) lo aplica el frontend a sus sonidos; los buzzers
+/// GPIO no tienen volumen. `patternSet` elige un juego de `AUDIO_PROFILE.pattern_sets` (vacío =
+/// los patrones de cada salida).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct AudioProfile {
+    #[serde(default = "default_audio_volume")]
+    volume: u8,
+    #[serde(default)]
+    pattern_set: String,
+    #[serde(default)]
+    mode: AudioMode,
+}
 
-                if let Err(err) = client.subscribe(MQTT_RPC_REQUEST_TOPIC, QoS::AtLeastOnce) {
-                    error!(
-                        "[MQTT] No se pudo suscribir a {}: {:?}. Reintentando en {:?}...",
-                        MQTT_RPC_REQUEST_TOPIC, err, retry_delay
-                    );
-                    sleep_with_shutdown(retry_delay);
-                    retry_delay = next_retry_delay(retry_delay);
-                    continue;
-                }
+/// Perfil inicial y juegos de patrones con nombre. Con `remote_control` la plataforma puede
+/// cambiar el perfil (RPC `setAudioProfile` o atributo `audioProfile`); el que fije un supervisor
+/// en el panel tiene precedencia sobre el remoto hasta que lo retire.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AudioProfileConfig {
+    #[serde(default = "default_audio_volume")]
+    volume: u8,
+    #[serde(default)]
+    pattern_set: String,
+    #[serde(default)]
+    mode: AudioMode,
+    #[serde(default)]
+    pattern_sets: BTreeMap<String, Vec<OutputPattern>>,
+    #[serde(default)]
+    remote_control: bool,
+}
 
-                info!(
-                    "[MQTT] Suscrito a solicitudes RPC en {}",
-                    MQTT_RPC_REQUEST_TOPIC
-                );
-                retry_delay = MQTT_RETRY_DELAY;
+impl Default for AudioProfileConfig {
+    fn default() -> Self {
+        Self {
+            volume: default_audio_volume(),
+            pattern_set: String::new(),
+            mode: AudioMode::default(),
+            pattern_sets: BTreeMap::new(),
+            remote_control: false,
+        }
+    }
+}
 
-                for event in connection.iter() {
-                    if is_shutting_down() {
-                        info!("[MQTT] Loop detenido por shutdown");
-                        break;
-                    }
+impl AudioProfileConfig {
+    fn profile(&self) -> AudioProfile {
+        AudioProfile {
+            volume: self.volume,
+            pattern_set: self.pattern_set.clone(),
+            mode: self.mode,
+        }
+    }
+}
 
-                    match event {
-                        Ok(Event::Incoming(Packet::Publish(publish))) => {
-                            MQTT_CONNECTED.store(true, Ordering::SeqCst);
-                            handle_rpc_payload(&publish.payload, &app_handle);
-                        }
-                        Ok(Event::Incoming(pkt)) => {
-                            MQTT_CONNECTED.store(true, Ordering::SeqCst);
-                            debug!("[MQTT] Evento entrante: {:?}", pkt);
-                        }
-                        Ok(Event::Outgoing(pkt)) => {
-                            debug!("[MQTT] Evento saliente: {:?}", pkt);
-                        }
-                        Err(e) => {
-                            error!("[MQTT] Error en loop: {:?}", e);
-                            MQTT_CONNECTED.store(false, Ordering::SeqCst);
-                            break;
-                        }
-                    }
-                }
+fn default_audio_volume() -> u8 {
+    100
+}
 
-                if is_shutting_down() {
-                    break;
-                }
+#[derive(Debug, Clone)]
+struct PwmLine {
+    chip: PathBuf,
+    channel: u32,
+    duty_percent: u8,
+}
 
-                warn!(
-                    "[MQTT] Loop MQTT finalizado. Reintentando en {:?}...",
-                    retry_delay
-                );
+/// Gana la primera regla cuyo tipo y severidad coinciden; los campos vacíos heredan el valor por defecto.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AlertDisplayRule {
+    #[serde(rename = "type", default)]
+    alert_type: Option<AlertType>,
+    #[serde(default)]
+    severity: Option<AlertSeverity>,
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    priority: Option<u8>,
+}
 
-                sleep_with_shutdown(retry_delay);
-                retry_delay = next_retry_delay(retry_delay);
-            }
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum PanelRole {
+    #[default]
+    Operator,
+    Spectator,
+}
 
-            info!("[MQTT] Loop terminado");
-        })
-    {
-        error!("[MQTT] No se pudo iniciar hilo de conexion: {:?}", err);
-    }
+/// Registro de equipos (el `device` de la alarma): nombre visible y umbrales críticos propios que
+/// reemplazan a `CRITICAL_TEMPERATURE_HIGH/LOW`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct DeviceEntry {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    critical_temperature_high: Option<f64>,
+    #[serde(default)]
+    critical_temperature_low: Option<f64>,
 }
 
-#[tauri::command]
-fn is_mqtt_connected() -> bool {
-    MQTT_CONNECTED.load(Ordering::SeqCst)
+/// Operadores del panel; `badges` son los códigos de sus credenciales (código de barras o RFID).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OperatorEntry {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    badges: Vec<String>,
+    /// SHA-256 en hex del PIN (`printf 1234 | sha256sum`); vacío = sin ingreso por PIN.
+    #[serde(default)]
+    pin_sha256: String,
+    /// Puede fijar el perfil de audio local por encima del remoto.
+    #[serde(default)]
+    supervisor: bool,
 }
 
-#[tauri::command]
-fn is_supabase_connected() -> bool {
-    SUPABASE_CONNECTED.load(Ordering::SeqCst)
+/// Bloqueo del panel tras `max_attempts` PIN incorrectos seguidos. La espera empieza en
+/// `lockout_secs` y se duplica en cada bloqueo hasta `max_lockout_secs`; un supervisor puede
+/// desbloquear con el RPC `unlockPanel` (también desde la API REST de la plataforma).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PinLockoutConfig {
+    #[serde(default = "default_pin_max_attempts")]
+    max_attempts: u32,
+    #[serde(default = "default_pin_lockout_secs")]
+    lockout_secs: u64,
+    #[serde(default = "default_pin_max_lockout_secs")]
+    max_lockout_secs: u64,
 }
 
-fn start_supabase_loop(app_handle: tauri::AppHandle) {
-    let cfg = app_config();
-    
-    if cfg.supabase_url.is_empty() || cfg.supabase_anon_key.is_empty() {
-        info!("[SUPABASE] No configurado, omitiendo inicialización");
-        return;
+impl Default for PinLockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_pin_max_attempts(),
+            lockout_secs: default_pin_lockout_secs(),
+            max_lockout_secs: default_pin_max_lockout_secs(),
+        }
     }
+}
 
-    let supabase_url = cfg.supabase_url.clone();
-    let supabase_key = cfg.supabase_anon_key.clone();
+fn default_pin_max_attempts() -> u32 {
+    5
+}
 
-    if let Err(err) = thread::Builder::new()
-        .name("supabase-loop".to_string())
-        .spawn(move || {
-            let rt = tokio::runtime::Runtime::new().unwrap_or_else(|e| {
-                error!("[SUPABASE] No se pudo crear runtime: {:?}", e);
-                panic!("Runtime error");
-            });
+fn default_pin_lockout_secs() -> u64 {
+    30
+}
 
-            rt.block_on(async {
-                let mut retry_delay = SUPABASE_RETRY_DELAY;
+fn default_pin_max_lockout_secs() -> u64 {
+    900
+}
 
-                while !is_shutting_down() {
-                    SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
+/// Cierre de sesión por inactividad: tras `idle_minutes` sin toques se avisa al frontend
+/// (`session://idle_warning`) y, si nadie toca la pantalla en `grace_secs`, la sesión termina
+/// (`session://expired`). `idle_minutes: 0` lo desactiva.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SessionIdleConfig {
+    #[serde(default = "default_session_idle_minutes")]
+    idle_minutes: u64,
+    #[serde(default = "default_session_idle_grace_secs")]
+    grace_secs: u64,
+}
+
+impl Default for SessionIdleConfig {
+    fn default() -> Self {
+        Self {
+            idle_minutes: default_session_idle_minutes(),
+            grace_secs: default_session_idle_grace_secs(),
+        }
+    }
+}
+
+fn default_session_idle_minutes() -> u64 {
+    10
+}
+
+fn default_session_idle_grace_secs() -> u64 {
+    60
+}
+
+/// Lector de credenciales USB HID. Con `device` (`/dev/input/by-id/...-event-kbd`) se lee por evdev;
+/// sin él, el frontend reenvía las teclas a `badge_key` (modo cuña de teclado). Leer la misma
+/// credencial de nuevo cierra la sesión.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct BadgeReaderConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    device: String,
+    /// Pausa máxima entre teclas de una misma lectura; un operador tecleando es más lento.
+    #[serde(default = "default_badge_max_key_gap_ms")]
+    max_key_gap_ms: u64,
+    #[serde(default = "default_badge_min_length")]
+    min_length: usize,
+    /// Cierra la sesión tras este tiempo desde el ingreso; 0 = sin límite.
+    #[serde(default = "default_operator_session_minutes")]
+    session_timeout_minutes: u64,
+}
+
+impl Default for BadgeReaderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            device: String::new(),
+            max_key_gap_ms: default_badge_max_key_gap_ms(),
+            min_length: default_badge_min_length(),
+            session_timeout_minutes: default_operator_session_minutes(),
+        }
+    }
+}
+
+fn default_badge_max_key_gap_ms() -> u64 {
+    50
+}
+
+fn default_badge_min_length() -> usize {
+    4
+}
+
+fn default_operator_session_minutes() -> u64 {
+    30
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct DefrostSchedule {
+    device: String,
+    start: String,
+    #[serde(default = "default_defrost_duration_minutes")]
+    duration_minutes: u32,
+    #[serde(default)]
+    action: DefrostAction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum DefrostAction {
+    Suppress,
+    #[default]
+    Downgrade,
+}
+
+impl DefrostSchedule {
+    /// La ventana dura `duration_minutes` reales aunque un cambio de hora caiga dentro.
+    fn contains(&self, at: &DateTime<Tz>) -> bool {
+        let Some(start) = schedule::parse_hhmm(&self.start) else {
+            debug!(
+                "[DEFROST] Hora de inicio inválida para {}: {}",
+                self.device, self.start
+            );
+            return false;
+        };
+        let length = chrono::Duration::minutes(i64::from(self.duration_minutes));
+        schedule::in_daily_window(at, start, length)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct RateOfChangeRule {
+    #[serde(default)]
+    device: Option<String>,
+    #[serde(default)]
+    max_rise: Option<f64>,
+    #[serde(default)]
+    max_fall: Option<f64>,
+    #[serde(default = "default_trend_window_minutes")]
+    window_minutes: u64,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            mqtt_server: "j0661b06.ala.us-east-1.emqxsl.com".to_string(),
+            mqtt_use_secure_client: true,
+            mqtt_port: 8883,
+            mqtt_client_id: "hmi-cli".to_string(),
+            mqtt_username: "test".to_string(),
+            mqtt_password: "test".to_string(),
+            mute_duration: 600,
+            buzzer_enabled: default_buzzer_enabled(),
+            supabase_url: String::new(),
+            supabase_anon_key: String::new(),
+            clock_skew_threshold_secs: default_clock_skew_threshold_secs(),
+            server_time: ServerTimeConfig::default(),
+            trend_window_minutes: default_trend_window_minutes(),
+            critical_temperature_high: None,
+            critical_temperature_low: None,
+            mqtt_telemetry_topic: String::new(),
+            rate_of_change_rules: Vec::new(),
+            defrost_schedules: Vec::new(),
+            devices: Vec::new(),
+            operators: Vec::new(),
+            badge_reader: BadgeReaderConfig::default(),
+            pin_lockout: PinLockoutConfig::default(),
+            session_idle: SessionIdleConfig::default(),
+            panel_id: String::new(),
+            network_monitor_enabled: default_network_monitor_enabled(),
+            network: NetworkConfig::default(),
+            modem: ModemConfig::default(),
+            peer_sync_enabled: false,
+            peer_sync_topic: default_peer_sync_topic(),
+            mdns_enabled: false,
+            mdns_port: 0,
+            mdns_endpoints: HashMap::new(),
+            data_dir: default_data_dir(),
+            disk_min_free_mb: default_disk_min_free_mb(),
+            mqtt_client_key: String::new(),
+            mqtt_client_cert: String::new(),
+            panel_role: PanelRole::default(),
+            spectator_windows: Vec::new(),
+            alert_display_rules: Vec::new(),
+            history_enabled: default_history_enabled(),
+            retention: RetentionConfig::default(),
+            maintenance_hour: default_maintenance_hour(),
+            timezone: String::new(),
+            holidays: HolidayConfig::default(),
+            log_dir: String::new(),
+            metrics_enabled: false,
+            metrics_interval_minutes: default_metrics_interval_minutes(),
+            shift_start_hours: default_shift_start_hours(),
+            remote_buzzer_inhibit_enabled: false,
+            buzzer_inhibit_max_minutes: default_buzzer_inhibit_max_minutes(),
+            hardware_revision: String::new(),
+            hardware_profiles: Vec::new(),
+            hardware_profile: default_hardware_profile_name(),
+            board_eeprom_path: String::new(),
+            audible_outputs: Vec::new(),
+            critical_sounds_all_zones: default_output_enabled(),
+            audio_profile: AudioProfileConfig::default(),
+            audible_test: AudibleTestConfig::default(),
+            ack_policy: AckPolicyConfig::default(),
+            alert_identity: AlertIdentityConfig::default(),
+            zones: ZonesConfig::default(),
+            floorplan: FloorplanConfig::default(),
+            site_pack_secret: String::new(),
+            strobe_enabled: default_output_enabled(),
+            visual_alarm_enabled: false,
+            visual_alarm_backlight_pulse: false,
+            shifts: Vec::new(),
+            smtp: SmtpConfig::default(),
+            handover_email_to: Vec::new(),
+            printer: PrinterConfig::default(),
+            email_templates: HashMap::new(),
+            email_footer: default_email_footer(),
+            webhooks: Vec::new(),
+            pagerduty: PagerDutyConfig::default(),
+            opsgenie: OpsgenieConfig::default(),
+            notifications: NotificationConfig::default(),
+            presence_check: PresenceCheckConfig::default(),
+            on_call: OnCallConfig::default(),
+            notification_routing: NotificationRoutingConfig::default(),
+            mqtt_bridge: MqttBridgeConfig::default(),
+            payload_mappings: Vec::new(),
+            payload_schemas: Vec::new(),
+            rpc_security: RpcSecurityConfig::default(),
+            mqtt_auth: MqttAuthConfig::default(),
+            mqtt_protocol: MqttProtocol::default(),
+            mqtt_v5: MqttV5Config::default(),
+            mqtt_transport: MqttTransport::default(),
+            mqtt_ca_source: MqttCaSource::default(),
+            mqtt_ws_path: default_mqtt_ws_path(),
+            hardware_fault_injection: FaultInjectionConfig::default(),
+            system_fault: SystemFaultConfig::default(),
+            cert_expiry: CertExpiryConfig::default(),
+            output_verification: OutputVerificationConfig::default(),
+            log_forwarding: LogForwardingConfig::default(),
+            alert_snapshots: SnapshotConfig::default(),
+            otel: OtelConfig::default(),
+            grpc: GrpcConfig::default(),
+            display_latency_slo_ms: default_display_latency_slo_ms(),
+        }
+    }
+}
+
+fn default_buzzer_enabled() -> bool {
+    true
+}
+
+/// Hora de la plataforma: RPC del lado del dispositivo (`rpc_method`, cada `interval_secs`) o un
+/// atributo compartido en ms epoch o RFC 3339. Vacíos deshabilitan cada fuente; sin ninguna no se
+/// corrige el reloj local.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ServerTimeConfig {
+    #[serde(default = "default_server_time_rpc_method")]
+    rpc_method: String,
+    #[serde(default = "default_server_time_interval_secs")]
+    interval_secs: u64,
+    #[serde(default)]
+    attribute: String,
+}
+
+impl Default for ServerTimeConfig {
+    fn default() -> Self {
+        Self {
+            rpc_method: default_server_time_rpc_method(),
+            interval_secs: default_server_time_interval_secs(),
+            attribute: String::new(),
+        }
+    }
+}
+
+fn default_server_time_rpc_method() -> String {
+    "getCurrentTime".to_string()
+}
+
+fn default_server_time_interval_secs() -> u64 {
+    300
+}
+
+fn default_clock_skew_threshold_secs() -> u64 {
+    120
+}
+
+fn default_trend_window_minutes() -> u64 {
+    10
+}
+
+fn default_defrost_duration_minutes() -> u32 {
+    30
+}
+
+fn default_peer_sync_topic() -> String {
+    "nxt-hmi/peers/sync".to_string()
+}
+
+fn default_hardware_profile_name() -> String {
+    DEFAULT_HARDWARE_PROFILE.to_string()
+}
+
+fn default_output_enabled() -> bool {
+    true
+}
+
+fn default_strobe_duty_percent() -> u8 {
+    100
+}
+
+fn default_strobe_sync() -> bool {
+    true
+}
+
+fn default_strobe_flash_on_ms() -> u64 {
+    100
+}
+
+fn default_strobe_flash_off_ms() -> u64 {
+    900
+}
+
+fn default_buzzer_gpio() -> String {
+    "BUZZER_EN".to_string()
+}
+
+fn default_data_dir() -> String {
+    "data".to_string()
+}
+
+fn default_disk_min_free_mb() -> u64 {
+    100
+}
+
+fn default_mqtt_ws_path() -> String {
+    "/mqtt".to_string()
+}
+
+fn default_history_enabled() -> bool {
+    true
+}
+
+fn default_network_monitor_enabled() -> bool {
+    true
+}
+
+/// Familias IP y sondas de conectividad. Con `happy_eyeballs` las direcciones del broker se
+/// prueban escalonadas y rumqttc recibe la que conectó primero (sólo sin TLS: SNI y el
+/// certificado necesitan el nombre).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct NetworkConfig {
+    #[serde(default)]
+    ip_preference: IpPreference,
+    #[serde(default = "default_happy_eyeballs")]
+    happy_eyeballs: bool,
+    /// `host:puerto` que confirman salida a internet; IPv6 entre corchetes.
+    #[serde(default = "default_connectivity_probes")]
+    connectivity_probes: Vec<String>,
+    /// URL HTTP que responde 204; otra respuesta indica un portal cautivo. Vacío lo deshabilita.
+    #[serde(default = "default_captive_portal_url")]
+    captive_portal_url: String,
+    #[serde(default = "default_captive_portal_interval_secs")]
+    captive_portal_interval_secs: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            ip_preference: IpPreference::default(),
+            happy_eyeballs: default_happy_eyeballs(),
+            connectivity_probes: default_connectivity_probes(),
+            captive_portal_url: default_captive_portal_url(),
+            captive_portal_interval_secs: default_captive_portal_interval_secs(),
+        }
+    }
+}
+
+fn default_happy_eyeballs() -> bool {
+    true
+}
+
+fn default_connectivity_probes() -> Vec<String> {
+    vec![
+        "8.8.8.8:53".to_string(),
+        "[2001:4860:4860::8888]:53".to_string(),
+    ]
+}
+
+fn default_captive_portal_url() -> String {
+    "http://connectivitycheck.gstatic.com/generate_204".to_string()
+}
+
+fn default_captive_portal_interval_secs() -> u64 {
+    60
+}
+
+/// Módem celular administrado por ModemManager (`mmcli`). El tráfico se lee de los contadores de
+/// `interface` (p. ej. `wwan0`); con `sms_recipients` las alertas de `sms_min_severity` o más se
+/// envían por SMS desde el módem mientras no haya camino de datos.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ModemConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_modem_index")]
+    modem: String,
+    #[serde(default)]
+    interface: String,
+    #[serde(default = "default_modem_poll_secs")]
+    poll_secs: u64,
+    #[serde(default)]
+    sms_recipients: Vec<String>,
+    #[serde(default = "default_sms_min_severity")]
+    sms_min_severity: Option<AlertSeverity>,
+}
+
+impl Default for ModemConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            modem: default_modem_index(),
+            interface: String::new(),
+            poll_secs: default_modem_poll_secs(),
+            sms_recipients: Vec::new(),
+            sms_min_severity: default_sms_min_severity(),
+        }
+    }
+}
+
+fn default_modem_index() -> String {
+    "0".to_string()
+}
+
+fn default_modem_poll_secs() -> u64 {
+    60
+}
+
+fn default_sms_min_severity() -> Option<AlertSeverity> {
+    Some(AlertSeverity::Critical)
+}
+
+fn default_display_latency_slo_ms() -> u64 {
+    2000
+}
+
+fn default_maintenance_hour() -> u32 {
+    3
+}
+
+fn default_metrics_interval_minutes() -> u64 {
+    60
+}
+
+fn default_shift_start_hours() -> Vec<u32> {
+    vec![6, 14, 22]
+}
+
+fn default_notification_mqtt() -> bool {
+    true
+}
+
+fn default_action_ttl_minutes() -> u64 {
+    60
+}
+
+fn default_throttle_max() -> u32 {
+    3
+}
+
+fn default_throttle_window_minutes() -> u64 {
+    15
+}
+
+fn default_digest_interval_minutes() -> u64 {
+    60
+}
+
+fn default_presence_interval_minutes() -> u64 {
+    30
+}
+
+fn default_presence_grace_minutes() -> u64 {
+    5
+}
+
+fn default_on_call_ack_timeout_minutes() -> u64 {
+    15
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_smtp_starttls() -> bool {
+    true
+}
+
+fn default_smtp_max_attempts() -> u32 {
+    10
+}
+
+fn default_smtp_retry_max_minutes() -> u64 {
+    60
+}
+
+fn default_token_refresh_margin_seconds() -> u64 {
+    60
+}
+
+fn default_rpc_max_skew_seconds() -> u64 {
+    60
+}
+
+/// Todos los RPC que actúan sobre el panel; `GET_STATE` y `ALARM` sólo informan.
+fn default_rpc_protected_methods() -> Vec<String> {
+    [
+        BUZZER_INHIBIT_RPC_METHOD,
+        NOTIFICATION_ACTION_RPC_METHOD,
+        UNLOCK_PANEL_RPC_METHOD,
+        AUDIO_PROFILE_RPC_METHOD,
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+fn default_bridge_port() -> u16 {
+    1883
+}
+
+fn default_pagerduty_url() -> String {
+    "https://events.pagerduty.com/v2/enqueue".to_string()
+}
+
+fn default_opsgenie_url() -> String {
+    "https://api.opsgenie.com".to_string()
+}
+
+fn default_email_footer() -> String {
+    "--\nPanel {{panelId}} · nxt-hmi {{appVersion}}\n{{hardwareProfile}} {{hardwareRevision}}"
+        .to_string()
+}
+
+fn default_buzzer_inhibit_max_minutes() -> u64 {
+    240
+}
+
+fn default_history_retention() -> RetentionPolicy {
+    RetentionPolicy {
+        days: Some(365),
+        max_rows: Some(200_000),
+    }
+}
+
+fn default_audit_retention() -> RetentionPolicy {
+    RetentionPolicy::days(730)
+}
+
+fn default_telemetry_retention() -> RetentionPolicy {
+    RetentionPolicy::days(1)
+}
+
+fn default_log_retention() -> RetentionPolicy {
+    RetentionPolicy::days(30)
+}
+
+/// ureq trae rustls con `ring` y rumqttc con `aws_lc_rs`; con ambos proveedores rustls no elige
+/// ninguno por su cuenta y `ClientConfig::builder()` entra en pánico, así que se fija aws_lc_rs.
+fn install_crypto_provider() {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+}
+
+fn init_logging() {
+    LOGGER_INITIALIZED.get_or_init(|| {
+        let env = env_logger::Env::default().default_filter_or("info");
+        // Con un nivel simple (o sin RUST_LOG) el módulo queda abierto y el nivel
+        // efectivo se controla en caliente con log::set_max_level.
+        let runtime_level = match std::env::var("RUST_LOG") {
+            Ok(value) => value.trim().parse::<LevelFilter>().ok(),
+            Err(_) => Some(LevelFilter::Info),
+        };
+        let mut builder = env_logger::Builder::from_env(env);
+        if runtime_level.is_some() {
+            builder.filter_module(module_path!(), LevelFilter::Trace);
+        }
+        if let Err(err) = builder
+            .format(|buf, record| {
+                let message = record.args().to_string();
+                record_backend_event(record.level(), &message);
+                if record.target().starts_with(module_path!()) {
+                    forward_log_record(record.level(), &message);
+                }
+                writeln!(
+                    buf,
+                    "[{}][{}] {}",
+                    buf.timestamp_millis(),
+                    record.level(),
+                    message
+                )
+            })
+            .try_init()
+        {
+            eprintln!("[LOG] No se pudo inicializar logger: {:?}", err);
+        } else if let Some(level) = runtime_level {
+            log::set_max_level(level);
+        }
+    });
+}
+
+/// Entrada de la consola de servicio: la misma línea del log con el `[TAG]` separado.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct BackendEvent {
+    seq: u64,
+    ts: String,
+    level: String,
+    tag: String,
+    message: String,
+}
+
+fn with_recent_events<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut VecDeque<BackendEvent>) -> R,
+{
+    let events = RECENT_EVENTS.get_or_init(|| Mutex::new(VecDeque::new()));
+    let mut guard = events
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Se llama desde el formateador del logger: no debe loguear ni tomar otros locks.
+fn record_backend_event(level: log::Level, message: &str) {
+    if level > log::Level::Info {
+        return;
+    }
+    let (tag, text) = split_log_tag(message);
+    let event = BackendEvent {
+        seq: RECENT_EVENT_SEQUENCE.fetch_add(1, Ordering::Relaxed) + 1,
+        ts: corrected_now().to_rfc3339_opts(SecondsFormat::Millis, false),
+        level: level.to_string(),
+        tag: tag.to_string(),
+        message: text.chars().take(RECENT_EVENT_MESSAGE_LIMIT).collect(),
+    };
+    with_recent_events(|events| {
+        if events.len() >= RECENT_EVENT_CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(event);
+    });
+}
+
+/// Separa el prefijo `[TAG]` que usan todas las líneas del log.
+fn split_log_tag(message: &str) -> (&str, &str) {
+    message
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .map(|(tag, text)| (tag, text.trim_start()))
+        .unwrap_or(("", message))
+}
+
+#[derive(Debug, Clone)]
+struct ForwardedLog {
+    ts: DateTime<Utc>,
+    level: log::Level,
+    tag: String,
+    message: String,
+}
+
+/// Hasta que arranca el backend se acumula con valores por defecto, para no perder el arranque.
+struct LogForwardBuffer {
+    records: VecDeque<ForwardedLog>,
+    accepting: bool,
+    capacity: usize,
+    level: LevelFilter,
+    dropped: u64,
+    failing: bool,
+}
+
+fn with_log_forward<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut LogForwardBuffer) -> R,
+{
+    let buffer = LOG_FORWARD.get_or_init(|| {
+        Mutex::new(LogForwardBuffer {
+            records: VecDeque::new(),
+            accepting: true,
+            capacity: default_log_forward_buffer(),
+            level: LevelFilter::Info,
+            dropped: 0,
+            failing: false,
+        })
+    });
+    let mut guard = buffer
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Igual que `record_backend_event`: corre dentro del logger, sin loguear ni leer la config.
+fn forward_log_record(level: log::Level, message: &str) {
+    with_log_forward(|buffer| {
+        if !buffer.accepting || level > buffer.level {
+            return;
+        }
+        let (tag, text) = split_log_tag(message);
+        if buffer.records.len() >= buffer.capacity {
+            buffer.records.pop_front();
+            buffer.dropped += 1;
+        }
+        buffer.records.push_back(ForwardedLog {
+            ts: Utc::now(),
+            level,
+            tag: tag.to_string(),
+            message: text.to_string(),
+        });
+    });
+}
+
+/// Línea RFC 5424; el `[TAG]` del log va como MSGID.
+fn syslog_line(record: &ForwardedLog, host: &str) -> String {
+    let severity = match record.level {
+        log::Level::Error => 3,
+        log::Level::Warn => 4,
+        log::Level::Info => 6,
+        log::Level::Debug | log::Level::Trace => 7,
+    };
+    format!(
+        "<{}>1 {} {} {} {} {} - {}",
+        SYSLOG_FACILITY * 8 + severity,
+        record.ts.to_rfc3339_opts(SecondsFormat::Millis, true),
+        host,
+        LOG_FORWARD_APP_NAME,
+        std::process::id(),
+        if record.tag.is_empty() {
+            "-"
+        } else {
+            record.tag.as_str()
+        },
+        record.message
+    )
+}
+
+fn send_syslog(endpoint: &str, batch: &[ForwardedLog]) -> Result<(), String> {
+    let host: String = panel_id()
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '-' })
+        .collect();
+    let (scheme, address) = endpoint.split_once("://").unwrap_or(("udp", endpoint));
+    let address = address
+        .to_socket_addrs()
+        .map_err(|err| format!("{}: {}", address, err))?
+        .next()
+        .ok_or_else(|| format!("{}: sin dirección", address))?;
+    match scheme {
+        "udp" => {
+            let socket = std::net::UdpSocket::bind(("0.0.0.0", 0))
+                .and_then(|socket| socket.connect(address).map(|()| socket))
+                .map_err(|err| format!("{:?}", err))?;
+            for record in batch {
+                socket
+                    .send(syslog_line(record, &host).as_bytes())
+                    .map_err(|err| format!("{:?}", err))?;
+            }
+            Ok(())
+        }
+        "tcp" => {
+            let mut stream = TcpStream::connect_timeout(&address, LOG_FORWARD_TIMEOUT)
+                .map_err(|err| format!("{:?}", err))?;
+            let _ = stream.set_write_timeout(Some(LOG_FORWARD_TIMEOUT));
+            // Octet counting (RFC 6587): el mensaje puede contener saltos de línea.
+            let mut frames = String::new();
+            for record in batch {
+                let line = syslog_line(record, &host);
+                frames.push_str(&format!("{} {}", line.len(), line));
+            }
+            stream
+                .write_all(frames.as_bytes())
+                .map_err(|err| format!("{:?}", err))
+        }
+        other => Err(format!("Esquema de syslog no soportado: {}", other)),
+    }
+}
+
+fn send_loki(cfg: &LogForwardingConfig, batch: &[ForwardedLog]) -> Result<(), String> {
+    let mut streams: BTreeMap<String, Vec<[String; 2]>> = BTreeMap::new();
+    for record in batch {
+        let line = if record.tag.is_empty() {
+            record.message.clone()
+        } else {
+            format!("[{}] {}", record.tag, record.message)
+        };
+        let ts_ns = record.ts.timestamp_nanos_opt().unwrap_or_default();
+        streams
+            .entry(record.level.as_str().to_ascii_lowercase())
+            .or_default()
+            .push([ts_ns.to_string(), line]);
+    }
+    let streams: Vec<serde_json::Value> = streams
+        .into_iter()
+        .map(|(level, values)| {
+            let mut labels: BTreeMap<String, String> = cfg
+                .labels
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            labels.insert("app".to_string(), LOG_FORWARD_APP_NAME.to_string());
+            labels.insert("panel".to_string(), panel_id().to_string());
+            labels.insert("level".to_string(), level);
+            serde_json::json!({ "stream": labels, "values": values })
+        })
+        .collect();
+    let url = format!("{}/loki/api/v1/push", cfg.endpoint.trim_end_matches('/'));
+    http_post(
+        &url,
+        "application/json",
+        &[],
+        &serde_json::json!({ "streams": streams }).to_string(),
+    )
+}
+
+/// Envía por lotes; si el destino falla, el lote vuelve al frente del buffer y se reintenta luego.
+fn flush_log_forward() {
+    let cfg = &app_config().log_forwarding;
+    loop {
+        let batch: Vec<ForwardedLog> = with_log_forward(|buffer| {
+            let count = buffer.records.len().min(LOG_FORWARD_BATCH);
+            buffer.records.drain(..count).collect()
+        });
+        if batch.is_empty() {
+            return;
+        }
+        let result = match cfg.target {
+            LogForwardTarget::Syslog => send_syslog(&cfg.endpoint, &batch),
+            LogForwardTarget::Loki => send_loki(cfg, &batch),
+        };
+        match result {
+            Ok(()) => {
+                let (recovered, dropped) = with_log_forward(|buffer| {
+                    let recovered = std::mem::take(&mut buffer.failing);
+                    (recovered, std::mem::take(&mut buffer.dropped))
+                });
+                if recovered || dropped > 0 {
+                    info!(
+                        "[LOGFWD] Destino disponible de nuevo ({} registros descartados)",
+                        dropped
+                    );
+                }
+            }
+            Err(err) => {
+                let first_failure = with_log_forward(|buffer| {
+                    for record in batch.into_iter().rev() {
+                        buffer.records.push_front(record);
+                    }
+                    while buffer.records.len() > buffer.capacity {
+                        buffer.records.pop_front();
+                        buffer.dropped += 1;
+                    }
+                    !std::mem::replace(&mut buffer.failing, true)
+                });
+                if first_failure {
+                    warn!(
+                        "[LOGFWD] No se pudo enviar a {}, se acumulan registros: {}",
+                        cfg.endpoint, err
+                    );
+                }
+                return;
+            }
+        }
+    }
+}
+
+fn start_log_forward_loop() {
+    let cfg = &app_config().log_forwarding;
+    with_log_forward(|buffer| {
+        buffer.accepting = cfg.enabled;
+        buffer.capacity = cfg.buffer.max(1);
+        buffer.level = cfg.min_level.parse().unwrap_or(LevelFilter::Info);
+        if !cfg.enabled {
+            buffer.records.clear();
+        }
+        while buffer.records.len() > buffer.capacity {
+            buffer.records.pop_front();
+        }
+    });
+    if !cfg.enabled {
+        return;
+    }
+    info!(
+        "[LOGFWD] Reenviando log ({:?}) a {}",
+        cfg.target, cfg.endpoint
+    );
+    supervise(
+        "log-forward",
+        false,
+        Some(LOG_FORWARD_TICK),
+        RestartPolicy::Always,
+        move |task| async move {
+            while !is_shutting_down() {
+                tokio::time::sleep(LOG_FORWARD_TICK).await;
+                task.beat();
+                let _ = async_runtime::spawn_blocking(flush_log_forward).await;
+            }
+            // Último intento para no perder el motivo del apagado.
+            let _ = async_runtime::spawn_blocking(flush_log_forward).await;
+        },
+    );
+}
+
+#[cfg(feature = "otel")]
+static OTEL_PROVIDER: OnceLock<opentelemetry_sdk::trace::SdkTracerProvider> = OnceLock::new();
+
+/// Span activo en el hilo actual hasta que se suelta; sin la feature `otel` no hace nada.
+struct PipelineSpan {
+    #[cfg(feature = "otel")]
+    _guard: opentelemetry::ContextGuard,
+}
+
+/// Abre un span hijo del span activo (o raíz si no hay ninguno).
+fn pipeline_span(name: &'static str, attribute: Option<(&'static str, &str)>) -> PipelineSpan {
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry::trace::{Span, TraceContextExt, Tracer};
+        let mut span = opentelemetry::global::tracer("nxt-hmi").start(name);
+        if let Some((key, value)) = attribute {
+            span.set_attribute(opentelemetry::KeyValue::new(key, value.to_string()));
+        }
+        PipelineSpan {
+            _guard: opentelemetry::Context::current_with_span(span).attach(),
+        }
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        let _ = (name, attribute);
+        PipelineSpan {}
+    }
+}
+
+#[cfg(feature = "otel")]
+fn otel_traces_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+fn init_tracing() {
+    let cfg = &app_config().otel;
+    if !cfg.enabled {
+        return;
+    }
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry_otlp::WithExportConfig;
+        let endpoint = otel_traces_endpoint(&cfg.endpoint);
+        let exporter = match opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint.clone())
+            .build()
+        {
+            Ok(exporter) => exporter,
+            Err(err) => {
+                error!("[OTEL] No se pudo crear el exportador OTLP: {}", err);
+                return;
+            }
+        };
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                opentelemetry_sdk::Resource::builder()
+                    .with_service_name(cfg.service_name.clone())
+                    .build(),
+            )
+            .build();
+        opentelemetry::global::set_tracer_provider(provider.clone());
+        let _ = OTEL_PROVIDER.set(provider);
+        info!("[OTEL] Exportando trazas a {}", endpoint);
+    }
+    #[cfg(not(feature = "otel"))]
+    warn!("[OTEL] OTEL habilitado pero el binario se compiló sin la feature otel");
+}
+
+/// Envía los spans pendientes antes de salir.
+fn shutdown_tracing() {
+    #[cfg(feature = "otel")]
+    if let Some(provider) = OTEL_PROVIDER.get() {
+        if let Err(err) = provider.shutdown() {
+            warn!("[OTEL] Error al cerrar el exportador: {}", err);
+        }
+    }
+}
+
+/// Últimos `n` eventos del backend (info o más graves), del más antiguo al más reciente.
+/// Con `since` sólo devuelve los posteriores a ese `seq`, para refrescar la consola sin repetir.
+#[tauri::command]
+fn get_recent_events(n: Option<usize>, since: Option<u64>) -> Vec<BackendEvent> {
+    let n = n.unwrap_or(RECENT_EVENTS_DEFAULT);
+    with_recent_events(|events| {
+        let newer: Vec<&BackendEvent> = events
+            .iter()
+            .filter(|event| since.is_none_or(|since| event.seq > since))
+            .collect();
+        newer[newer.len().saturating_sub(n)..]
+            .iter()
+            .map(|event| (*event).clone())
+            .collect()
+    })
+}
+
+fn app_config() -> &'static AppConfig {
+    APP_CONFIG.get_or_init(load_or_create_config)
+}
+
+fn load_or_create_config() -> AppConfig {
+    let path = Path::new(CONFIG_PATH);
+    // La CLI sólo lee: no debe dejar un config.yaml por defecto en el directorio desde el que se lanza.
+    let fallback = |path| {
+        if CLI_MODE.load(Ordering::SeqCst) {
+            AppConfig::default()
+        } else {
+            persist_default_config(path)
+        }
+    };
+    match fs::read_to_string(path) {
+        Ok(contents) if !contents.trim().is_empty() => match serde_yaml::from_str(&contents) {
+            Ok(cfg) => cfg,
+            Err(err) => {
+                error!("[CONFIG] Error al parsear {}: {:?}", CONFIG_PATH, err);
+                fallback(path)
+            }
+        },
+        _ => fallback(path),
+    }
+}
+
+fn persist_default_config(path: &Path) -> AppConfig {
+    let default_cfg = AppConfig::default();
+    if let Some(parent) = path.parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            error!("[CONFIG] No se pudo crear carpeta {:?}: {:?}", parent, err);
+            return default_cfg;
+        }
+    }
+
+    match serde_yaml::to_string(&default_cfg) {
+        Ok(yaml) => {
+            if let Err(err) = fs::write(path, yaml) {
+                error!("[CONFIG] No se pudo escribir {:?}: {:?}", path, err);
+            }
+        }
+        Err(err) => error!("[CONFIG] No se pudo serializar config por defecto: {:?}", err),
+    }
+
+    default_cfg
+}
+
+fn mute_duration() -> Duration {
+    Duration::from_secs(app_config().mute_duration.max(1))
+}
+
+fn strobe_output(
+    cfg: &AppConfig,
+    profile: &HardwareProfile,
+    strobe: &StrobeConfig,
+) -> SignalOutput {
+    let patterns = if strobe.sync {
+        Vec::new()
+    } else {
+        vec![OutputPattern {
+            severity: None,
+            on_ms: strobe.flash_on_ms,
+            off_ms: strobe.flash_off_ms,
+        }]
+    };
+    SignalOutput {
+        name: STROBE_OUTPUT.to_string(),
+        gpio: strobe.gpio.clone(),
+        enabled: cfg.strobe_enabled,
+        min_severity: strobe.min_severity,
+        patterns,
+        sync_with: strobe.sync.then(|| ONBOARD_BUZZER_OUTPUT.to_string()),
+        zones: Vec::new(),
+        pwm: strobe.pwm_channel.map(|channel| PwmLine {
+            chip: PathBuf::from(&profile.pwm_chip),
+            channel,
+            duty_percent: strobe.pwm_duty_percent.min(100),
+        }),
+    }
+}
+
+/// Buzzer de la placa y baliza (según el perfil de hardware) seguidos de las salidas externas configuradas.
+fn signal_outputs_for(cfg: &AppConfig, profile: &HardwareProfile) -> Vec<SignalOutput> {
+    let onboard = SignalOutput {
+        name: ONBOARD_BUZZER_OUTPUT.to_string(),
+        gpio: profile.buzzer_gpio.clone(),
+        enabled: cfg.buzzer_enabled,
+        min_severity: None,
+        patterns: Vec::new(),
+        sync_with: None,
+        zones: Vec::new(),
+        pwm: None,
+    };
+    let strobe = profile
+        .strobe
+        .as_ref()
+        .map(|strobe| strobe_output(cfg, profile, strobe));
+    std::iter::once(onboard)
+        .chain(strobe)
+        .chain(cfg.audible_outputs.iter().cloned())
+        .collect()
+}
+
+fn signal_outputs() -> &'static [SignalOutput] {
+    SIGNAL_OUTPUTS.get_or_init(|| signal_outputs_for(app_config(), hardware_profile()))
+}
+
+fn buzzer_gpio_cache() -> &'static Mutex<HashMap<String, (String, String)>> {
+    BUZZER_GPIO_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn is_shutting_down() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// Se resuelve al pedir el shutdown, para cortar esperas async sin sondear.
+async fn shutdown_requested() {
+    let notified = SHUTDOWN_NOTIFY.notified();
+    if is_shutting_down() {
+        return;
+    }
+    notified.await;
+}
+
+fn next_retry_delay(current: Duration) -> Duration {
+    (current * 2).min(MQTT_MAX_RETRY_DELAY)
+}
+
+/// Espera `retry_delay` antes de reconectar, pero vuelve en cuanto sube el enlace y no reintenta
+/// mientras el monitor de red no vea ruta. Deja en `retry_delay` la espera del próximo intento.
+fn sleep_mqtt_retry(retry_delay: &mut Duration) {
+    let mut wait = MqttRetryWait::new();
+    while !is_shutting_down() && !wait.poll(retry_delay) {
+        thread::sleep(SLEEP_CHUNK);
+    }
+}
+
+/// Igual que `sleep_mqtt_retry` para el loop async; el shutdown corta la espera.
+async fn wait_mqtt_retry(retry_delay: &mut Duration) {
+    let mut wait = MqttRetryWait::new();
+    while !is_shutting_down() && !wait.poll(retry_delay) {
+        tokio::select! {
+            _ = tokio::time::sleep(SLEEP_CHUNK) => {}
+            _ = shutdown_requested() => {}
+        }
+    }
+}
+
+struct MqttRetryWait {
+    link_ups: u64,
+    credentials: u64,
+    elapsed: Duration,
+    deferred: bool,
+}
+
+impl MqttRetryWait {
+    fn new() -> Self {
+        Self {
+            link_ups: NETWORK_LINK_UPS.load(Ordering::SeqCst),
+            credentials: MQTT_CREDENTIALS_GENERATION.load(Ordering::SeqCst),
+            elapsed: Duration::ZERO,
+            deferred: false,
+        }
+    }
+
+    /// Se llama cada `SLEEP_CHUNK`; true cuando toca reintentar, con `retry_delay` actualizado.
+    fn poll(&mut self, retry_delay: &mut Duration) -> bool {
+        if NETWORK_LINK_UPS.load(Ordering::SeqCst) != self.link_ups {
+            info!("[MQTT] Enlace de red restablecido; se reconecta sin esperar");
+            *retry_delay = MQTT_RETRY_DELAY;
+            return true;
+        }
+        if MQTT_CREDENTIALS_GENERATION.load(Ordering::SeqCst) != self.credentials {
+            info!("[MQTT] Credenciales nuevas; se reconecta sin esperar");
+            *retry_delay = MQTT_RETRY_DELAY;
+            return true;
+        }
+        if self.elapsed >= *retry_delay {
+            if NETWORK_LINK_UP.load(Ordering::SeqCst) {
+                *retry_delay = next_retry_delay(*retry_delay);
+                return true;
+            }
+            if !self.deferred {
+                self.deferred = true;
+                MQTT_DEFERRED_RETRIES.fetch_add(1, Ordering::Relaxed);
+                info!("[MQTT] Sin enlace ni ruta por defecto; el reintento espera a la red");
+            }
+        }
+        self.elapsed = self.elapsed.saturating_add(SLEEP_CHUNK);
+        false
+    }
+}
+
+/// Si la interfaz de alguna ruta por defecto tiene enlace; `None` sin tabla de rutas (no Linux).
+fn default_route_link_up() -> Option<bool> {
+    let table = fs::read_to_string(NETWORK_ROUTE_PATH).ok()?;
+    Some(table.lines().skip(1).any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [iface, destination, _gateway, flags, ..] = fields[..] else {
+            return false;
+        };
+        // RTF_UP
+        let route_up = u32::from_str_radix(flags, 16).is_ok_and(|flags| flags & 0x1 != 0);
+        destination == "00000000" && route_up && interface_link_up(iface)
+    }))
+}
+
+fn interface_link_up(iface: &str) -> bool {
+    let dir = Path::new(NETWORK_CLASS_DIR).join(iface);
+    let read =
+        |name: &str| fs::read_to_string(dir.join(name)).map(|value| value.trim().to_string());
+    match read("operstate").as_deref() {
+        Ok("down" | "lowerlayerdown" | "notpresent") => false,
+        // `carrier` no se puede leer con la interfaz administrativamente abajo.
+        _ => read("carrier").map_or(true, |carrier| carrier != "0"),
+    }
+}
+
+/// Sigue el enlace y la ruta por defecto para que los loops MQTT no reintenten a ciegas y, con
+/// enlace, consulta periódicamente la URL de portal cautivo.
+fn start_network_monitor(app_handle: EventSink) {
+    let cfg = app_config();
+    let watch_link = cfg.network_monitor_enabled && default_route_link_up().is_some();
+    if cfg.network_monitor_enabled && !watch_link {
+        info!("[NET] Sin tabla de rutas; los reintentos MQTT no dependen del enlace");
+    }
+    let portal_url = cfg.network.captive_portal_url.clone();
+    let portal_interval = Duration::from_secs(cfg.network.captive_portal_interval_secs.max(10));
+    if !watch_link && portal_url.is_empty() {
+        return;
+    }
+    supervise(
+        "network-monitor",
+        false,
+        Some(NETWORK_MONITOR_INTERVAL),
+        RestartPolicy::Always,
+        move |task| {
+            let app_handle = app_handle.clone();
+            let portal_url = portal_url.clone();
+            async move {
+                let mut last_portal_check: Option<Instant> = None;
+                while !is_shutting_down() {
+                    task.beat();
+                    let up = !watch_link || default_route_link_up().unwrap_or(true);
+                    let changed = NETWORK_LINK_UP.swap(up, Ordering::SeqCst) != up;
+                    if changed {
+                        if up {
+                            NETWORK_LINK_UPS.fetch_add(1, Ordering::SeqCst);
+                            info!("[NET] Enlace y ruta por defecto disponibles");
+                        } else {
+                            warn!("[NET] Sin enlace o sin ruta por defecto");
+                        }
+                    }
+                    if !portal_url.is_empty() {
+                        if !up {
+                            last_portal_check = None;
+                            if changed {
+                                let sink = app_handle.clone();
+                                let _ = async_runtime::spawn_blocking(move || {
+                                    set_connectivity(ConnectivityState::Offline, None, &sink)
+                                })
+                                .await;
+                            }
+                        } else if changed
+                            || last_portal_check.is_none_or(|at| at.elapsed() >= portal_interval)
+                        {
+                            last_portal_check = Some(Instant::now());
+                            let (url, sink) = (portal_url.clone(), app_handle.clone());
+                            let checked = async_runtime::spawn_blocking(move || {
+                                let (state, portal) = check_captive_portal(&url);
+                                set_connectivity(state, portal, &sink);
+                            })
+                            .await;
+                            if let Err(err) = checked {
+                                warn!("[NET] Fallo al consultar portal cautivo: {:?}", err);
+                            }
+                        }
+                    }
+                    tokio::time::sleep(NETWORK_MONITOR_INTERVAL).await;
+                }
+            }
+        },
+    );
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum ConnectivityState {
+    Unknown,
+    /// Sin enlace o sin ruta por defecto.
+    Offline,
+    /// Hay enlace pero no se llega a internet.
+    NoInternet,
+    /// Se llega a un servidor, pero la red intercepta HTTP hasta que se inicie sesión.
+    CaptivePortal,
+    Online,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConnectivityStatus {
+    state: ConnectivityState,
+    /// Destino de la redirección del portal, si la informó.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    portal_url: Option<String>,
+    link_up: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checked_at_ms: Option<i64>,
+}
+
+fn with_connectivity<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut ConnectivityStatus) -> R,
+{
+    let status = CONNECTIVITY.get_or_init(|| {
+        Mutex::new(ConnectivityStatus {
+            state: ConnectivityState::Unknown,
+            portal_url: None,
+            link_up: true,
+            checked_at_ms: None,
+        })
+    });
+    let mut guard = status
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn snapshot_connectivity() -> ConnectivityStatus {
+    with_connectivity(|status| ConnectivityStatus {
+        link_up: NETWORK_LINK_UP.load(Ordering::SeqCst),
+        ..status.clone()
+    })
+}
+
+/// Un 204 es salida libre; cualquier otra respuesta (redirección, página de login) es un portal.
+fn check_captive_portal(url: &str) -> (ConnectivityState, Option<String>) {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(CAPTIVE_PORTAL_TIMEOUT)
+        .redirects(0)
+        .build();
+    let response = match agent.get(url).call() {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => {
+            debug!("[NET] Sin respuesta de {}: {}", url, err);
+            return (ConnectivityState::NoInternet, None);
+        }
+    };
+    if response.status() == 204 {
+        return (ConnectivityState::Online, None);
+    }
+    debug!(
+        "[NET] {} respondió {} en vez de 204",
+        url,
+        response.status()
+    );
+    let portal = response.header("location").map(str::to_string);
+    (ConnectivityState::CaptivePortal, portal)
+}
+
+/// Emite el cambio de estado y mantiene la alerta local mientras haya un portal cautivo.
+fn set_connectivity(state: ConnectivityState, portal_url: Option<String>, app_handle: &EventSink) {
+    let changed = with_connectivity(|status| {
+        status.checked_at_ms = Some(corrected_now().timestamp_millis());
+        let changed = status.state != state || status.portal_url != portal_url;
+        status.state = state;
+        status.portal_url = portal_url.clone();
+        changed
+    });
+    if !changed {
+        return;
+    }
+    info!("[NET] Conectividad: {:?}", state);
+    let description = (state == ConnectivityState::CaptivePortal).then(|| match &portal_url {
+        Some(url) => format!(
+            "Internet bloqueado por portal cautivo ({}): inicie sesión en la red",
+            url
+        ),
+        None => "Internet bloqueado por portal cautivo: inicie sesión en la red".to_string(),
+    });
+    apply_local_alert(CAPTIVE_PORTAL_ALERT_ID, description, app_handle);
+    if let Err(err) = app_handle.emit(CONNECTIVITY_EVENT, snapshot_connectivity()) {
+        warn!("[NET] No se pudo emitir estado de conectividad: {:?}", err);
+    }
+}
+
+#[tauri::command]
+fn get_connectivity_status() -> ConnectivityStatus {
+    snapshot_connectivity()
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct ModemStatus {
+    state: String,
+    /// Calidad de señal 0-100 según ModemManager.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal_quality: Option<u32>,
+    access_tech: String,
+    operator: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rx_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tx_bytes: Option<u64>,
+    checked_at_ms: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn with_modem_status<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Option<ModemStatus>) -> R,
+{
+    let status = MODEM_STATUS.get_or_init(|| Mutex::new(None));
+    let mut guard = status
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn run_mmcli(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("mmcli")
+        .args(args)
+        .output()
+        .map_err(|err| format!("No se pudo ejecutar mmcli: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "mmcli devolvio codigo {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Salida `--output-keyvalue` de mmcli (`clave : valor`); `--` es un valor vacío.
+fn parse_mmcli_keyvalue(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|line| line.split_once(" : "))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = if value == "--" { "" } else { value };
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+fn interface_counter(iface: &str, counter: &str) -> Option<u64> {
+    let path = Path::new(NETWORK_CLASS_DIR)
+        .join(iface)
+        .join("statistics")
+        .join(counter);
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_modem_status(cfg: &ModemConfig) -> ModemStatus {
+    let mut status = ModemStatus {
+        checked_at_ms: corrected_now().timestamp_millis(),
+        ..ModemStatus::default()
+    };
+    if !cfg.interface.is_empty() {
+        status.rx_bytes = interface_counter(&cfg.interface, "rx_bytes");
+        status.tx_bytes = interface_counter(&cfg.interface, "tx_bytes");
+    }
+    match run_mmcli(&["-m", &cfg.modem, "--output-keyvalue"]) {
+        Ok(text) => {
+            let values = parse_mmcli_keyvalue(&text);
+            let value = |key: &str| values.get(key).cloned().unwrap_or_default();
+            status.state = value("modem.generic.state");
+            status.signal_quality = value("modem.generic.signal-quality.value").parse().ok();
+            status.access_tech = value("modem.generic.access-technologies.value[1]");
+            status.operator = value("modem.3gpp.operator-name");
+        }
+        Err(err) => status.error = Some(err),
+    }
+    status
+}
+
+fn publish_modem_telemetry(status: &ModemStatus) {
+    let mut values = serde_json::Map::new();
+    if let Some(quality) = status.signal_quality {
+        values.insert("modemSignalQuality".to_string(), quality.into());
+    }
+    if !status.access_tech.is_empty() {
+        values.insert(
+            "modemAccessTech".to_string(),
+            status.access_tech.clone().into(),
+        );
+    }
+    if let Some(rx_bytes) = status.rx_bytes {
+        values.insert("modemRxBytes".to_string(), rx_bytes.into());
+    }
+    if let Some(tx_bytes) = status.tx_bytes {
+        values.insert("modemTxBytes".to_string(), tx_bytes.into());
+    }
+    if values.is_empty() {
+        return;
+    }
+    match serde_json::to_vec(&values) {
+        Ok(bytes) => {
+            mqtt_publish(MQTT_TELEMETRY_PUBLISH_TOPIC, bytes, QoS::AtMostOnce);
+        }
+        Err(err) => warn!("[MODEM] No se pudo serializar telemetría: {:?}", err),
+    }
+}
+
+fn start_modem_loop() {
+    let cfg = &app_config().modem;
+    if !cfg.enabled {
+        return;
+    }
+    let interval = Duration::from_secs(cfg.poll_secs.max(10));
+    supervise(
+        "modem",
+        false,
+        Some(interval),
+        RestartPolicy::Always,
+        move |task| async move {
+            while !is_shutting_down() {
+                task.beat();
+                let polled = async_runtime::spawn_blocking(|| {
+                    let status = read_modem_status(&app_config().modem);
+                    if let Some(err) = &status.error {
+                        debug!("[MODEM] {}", err);
+                    }
+                    publish_modem_telemetry(&status);
+                    with_modem_status(|slot| *slot = Some(status));
+                })
+                .await;
+                if let Err(err) = polled {
+                    warn!("[MODEM] Fallo al consultar módem: {:?}", err);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        },
+    );
+}
+
+#[tauri::command]
+fn get_modem_status() -> Option<ModemStatus> {
+    with_modem_status(|status| status.clone())
+}
+
+/// Sin MQTT ni salida a internet el único canal hacia la guardia es el módem.
+fn data_path_down() -> bool {
+    !MQTT_CONNECTED.load(Ordering::SeqCst)
+        || matches!(
+            snapshot_connectivity().state,
+            ConnectivityState::Offline
+                | ConnectivityState::NoInternet
+                | ConnectivityState::CaptivePortal
+        )
+}
+
+/// Crea el SMS en el módem y lo envía; mmcli responde con la ruta D-Bus del mensaje creado.
+fn send_sms(modem: &str, number: &str, text: &str) -> Result<(), String> {
+    let text: String = text
+        .chars()
+        .map(|c| if c == '\'' { ' ' } else { c })
+        .take(SMS_MAX_CHARS)
+        .collect();
+    let created = run_mmcli(&[
+        "-m",
+        modem,
+        &format!("--messaging-create-sms=number='{}',text='{}'", number, text),
+    ])?;
+    let sms_path = created
+        .split_whitespace()
+        .find(|token| token.contains("/SMS/"))
+        .ok_or_else(|| format!("mmcli no devolvió el SMS creado: {}", created.trim()))?;
+    run_mmcli(&["-s", sms_path, "--send"]).map(|_| ())
+}
+
+fn with_sms_notified<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashSet<String>) -> R,
+{
+    let notified = SMS_NOTIFIED.get_or_init(|| Mutex::new(HashSet::new()));
+    let mut guard = notified
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn sms_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    let cfg = &app_config().modem;
+    if !cfg.enabled || cfg.sms_recipients.is_empty() {
+        return;
+    }
+    let alert = match event {
+        DomainEvent::AlertAdded(alert) => alert.clone(),
+        DomainEvent::AlertRemoved(alert) => {
+            with_sms_notified(|notified| notified.remove(&alert.id));
+            return;
+        }
+        _ => return,
+    };
+    if cfg.sms_min_severity.is_none()
+        || !meets_severity(alert.severity, cfg.sms_min_severity)
+        || !route_allows(alert.severity, NotificationChannel::Sms)
+        || !data_path_down()
+    {
+        return;
+    }
+    if !with_sms_notified(|notified| notified.insert(alert.id.clone())) {
+        return;
+    }
+    let text = format!(
+        "[{}] {}",
+        serde_name(&alert.severity).to_uppercase(),
+        alert_summary(&alert)
+    );
+    async_runtime::spawn_blocking(move || {
+        let cfg = &app_config().modem;
+        for number in &cfg.sms_recipients {
+            match send_sms(&cfg.modem, number, &text) {
+                Ok(()) => {
+                    info!("[MODEM] SMS de {} enviado a {}", alert.id, number);
+                    record_audit("modem", "sms", number, &alert.id);
+                }
+                Err(err) => warn!("[MODEM] SMS de {} a {}: {}", alert.id, number, err),
+            }
+        }
+    });
+}
+
+fn sleep_with_shutdown(total: Duration) {
+    let mut elapsed = Duration::ZERO;
+    while elapsed < total {
+        if is_shutting_down() {
+            break;
+        }
+        let remaining = total.saturating_sub(elapsed);
+        let slice = if remaining < SLEEP_CHUNK {
+            remaining
+        } else {
+            SLEEP_CHUNK
+        };
+        if slice.is_zero() {
+            break;
+        }
+        thread::sleep(slice);
+        elapsed = elapsed.saturating_add(slice);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AlertType {
+    #[serde(rename = "disconnect")]
+    Disconnect,
+    #[serde(rename = "tempUp")]
+    TempUp,
+    #[serde(rename = "tempDown")]
+    TempDown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Alert {
+    pub id: String,
+
+    /// Sólo para mostrar; para ordenar o calcular usar `createdAtIso`/`createdAtMs`.
+    #[serde(rename = "dateTime")]
+    pub date_time: String,
+
+    /// RFC 3339 en UTC.
+    #[serde(rename = "createdAtIso", default)]
+    pub created_at_iso: String,
+
+    #[serde(rename = "createdAtMs", default)]
+    pub created_at_ms: i64,
+
+    #[serde(rename = "type")]
+    pub alert_type: AlertType,
+
+    pub device: String,
+    pub description: String,
+
+    #[serde(default)]
+    pub severity: AlertSeverity,
+
+    #[serde(default)]
+    pub acknowledged: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<AlertDetails>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trend: Option<AlertTrend>,
+
+    #[serde(
+        rename = "etaToLimit",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub eta_to_limit: Option<u64>,
+
+    #[serde(default)]
+    pub defrost: bool,
+
+    #[serde(rename = "pinOrder", default, skip_serializing_if = "Option::is_none")]
+    pub pin_order: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<AlertDisplay>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<serde_json::Value>,
+
+    /// UUID de la alarma de la plataforma que respalda la alerta; vacío en las alertas locales.
+    #[serde(rename = "alarmId", default, skip_serializing_if = "String::is_empty")]
+    pub alarm_id: String,
+}
+
+/// Presentación común para todos los frontends: color hex, icono, etiqueta y prioridad.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertDisplay {
+    pub color: String,
+    pub icon: String,
+    pub label: String,
+    pub priority: u8,
+    /// El reloj local difiere del servidor más que el umbral: las marcas pueden no ser confiables.
+    #[serde(
+        rename = "clockWarning",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub clock_warning: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AlertDetails {
+    #[serde(
+        rename = "currentValue",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub current_value: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub fields: HashMap<String, serde_json::Value>,
+
+    #[serde(
+        rename = "snapshotUrl",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub snapshot_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlertSeverity {
+    #[default]
+    Critical,
+    Major,
+    Minor,
+    Warning,
+    #[serde(other)]
+    Indeterminate,
+}
+
+impl AlertSeverity {
+    fn rank(self) -> u8 {
+        match self {
+            AlertSeverity::Critical => 4,
+            AlertSeverity::Major => 3,
+            AlertSeverity::Minor => 2,
+            AlertSeverity::Warning => 1,
+            AlertSeverity::Indeterminate => 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendDirection {
+    Rising,
+    Falling,
+    Stable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertTrend {
+    pub direction: TrendDirection,
+
+    pub worsening: bool,
+
+    #[serde(rename = "ratePerMinute")]
+    pub rate_per_minute: f64,
+
+    #[serde(rename = "windowMinutes")]
+    pub window_minutes: u64,
+
+    #[serde(
+        rename = "beyondThreshold",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub beyond_threshold: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelemetryPayload {
+    device: String,
+    #[serde(alias = "temperature")]
+    value: f64,
+    #[serde(default)]
+    ts: Option<i64>,
+}
+
+impl TelemetryPayload {
+    /// `ts` en ms o en segundos epoch; sin marca, la hora corregida del panel.
+    fn ts_ms(&self) -> i64 {
+        self.ts
+            .map(epoch_ms)
+            .unwrap_or_else(|| corrected_now().timestamp_millis())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TelemetrySample {
+    ts_ms: i64,
+    value: f64,
+    defrost: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlarmRpcEnvelope {
+    method: String,
+    params: AlarmParams,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AlarmParams {
+    id: AlarmEntityId,
+    created_time: i64,
+    #[serde(rename = "type")]
+    alarm_type: String,
+    originator_name: String,
+    status: AlarmStatus,
+    #[serde(default)]
+    severity: AlertSeverity,
+    #[serde(default)]
+    acknowledged: bool,
+    #[serde(default)]
+    details: Option<AlarmDetails>,
+    #[serde(default)]
+    end_ts: i64,
+    #[serde(default)]
+    ack_ts: i64,
+    #[serde(default)]
+    clear_ts: i64,
+    #[serde(skip)]
+    raw: Option<serde_json::Value>,
+}
+
+impl AlarmParams {
+    /// Marca más reciente del servidor presente en la alarma.
+    fn latest_server_ts(&self) -> i64 {
+        self.created_time
+            .max(self.end_ts)
+            .max(self.ack_ts)
+            .max(self.clear_ts)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct AlarmEntityId {
+    #[serde(rename = "id")]
+    value: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct AlarmDetails {
+    #[serde(default)]
+    data: Option<String>,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum AlarmStatus {
+    ActiveUnack,
+    ActiveAck,
+    ClearedUnack,
+    ClearedAck,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+struct SupabaseUpdatePayload {
+    commit_timestamp: String,
+    new: SupabaseNewData,
+}
+
+#[derive(Debug, Deserialize)]
+struct SupabaseNewData {
+    message: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct DeviceStatusUpdate {
+    timestamp: String,
+    #[serde(rename = "timestampIso")]
+    timestamp_iso: String,
+    #[serde(rename = "timestampMs")]
+    timestamp_ms: i64,
+    status: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct ClockSkewStatus {
+    #[serde(rename = "offsetMs")]
+    offset_ms: i64,
+    exceeded: bool,
+    #[serde(rename = "thresholdSecs")]
+    threshold_secs: u64,
+    /// `rpc` o `attribute`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'static str>,
+    #[serde(rename = "rttMs", skip_serializing_if = "Option::is_none")]
+    rtt_ms: Option<u64>,
+    #[serde(rename = "syncedAtMs", skip_serializing_if = "Option::is_none")]
+    synced_at_ms: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+struct ServerTimeSync {
+    /// Solicitud RPC en vuelo: id y cuándo se envió.
+    pending: Option<(u64, Instant)>,
+    last: Option<ServerTimeSample>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ServerTimeSample {
+    source: &'static str,
+    rtt_ms: Option<u64>,
+    synced_at_ms: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum PeerAction {
+    Remove,
+    Mute,
+    Unmute,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PeerSyncMessage {
+    panel: String,
+    action: PeerAction,
+    #[serde(rename = "alertId", default, skip_serializing_if = "Option::is_none")]
+    alert_id: Option<String>,
+    ts: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct AlertRemovalEvent {
+    id: String,
+    #[serde(rename = "removedAtIso")]
+    removed_at_iso: String,
+    #[serde(rename = "removedAtMs")]
+    removed_at_ms: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BuzzerPattern {
+    Off,
+    Blink { on: Duration, off: Duration },
+    Steady,
+}
+
+#[derive(Default)]
+struct BuzzerController {
+    handle: Option<JoinHandle<()>>,
+    pattern: Option<BuzzerPattern>,
+    started_at: Option<Instant>,
+}
+
+struct MuteController {
+    muted: bool,
+    deadline: Option<SystemTime>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl Default for MuteController {
+    fn default() -> Self {
+        Self {
+            muted: false,
+            deadline: None,
+            timer: None,
+        }
+    }
+}
+
+/// Evento de dominio emitido por cada mutación del store de alertas o del mute.
+#[derive(Debug, Clone)]
+enum DomainEvent {
+    AlertAdded(Alert),
+    AlertUpdated(Alert),
+    AlertRemoved(Alert),
+    MuteChanged(MuteStatePayload),
+}
+
+type SideEffectHandler = Arc<dyn Fn(&DomainEvent, &EventSink) + Send + Sync>;
+
+#[derive(Debug, Serialize, Clone)]
+struct MuteStatePayload {
+    muted: bool,
+    #[serde(rename = "expiresAt")]
+    expires_at: Option<String>,
+    #[serde(rename = "expiresAtMs")]
+    expires_at_ms: Option<i64>,
+}
+
+fn with_alert_store<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashMap<String, Alert>) -> R,
+{
+    let store = ALERT_STORE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = store
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn with_buzzer_controller<F, R>(output: &str, f: F) -> R
+where
+    F: FnOnce(&mut BuzzerController) -> R,
+{
+    let controllers = BUZZER_CONTROLLERS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = controllers
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(guard.entry(output.to_string()).or_default())
+}
+
+fn with_mute_controller<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut MuteController) -> R,
+{
+    let controller = MUTE_CONTROLLER.get_or_init(|| Mutex::new(MuteController::default()));
+    let mut guard = controller
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn snapshot_mute_state() -> MuteStatePayload {
+    with_mute_controller(|ctrl| MuteStatePayload {
+        muted: ctrl.muted,
+        expires_at: format_deadline(ctrl.deadline),
+        expires_at_ms: ctrl
+            .deadline
+            .map(|ts| DateTime::<Utc>::from(ts).timestamp_millis()),
+    })
+}
+
+fn format_deadline(deadline: Option<SystemTime>) -> Option<String> {
+    deadline.map(|ts| {
+        let datetime: chrono::DateTime<Utc> = ts.into();
+        datetime.to_rfc3339_opts(SecondsFormat::Secs, true)
+    })
+}
+
+/// Destino de los eventos del backend: la ventana Tauri o ninguno en modo headless.
+#[derive(Clone)]
+enum EventSink {
+    App(tauri::AppHandle),
+    Headless,
+    /// Registra los eventos para que los tests de integración puedan verificarlos.
+    #[cfg(feature = "e2e")]
+    Recorder(e2e::EventLog),
+}
+
+impl EventSink {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) -> tauri::Result<()> {
+        let _span = pipeline_span("ui.emit", Some(("event", event)));
+        match self {
+            EventSink::App(app_handle) => app_handle.emit(event, payload),
+            EventSink::Headless => {
+                trace!("[CORE] Evento {} descartado (headless)", event);
+                Ok(())
+            }
+            #[cfg(feature = "e2e")]
+            EventSink::Recorder(log) => {
+                e2e::record_event(log, event, &payload);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn emit_mute_state(app_handle: &EventSink, payload: &MuteStatePayload) {
+    if let Err(err) = app_handle.emit(MUTE_CHANGED_EVENT, payload) {
+        warn!("[MUTE] No se pudo emitir estado mute: {:?}", err);
+    }
+}
+
+fn cancel_mute_timer(ctrl: &mut MuteController) {
+    if let Some(handle) = ctrl.timer.take() {
+        handle.abort();
+    }
+}
+
+fn schedule_mute_timer(app_handle: &EventSink) -> JoinHandle<()> {
+    let app_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        let _task = track_task("mute-timer", TaskKind::Timer, true, None);
+        tokio::time::sleep(mute_duration()).await;
+        if let Err(err) =
+            async_runtime::spawn_blocking(move || handle_mute_timeout(app_handle)).await
+        {
+            warn!("[MUTE] Fallo al procesar fin de silencio: {:?}", err);
+        }
+    })
+}
+
+fn handle_mute_timeout(app_handle: EventSink) {
+    let should_emit = with_mute_controller(|ctrl| {
+        if ctrl.muted {
+            ctrl.muted = false;
+            ctrl.deadline = None;
+            ctrl.timer = None;
+            true
+        } else {
+            ctrl.timer = None;
+            false
+        }
+    });
+
+    if !should_emit {
+        return;
+    }
+
+    publish_mute_change(&app_handle);
+}
+
+fn has_active_alerts() -> bool {
+    with_alert_store(|store| !store.is_empty())
+}
+
+fn force_unmute(app_handle: &EventSink) -> Option<MuteStatePayload> {
+    let changed = with_mute_controller(|ctrl| {
+        if ctrl.muted || ctrl.deadline.is_some() || ctrl.timer.is_some() {
+            ctrl.muted = false;
+            ctrl.deadline = None;
+            cancel_mute_timer(ctrl);
+            true
+        } else {
+            false
+        }
+    });
+
+    if changed {
+        Some(publish_mute_change(app_handle))
+    } else {
+        None
+    }
+}
+
+fn mute_alerts_internal(app_handle: &EventSink) -> MuteStatePayload {
+    let expires_at = SystemTime::now()
+        .checked_add(mute_duration())
+        .unwrap_or_else(|| SystemTime::now());
+    let timer = schedule_mute_timer(app_handle);
+
+    with_mute_controller(|ctrl| {
+        cancel_mute_timer(ctrl);
+        ctrl.muted = true;
+        ctrl.deadline = Some(expires_at);
+        ctrl.timer = Some(timer);
+    });
+
+    publish_mute_change(app_handle)
+}
+
+fn publish_mute_change(app_handle: &EventSink) -> MuteStatePayload {
+    let payload = snapshot_mute_state();
+    publish_domain_event(app_handle, DomainEvent::MuteChanged(payload.clone()));
+    payload
+}
+
+fn handle_alert_activation_side_effects(app_handle: &EventSink) {
+    let mut unmuted = false;
+    with_mute_controller(|ctrl| {
+        if ctrl.muted {
+            ctrl.muted = false;
+            ctrl.deadline = None;
+            cancel_mute_timer(ctrl);
+            unmuted = true;
+        }
+    });
+
+    if unmuted {
+        publish_mute_change(app_handle);
+    }
+}
+
+fn handle_no_active_alerts(app_handle: &EventSink) {
+    let mut changed = false;
+    with_mute_controller(|ctrl| {
+        if ctrl.muted || ctrl.deadline.is_some() || ctrl.timer.is_some() {
+            ctrl.muted = false;
+            ctrl.deadline = None;
+            cancel_mute_timer(ctrl);
+            changed = true;
+        }
+    });
+
+    if changed {
+        publish_mute_change(app_handle);
+    }
+}
+
+fn with_side_effect_handlers<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Vec<(&'static str, SideEffectHandler)>) -> R,
+{
+    let handlers = SIDE_EFFECT_HANDLERS.get_or_init(|| Mutex::new(Vec::new()));
+    let mut guard = handlers
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn register_side_effect<F>(name: &'static str, handler: F)
+where
+    F: Fn(&DomainEvent, &EventSink) + Send + Sync + 'static,
+{
+    with_side_effect_handlers(|handlers| {
+        handlers.retain(|(existing, _)| *existing != name);
+        handlers.push((name, Arc::new(handler)));
+    });
+    debug!("[BUS] Handler registrado: {}", name);
+}
+
+/// Despacha el evento a todos los handlers registrados, en orden de registro.
+fn publish_domain_event(app_handle: &EventSink, event: DomainEvent) {
+    let handlers: Vec<(&'static str, SideEffectHandler)> =
+        with_side_effect_handlers(|handlers| handlers.clone());
+    for (name, handler) in handlers {
+        let _span = pipeline_span("side_effect", Some(("handler", name)));
+        handler(&event, app_handle);
+    }
+}
+
+fn frontend_side_effect(event: &DomainEvent, app_handle: &EventSink) {
+    match event {
+        DomainEvent::AlertAdded(alert) => emit_alert_added(app_handle, alert),
+        DomainEvent::AlertUpdated(alert) => emit_alert_updated(app_handle, alert),
+        DomainEvent::AlertRemoved(alert) => emit_alert_removed(app_handle, &alert.id),
+        DomainEvent::MuteChanged(payload) => emit_mute_state(app_handle, payload),
+    }
+}
+
+fn mute_side_effect(event: &DomainEvent, app_handle: &EventSink) {
+    match event {
+        DomainEvent::AlertAdded(_) => handle_alert_activation_side_effects(app_handle),
+        DomainEvent::AlertRemoved(_) if !has_active_alerts() => handle_no_active_alerts(app_handle),
+        _ => {}
+    }
+}
+
+fn buzzer_side_effect(_event: &DomainEvent, _app_handle: &EventSink) {
+    apply_buzzer_policy();
+}
+
+/// URL de la foto si su host está permitido; el panel no descarga de cualquier sitio que
+/// venga en el payload de la alarma.
+fn alert_snapshot_url(alert: &Alert) -> Option<&str> {
+    let url = alert.details.as_ref()?.snapshot_url.as_deref()?;
+    if let Err(err) = check_snapshot_url(url) {
+        debug!("[SNAPSHOT] Foto de {} descartada: {}", alert.id, err);
+        return None;
+    }
+    Some(url)
+}
+
+/// Host de una URL `https://` según el mismo parser que usa `ureq`; `None` para otro esquema.
+fn snapshot_host(url: &str) -> Option<String> {
+    let parsed = url::Url::parse(url).ok()?;
+    if parsed.scheme() != "https" {
+        return None;
+    }
+    parsed.host_str().map(str::to_ascii_lowercase)
+}
+
+/// Sólo https y hosts de `allowed_hosts`; la lista vacía no permite ninguno.
+fn check_snapshot_url(url: &str) -> Result<(), String> {
+    let host = snapshot_host(url).ok_or_else(|| format!("{} no es una URL https", url))?;
+    let allowed = app_config()
+        .alert_snapshots
+        .allowed_hosts
+        .iter()
+        .any(|entry| entry.trim().eq_ignore_ascii_case(&host));
+    if allowed {
+        Ok(())
+    } else {
+        Err(format!("host no permitido: {}", host))
+    }
+}
+
+fn snapshot_base_path(url: &str) -> PathBuf {
+    Path::new(&app_config().data_dir)
+        .join(SNAPSHOT_DIR)
+        .join(sha256_hex(url.as_bytes()))
+}
+
+/// Copia local de la foto y su tipo MIME; con `fresh` sólo si no superó `TTL_HOURS`.
+fn cached_snapshot(url: &str, fresh: bool) -> Option<(PathBuf, &'static str)> {
+    let ttl = Duration::from_secs(app_config().alert_snapshots.ttl_hours * 3600);
+    let base = snapshot_base_path(url);
+    SNAPSHOT_TYPES.iter().find_map(|(mime, ext)| {
+        let path = base.with_extension(ext);
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+        let expired = modified.elapsed().is_ok_and(|age| age > ttl);
+        (!fresh || !expired).then_some((path, *mime))
+    })
+}
+
+fn download_snapshot(url: &str) -> Result<(PathBuf, &'static str), String> {
+    check_snapshot_url(url)?;
+    let max_bytes = app_config().alert_snapshots.max_bytes;
+    // Sin redirecciones: un host permitido no puede desviar la descarga a otro host ni a http.
+    let response = ureq::AgentBuilder::new()
+        .timeout(SNAPSHOT_DOWNLOAD_TIMEOUT)
+        .redirects(0)
+        .build()
+        .get(url)
+        .call()
+        .map_err(|err| format!("No se pudo descargar {}: {}", url, err))?;
+    if response.status() != 200 {
+        return Err(format!(
+            "{} respondió {} (no se siguen redirecciones)",
+            url,
+            response.status()
+        ));
+    }
+    let content_type = response.content_type().to_ascii_lowercase();
+    let (mime, ext) = SNAPSHOT_TYPES
+        .iter()
+        .find(|(mime, _)| *mime == content_type)
+        .ok_or_else(|| format!("Tipo de imagen no soportado en {}: {}", url, content_type))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(max_bytes + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|err| format!("Descarga incompleta de {}: {}", url, err))?;
+    if bytes.len() as u64 > max_bytes {
+        return Err(format!("Imagen de {} supera {} bytes", url, max_bytes));
+    }
+
+    let path = snapshot_base_path(url).with_extension(ext);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("No se pudo crear {:?}: {:?}", dir, err))?;
+    }
+    let partial = path.with_extension("part");
+    fs::write(&partial, &bytes)
+        .and_then(|_| fs::rename(&partial, &path))
+        .map_err(|err| format!("No se pudo guardar {:?}: {:?}", path, err))?;
+    Ok((path, mime))
+}
+
+/// Descarga en segundo plano (una por URL) y avisa al frontend cuando la foto está disponible.
+fn fetch_snapshot_async(id: String, url: String, app_handle: EventSink) {
+    let downloads = SNAPSHOT_DOWNLOADS.get_or_init(|| Mutex::new(HashSet::new()));
+    let started = downloads
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(url.clone());
+    if !started {
+        return;
+    }
+    async_runtime::spawn_blocking(move || {
+        let result = download_snapshot(&url);
+        downloads
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&url);
+        match result {
+            Ok((path, _)) => {
+                debug!("[SNAPSHOT] Foto de {} guardada en {:?}", id, path);
+                let payload = serde_json::json!({ "id": id, "url": url });
+                if let Err(err) = app_handle.emit(SNAPSHOT_READY_EVENT, payload) {
+                    warn!("[SNAPSHOT] No se pudo emitir foto lista: {:?}", err);
+                }
+            }
+            Err(err) => warn!("[SNAPSHOT] {}", err),
+        }
+    });
+}
+
+fn snapshot_side_effect(event: &DomainEvent, app_handle: &EventSink) {
+    let (DomainEvent::AlertAdded(alert) | DomainEvent::AlertUpdated(alert)) = event else {
+        return;
+    };
+    let Some(url) = alert_snapshot_url(alert) else {
+        return;
+    };
+    if app_config().alert_snapshots.enabled && cached_snapshot(url, true).is_none() {
+        fetch_snapshot_async(alert.id.clone(), url.to_string(), app_handle.clone());
+    }
+}
+
+/// Borra las fotos vencidas y, si el directorio sigue sobre el tope, las más viejas.
+fn purge_snapshots(cfg: &SnapshotConfig, data_dir: &str) -> std::io::Result<u64> {
+    let dir = Path::new(data_dir).join(SNAPSHOT_DIR);
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let ttl = Duration::from_secs(cfg.ttl_hours * 3600);
+    let mut purged = 0;
+    let mut kept = Vec::new();
+    for entry in fs::read_dir(&dir)?.flatten() {
+        let Some((modified, len)) = purge_candidate(&entry) else {
+            continue;
+        };
+        if !modified.elapsed().is_ok_and(|age| age > ttl) {
+            kept.push((modified, len, entry.path()));
+        } else if purge_file(&entry.path()) {
+            purged += 1;
+        }
+    }
+    kept.sort_by_key(|(modified, _, _)| *modified);
+    let limit = cfg.max_cache_mb * 1024 * 1024;
+    let mut total: u64 = kept.iter().map(|(_, len, _)| len).sum();
+    for (_, len, path) in kept {
+        if total <= limit {
+            break;
+        }
+        // Si no se puede borrar, se sigue con la siguiente para no quedar sobre el tope.
+        if purge_file(&path) {
+            total -= len;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+fn register_default_side_effects() {
+    // Antes que el frontend: un visor que reacciona al evento ya encuentra el cambio en el diario.
+    register_side_effect("journal", journal_side_effect);
+    register_side_effect("frontend", frontend_side_effect);
+    register_side_effect("mute", mute_side_effect);
+    register_side_effect("buzzer", buzzer_side_effect);
+    register_side_effect("pin", pin_side_effect);
+    register_side_effect("history", history_side_effect);
+    register_side_effect("metrics", metrics_side_effect);
+    register_side_effect("visual_alarm", visual_alarm_side_effect);
+    register_side_effect("notifications", notification_side_effect);
+    register_side_effect("incidents", incident_side_effect);
+    register_side_effect("snapshots", snapshot_side_effect);
+    register_side_effect("floorplan", floorplan_side_effect);
+    register_side_effect("sms", sms_side_effect);
+    register_side_effect("printer", printer_side_effect);
+}
+
+fn pin_side_effect(event: &DomainEvent, app_handle: &EventSink) {
+    if let DomainEvent::AlertRemoved(alert) = event {
+        update_pinned_alerts(app_handle, |pinned| pinned.retain(|id| *id != alert.id));
+    }
+}
+
+/// Fijadas primero (en su orden manual), luego por prioridad de presentación.
+fn snapshot_alerts() -> Vec<Alert> {
+    let mut alerts: Vec<Alert> =
+        with_alert_store(|store| store.values().map(with_display).collect());
+    alerts.sort_by_key(|alert| {
+        (
+            alert.pin_order.is_none(),
+            alert.pin_order,
+            Reverse(alert.display.as_ref().map_or(0, |display| display.priority)),
+        )
+    });
+    alerts
+}
+
+fn pinned_alerts_path() -> PathBuf {
+    Path::new(&app_config().data_dir).join(PINNED_ALERTS_FILE)
+}
+
+fn load_pinned_alerts() -> Vec<String> {
+    let Ok(contents) = fs::read_to_string(pinned_alerts_path()) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&contents).unwrap_or_else(|err| {
+        warn!("[PIN] No se pudo leer {}: {:?}", PINNED_ALERTS_FILE, err);
+        Vec::new()
+    })
+}
+
+fn persist_pinned_alerts(ids: &[String]) {
+    let path = pinned_alerts_path();
+    if let Some(parent) = path.parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            error!("[PIN] No se pudo crear carpeta {:?}: {:?}", parent, err);
+            return;
+        }
+    }
+    match serde_json::to_string(ids) {
+        Ok(json) => {
+            if let Err(err) = fs::write(&path, json) {
+                error!("[PIN] No se pudo escribir {:?}: {:?}", path, err);
+            }
+        }
+        Err(err) => error!("[PIN] No se pudo serializar alertas fijadas: {:?}", err),
+    }
+}
+
+fn with_pinned_alerts<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Vec<String>) -> R,
+{
+    let pinned = PINNED_ALERTS.get_or_init(|| Mutex::new(load_pinned_alerts()));
+    let mut guard = pinned
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn pin_position(id: &str) -> Option<usize> {
+    with_pinned_alerts(|pinned| pinned.iter().position(|pinned_id| pinned_id == id))
+}
+
+/// Aplica el cambio, lo persiste y reemite las alertas cuya posición cambió.
+fn update_pinned_alerts<F>(app_handle: &EventSink, change: F)
+where
+    F: FnOnce(&mut Vec<String>),
+{
+    let Some(order) = with_pinned_alerts(|pinned| {
+        let before = pinned.clone();
+        change(pinned);
+        if *pinned == before {
+            return None;
+        }
+        persist_pinned_alerts(pinned);
+        Some(pinned.clone())
+    }) else {
+        return;
+    };
+
+    let updated: Vec<Alert> = with_alert_store(|store| {
+        store
+            .values_mut()
+            .filter_map(|alert| {
+                let position = order.iter().position(|id| *id == alert.id);
+                if alert.pin_order == position {
+                    return None;
+                }
+                alert.pin_order = position;
+                Some(alert.clone())
+            })
+            .collect()
+    });
+    for alert in updated {
+        publish_domain_event(app_handle, DomainEvent::AlertUpdated(alert));
+    }
+}
+
+fn open_history_db() -> rusqlite::Result<Connection> {
+    let dir = Path::new(&app_config().data_dir);
+    if let Err(err) = fs::create_dir_all(dir) {
+        error!("[HISTORY] No se pudo crear carpeta {:?}: {:?}", dir, err);
+    }
+    let conn = Connection::open(dir.join(HISTORY_DB_FILE))?;
+    conn.execute_batch(HISTORY_SCHEMA)?;
+    migrate_history_db(&conn)?;
+    Ok(conn)
+}
+
+/// Columnas agregadas después de que ya había bases instaladas.
+fn migrate_history_db(conn: &Connection) -> rusqlite::Result<()> {
+    if conn
+        .prepare("SELECT operator FROM audit_log LIMIT 0")
+        .is_err()
+    {
+        info!("[HISTORY] Agregando columna operator a la auditoría");
+        conn.execute_batch("ALTER TABLE audit_log ADD COLUMN operator TEXT NOT NULL DEFAULT ''")?;
+    }
+    if conn
+        .prepare("SELECT alarm_id FROM alert_history LIMIT 0")
+        .is_err()
+    {
+        info!("[HISTORY] Agregando columna alarm_id al historial");
+        conn.execute_batch(
+            "ALTER TABLE alert_history ADD COLUMN alarm_id TEXT NOT NULL DEFAULT ''",
+        )?;
+    }
+    Ok(())
+}
+
+/// Abre la base en el primer uso; si falla se reintenta en la siguiente llamada.
+fn with_history_db<F, R>(f: F) -> Result<R, String>
+where
+    F: FnOnce(&Connection) -> rusqlite::Result<R>,
+{
+    if !app_config().history_enabled {
+        return Err("Historial deshabilitado en configuración".to_string());
+    }
+    let slot = HISTORY_DB.get_or_init(|| Mutex::new(None));
+    let mut guard = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if guard.is_none() {
+        *guard = Some(open_history_db().map_err(|err| {
+            error!("[HISTORY] No se pudo abrir {}: {:?}", HISTORY_DB_FILE, err);
+            set_persistence_error(Some(err.to_string()));
+            err.to_string()
+        })?);
+    }
+    match guard.as_ref() {
+        Some(conn) => {
+            let result = f(conn);
+            track_persistence_result(result.as_ref().err());
+            result.map_err(|err| err.to_string())
+        }
+        None => Err("Historial no disponible".to_string()),
+    }
+}
+
+fn set_persistence_error(error: Option<String>) {
+    *PERSISTENCE_ERROR
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = error;
+}
+
+/// Sólo los errores de almacenamiento degradan la persistencia; los de consulta no.
+fn track_persistence_result(error: Option<&rusqlite::Error>) {
+    use rusqlite::ErrorCode;
+    match error.map(|err| (err, err.sqlite_error_code())) {
+        None => set_persistence_error(None),
+        Some((
+            err,
+            Some(
+                ErrorCode::DiskFull
+                | ErrorCode::ReadOnly
+                | ErrorCode::SystemIoFailure
+                | ErrorCode::CannotOpen
+                | ErrorCode::DatabaseCorrupt
+                | ErrorCode::NotADatabase,
+            ),
+        )) => set_persistence_error(Some(err.to_string())),
+        Some(_) => {}
+    }
+}
+
+fn serde_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn record_history(alert: &Alert, event: &str) {
+    let result = with_history_db(|conn| {
+        let severity = serde_name(&alert.severity);
+        if event == "updated" {
+            let last: Option<(String, bool, String, String)> = conn
+                .query_row(
+                    "SELECT severity, acknowledged, description, alarm_id FROM alert_history
+                     WHERE alert_id = ?1 ORDER BY id DESC LIMIT 1",
+                    params![alert.id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .optional()?;
+            if last
+                == Some((
+                    severity.clone(),
+                    alert.acknowledged,
+                    alert.description.clone(),
+                    alert.alarm_id.clone(),
+                ))
+            {
+                return Ok(());
+            }
+        }
+        conn.execute(
+            "INSERT INTO alert_history
+             (alert_id, event, ts_ms, alert_type, severity, acknowledged, device, description, alarm_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                alert.id,
+                event,
+                corrected_now().timestamp_millis(),
+                serde_name(&alert.alert_type),
+                severity,
+                alert.acknowledged,
+                alert.device,
+                alert.description,
+                alert.alarm_id,
+            ],
+        )?;
+        Ok(())
+    });
+    if let Err(err) = result {
+        debug!(
+            "[HISTORY] Evento {} de {} no registrado: {}",
+            event, alert.id, err
+        );
+    }
+}
+
+/// Registro de acciones que cambian el estado del panel (origen, acción, objetivo).
+/// Las acciones `local` se atribuyen al operador con sesión abierta en el panel.
+fn record_audit(source: &str, action: &str, target: &str, detail: &str) {
+    let operator = match source {
+        "local" => with_operator_session(|session| {
+            session.as_ref().map(|session| session.operator_id.clone())
+        })
+        .unwrap_or_default(),
+        _ => String::new(),
+    };
+    let result = with_history_db(|conn| {
+        conn.execute(
+            "INSERT INTO audit_log (ts_ms, source, action, target, detail, operator)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                corrected_now().timestamp_millis(),
+                source,
+                action,
+                target,
+                detail,
+                operator
+            ],
+        )
+    });
+    if let Err(err) = result {
+        debug!("[AUDIT] Acción {} no registrada: {}", action, err);
+    }
+}
+
+/// Las actualizaciones sólo se registran si cambian severidad, reconocimiento o descripción.
+fn history_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    match event {
+        DomainEvent::AlertAdded(alert) => record_history(alert, "added"),
+        DomainEvent::AlertUpdated(alert) => record_history(alert, "updated"),
+        DomainEvent::AlertRemoved(alert) => record_history(alert, "removed"),
+        DomainEvent::MuteChanged(_) => {}
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+struct HistoryRange {
+    #[serde(default)]
+    from_ms: Option<i64>,
+    #[serde(default)]
+    to_ms: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct HistoryEntry {
+    id: i64,
+    alert_id: String,
+    event: String,
+    ts_ms: i64,
+    #[serde(rename = "type")]
+    alert_type: String,
+    severity: String,
+    acknowledged: bool,
+    device: String,
+    description: String,
+    notes: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    alarm_id: String,
+}
+
+/// Cada palabra se busca como término literal para que comillas o guiones no rompan FTS5.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Con `devices` sólo se devuelven registros de esos equipos.
+fn search_history_entries(
+    query: &str,
+    range: HistoryRange,
+    devices: Option<&[String]>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let query = fts_query(query);
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let devices = devices
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|err| format!("{:?}", err))?;
+    with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT h.id, h.alert_id, h.event, h.ts_ms, h.alert_type, h.severity,
+                    h.acknowledged, h.device, h.description, h.notes, h.alarm_id
+             FROM alert_history_fts f JOIN alert_history h ON h.id = f.rowid
+             WHERE alert_history_fts MATCH ?1 AND h.ts_ms BETWEEN ?2 AND ?3
+               AND (?5 IS NULL OR h.device IN (SELECT value FROM json_each(?5)))
+             ORDER BY h.ts_ms DESC LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                query,
+                range.from_ms.unwrap_or(0),
+                range.to_ms.unwrap_or(i64::MAX),
+                HISTORY_SEARCH_LIMIT,
+                devices
+            ],
+            history_entry,
+        )?;
+        rows.collect()
+    })
+}
+
+/// Columnas en el orden de `HistoryEntry`: id, alert_id, event, ts_ms, tipo, severidad, ack, equipo, descripción, notas, alarma.
+fn history_entry(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        alert_id: row.get(1)?,
+        event: row.get(2)?,
+        ts_ms: row.get(3)?,
+        alert_type: row.get(4)?,
+        severity: row.get(5)?,
+        acknowledged: row.get(6)?,
+        device: row.get(7)?,
+        description: row.get(8)?,
+        notes: row.get(9)?,
+        alarm_id: row.get(10)?,
+    })
+}
+
+fn last_history_entry(alert_id: &str) -> Result<Option<HistoryEntry>, String> {
+    with_history_db(|conn| {
+        conn.query_row(
+            "SELECT id, alert_id, event, ts_ms, alert_type, severity,
+                    acknowledged, device, description, notes, alarm_id
+             FROM alert_history WHERE alert_id = ?1 ORDER BY id DESC LIMIT 1",
+            params![alert_id],
+            history_entry,
+        )
+        .optional()
+    })
+}
+
+fn validate_binary_array(message: &str) -> Result<Vec<u8>> {
+    let values: Vec<u8> = serde_json::from_str(message)
+        .map_err(|e| anyhow::anyhow!("Formato JSON inválido: {}", e))?;
+
+    if values.len() != BINARY_ARRAY_SIZE {
+        return Err(anyhow::anyhow!(
+            "El array debe tener exactamente {} elementos, pero tiene {}",
+            BINARY_ARRAY_SIZE,
+            values.len()
+        ));
+    }
+
+    for (index, &value) in values.iter().enumerate() {
+        if value != 0 && value != 1 {
+            return Err(anyhow::anyhow!(
+                "El elemento en posición {} tiene valor {}, debe ser 0 o 1",
+                index, value
+            ));
+        }
+    }
+
+    Ok(values)
+}
+
+fn parse_supabase_timestamp(timestamp: &str) -> String {
+    let guatemala_tz = FixedOffset::west_opt(6 * 3600).unwrap_or_else(|| FixedOffset::west_opt(0).unwrap());
+    
+    match timestamp.parse::<DateTime<Utc>>() {
+        Ok(utc_time) => utc_time
+            .with_timezone(&guatemala_tz)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        Err(_) => corrected_now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    }
+}
+
+fn clock_skew_threshold_ms() -> i64 {
+    i64::try_from(app_config().clock_skew_threshold_secs)
+        .unwrap_or(i64::MAX / 1000)
+        .saturating_mul(1000)
+}
+
+fn snapshot_clock_skew() -> ClockSkewStatus {
+    let last = with_server_time_sync(|sync| sync.last);
+    ClockSkewStatus {
+        offset_ms: CLOCK_SKEW_MS.load(Ordering::SeqCst),
+        exceeded: CLOCK_SKEW_EXCEEDED.load(Ordering::SeqCst),
+        threshold_secs: app_config().clock_skew_threshold_secs,
+        source: last.map(|sample| sample.source),
+        rtt_ms: last.and_then(|sample| sample.rtt_ms),
+        synced_at_ms: last.map(|sample| sample.synced_at_ms),
+    }
+}
+
+fn with_server_time_sync<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut ServerTimeSync) -> R,
+{
+    let sync = SERVER_TIME_SYNC.get_or_init(|| Mutex::new(ServerTimeSync::default()));
+    let mut guard = sync.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Registra la hora actual del servidor para estimar el desfase del reloj local. Sólo vale una
+/// lectura del reloj de la plataforma: la marca de un evento puede llegar tarde o repetido.
+fn record_server_time(
+    server_ts_ms: i64,
+    source: &'static str,
+    rtt_ms: Option<u64>,
+    app_handle: &EventSink,
+) {
+    let now_ms = Utc::now().timestamp_millis();
+    let offset_ms = server_ts_ms.saturating_sub(now_ms);
+    if offset_ms.saturating_abs() > MAX_CLOCK_SKEW_MS {
+        warn!(
+            "[CLOCK] Hora del servidor descartada ({}): desfase de {} ms fuera de rango",
+            source, offset_ms
+        );
+        return;
+    }
+    CLOCK_SKEW_MS.store(offset_ms, Ordering::SeqCst);
+    with_server_time_sync(|sync| {
+        sync.last = Some(ServerTimeSample {
+            source,
+            rtt_ms,
+            synced_at_ms: now_ms,
+        });
+    });
+
+    let exceeded = offset_ms.saturating_abs() > clock_skew_threshold_ms();
+    if CLOCK_SKEW_EXCEEDED.swap(exceeded, Ordering::SeqCst) == exceeded {
+        return;
+    }
+
+    if exceeded {
+        warn!(
+            "[CLOCK] Desfase de reloj de {} ms respecto al servidor, se corrigen marcas locales",
+            offset_ms
+        );
+    } else {
+        info!("[CLOCK] Reloj local sincronizado con el servidor ({} ms)", offset_ms);
+    }
+
+    if let Err(err) = app_handle.emit(CLOCK_SKEW_EVENT, snapshot_clock_skew()) {
+        warn!("[CLOCK] No se pudo emitir estado de desfase: {:?}", err);
+    }
+    // Reenvía las alertas para que el frontend actualice la marca `clockWarning`.
+    for alert in snapshot_alerts() {
+        emit_alert_updated(app_handle, &alert);
+    }
+}
+
+/// Marca epoch en ms; como en `parse_server_time`, una anterior a 2001 en ms son segundos.
+fn epoch_ms(ts: i64) -> i64 {
+    if ts < 1_000_000_000_000 {
+        ts.saturating_mul(1000)
+    } else {
+        ts
+    }
+}
+
+/// Milisegundos epoch, segundos epoch o RFC 3339, directo o en `time`/`ts`/`serverTime`.
+fn parse_server_time(value: &serde_json::Value) -> Option<i64> {
+    let value = ["time", "ts", "serverTime"]
+        .iter()
+        .find_map(|key| value.get(*key))
+        .unwrap_or(value);
+    match value {
+        serde_json::Value::Number(number) => {
+            let ts = number.as_f64()?;
+            // Antes de 2001 en ms es imposible: se interpreta como segundos.
+            Some(if ts < 1e12 { ts * 1000.0 } else { ts } as i64)
+        }
+        serde_json::Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|at| at.timestamp_millis())
+            .or_else(|| text.parse::<i64>().ok().map(epoch_ms)),
+        _ => None,
+    }
+}
+
+/// Pide la hora con un RPC del lado del dispositivo; la respuesta llega a `rpc/response/{id}`.
+fn request_server_time(method: &str) {
+    let id = SERVER_TIME_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let payload = serde_json::json!({ "method": method, "params": {} });
+    let Ok(bytes) = serde_json::to_vec(&payload) else {
+        return;
+    };
+    if mqtt_publish(
+        &format!("{}{}", MQTT_RPC_REQUEST_PREFIX, id),
+        bytes,
+        QoS::AtLeastOnce,
+    ) {
+        with_server_time_sync(|sync| sync.pending = Some((id, Instant::now())));
+    }
+}
+
+fn handle_rpc_response_payload(topic: &str, payload: &[u8], app_handle: &EventSink) {
+    let id = topic
+        .strip_prefix(MQTT_RPC_RESPONSE_PREFIX)
+        .and_then(|id| id.parse::<u64>().ok());
+    let sent = with_server_time_sync(|sync| match sync.pending {
+        Some((pending, sent)) if Some(pending) == id => sync.pending.take().map(|_| sent),
+        _ => None,
+    });
+    let Some(sent) = sent else {
+        trace!("[CLOCK] Respuesta RPC ajena ignorada: {}", topic);
+        return;
+    };
+    let server_ms = serde_json::from_slice::<serde_json::Value>(payload)
+        .ok()
+        .as_ref()
+        .and_then(parse_server_time);
+    let Some(server_ms) = server_ms else {
+        warn!(
+            "[CLOCK] Respuesta de hora del servidor sin marca válida: {}",
+            String::from_utf8_lossy(payload)
+        );
+        return;
+    };
+    // La marca se tomó, en promedio, a mitad del viaje de ida y vuelta.
+    let rtt_ms = u64::try_from(sent.elapsed().as_millis()).unwrap_or(u64::MAX);
+    let server_ms = server_ms.saturating_add(i64::try_from(rtt_ms / 2).unwrap_or(0));
+    record_server_time(server_ms, "rpc", Some(rtt_ms), app_handle);
+}
+
+fn handle_server_time_attribute(value: &serde_json::Value, app_handle: &EventSink) {
+    match parse_server_time(value) {
+        Some(server_ms) => record_server_time(server_ms, "attribute", None, app_handle),
+        None => warn!("[CLOCK] Atributo de hora del servidor inválido: {}", value),
+    }
+}
+
+fn start_server_time_loop() {
+    let cfg = &app_config().server_time;
+    if cfg.rpc_method.is_empty() {
+        return;
+    }
+    let interval = Duration::from_secs(cfg.interval_secs.max(10));
+    supervise(
+        "server-time",
+        false,
+        Some(SERVER_TIME_TICK),
+        RestartPolicy::Always,
+        move |task| async move {
+            let mut last_request: Option<Instant> = None;
+            while !is_shutting_down() {
+                task.beat();
+                let connected = MQTT_CONNECTED.load(Ordering::SeqCst);
+                if !connected {
+                    last_request = None;
+                } else if last_request.is_none_or(|at| at.elapsed() >= interval) {
+                    last_request = Some(Instant::now());
+                    request_server_time(&app_config().server_time.rpc_method);
+                }
+                tokio::time::sleep(SERVER_TIME_TICK).await;
+            }
+        },
+    );
+}
+
+/// Hora local corregida con el desfase del servidor cuando supera el umbral.
+fn corrected_now() -> DateTime<Local> {
+    let now = Local::now();
+    if !CLOCK_SKEW_EXCEEDED.load(Ordering::SeqCst) {
+        return now;
+    }
+    now + chrono::Duration::milliseconds(CLOCK_SKEW_MS.load(Ordering::SeqCst))
+}
+
+/// Zona horaria de la planta (`TIMEZONE`); si es inválida se usa la del sistema.
+fn plant_timezone() -> Tz {
+    *PLANT_TIMEZONE.get_or_init(|| {
+        schedule::parse_timezone(&app_config().timezone).unwrap_or_else(|err| {
+            warn!("[SCHEDULE] {}; se usa la zona del sistema", err);
+            schedule::system_timezone().unwrap_or(Tz::UTC)
+        })
+    })
+}
+
+/// Calendario de `HOLIDAYS`; las entradas inválidas se omiten (las reporta la validación).
+fn holiday_calendar() -> &'static schedule::HolidayCalendar {
+    HOLIDAY_CALENDAR.get_or_init(|| {
+        let (calendar, errors) = load_holiday_calendar(&app_config().holidays);
+        for err in errors {
+            warn!("[SCHEDULE] {}", err);
+        }
+        if !calendar.is_empty() {
+            info!("[SCHEDULE] Calendario de feriados cargado");
+        }
+        calendar
+    })
+}
+
+fn load_holiday_calendar(cfg: &HolidayConfig) -> (schedule::HolidayCalendar, Vec<String>) {
+    let mut calendar = schedule::HolidayCalendar::default();
+    let mut errors: Vec<String> = cfg
+        .dates
+        .iter()
+        .filter_map(|entry| calendar.add_entry(entry).err())
+        .collect();
+    if !cfg.ical_path.trim().is_empty() {
+        match fs::read_to_string(cfg.ical_path.trim()) {
+            Ok(text) => {
+                if let Err(err) = calendar.add_ical(&text) {
+                    errors.push(format!("{}: {}", cfg.ical_path, err));
+                }
+            }
+            Err(err) => errors.push(format!("No se pudo leer {}: {}", cfg.ical_path, err)),
+        }
+    }
+    (calendar, errors)
+}
+
+/// Hora corregida expresada en la zona de la planta, base de todos los horarios.
+fn plant_now() -> DateTime<Tz> {
+    corrected_now().with_timezone(&plant_timezone())
+}
+
+fn cache_alert(alert: &Alert) {
+    let _span = pipeline_span("store.insert", Some(("alert.id", &alert.id)));
+    let alert_clone = alert.clone();
+    with_alert_store(|store| {
+        store.insert(alert_clone.id.clone(), alert_clone);
+    });
+}
+
+fn remove_alert_by_id(id: &str) -> Option<Alert> {
+    let _span = pipeline_span("store.remove", Some(("alert.id", id)));
+    with_alert_store(|store| store.remove(id))
+}
+
+fn format_timestamp_ms(ts_ms: i64) -> String {
+    if let Some(datetime) = chrono::DateTime::<Utc>::from_timestamp_millis(ts_ms) {
+        datetime
+            .with_timezone(&Local)
+            .format("%d/%m/%Y %H:%M:%S")
+            .to_string()
+    } else {
+        corrected_now().format("%d/%m/%Y %H:%M:%S").to_string()
+    }
+}
+
+/// Forma canónica de los instantes en los payloads: RFC 3339 en UTC con milisegundos.
+fn iso_utc_ms(ts_ms: i64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(ts_ms)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn map_alert_type(source: &str) -> AlertType {
+    match source {
+        "Temperature out of range" => AlertType::TempUp,
+        "Inactivity TimeOut" => AlertType::Disconnect,
+        _ => AlertType::TempUp,
+    }
+}
+
+fn map_description(source: &str, details: Option<&AlarmDetails>) -> String {
+    match source {
+        "Temperature out of range" => details
+            .and_then(|d| d.data.clone())
+            .unwrap_or_else(|| "Temperatura fuera de rango".to_string()),
+        "Inactivity TimeOut" => "Dispositivo desconectado".to_string(),
+        _ => "Detalle no disponible".to_string(),
+    }
+}
+
+fn detail_number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(text) => parse_number_in_text(text),
+        _ => None,
+    }
+}
+
+/// Extrae el último valor numérico de un texto como "Temperatura actual = 4.79".
+fn parse_number_in_text(text: &str) -> Option<f64> {
+    text.split(|c: char| c.is_whitespace() || c == '=' || c == ':')
+        .rev()
+        .map(|token| {
+            token
+                .trim_matches(|c: char| !(c.is_ascii_digit() || c == '-' || c == '.' || c == ','))
+                .replace(',', ".")
+        })
+        .find_map(|token| token.parse::<f64>().ok())
+}
+
+fn find_detail<'a>(
+    fields: &'a HashMap<String, serde_json::Value>,
+    keys: &[&str],
+) -> Option<&'a serde_json::Value> {
+    keys.iter().find_map(|key| fields.get(*key))
+}
+
+fn map_details(source: &str, details: Option<&AlarmDetails>) -> Option<AlertDetails> {
+    let details = details?;
+    let mut fields = details.extra.clone();
+    if let Some(data) = &details.data {
+        fields.insert("data".to_string(), serde_json::Value::String(data.clone()));
+    }
+    if fields.is_empty() {
+        return None;
+    }
+
+    let current_value = find_detail(&fields, &DETAIL_CURRENT_KEYS)
+        .and_then(detail_number)
+        .or_else(|| details.data.as_deref().and_then(parse_number_in_text));
+    let threshold = find_detail(&fields, &DETAIL_THRESHOLD_KEYS).and_then(detail_number);
+    let unit = find_detail(&fields, &DETAIL_UNIT_KEYS)
+        .and_then(|value| value.as_str())
+        .map(str::to_string)
+        .or_else(|| {
+            (source == TEMPERATURE_ALARM_TYPE && current_value.is_some())
+                .then(|| TEMPERATURE_UNIT.to_string())
+        });
+
+    let snapshot_url = find_detail(&fields, &DETAIL_SNAPSHOT_KEYS)
+        .and_then(|value| value.as_str())
+        .filter(|url| snapshot_host(url).is_some())
+        .map(str::to_string);
+
+    Some(AlertDetails {
+        current_value,
+        threshold,
+        unit,
+        fields,
+        snapshot_url,
+    })
+}
+
+fn alert_from_params(params: &AlarmParams) -> Alert {
+    let alert = map_alarm(params, app_config().alert_identity.mode);
+    Alert {
+        pin_order: pin_position(&alert.id),
+        ..alert
+    }
+}
+
+/// Traducción pura de la alarma de la plataforma; la posición de fijado la agrega `alert_from_params`.
+fn map_alarm(params: &AlarmParams, identity: AlertIdentityMode) -> Alert {
+    Alert {
+        id: identity.alert_id(params),
+        date_time: format_timestamp_ms(params.created_time),
+        created_at_iso: iso_utc_ms(params.created_time),
+        created_at_ms: params.created_time,
+        alert_type: map_alert_type(&params.alarm_type),
+        device: params.originator_name.clone(),
+        description: map_description(&params.alarm_type, params.details.as_ref()),
+        severity: params.severity,
+        acknowledged: params.acknowledged || matches!(params.status, AlarmStatus::ActiveAck),
+        details: map_details(&params.alarm_type, params.details.as_ref()),
+        trend: None,
+        eta_to_limit: None,
+        defrost: false,
+        pin_order: None,
+        display: None,
+        raw: params.raw.clone(),
+        alarm_id: params.id.value.clone(),
+    }
+}
+
+/// Id de la alerta del panel para una alarma según `ALERT_IDENTITY.mode`.
+fn panel_alert_id(params: &AlarmParams) -> String {
+    app_config().alert_identity.mode.alert_id(params)
+}
+
+fn with_pending_clears<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashMap<String, String>) -> R,
+{
+    let pending = PENDING_CLEARS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = pending
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn default_alert_display(alert_type: &AlertType, severity: AlertSeverity) -> AlertDisplay {
+    let (color, icon, label) = match alert_type {
+        AlertType::Disconnect => ("#EF4444", "wifi-off", "Desconexión"),
+        AlertType::TempUp => ("#F97316", "trending-up", "Aumento temp."),
+        AlertType::TempDown => ("#3B82F6", "trending-down", "Disminución temp."),
+    };
+    AlertDisplay {
+        color: color.to_string(),
+        icon: icon.to_string(),
+        label: label.to_string(),
+        priority: severity.rank(),
+        clock_warning: false,
+    }
+}
+
+fn resolve_alert_display(alert: &Alert) -> AlertDisplay {
+    let mut display = default_alert_display(&alert.alert_type, alert.severity);
+    let rule = app_config().alert_display_rules.iter().find(|rule| {
+        rule.alert_type
+            .as_ref()
+            .is_none_or(|alert_type| *alert_type == alert.alert_type)
+            && rule
+                .severity
+                .is_none_or(|severity| severity == alert.severity)
+    });
+    if let Some(rule) = rule {
+        if let Some(color) = &rule.color {
+            display.color = color.clone();
+        }
+        if let Some(icon) = &rule.icon {
+            display.icon = icon.clone();
+        }
+        if let Some(label) = &rule.label {
+            display.label = label.clone();
+        }
+        if let Some(priority) = rule.priority {
+            display.priority = priority;
+        }
+    }
+    display.clock_warning = CLOCK_SKEW_EXCEEDED.load(Ordering::SeqCst);
+    display
+}
+
+/// Completa los metadatos de presentación justo antes de exponer la alerta.
+fn with_display(alert: &Alert) -> Alert {
+    let mut alert = alert.clone();
+    alert.display = Some(resolve_alert_display(&alert));
+    alert
+}
+
+fn emit_alert_added(app_handle: &EventSink, alert: &Alert) {
+    let alert = &with_display(alert);
+    let origin_ms = take_display_origin(&alert.id);
+    if let Err(err) = app_handle.emit(ALERT_ADDED_EVENT, alert) {
+        warn!(
+            "[ALERT] No se pudo emitir evento de alerta agregada {}: {:?}",
+            alert.id, err
+        );
+    } else if let Some(origin_ms) = origin_ms {
+        record_display_latency(&alert.id, origin_ms);
+    }
+}
+
+/// Latencias publicación→pantalla de las últimas alarmas recibidas por MQTT.
+#[derive(Default)]
+struct DisplayLatency {
+    pending: HashMap<String, i64>,
+    samples: VecDeque<u64>,
+    total: u64,
+    breaches: u64,
+    max_ms: u64,
+    reported_total: u64,
+    reported_at: Option<Instant>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DisplayLatencyStats {
+    slo_ms: u64,
+    /// Alarmas medidas desde el arranque; los percentiles cubren sólo las últimas muestras.
+    count: u64,
+    breaches: u64,
+    p50_ms: Option<u64>,
+    p95_ms: Option<u64>,
+    p99_ms: Option<u64>,
+    max_ms: Option<u64>,
+}
+
+fn with_display_latency<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut DisplayLatency) -> R,
+{
+    let latency = DISPLAY_LATENCY.get_or_init(|| Mutex::new(DisplayLatency::default()));
+    let mut guard = latency
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Registra la marca de origen de una alarma nueva para medirla cuando se emita al frontend.
+fn mark_display_origin(id: &str, origin_ms: i64) {
+    // Una alarma creada antes de esta sesión MQTT llega por reinicio o reconexión: su demora
+    // mide el tiempo desconectado, no el camino hasta la pantalla.
+    if origin_ms < MQTT_SESSION_START_MS.load(Ordering::SeqCst) {
+        debug!("[SLO] Alarma {} anterior a la sesión MQTT; no se mide", id);
+        return;
+    }
+    with_display_latency(|latency| {
+        if latency.pending.len() >= DISPLAY_LATENCY_PENDING_LIMIT {
+            let oldest = latency
+                .pending
+                .iter()
+                .min_by_key(|(_, origin_ms)| **origin_ms)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                latency.pending.remove(&oldest);
+            }
+        }
+        latency.pending.insert(id.to_string(), origin_ms);
+    });
+}
+
+fn take_display_origin(id: &str) -> Option<i64> {
+    with_display_latency(|latency| latency.pending.remove(id))
+}
+
+fn record_display_latency(id: &str, origin_ms: i64) {
+    let elapsed_ms = (corrected_now().timestamp_millis() - origin_ms).max(0) as u64;
+    let slo_ms = app_config().display_latency_slo_ms;
+    with_display_latency(|latency| {
+        if latency.samples.len() >= DISPLAY_LATENCY_SAMPLES {
+            latency.samples.pop_front();
+        }
+        latency.samples.push_back(elapsed_ms);
+        latency.total += 1;
+        latency.max_ms = latency.max_ms.max(elapsed_ms);
+        if elapsed_ms > slo_ms {
+            latency.breaches += 1;
+        }
+    });
+    if elapsed_ms > slo_ms {
+        warn!(
+            "[SLO] Alarma {} mostrada {} ms después de creada (SLO {} ms)",
+            id, elapsed_ms, slo_ms
+        );
+    } else {
+        debug!("[SLO] Alarma {} mostrada en {} ms", id, elapsed_ms);
+    }
+}
+
+/// Percentil por rango más cercano sobre muestras ya ordenadas.
+fn percentile(sorted: &[u64], pct: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+fn display_latency_stats() -> DisplayLatencyStats {
+    let slo_ms = app_config().display_latency_slo_ms;
+    with_display_latency(|latency| {
+        let mut sorted: Vec<u64> = latency.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        DisplayLatencyStats {
+            slo_ms,
+            count: latency.total,
+            breaches: latency.breaches,
+            p50_ms: percentile(&sorted, 50),
+            p95_ms: percentile(&sorted, 95),
+            p99_ms: percentile(&sorted, 99),
+            max_ms: (latency.total > 0).then_some(latency.max_ms),
+        }
+    })
+}
+
+/// Publica los percentiles como telemetría si hubo alarmas nuevas desde el último reporte.
+fn report_display_latency() {
+    let due = with_display_latency(|latency| {
+        let elapsed = latency
+            .reported_at
+            .is_none_or(|at| at.elapsed() >= DISPLAY_LATENCY_REPORT_INTERVAL);
+        if !elapsed || latency.total == latency.reported_total {
+            return false;
+        }
+        latency.reported_total = latency.total;
+        latency.reported_at = Some(Instant::now());
+        true
+    });
+    if !due {
+        return;
+    }
+    let stats = display_latency_stats();
+    let telemetry = serde_json::json!({
+        "displayLatencyP50Ms": stats.p50_ms,
+        "displayLatencyP95Ms": stats.p95_ms,
+        "displayLatencyP99Ms": stats.p99_ms,
+        "displayLatencyMaxMs": stats.max_ms,
+        "displayLatencySloMs": stats.slo_ms,
+        "displayLatencySloBreaches": stats.breaches,
+    });
+    if let Ok(bytes) = serde_json::to_vec(&telemetry) {
+        mqtt_publish(MQTT_TELEMETRY_PUBLISH_TOPIC, bytes, QoS::AtLeastOnce);
+    }
+}
+
+fn emit_alert_removed(app_handle: &EventSink, id: &str) {
+    let now_ms = corrected_now().timestamp_millis();
+    let payload = AlertRemovalEvent {
+        id: id.to_string(),
+        removed_at_iso: iso_utc_ms(now_ms),
+        removed_at_ms: now_ms,
+    };
+    if let Err(err) = app_handle.emit(ALERT_REMOVED_EVENT, &payload) {
+        warn!(
+            "[ALERT] No se pudo emitir evento de alerta eliminada {}: {:?}",
+            id, err
+        );
+    }
+}
+
+fn emit_alert_updated(app_handle: &EventSink, alert: &Alert) {
+    let alert = &with_display(alert);
+    if let Err(err) = app_handle.emit(ALERT_UPDATED_EVENT, alert) {
+        warn!(
+            "[ALERT] No se pudo emitir evento de alerta actualizada {}: {:?}",
+            alert.id, err
+        );
+    }
+}
+
+fn with_telemetry_buffer<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashMap<String, VecDeque<TelemetrySample>>) -> R,
+{
+    let buffer = TELEMETRY_BUFFER.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = buffer
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn active_defrost(device: &str, at: &DateTime<Tz>) -> Option<&'static DefrostSchedule> {
+    app_config()
+        .defrost_schedules
+        .iter()
+        .find(|schedule| schedule.device == device && schedule.contains(at))
+}
+
+fn device_entry(device: &str) -> Option<&'static DeviceEntry> {
+    app_config().devices.iter().find(|entry| entry.id == device)
+}
+
+/// Umbrales críticos (alto, bajo) del equipo; los que no define usan los globales.
+fn device_limits(device: &str) -> (Option<f64>, Option<f64>) {
+    let cfg = app_config();
+    let entry = device_entry(device);
+    (
+        entry
+            .and_then(|entry| entry.critical_temperature_high)
+            .or(cfg.critical_temperature_high),
+        entry
+            .and_then(|entry| entry.critical_temperature_low)
+            .or(cfg.critical_temperature_low),
+    )
+}
+
+fn active_defrost_at(device: &str, ts_ms: i64) -> Option<&'static DefrostSchedule> {
+    let at = DateTime::<Utc>::from_timestamp_millis(ts_ms)?.with_timezone(&plant_timezone());
+    active_defrost(device, &at)
+}
+
+fn record_telemetry(device: &str, ts_ms: i64, value: f64) {
+    with_telemetry_buffer(|buffer| {
+        let samples = buffer.entry(device.to_string()).or_default();
+        if samples.back().is_some_and(|last| last.ts_ms == ts_ms) {
+            samples.pop_back();
+        }
+        samples.push_back(TelemetrySample {
+            ts_ms,
+            value,
+            defrost: active_defrost_at(device, ts_ms).is_some(),
+        });
+        while samples.len() > TELEMETRY_BUFFER_CAPACITY {
+            samples.pop_front();
+        }
+    });
+}
+
+/// Pendiente (unidades por minuto) por mínimos cuadrados sobre la ventana configurada.
+fn telemetry_rate_per_minute(device: &str, window: Duration) -> Option<f64> {
+    let window_ms = i64::try_from(window.as_millis()).unwrap_or(i64::MAX);
+    let samples: Vec<TelemetrySample> = with_telemetry_buffer(|buffer| {
+        let samples = buffer.get(device)?;
+        let newest = samples.back()?.ts_ms;
+        Some(
+            samples
+                .iter()
+                .filter(|sample| {
+                    !sample.defrost && newest.saturating_sub(sample.ts_ms) <= window_ms
+                })
+                .copied()
+                .collect(),
+        )
+    })?;
+
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let origin = samples[0].ts_ms;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|sample| ((sample.ts_ms - origin) as f64 / 60_000.0, sample.value))
+        .collect();
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+    if variance <= f64::EPSILON {
+        return None;
+    }
+
+    Some(covariance / variance)
+}
+
+fn compute_alert_trend(alert: &Alert) -> Option<AlertTrend> {
+    let worsening_direction = match alert.alert_type {
+        AlertType::TempUp => TrendDirection::Rising,
+        AlertType::TempDown => TrendDirection::Falling,
+        AlertType::Disconnect => return None,
+    };
+
+    let window_minutes = app_config().trend_window_minutes.max(1);
+    let rate = telemetry_rate_per_minute(
+        &alert.device,
+        Duration::from_secs(window_minutes.saturating_mul(60)),
+    )?;
+    let direction = if rate > TREND_STABLE_RATE_PER_MINUTE {
+        TrendDirection::Rising
+    } else if rate < -TREND_STABLE_RATE_PER_MINUTE {
+        TrendDirection::Falling
+    } else {
+        TrendDirection::Stable
+    };
+
+    let beyond_threshold = alert.details.as_ref().and_then(|details| {
+        let current = details.current_value?;
+        let threshold = details.threshold?;
+        Some(match alert.alert_type {
+            AlertType::TempDown => threshold - current,
+            _ => current - threshold,
+        })
+    });
+
+    Some(AlertTrend {
+        direction,
+        worsening: direction == worsening_direction,
+        rate_per_minute: (rate * 100.0).round() / 100.0,
+        window_minutes,
+        beyond_threshold,
+    })
+}
+
+fn latest_telemetry(device: &str) -> Option<TelemetrySample> {
+    with_telemetry_buffer(|buffer| {
+        buffer
+            .get(device)
+            .and_then(|samples| samples.back().copied())
+    })
+}
+
+/// Tiempo estimado (segundos) hasta el límite crítico con un ajuste lineal de la telemetría.
+fn compute_eta_to_limit(alert: &Alert) -> Option<u64> {
+    let cfg = app_config();
+    let window_minutes = cfg.trend_window_minutes.max(1);
+    let rate = telemetry_rate_per_minute(
+        &alert.device,
+        Duration::from_secs(window_minutes.saturating_mul(60)),
+    )?;
+    let latest = latest_telemetry(&alert.device)?;
+    let elapsed_minutes =
+        (corrected_now().timestamp_millis() - latest.ts_ms).max(0) as f64 / 60_000.0;
+    let current = latest.value + rate * elapsed_minutes;
+    let threshold = alert.details.as_ref().and_then(|details| details.threshold);
+    let (high, low) = device_limits(&alert.device);
+
+    let remaining = match alert.alert_type {
+        AlertType::TempUp if rate > TREND_STABLE_RATE_PER_MINUTE => {
+            let limit = threshold.filter(|limit| *limit > current).or(high)?;
+            limit - current
+        }
+        AlertType::TempDown if rate < -TREND_STABLE_RATE_PER_MINUTE => {
+            let limit = threshold.filter(|limit| *limit < current).or(low)?;
+            current - limit
+        }
+        _ => return None,
+    };
+
+    if remaining <= 0.0 {
+        return Some(0);
+    }
+
+    let minutes = (remaining / rate.abs()).ceil();
+    Some((minutes as u64).saturating_mul(60))
+}
+
+fn apply_projection(alert: &mut Alert) {
+    alert.trend = compute_alert_trend(alert);
+    alert.eta_to_limit = compute_eta_to_limit(alert);
+}
+
+/// Recalcula tendencia y ETA de las alertas filtradas y emite las que cambiaron.
+fn refresh_projections<P>(app_handle: &EventSink, predicate: P)
+where
+    P: Fn(&Alert) -> bool,
+{
+    let candidates: Vec<Alert> = with_alert_store(|store| {
+        store
+            .values()
+            .filter(|alert| predicate(alert))
+            .cloned()
+            .collect()
+    });
+
+    for mut alert in candidates {
+        let (previous_trend, previous_eta) = (alert.trend.clone(), alert.eta_to_limit);
+        apply_projection(&mut alert);
+        if alert.trend == previous_trend && alert.eta_to_limit == previous_eta {
+            continue;
+        }
+        let still_active = with_alert_store(|store| match store.get_mut(&alert.id) {
+            Some(stored) => {
+                stored.trend = alert.trend.clone();
+                stored.eta_to_limit = alert.eta_to_limit;
+                true
+            }
+            None => false,
+        });
+        if still_active {
+            publish_domain_event(app_handle, DomainEvent::AlertUpdated(alert));
+        }
+    }
+}
+
+/// Aumentos de temperatura durante el descongelamiento: con `Downgrade` la alerta baja a advertencia,
+/// con `Suppress` quien llama debe descartarla.
+fn apply_defrost_rules(alert: &mut Alert) -> Option<DefrostAction> {
+    if !matches!(alert.alert_type, AlertType::TempUp) {
+        return None;
+    }
+    let schedule = active_defrost(&alert.device, &plant_now())?;
+    if schedule.action == DefrostAction::Downgrade {
+        alert.severity = AlertSeverity::Warning;
+        alert.defrost = true;
+    }
+    Some(schedule.action)
+}
+
+fn handle_active_alarm(params: AlarmParams, app_handle: &EventSink) {
+    let mut alert = alert_from_params(&params);
+    if let Some(value) = alert
+        .details
+        .as_ref()
+        .and_then(|details| details.current_value)
+    {
+        record_telemetry(&alert.device, params.latest_server_ts(), value);
+    }
+    apply_projection(&mut alert);
+
+    if apply_defrost_rules(&mut alert) == Some(DefrostAction::Suppress) {
+        info!(
+            "[DEFROST] Alarma {} suprimida en ventana de descongelamiento de {}",
+            alert.id, alert.device
+        );
+        return;
+    }
+
+    let existing = with_alert_store(|store| store.get(&alert.id).cloned());
+    let is_update = existing.is_some();
+    let was_clearing =
+        is_update && with_pending_clears(|pending| pending.remove(&alert.id)).is_some();
+    let new_alarm = existing
+        .as_ref()
+        .is_some_and(|existing| existing.alarm_id != alert.alarm_id);
+    // Sólo una reactivación real (otra alarma, o ACTIVE_UNACK tras un CLEAR) quita el silencio;
+    // un ACK o un refresco de detalles de la misma alarma no debe volver a hacer sonar el buzzer.
+    let reactivated = is_update
+        && matches!(params.status, AlarmStatus::ActiveUnack)
+        && (new_alarm || was_clearing);
+    if let Some(existing) = existing.filter(|_| new_alarm) {
+        // Misma condición re-creada en la plataforma: se conserva el origen de la alerta del panel.
+        if was_clearing {
+            info!(
+                "[ALERT] {} re-creada en plataforma ({} → {}); se cancela el despeje",
+                alert.id, existing.alarm_id, alert.alarm_id
+            );
+        } else {
+            info!(
+                "[ALERT] {} pasa de la alarma {} a {}",
+                alert.id, existing.alarm_id, alert.alarm_id
+            );
+        }
+        alert.date_time = existing.date_time;
+        alert.created_at_iso = existing.created_at_iso;
+        alert.created_at_ms = existing.created_at_ms;
+        alert.acknowledged |= existing.acknowledged;
+    }
+    cache_alert(&alert);
+
+    if is_update {
+        debug!(
+            "[ALERT] ACTUALIZADA {} tipo={} dispositivo={}",
+            alert.id, params.alarm_type, params.originator_name
+        );
+    } else {
+        info!(
+            "[ALERT] ACTIVADA {} tipo={} dispositivo={}",
+            alert.id, params.alarm_type, params.originator_name
+        );
+    }
+
+    let (device, id) = (alert.device.clone(), alert.id.clone());
+    let event = if is_update {
+        if reactivated {
+            // La UI sólo recibe `alerts://updated`, pero el silencio se levanta como en un alta.
+            handle_alert_activation_side_effects(app_handle);
+        }
+        DomainEvent::AlertUpdated(alert)
+    } else {
+        mark_display_origin(&id, params.created_time);
+        DomainEvent::AlertAdded(alert)
+    };
+    publish_domain_event(app_handle, event);
+
+    refresh_projections(app_handle, |alert| alert.device == device && alert.id != id);
+    evaluate_rate_of_change_rules(&device, app_handle);
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PurgeResult {
+    category: &'static str,
+    purged: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MaintenanceReport {
+    ran_at: String,
+    results: Vec<PurgeResult>,
+}
+
+fn purge_table(
+    conn: &Connection,
+    table: &str,
+    policy: &RetentionPolicy,
+    now_ms: i64,
+) -> rusqlite::Result<usize> {
+    let mut purged = 0;
+    if let Some(cutoff) = policy.cutoff_ms(now_ms) {
+        purged += conn.execute(
+            &format!("DELETE FROM {} WHERE ts_ms < ?1", table),
+            params![cutoff],
+        )?;
+    }
+    if let Some(max_rows) = policy.max_rows {
+        purged += conn.execute(
+            &format!(
+                "DELETE FROM {0} WHERE id <= (SELECT id FROM {0} ORDER BY id DESC LIMIT 1 OFFSET ?1)",
+                table
+            ),
+            params![max_rows],
+        )?;
+    }
+    Ok(purged)
+}
+
+fn purge_telemetry(policy: &RetentionPolicy, now_ms: i64) -> u64 {
+    with_telemetry_buffer(|buffer| {
+        let mut purged = 0;
+        for samples in buffer.values_mut() {
+            let before = samples.len();
+            if let Some(cutoff) = policy.cutoff_ms(now_ms) {
+                samples.retain(|sample| sample.ts_ms >= cutoff);
+            }
+            if let Some(max_rows) = policy.max_rows {
+                while samples.len() > max_rows as usize {
+                    samples.pop_front();
+                }
+            }
+            purged += (before - samples.len()) as u64;
+        }
+        buffer.retain(|_, samples| !samples.is_empty());
+        purged
+    })
+}
+
+/// Borra archivos de `LOG_DIR` (si el servicio redirige los logs a archivos).
+fn purge_log_files(dir: &str, policy: &RetentionPolicy) -> std::io::Result<u64> {
+    let Some(days) = policy.days else {
+        return Ok(0);
+    };
+    let max_age = Duration::from_secs(u64::from(days) * 86_400);
+    let mut purged = 0;
+    for entry in fs::read_dir(dir)?.flatten() {
+        let Some((modified, _)) = purge_candidate(&entry) else {
+            continue;
+        };
+        if modified.elapsed().is_ok_and(|age| age > max_age) && purge_file(&entry.path()) {
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+/// Fecha y tamaño de un fichero a purgar. Un fichero ilegible se avisa y se salta para no
+/// detener la purga del resto.
+fn purge_candidate(entry: &fs::DirEntry) -> Option<(SystemTime, u64)> {
+    let read = entry.metadata().and_then(|metadata| {
+        if !metadata.is_file() {
+            return Ok(None);
+        }
+        Ok(Some((metadata.modified()?, metadata.len())))
+    });
+    read.unwrap_or_else(|err| {
+        warn!(
+            "[MAINT] No se pudo leer {}: {}",
+            entry.path().display(),
+            err
+        );
+        None
+    })
+}
+
+fn purge_file(path: &Path) -> bool {
+    match fs::remove_file(path) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("[MAINT] No se pudo borrar {}: {}", path.display(), err);
+            false
+        }
+    }
+}
+
+fn run_maintenance() -> MaintenanceReport {
+    let cfg = app_config();
+    let now = corrected_now();
+    let now_ms = now.timestamp_millis();
+    let retention = &cfg.retention;
+
+    let table_result = |category, table, policy: &RetentionPolicy| match with_history_db(|conn| {
+        purge_table(conn, table, policy, now_ms)
+    }) {
+        Ok(purged) => PurgeResult {
+            category,
+            purged: purged as u64,
+            error: None,
+        },
+        Err(err) => PurgeResult {
+            category,
+            purged: 0,
+            error: Some(err),
+        },
+    };
+
+    let mut results = vec![
+        table_result("alertHistory", "alert_history", &retention.alert_history),
+        table_result("auditLog", "audit_log", &retention.audit_log),
+        PurgeResult {
+            category: "telemetry",
+            purged: purge_telemetry(&retention.telemetry, now_ms),
+            error: None,
+        },
+    ];
+    let (purged, error) = match purge_snapshots(&cfg.alert_snapshots, &cfg.data_dir) {
+        Ok(purged) => (purged, None),
+        Err(err) => (0, Some(err.to_string())),
+    };
+    results.push(PurgeResult {
+        category: "snapshots",
+        purged,
+        error,
+    });
+    if !cfg.log_dir.is_empty() {
+        let (purged, error) = match purge_log_files(&cfg.log_dir, &retention.logs) {
+            Ok(purged) => (purged, None),
+            Err(err) => (0, Some(err.to_string())),
+        };
+        results.push(PurgeResult {
+            category: "logs",
+            purged,
+            error,
+        });
+    }
+
+    for result in &results {
+        match &result.error {
+            Some(err) => warn!("[MAINT] {}: {}", result.category, err),
+            None => info!(
+                "[MAINT] {}: {} registros purgados",
+                result.category, result.purged
+            ),
+        }
+    }
+
+    MaintenanceReport {
+        ran_at: now.to_rfc3339_opts(SecondsFormat::Secs, true),
+        results,
+    }
+}
+
+fn emit_maintenance_report(app_handle: &EventSink, report: &MaintenanceReport) {
+    if let Err(err) = app_handle.emit(MAINTENANCE_EVENT, report) {
+        warn!("[MAINT] No se pudo emitir reporte: {:?}", err);
+    }
+}
+
+fn until_next_maintenance(now: DateTime<Tz>, hour: u32) -> Duration {
+    let time = NaiveTime::from_hms_opt(hour.min(23), 0, 0).unwrap_or(NaiveTime::MIN);
+    (schedule::next_daily(&now, time) - now)
+        .to_std()
+        .unwrap_or(Duration::from_secs(3600))
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum TaskKind {
+    /// Debe vivir mientras corre el panel; terminar fuera del apagado es una falla.
+    Service,
+    /// Temporizador puntual: terminar es normal, sólo el pánico es una falla.
+    Timer,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum TaskState {
+    Running,
+    Finished,
+    Panicked,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum RestartPolicy {
+    /// Sólo si la tarea terminó por pánico.
+    OnPanic,
+    /// Ante cualquier salida fuera del apagado.
+    Always,
+}
+
+#[derive(Debug)]
+struct TaskEntry {
+    id: u64,
+    restarts: u32,
+    policy: Option<RestartPolicy>,
+    kind: TaskKind,
+    critical: bool,
+    interval: Option<Duration>,
+    started_at: DateTime<Local>,
+    last_beat: Instant,
+    state: TaskState,
+}
+
+impl TaskEntry {
+    fn stalled(&self) -> bool {
+        self.state == TaskState::Running
+            && self
+                .interval
+                .is_some_and(|interval| self.last_beat.elapsed() > interval * TASK_STALL_FACTOR)
+    }
+
+    /// Tarea crítica muerta o colgada.
+    fn failed(&self) -> bool {
+        self.critical
+            && match self.state {
+                TaskState::Panicked => true,
+                TaskState::Finished => self.kind == TaskKind::Service && !is_shutting_down(),
+                TaskState::Running => self.stalled(),
+            }
+    }
+}
+
+fn with_tasks<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut BTreeMap<String, TaskEntry>) -> R,
+{
+    let tasks = TASKS.get_or_init(|| Mutex::new(BTreeMap::new()));
+    let mut guard = tasks
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Registro de una tarea en segundo plano; al soltarse (también por pánico) marca cómo terminó.
+struct TaskGuard {
+    name: String,
+    id: u64,
+}
+
+impl TaskGuard {
+    fn beat(&self) {
+        self.update(|entry| entry.last_beat = Instant::now());
+    }
+
+    fn update(&self, f: impl FnOnce(&mut TaskEntry)) {
+        with_tasks(|tasks| {
+            if let Some(entry) = tasks
+                .get_mut(&self.name)
+                .filter(|entry| entry.id == self.id)
+            {
+                f(entry);
+            }
+        });
+    }
+}
+
+fn mark_task_panicked(name: &str, id: u64) {
+    with_tasks(|tasks| {
+        if let Some(entry) = tasks.get_mut(name).filter(|entry| entry.id == id) {
+            entry.state = TaskState::Panicked;
+        }
+    });
+}
+
+/// Decide si reiniciar y cuánto esperar; `None` deja la tarea detenida.
+fn next_restart(
+    name: &str,
+    policy: RestartPolicy,
+    panicked: bool,
+    ran_for: Duration,
+    backoff: &mut Duration,
+) -> Option<Duration> {
+    if is_shutting_down() {
+        return None;
+    }
+    let restart = match policy {
+        RestartPolicy::OnPanic => panicked,
+        RestartPolicy::Always => true,
+    };
+    if !restart {
+        warn!(
+            "[SUPERVISOR] {} terminó ({}) y no se reinicia",
+            name,
+            if panicked { "pánico" } else { "normal" }
+        );
+        return None;
+    }
+    if ran_for >= SUPERVISOR_STABLE_RUN {
+        *backoff = SUPERVISOR_MIN_BACKOFF;
+    }
+    let delay = *backoff;
+    *backoff = (*backoff * 2).min(SUPERVISOR_MAX_BACKOFF);
+    warn!(
+        "[SUPERVISOR] {} terminó ({}), reinicio en {:?}",
+        name,
+        if panicked { "pánico" } else { "normal" },
+        delay
+    );
+    Some(delay)
+}
+
+/// Servicio asíncrono supervisado: `factory` crea una instancia nueva en cada reinicio.
+fn supervise<F, Fut>(
+    name: &'static str,
+    critical: bool,
+    interval: Option<Duration>,
+    policy: RestartPolicy,
+    factory: F,
+) where
+    F: Fn(TaskGuard) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    async_runtime::spawn(async move {
+        let mut backoff = SUPERVISOR_MIN_BACKOFF;
+        let mut restarts = 0;
+        loop {
+            let task = track_task(name, TaskKind::Service, critical, interval);
+            let id = task.id;
+            task.update(|entry| {
+                entry.restarts = restarts;
+                entry.policy = Some(policy);
+            });
+            let started = Instant::now();
+            let panicked = async_runtime::spawn(factory(task)).await.is_err();
+            if panicked {
+                error!("[SUPERVISOR] {} terminó por pánico", name);
+                mark_task_panicked(name, id);
+            }
+            let Some(delay) = next_restart(name, policy, panicked, started.elapsed(), &mut backoff)
+            else {
+                break;
+            };
+            tokio::time::sleep(delay).await;
+            restarts += 1;
+        }
+    });
+}
+
+/// Igual que `supervise` para bucles bloqueantes que necesitan su propio hilo.
+fn supervise_thread<F>(name: &'static str, critical: bool, policy: RestartPolicy, body: F)
+where
+    F: Fn(&TaskGuard) + Send + 'static,
+{
+    let spawned = thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            let mut backoff = SUPERVISOR_MIN_BACKOFF;
+            let mut restarts = 0;
+            loop {
+                let task = track_task(name, TaskKind::Service, critical, None);
+                let id = task.id;
+                task.update(|entry| {
+                    entry.restarts = restarts;
+                    entry.policy = Some(policy);
+                });
+                let started = Instant::now();
+                let panicked =
+                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| body(&task))).is_err();
+                drop(task);
+                if panicked {
+                    mark_task_panicked(name, id);
+                }
+                let Some(delay) =
+                    next_restart(name, policy, panicked, started.elapsed(), &mut backoff)
+                else {
+                    break;
+                };
+                sleep_with_shutdown(delay);
+                restarts += 1;
+            }
+        });
+    if let Err(err) = spawned {
+        error!(
+            "[SUPERVISOR] No se pudo iniciar el hilo {}: {:?}",
+            name, err
+        );
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        let state = if thread::panicking() {
+            TaskState::Panicked
+        } else {
+            TaskState::Finished
+        };
+        if state == TaskState::Panicked {
+            error!("[HEALTH] La tarea {} terminó por pánico", self.name);
+        }
+        with_tasks(|tasks| {
+            // Un temporizador reprogramado reutiliza el nombre; sólo el registro vigente se actualiza.
+            if let Some(entry) = tasks
+                .get_mut(&self.name)
+                .filter(|entry| entry.id == self.id)
+            {
+                entry.state = state;
+            }
+        });
+    }
+}
+
+/// `interval` es el latido esperado; sin intervalo no se detectan cuelgues, sólo la muerte.
+fn track_task(name: &str, kind: TaskKind, critical: bool, interval: Option<Duration>) -> TaskGuard {
+    let id = TASK_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    with_tasks(|tasks| {
+        tasks.insert(
+            name.to_string(),
+            TaskEntry {
+                id,
+                restarts: 0,
+                policy: None,
+                kind,
+                critical,
+                interval,
+                started_at: Local::now(),
+                last_beat: Instant::now(),
+                state: TaskState::Running,
+            },
+        )
+    });
+    TaskGuard {
+        name: name.to_string(),
+        id,
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct TaskStatus {
+    name: String,
+    kind: TaskKind,
+    critical: bool,
+    state: TaskState,
+    stalled: bool,
+    restarts: u32,
+    policy: Option<RestartPolicy>,
+    started_at: String,
+    last_beat_ms_ago: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeHealth {
+    event_loop_lag_ms: u64,
+    max_event_loop_lag_ms: u64,
+    alive_tasks: usize,
+    failed_tasks: Vec<String>,
+    tasks: Vec<TaskStatus>,
+}
+
+fn runtime_health() -> RuntimeHealth {
+    with_tasks(|tasks| RuntimeHealth {
+        event_loop_lag_ms: EVENT_LOOP_LAG_MS.load(Ordering::Relaxed),
+        max_event_loop_lag_ms: EVENT_LOOP_MAX_LAG_MS.load(Ordering::Relaxed),
+        alive_tasks: tasks
+            .values()
+            .filter(|entry| entry.state == TaskState::Running)
+            .count(),
+        failed_tasks: tasks
+            .iter()
+            .filter(|(_, entry)| entry.failed())
+            .map(|(name, _)| name.clone())
+            .collect(),
+        tasks: tasks
+            .iter()
+            .map(|(name, entry)| TaskStatus {
+                name: name.clone(),
+                kind: entry.kind,
+                critical: entry.critical,
+                state: entry.state,
+                stalled: entry.stalled(),
+                restarts: entry.restarts,
+                policy: entry.policy,
+                started_at: entry.started_at.to_rfc3339_opts(SecondsFormat::Secs, false),
+                last_beat_ms_ago: entry.last_beat.elapsed().as_millis() as u64,
+            })
+            .collect(),
+    })
+}
+
+#[tauri::command]
+fn get_runtime_health() -> RuntimeHealth {
+    runtime_health()
+}
+
+/// Modos degradados que la UI resume en un único aviso de salud del sistema.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+enum DegradedMode {
+    NoBroker,
+    NoGpio,
+    NoPersistence,
+    ClockUnsynced,
+    DiskFull,
+}
+
+impl DegradedMode {
+    /// Sin broker no llegan alarmas y sin GPIO no suenan: el resto sólo resta funciones.
+    fn critical(self) -> bool {
+        matches!(self, DegradedMode::NoBroker | DegradedMode::NoGpio)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DegradedEntry {
+    since_ms: i64,
+    detail: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DegradedModeStatus {
+    mode: DegradedMode,
+    critical: bool,
+    since_ms: i64,
+    detail: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DegradationStatus {
+    healthy: bool,
+    critical: bool,
+    modes: Vec<DegradedModeStatus>,
+}
+
+fn snapshot_degradation() -> DegradationStatus {
+    let modes: Vec<DegradedModeStatus> = DEGRADATION
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .map(|(mode, entry)| DegradedModeStatus {
+            mode: *mode,
+            critical: mode.critical(),
+            since_ms: entry.since_ms,
+            detail: entry.detail.clone(),
+        })
+        .collect();
+    DegradationStatus {
+        healthy: modes.is_empty(),
+        critical: modes.iter().any(|mode| mode.critical),
+        modes,
+    }
+}
+
+/// Modos activos ahora mismo según cada subsistema, con su detalle.
+fn current_degraded_modes() -> BTreeMap<DegradedMode, String> {
+    let cfg = app_config();
+    let mut modes = BTreeMap::new();
+    if !MQTT_CONNECTED.load(Ordering::SeqCst) {
+        // Sin error todavía es el arranque o una reconexión pedida, no una caída.
+        if let Some(err) = MQTT_LAST_ERROR
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+        {
+            modes.insert(DegradedMode::NoBroker, err);
+        }
+    }
+    let failing = with_hardware_health(|health| failing_outputs(health));
+    if !failing.is_empty() {
+        modes.insert(
+            DegradedMode::NoGpio,
+            format!("Salidas con fallo: {}", failing.join(", ")),
+        );
+    }
+    if let Some(err) = PERSISTENCE_ERROR
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+    {
+        modes.insert(DegradedMode::NoPersistence, err);
+    }
+    let skew = snapshot_clock_skew();
+    let stale_after_ms = (cfg.server_time.interval_secs.max(10) * 3 * 1000) as i64;
+    if skew.exceeded {
+        modes.insert(
+            DegradedMode::ClockUnsynced,
+            format!("Desfase de {} ms con el servidor", skew.offset_ms),
+        );
+    } else if let Some(synced_at_ms) = skew.synced_at_ms {
+        let age_ms = Utc::now().timestamp_millis() - synced_at_ms;
+        if !cfg.server_time.rpc_method.is_empty() && age_ms > stale_after_ms {
+            modes.insert(
+                DegradedMode::ClockUnsynced,
+                format!("Sin hora del servidor hace {} s", age_ms / 1000),
+            );
+        }
+    }
+    if let Some(detail) = DISK_FULL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+    {
+        modes.insert(DegradedMode::DiskFull, detail);
+    }
+    modes
+}
+
+/// Recalcula la matriz de degradación y emite `system://degradation_changed` si algún modo
+/// entró o salió; un cambio de detalle solo se guarda.
+fn refresh_degradation(app_handle: &EventSink) {
+    let current = current_degraded_modes();
+    let now_ms = corrected_now().timestamp_millis();
+    let changed = {
+        let mut tracked = DEGRADATION
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut changed = false;
+        tracked.retain(|mode, _| {
+            let keep = current.contains_key(mode);
+            if !keep {
+                info!("[HEALTH] Fin de modo degradado {:?}", mode);
+                changed = true;
+            }
+            keep
+        });
+        for (mode, detail) in current {
+            match tracked.get_mut(&mode) {
+                Some(entry) => entry.detail = detail,
+                None => {
+                    warn!("[HEALTH] Modo degradado {:?}: {}", mode, detail);
+                    tracked.insert(
+                        mode,
+                        DegradedEntry {
+                            since_ms: now_ms,
+                            detail,
+                        },
+                    );
+                    changed = true;
+                }
+            }
+        }
+        changed
+    };
+    if changed {
+        if let Err(err) = app_handle.emit(DEGRADATION_EVENT, snapshot_degradation()) {
+            warn!(
+                "[HEALTH] No se pudo emitir estado de degradación: {:?}",
+                err
+            );
+        }
+    }
+}
+
+/// Espacio libre en `dir` según `df -Pk`, en MB.
+fn free_disk_mb(dir: &Path) -> Result<u64, String> {
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .map_err(|err| format!("No se pudo ejecutar df: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "df devolvio codigo {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb / 1024)
+        .ok_or_else(|| "Salida de df inesperada".to_string())
+}
+
+/// Controla el espacio de `DATA_DIR` cada `DISK_CHECK_INTERVAL`; bloquea (ejecuta `df`).
+fn check_disk_space() {
+    {
+        let mut checked_at = DISK_CHECKED_AT
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if checked_at.is_some_and(|at| at.elapsed() < DISK_CHECK_INTERVAL) {
+            return;
+        }
+        *checked_at = Some(Instant::now());
+    }
+    let cfg = app_config();
+    let full = match free_disk_mb(Path::new(&cfg.data_dir)) {
+        Ok(free_mb) if free_mb < cfg.disk_min_free_mb => Some(format!(
+            "Quedan {} MB libres en {} (mínimo {} MB)",
+            free_mb, cfg.data_dir, cfg.disk_min_free_mb
+        )),
+        Ok(_) => None,
+        Err(err) => {
+            debug!("[HEALTH] No se pudo medir el espacio libre: {}", err);
+            return;
+        }
+    };
+    *DISK_FULL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = full;
+}
+
+#[tauri::command]
+fn get_degradation_status() -> DegradationStatus {
+    snapshot_degradation()
+}
+
+struct SystemFaultState {
+    /// Primera vez que se vio la falla, mientras corre el margen de `grace_secs`.
+    pending_since: Option<Instant>,
+    active: Option<(i64, String)>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SystemFaultStatus {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    since_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+fn snapshot_system_fault() -> SystemFaultStatus {
+    let state = SYSTEM_FAULT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    SystemFaultStatus {
+        active: state.active.is_some(),
+        since_ms: state.active.as_ref().map(|(since_ms, _)| *since_ms),
+        reason: state.active.as_ref().map(|(_, reason)| reason.clone()),
+    }
+}
+
+fn system_fault_active() -> bool {
+    SYSTEM_FAULT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .active
+        .is_some()
+}
+
+fn system_fault_reason(cfg: &SystemFaultConfig, failed: &[String]) -> Option<String> {
+    let mut reasons = Vec::new();
+    if cfg.on_task_failure && !failed.is_empty() {
+        reasons.push(format!("Tareas críticas detenidas: {}", failed.join(", ")));
+    }
+    if cfg.on_persistence_failure {
+        if let Some(err) = PERSISTENCE_ERROR
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_ref()
+        {
+            reasons.push(format!("Historial sin grabar: {}", err));
+        }
+    }
+    (!reasons.is_empty()).then(|| reasons.join("; "))
+}
+
+/// Activa la falla del sistema cuando se sostiene `grace_secs` y la retira al recuperarse; en
+/// cada transición recalcula las salidas y manda el aviso de último recurso.
+fn apply_system_fault(failed: &[String], app_handle: &EventSink) {
+    let cfg = &app_config().system_fault;
+    let reason = cfg
+        .enabled
+        .then(|| system_fault_reason(cfg, failed))
+        .flatten();
+    // `Some(Some(motivo))` al activarse, `Some(None)` al recuperarse.
+    let transition = {
+        let mut state = SYSTEM_FAULT
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match reason {
+            None => {
+                state.pending_since = None;
+                state.active.take().map(|_| None)
+            }
+            Some(reason) => match state.active.as_mut() {
+                // Si cambia el motivo se actualiza sin volver a notificar.
+                Some((_, active)) => {
+                    *active = reason;
+                    None
+                }
+                None => {
+                    let since = *state.pending_since.get_or_insert_with(Instant::now);
+                    (since.elapsed() >= Duration::from_secs(cfg.grace_secs)).then(|| {
+                        state.active = Some((corrected_now().timestamp_millis(), reason.clone()));
+                        Some(reason)
+                    })
+                }
+            },
+        }
+    };
+    let Some(raised) = transition else {
+        return;
+    };
+    let panel = panel_id();
+    match raised {
+        Some(reason) => {
+            error!("[FAULT] Falla del sistema de alarmas: {}", reason);
+            record_audit("local", "system_fault", "raised", &reason);
+            notify(
+                "system_fault",
+                format!("{}: falla del sistema de alarmas", panel),
+                format!(
+                    "El panel {} no puede garantizar las alarmas: {}",
+                    panel, reason
+                ),
+            );
+        }
+        None => {
+            info!("[FAULT] Sistema de alarmas recuperado");
+            record_audit("local", "system_fault", "cleared", "");
+            notify(
+                "system_fault_cleared",
+                format!("{}: sistema de alarmas recuperado", panel),
+                format!("El panel {} volvió a funcionar con normalidad", panel),
+            );
+        }
+    }
+    apply_buzzer_policy();
+    if let Err(err) = app_handle.emit(SYSTEM_FAULT_EVENT, snapshot_system_fault()) {
+        warn!("[FAULT] No se pudo emitir falla del sistema: {:?}", err);
+    }
+}
+
+#[tauri::command]
+fn get_system_fault() -> SystemFaultStatus {
+    snapshot_system_fault()
+}
+
+/// Quién puede invocar un comando: cualquier ventana, sólo las de operador (los de escritura
+/// pasan por `check_write_access`) o además con la sesión de un supervisor.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum CommandRole {
+    Any,
+    Operator,
+    Supervisor,
+}
+
+/// Argumento con el nombre que espera `invoke` (camelCase).
+#[derive(Debug, Serialize, Clone)]
+struct CommandArg {
+    name: &'static str,
+    #[serde(rename = "type")]
+    arg_type: &'static str,
+    required: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct CommandSpec {
+    name: &'static str,
+    args: Vec<CommandArg>,
+    returns: &'static str,
+    role: CommandRole,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct EventSpec {
+    name: &'static str,
+    payload: &'static str,
+}
+
+/// Tipos en notación TypeScript; los nombres propios son los structs del backend tal como se
+/// serializan. `schemaVersion` cambia sólo si cambia la forma de este documento.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CommandSchema {
+    schema_version: u32,
+    app_version: &'static str,
+    commands: Vec<CommandSpec>,
+    events: Vec<EventSpec>,
+}
+
+/// `"csvPath: string, dryRun?: boolean"`: `?` marca los opcionales.
+fn parse_command_args(signature: &'static str) -> Vec<CommandArg> {
+    signature
+        .split(", ")
+        .filter_map(|arg| {
+            let (name, arg_type) = arg.split_once(": ")?;
+            let (name, required) = match name.strip_suffix('?') {
+                Some(name) => (name, false),
+                None => (name, true),
+            };
+            Some(CommandArg {
+                name,
+                arg_type,
+                required,
+            })
+        })
+        .collect()
+}
+
+/// Registro único de comandos: de aquí salen `generate_handler!` y `get_command_schema`, así que
+/// un comando no puede quedar fuera del esquema. Se invoca con la macro que consume la lista.
+macro_rules! app_commands {
+    ($consumer:ident) => {
+        $consumer! {
+            get_active_alerts("zone?: string") -> "Alert[]", Any;
+            get_alerts_since("cursor?: string") -> "AlertDiff", Any;
+            get_zone_status("") -> "SiteStatus", Any;
+            get_floorplan("") -> "Floorplan", Any;
+            get_floorplan_overlay("") -> "FloorplanOverlay[]", Any;
+            get_panel_role("") -> "PanelRole", Any;
+            remove_alert("id: string, reason?: string") -> "boolean", Operator;
+            pin_alert("id: string") -> "null", Operator;
+            unpin_alert("id: string") -> "null", Operator;
+            reorder_pinned_alerts("ids: string[]") -> "null", Operator;
+            search_history(
+                "query: string, range?: HistoryRange, zone?: string"
+            ) -> "HistoryEntry[]", Any;
+            add_alert_note("id: string, note: string") -> "null", Operator;
+            generate_handover_report("email?: boolean") -> "HandoverReport", Any;
+            get_device_statistics(
+                "device: string, range?: HistoryRange"
+            ) -> "DeviceStatistics", Any;
+            export_device_statistics("range?: HistoryRange") -> "string", Any;
+            export_ical("range?: HistoryRange") -> "string", Any;
+            playback("range: HistoryRange, speed: number") -> "PlaybackState", Any;
+            export_site_pack("") -> "string", Any;
+            import_site_pack("path: string") -> "SitePackImport", Operator;
+            import_devices("csvPath: string, dryRun?: boolean") -> "DeviceImportReport", Operator;
+            get_devices("") -> "DeviceEntry[]", Any;
+            print_test_ticket("") -> "null", Operator;
+            badge_key("key: string") -> "null", Any;
+            get_operator_session("") -> "OperatorSession | null", Any;
+            pin_login("operatorId: string, pin: string") -> "null", Operator;
+            set_mqtt_credentials(
+                "host: string, port: number, username: string, password: string"
+            ) -> "null", Operator;
+            get_panel_lock("") -> "PanelLockStatus", Any;
+            logout_operator("") -> "null", Operator;
+            stop_playback("") -> "null", Any;
+            get_alert_snapshot("id: string") -> "string", Any;
+            take_alert_focus("") -> "AlertFocus | null", Any;
+            run_maintenance_now("") -> "MaintenanceReport", Operator;
+            report_interaction("count?: number") -> "null", Any;
+            get_presence_status("") -> "PresenceStatus", Any;
+            confirm_presence("") -> "PresenceStatus", Operator;
+            get_escalations("") -> "EscalationStatus[]", Any;
+            get_email_queue("") -> "QueuedEmail[]", Any;
+            get_dead_letters("") -> "DeadLetter[]", Any;
+            evaluate_payload("sampleJson: string, topic?: string") -> "PayloadEvaluation", Any;
+            get_runtime_health("") -> "RuntimeHealth", Any;
+            get_command_schema("") -> "CommandSchema", Any;
+            get_degradation_status("") -> "DegradationStatus", Any;
+            get_startup_status("") -> "StartupStatus | null", Any;
+            get_system_fault("") -> "SystemFaultStatus", Any;
+            get_recent_events("n?: number, since?: number") -> "BackendEvent[]", Any;
+            get_mqtt_stats("") -> "MqttStats", Any;
+            get_connectivity_status("") -> "ConnectivityStatus", Any;
+            get_modem_status("") -> "ModemStatus | null", Any;
+            get_hardware_status("") -> "HardwareStatus", Any;
+            get_ack_policy("") -> "AckPolicy", Any;
+            get_audible_test_status("") -> "AudibleTestStatus", Any;
+            run_audible_test_now("") -> "AudibleTestResult", Operator;
+            set_fault_injection(
+                "enabled: boolean, failureRate?: number, outputs?: string[]"
+            ) -> "HardwareStatus", Operator;
+            clear_dead_letters("") -> "null", Operator;
+            get_on_call_chain("") -> "OnCallShift[]", Any;
+            get_notification_routes("") -> "NotificationRoute[]", Any;
+            set_notification_routes(
+                "routes: NotificationRoute[]"
+            ) -> "NotificationRoute[]", Operator;
+            acknowledge_escalation("id: string") -> "boolean", Operator;
+            get_buzzer_inhibit("") -> "BuzzerInhibitStatus", Any;
+            clear_buzzer_inhibit_local("") -> "BuzzerInhibitStatus", Operator;
+            get_audio_profile("") -> "AudioProfileStatus", Any;
+            set_audio_profile_override(
+                "profile?: AudioProfile"
+            ) -> "AudioProfileStatus", Supervisor;
+            check_internet_connection("") -> "boolean", Any;
+            get_mute_status("") -> "MuteStatePayload", Any;
+            get_visual_alarm("") -> "VisualAlarmState", Any;
+            toggle_alerts_mute("") -> "MuteStatePayload", Operator;
+            is_mqtt_connected("") -> "boolean", Any;
+            get_mqtt_status("") -> "MqttStatus", Any;
+            is_supabase_connected("") -> "boolean", Any;
+            get_clock_skew("") -> "ClockSkewStatus", Any;
+            get_log_level("") -> "string", Any;
+            set_log_level("level: string") -> "string", Operator;
+            validate_config("") -> "ConfigProblem[]", Any;
+            import_certificate("source: string, kind?: CertificateKind") -> "string", Operator;
+            get_certificate_status("") -> "CertExpiryStatus | null", Any;
+            wizard_get_config("") -> "AppConfig", Operator;
+            wizard_configure_network(
+                "ssid: string, password: string"
+            ) -> "WizardStepResult", Operator;
+            wizard_test_broker("draft: AppConfig") -> "WizardStepResult", Operator;
+            wizard_list_usb_certificates("") -> "string[]", Any;
+            wizard_import_certificate("path: string") -> "WizardStepResult", Operator;
+            wizard_test_buzzer("") -> "WizardStepResult", Operator;
+            wizard_commit_config("draft: AppConfig") -> "WizardStepResult", Operator;
+        }
+    };
+}
+
+macro_rules! command_handler {
+    ($($name:ident($args:literal) -> $returns:literal, $role:ident;)*) => {
+        tauri::generate_handler![$($name),*]
+    };
+}
+
+macro_rules! command_specs {
+    ($($name:ident($args:literal) -> $returns:literal, $role:ident;)*) => {
+        vec![$(CommandSpec {
+            name: stringify!($name),
+            args: parse_command_args($args),
+            returns: $returns,
+            role: CommandRole::$role,
+        }),*]
+    };
+}
+
+#[cfg(feature = "e2e")]
+macro_rules! command_names {
+    ($($name:ident($args:literal) -> $returns:literal, $role:ident;)*) => {
+        vec![$(stringify!($name)),*]
+    };
+}
+
+/// Nombres de los comandos registrados, en el orden de `generate_handler!`.
+#[cfg(feature = "e2e")]
+fn registered_commands() -> Vec<&'static str> {
+    app_commands!(command_names)
+}
+
+fn command_schema() -> CommandSchema {
+    CommandSchema {
+        schema_version: COMMAND_SCHEMA_VERSION,
+        app_version: env!("CARGO_PKG_VERSION"),
+        commands: app_commands!(command_specs),
+        events: APP_EVENTS
+            .iter()
+            .map(|&(name, payload)| EventSpec { name, payload })
+            .collect(),
+    }
+}
+
+/// Comandos y eventos del backend, para que el frontend (versionado aparte) y los integradores
+/// se adapten en tiempo de ejecución.
+#[tauri::command]
+fn get_command_schema() -> CommandSchema {
+    command_schema()
+}
+
+/// Alerta local mientras haya tareas críticas caídas; se libera cuando todas vuelven a estar sanas.
+fn apply_runtime_health_alert(failed: &[String], app_handle: &EventSink) {
+    let description =
+        (!failed.is_empty()).then(|| format!("Tareas internas detenidas: {}", failed.join(", ")));
+    apply_local_alert(RUNTIME_HEALTH_ALERT_ID, description, app_handle);
+}
+
+/// Alerta de advertencia generada por el propio panel; `None` la retira.
+fn apply_local_alert(id: &str, description: Option<String>, app_handle: &EventSink) {
+    let Some(description) = description else {
+        if let Some(alert) = remove_alert_by_id(id) {
+            info!("[HEALTH] {}: recuperado", id);
+            publish_domain_event(app_handle, DomainEvent::AlertRemoved(alert));
+        }
+        return;
+    };
+    let existing = with_alert_store(|store| store.get(id).cloned());
+    if existing
+        .as_ref()
+        .is_some_and(|alert| alert.description == description)
+    {
+        return;
+    }
+    warn!("[HEALTH] {}", description);
+    let now_ms = corrected_now().timestamp_millis();
+    let mut alert = existing.clone().unwrap_or_else(|| Alert {
+        id: id.to_string(),
+        date_time: format_timestamp_ms(now_ms),
+        created_at_iso: iso_utc_ms(now_ms),
+        created_at_ms: now_ms,
+        alert_type: AlertType::Disconnect,
+        device: panel_id().to_string(),
+        description: String::new(),
+        severity: AlertSeverity::Warning,
+        acknowledged: false,
+        details: None,
+        trend: None,
+        eta_to_limit: None,
+        defrost: false,
+        pin_order: pin_position(id),
+        display: None,
+        raw: None,
+        alarm_id: String::new(),
+    });
+    alert.description = description;
+    cache_alert(&alert);
+    if existing.is_some() {
+        publish_domain_event(app_handle, DomainEvent::AlertUpdated(alert));
+    } else {
+        publish_domain_event(app_handle, DomainEvent::AlertAdded(alert));
+    }
+}
+
+/// Mide el retraso del event loop (cuánto se pasa un `sleep` de su plazo) y vigila las tareas.
+fn start_runtime_health_loop(app_handle: EventSink) {
+    supervise(
+        "runtime-health",
+        false,
+        None,
+        RestartPolicy::Always,
+        move |_task| {
+            let app_handle = app_handle.clone();
+            async move {
+                while !is_shutting_down() {
+                    let started = Instant::now();
+                    tokio::time::sleep(RUNTIME_HEALTH_TICK).await;
+                    let lag_ms = started
+                        .elapsed()
+                        .saturating_sub(RUNTIME_HEALTH_TICK)
+                        .as_millis() as u64;
+                    EVENT_LOOP_LAG_MS.store(lag_ms, Ordering::Relaxed);
+                    EVENT_LOOP_MAX_LAG_MS.fetch_max(lag_ms, Ordering::Relaxed);
+                    if is_shutting_down() {
+                        break;
+                    }
+                    let failed = runtime_health().failed_tasks;
+                    let app_handle = app_handle.clone();
+                    let _ = async_runtime::spawn_blocking(move || {
+                        apply_runtime_health_alert(&failed, &app_handle);
+                        apply_hardware_status(&app_handle);
+                        check_disk_space();
+                        refresh_degradation(&app_handle);
+                        apply_system_fault(&failed, &app_handle);
+                        report_display_latency();
+                    })
+                    .await;
+                }
+            }
+        },
+    );
+}
+
+/// Mantenimiento nocturno a la hora `MAINTENANCE_HOUR` (hora de la planta).
+fn start_maintenance_loop(app_handle: EventSink) {
+    supervise(
+        "maintenance",
+        false,
+        None,
+        RestartPolicy::Always,
+        move |_task| {
+            let app_handle = app_handle.clone();
+            async move {
+                while !is_shutting_down() {
+                    let wait = until_next_maintenance(plant_now(), app_config().maintenance_hour);
+                    tokio::time::sleep(wait).await;
+                    if is_shutting_down() {
+                        break;
+                    }
+                    match async_runtime::spawn_blocking(run_maintenance).await {
+                        Ok(report) => emit_maintenance_report(&app_handle, &report),
+                        Err(err) => warn!("[MAINT] Fallo en mantenimiento: {:?}", err),
+                    }
+                }
+            }
+        },
+    );
+}
+
+/// Métricas de uso anónimas: sólo conteos y tiempos, sin identificar operadores.
+#[derive(Debug, Default)]
+struct InteractionMetrics {
+    touches: u64,
+    mutes_in_shift: u64,
+    shift_start: Option<DateTime<Tz>>,
+    pending_acks: HashMap<String, i64>,
+    ack_total_ms: i64,
+    ack_count: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InteractionMetricsPayload {
+    touches_per_hour: f64,
+    mutes_this_shift: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    avg_time_to_ack_secs: Option<f64>,
+    ack_samples: u64,
+}
+
+fn with_interaction_metrics<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut InteractionMetrics) -> R,
+{
+    let metrics = INTERACTION_METRICS.get_or_init(|| Mutex::new(InteractionMetrics::default()));
+    let mut guard = metrics
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Inicio del turno vigente según `SHIFT_START_HOURS` (puede ser del día anterior).
+fn shift_definitions(cfg: &AppConfig) -> Vec<(String, NaiveTime)> {
+    if cfg.shifts.is_empty() {
+        return cfg
+            .shift_start_hours
+            .iter()
+            .filter_map(|hour| {
+                NaiveTime::from_hms_opt(*hour, 0, 0)
+                    .map(|start| (format!("Turno {:02}:00", hour), start))
+            })
+            .collect();
+    }
+    cfg.shifts
+        .iter()
+        .filter_map(|shift| {
+            schedule::parse_hhmm(&shift.start).map(|start| (shift.name.clone(), start))
+        })
+        .collect()
+}
+
+/// Turno en curso: el inicio más reciente que no sea posterior a `now`, mirando también el día anterior.
+fn current_shift(
+    now: DateTime<Tz>,
+    shifts: &[(String, NaiveTime)],
+) -> Option<(String, DateTime<Tz>)> {
+    shifts
+        .iter()
+        .filter_map(|(name, start)| {
+            schedule::last_daily(&now, *start).map(|start| (name.clone(), start))
+        })
+        .max_by_key(|(_, start)| *start)
+}
+
+fn roll_shift(metrics: &mut InteractionMetrics) {
+    let shift_start =
+        current_shift(plant_now(), &shift_definitions(app_config())).map(|(_, start)| start);
+    if metrics.shift_start != shift_start {
+        metrics.shift_start = shift_start;
+        metrics.mutes_in_shift = 0;
+    }
+}
+
+/// Tiempo hasta la primera acción local (silenciar o eliminar); `None` reconoce todas las pendientes.
+fn record_acknowledgement(alert_id: Option<&str>) {
+    let now_ms = corrected_now().timestamp_millis();
+    with_interaction_metrics(|metrics| {
+        let acked: Vec<i64> = match alert_id {
+            Some(id) => metrics.pending_acks.remove(id).into_iter().collect(),
+            None => metrics
+                .pending_acks
+                .drain()
+                .map(|(_, added)| added)
+                .collect(),
+        };
+        for added_ms in acked {
+            metrics.ack_total_ms += (now_ms - added_ms).max(0);
+            metrics.ack_count += 1;
+        }
+    });
+}
+
+fn record_mute_metric() {
+    with_interaction_metrics(|metrics| {
+        roll_shift(metrics);
+        metrics.mutes_in_shift += 1;
+    });
+}
+
+fn metrics_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    match event {
+        DomainEvent::AlertAdded(alert) => {
+            let now_ms = corrected_now().timestamp_millis();
+            with_interaction_metrics(|metrics| {
+                metrics.pending_acks.insert(alert.id.clone(), now_ms);
+            });
+        }
+        DomainEvent::AlertRemoved(alert) => {
+            with_interaction_metrics(|metrics| {
+                metrics.pending_acks.remove(&alert.id);
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Toma los contadores del intervalo y los reinicia (salvo los del turno).
+fn take_interaction_metrics(interval: Duration) -> InteractionMetricsPayload {
+    with_interaction_metrics(|metrics| {
+        roll_shift(metrics);
+        let hours = interval.as_secs_f64() / 3600.0;
+        let payload = InteractionMetricsPayload {
+            touches_per_hour: if hours > 0.0 {
+                metrics.touches as f64 / hours
+            } else {
+                0.0
+            },
+            mutes_this_shift: metrics.mutes_in_shift,
+            avg_time_to_ack_secs: (metrics.ack_count > 0)
+                .then(|| metrics.ack_total_ms as f64 / metrics.ack_count as f64 / 1000.0),
+            ack_samples: metrics.ack_count,
+        };
+        metrics.touches = 0;
+        metrics.ack_total_ms = 0;
+        metrics.ack_count = 0;
+        payload
+    })
+}
+
+fn start_metrics_loop() {
+    let cfg = app_config();
+    if !cfg.metrics_enabled {
+        return;
+    }
+    let interval = Duration::from_secs(cfg.metrics_interval_minutes.max(1) * 60);
+    supervise(
+        "metrics",
+        false,
+        Some(interval),
+        RestartPolicy::Always,
+        move |task| async move {
+            while !is_shutting_down() {
+                tokio::time::sleep(interval).await;
+                task.beat();
+                let payload = take_interaction_metrics(interval);
+                match serde_json::to_vec(&payload) {
+                    Ok(bytes) => {
+                        if mqtt_publish(MQTT_TELEMETRY_PUBLISH_TOPIC, bytes, QoS::AtLeastOnce) {
+                            debug!("[METRICS] Publicadas: {:?}", payload);
+                        }
+                    }
+                    Err(err) => warn!("[METRICS] No se pudo serializar: {:?}", err),
+                }
+            }
+        },
+    );
+}
+
+fn start_projection_loop(app_handle: EventSink) {
+    supervise(
+        "projections",
+        true,
+        Some(PROJECTION_REFRESH_INTERVAL),
+        RestartPolicy::Always,
+        move |task| {
+            let app_handle = app_handle.clone();
+            async move {
+                while !is_shutting_down() {
+                    tokio::time::sleep(PROJECTION_REFRESH_INTERVAL).await;
+                    task.beat();
+                    let app_handle = app_handle.clone();
+                    let refreshed = async_runtime::spawn_blocking(move || {
+                        refresh_projections(&app_handle, |alert| {
+                            matches!(alert.alert_type, AlertType::TempUp | AlertType::TempDown)
+                        });
+                    })
+                    .await;
+                    if let Err(err) = refreshed {
+                        warn!("[TELEMETRY] Fallo al refrescar proyecciones: {:?}", err);
+                    }
+                }
+            }
+        },
+    );
+}
+
+fn handle_telemetry_payload(topic: &str, payload: &[u8], app_handle: &EventSink) {
+    let sample: TelemetryPayload = match serde_json::from_slice(payload) {
+        Ok(data) => data,
+        Err(err) => {
+            warn!("[TELEMETRY] No se pudo parsear payload: {:?}", err);
+            dead_letter(topic, payload, err.to_string());
+            return;
+        }
+    };
+    handle_telemetry_sample(sample, app_handle);
+}
+
+fn handle_telemetry_sample(sample: TelemetryPayload, app_handle: &EventSink) {
+    let ts_ms = sample.ts_ms();
+    record_telemetry(&sample.device, ts_ms, sample.value);
+    refresh_projections(app_handle, |alert| alert.device == sample.device);
+    evaluate_rate_of_change_rules(&sample.device, app_handle);
+}
+
+fn is_telemetry_topic(topic: &str) -> bool {
+    let filter = app_config().mqtt_telemetry_topic.as_str();
+    !filter.is_empty() && rumqttc::matches(topic, filter)
+}
+
+/// Reglas locales de velocidad de cambio: generan alertas predictivas antes del umbral absoluto.
+fn evaluate_rate_of_change_rules(device: &str, app_handle: &EventSink) {
+    let rules = &app_config().rate_of_change_rules;
+    for (index, rule) in rules.iter().enumerate() {
+        if rule
+            .device
+            .as_deref()
+            .is_some_and(|target| target != device)
+        {
+            continue;
+        }
+
+        let window_minutes = rule.window_minutes.max(1);
+        let alert_id = format!("rate-of-change-{}-{}", index, device);
+        let change = telemetry_rate_per_minute(
+            device,
+            Duration::from_secs(window_minutes.saturating_mul(60)),
+        )
+        .map(|rate| rate * window_minutes as f64);
+
+        let triggered = change.and_then(|change| {
+            if let Some(limit) = rule.max_rise.filter(|limit| change > *limit) {
+                Some((AlertType::TempUp, "subiendo", limit, change))
+            } else if let Some(limit) = rule.max_fall.filter(|limit| -change > *limit) {
+                Some((AlertType::TempDown, "bajando", limit, change))
+            } else {
+                None
+            }
+        });
+
+        let Some((alert_type, verb, limit, change)) = triggered else {
+            if let Some(alert) = remove_alert_by_id(&alert_id) {
+                info!("[RULES] LIBERADA {} dispositivo={}", alert_id, device);
+                publish_domain_event(app_handle, DomainEvent::AlertRemoved(alert));
+            }
+            continue;
+        };
+
+        // Fija mientras no cambie el sentido; el cambio medido al dispararse va en los detalles,
+        // así cada muestra nueva no vuelve a emitir `alerts://updated`.
+        let description = format!(
+            "Temperatura {} más de {:.1} {} en {} min",
+            verb, limit, TEMPERATURE_UNIT, window_minutes
+        );
+        let existing = with_alert_store(|store| store.get(&alert_id).cloned());
+        if existing
+            .as_ref()
+            .is_some_and(|alert| alert.description == description)
+        {
+            continue;
+        }
+
+        let now_ms = corrected_now().timestamp_millis();
+        let mut alert = existing.unwrap_or_else(|| Alert {
+            id: alert_id.clone(),
+            date_time: format_timestamp_ms(now_ms),
+            created_at_iso: iso_utc_ms(now_ms),
+            created_at_ms: now_ms,
+            alert_type: alert_type.clone(),
+            device: device.to_string(),
+            description: String::new(),
+            severity: AlertSeverity::Warning,
+            acknowledged: false,
+            details: None,
+            trend: None,
+            eta_to_limit: None,
+            defrost: false,
+            pin_order: pin_position(&alert_id),
+            display: None,
+            raw: None,
+            alarm_id: String::new(),
+        });
+        let is_update = !alert.description.is_empty();
+        alert.alert_type = alert_type;
+        alert.description = description;
+        alert.details = Some(AlertDetails {
+            threshold: Some(limit),
+            unit: Some(TEMPERATURE_UNIT.to_string()),
+            fields: HashMap::from([(
+                "change".to_string(),
+                serde_json::json!((change * 10.0).round() / 10.0),
+            )]),
+            ..AlertDetails::default()
+        });
+        apply_projection(&mut alert);
+        cache_alert(&alert);
+
+        if is_update {
+            publish_domain_event(app_handle, DomainEvent::AlertUpdated(alert));
+        } else {
+            info!(
+                "[RULES] ACTIVADA {} dispositivo={} {}",
+                alert.id, device, alert.description
+            );
+            publish_domain_event(app_handle, DomainEvent::AlertAdded(alert));
+        }
+    }
+}
+
+fn handle_cleared_alarm(params: AlarmParams, app_handle: &EventSink) {
+    let alert_id = panel_alert_id(&params);
+    let identity = &app_config().alert_identity;
+    if identity.mode == AlertIdentityMode::OriginatorType {
+        let current = with_alert_store(|store| store.get(&alert_id).map(|a| a.alarm_id.clone()));
+        match current {
+            Some(current) if current != params.id.value => {
+                debug!(
+                    "[ALERT] CLEAR de {} ignorado: {} ya sigue a la alarma {}",
+                    params.id.value, alert_id, current
+                );
+                return;
+            }
+            Some(_) if identity.clear_grace_secs > 0 => {
+                with_pending_clears(|pending| {
+                    pending.insert(alert_id.clone(), params.id.value.clone())
+                });
+                schedule_pending_clear(
+                    alert_id,
+                    Duration::from_secs(identity.clear_grace_secs),
+                    app_handle,
+                );
+                return;
+            }
+            _ => {}
+        }
+    }
+    remove_cleared_alert(&alert_id, &params, app_handle);
+}
+
+/// Retira la alerta si durante `grace` no llegó una re-creación que cancele el despeje.
+fn schedule_pending_clear(alert_id: String, grace: Duration, app_handle: &EventSink) {
+    let app_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        let _task = track_task(
+            &format!("alert-clear:{}", alert_id),
+            TaskKind::Timer,
+            false,
+            None,
+        );
+        tokio::time::sleep(grace).await;
+        let Some(alarm_id) = with_pending_clears(|pending| pending.remove(&alert_id)) else {
+            return;
+        };
+        let alert = with_alert_store(|store| match store.get(&alert_id) {
+            Some(alert) if alert.alarm_id == alarm_id => store.remove(&alert_id),
+            _ => None,
+        });
+        if let Some(alert) = alert {
+            info!(
+                "[ALERT] LIBERADA {} (alarma {}) tras {:?} sin re-creación",
+                alert_id, alarm_id, grace
+            );
+            publish_domain_event(&app_handle, DomainEvent::AlertRemoved(alert));
+        }
+    });
+}
+
+fn remove_cleared_alert(alert_id: &str, params: &AlarmParams, app_handle: &EventSink) {
+    if let Some(alert) = remove_alert_by_id(alert_id) {
+        info!(
+            "[ALERT] LIBERADA {} tipo={} dispositivo={}",
+            alert_id, params.alarm_type, params.originator_name
+        );
+        publish_domain_event(app_handle, DomainEvent::AlertRemoved(alert));
+    } else {
+        debug!(
+            "[ALERT] Se recibió CLEAR para {}, pero no existe en cache",
+            alert_id
+        );
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionStats {
+    mqtt_connected: bool,
+    supabase_connected: bool,
+    mqtt_reconnects: u64,
+    mqtt_pings: u64,
+    network_link_up: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MqttStats {
+    connected: bool,
+    reconnects: u64,
+    pings: u64,
+    network_link_up: bool,
+    network_link_ups: u64,
+    deferred_retries: u64,
+    display_latency: DisplayLatencyStats,
+}
+
+/// Estado de la conexión MQTT y latencia publicación→pantalla frente al SLO.
+#[tauri::command]
+fn get_mqtt_stats() -> MqttStats {
+    MqttStats {
+        connected: MQTT_CONNECTED.load(Ordering::SeqCst),
+        reconnects: MQTT_RECONNECTS.load(Ordering::Relaxed),
+        pings: MQTT_PING_COUNT.load(Ordering::Relaxed),
+        network_link_up: NETWORK_LINK_UP.load(Ordering::SeqCst),
+        network_link_ups: NETWORK_LINK_UPS.load(Ordering::Relaxed),
+        deferred_retries: MQTT_DEFERRED_RETRIES.load(Ordering::Relaxed),
+        display_latency: display_latency_stats(),
+    }
+}
+
+/// Volcado de estado para soporte remoto (`GET_STATE`).
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PanelState {
+    version: &'static str,
+    panel_id: String,
+    active_alerts: Vec<Alert>,
+    mute: MuteStatePayload,
+    buzzer_inhibit: BuzzerInhibitStatus,
+    clock_skew: ClockSkewStatus,
+    connections: ConnectionStats,
+}
+
+fn snapshot_panel_state() -> PanelState {
+    PanelState {
+        version: env!("CARGO_PKG_VERSION"),
+        panel_id: panel_id().to_string(),
+        active_alerts: snapshot_alerts(),
+        mute: snapshot_mute_state(),
+        buzzer_inhibit: snapshot_buzzer_inhibit(),
+        clock_skew: snapshot_clock_skew(),
+        connections: ConnectionStats {
+            mqtt_connected: MQTT_CONNECTED.load(Ordering::SeqCst),
+            supabase_connected: SUPABASE_CONNECTED.load(Ordering::SeqCst),
+            mqtt_reconnects: MQTT_RECONNECTS.load(Ordering::Relaxed),
+            mqtt_pings: MQTT_PING_COUNT.load(Ordering::Relaxed),
+            network_link_up: NETWORK_LINK_UP.load(Ordering::SeqCst),
+        },
+    }
+}
+
+/// Responde en `v1/devices/me/rpc/response/{id}` usando el id del topic de la solicitud.
+fn reply_rpc<T: Serialize>(request_topic: &str, response: &T) {
+    let Some(request_id) = request_topic
+        .strip_prefix(MQTT_RPC_REQUEST_PREFIX)
+        .filter(|id| !id.is_empty())
+    else {
+        warn!("[MQTT] Topic RPC sin id de solicitud: {}", request_topic);
+        return;
+    };
+    match serde_json::to_vec(response) {
+        Ok(payload) => {
+            let topic = format!("{}{}", MQTT_RPC_RESPONSE_PREFIX, request_id);
+            if !mqtt_publish(&topic, payload, QoS::AtLeastOnce) {
+                warn!("[MQTT] No se pudo responder RPC {}", request_id);
+            }
+        }
+        Err(err) => warn!("[MQTT] No se pudo serializar respuesta RPC: {:?}", err),
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientAttributes {
+    app_version: &'static str,
+    config_hash: String,
+    frontend_bundle_hash: &'static str,
+    enabled_features: Vec<&'static str>,
+    hardware_revision: String,
+    hardware_profile: &'static str,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+/// Hash de la configuración efectiva (incluye valores por defecto), no del archivo en disco.
+/// Claves ordenadas para que no cambie entre arranques; de los secretos sólo cuenta si existen.
+fn config_hash(cfg: &AppConfig) -> String {
+    let mut cfg = cfg.clone();
+    redact_secrets(&mut cfg);
+    match serde_json::to_value(&cfg) {
+        Ok(value) => sha256_hex(canonical_json(&value).as_bytes()),
+        Err(err) => {
+            warn!("[CONFIG] No se pudo serializar la configuración: {:?}", err);
+            "unknown".to_string()
+        }
+    }
+}
+
+fn enabled_features(cfg: &AppConfig) -> Vec<&'static str> {
+    [
+        ("buzzer", cfg.buzzer_enabled),
+        ("supabase", !cfg.supabase_url.is_empty()),
+        ("telemetry", !cfg.mqtt_telemetry_topic.is_empty()),
+        ("peerSync", cfg.peer_sync_enabled),
+        ("mdns", cfg.mdns_enabled),
+        ("networkMonitor", cfg.network_monitor_enabled),
+        ("captivePortal", !cfg.network.captive_portal_url.is_empty()),
+        (
+            "serverTime",
+            !cfg.server_time.rpc_method.is_empty() || !cfg.server_time.attribute.is_empty(),
+        ),
+        ("modem", cfg.modem.enabled),
+        (
+            "smsFallback",
+            cfg.modem.enabled && !cfg.modem.sms_recipients.is_empty(),
+        ),
+        (
+            "notificationRouting",
+            !cfg.notification_routing.routes.is_empty()
+                || cfg.notification_routing.sync_from_platform,
+        ),
+        ("deviceRegistry", !cfg.devices.is_empty()),
+        ("printer", cfg.printer.enabled),
+        ("badgeLogin", cfg.badge_reader.enabled),
+        (
+            "zonalOutputs",
+            cfg.audible_outputs
+                .iter()
+                .any(|output| !output.zones.is_empty()),
+        ),
+        (
+            "sessionIdleLogout",
+            cfg.session_idle.idle_minutes > 0 && !cfg.operators.is_empty(),
+        ),
+        (
+            "pinLogin",
+            cfg.operators
+                .iter()
+                .any(|operator| !operator.pin_sha256.is_empty()),
+        ),
+        ("history", cfg.history_enabled),
+        ("metrics", cfg.metrics_enabled),
+        ("remoteBuzzerInhibit", cfg.remote_buzzer_inhibit_enabled),
+        ("remoteAudioProfile", cfg.audio_profile.remote_control),
+        ("mqttV5", cfg.mqtt_protocol == MqttProtocol::V5),
+        ("mqttWss", cfg.mqtt_transport == MqttTransport::Wss),
+        ("systemFaultAlarm", cfg.system_fault.enabled),
+        ("mqttSystemCa", cfg.mqtt_ca_source == MqttCaSource::System),
+        ("mqttBridge", cfg.mqtt_bridge.enabled),
+        ("faultInjection", cfg.hardware_fault_injection.enabled),
+        ("outputVerification", cfg.output_verification.enabled),
+        ("logForwarding", cfg.log_forwarding.enabled),
+        ("otel", cfg!(feature = "otel") && cfg.otel.enabled),
+        ("grpc", cfg!(feature = "grpc") && cfg.grpc.enabled),
+        ("audibleTest", cfg.audible_test.enabled),
+        (
+            "stableAlertIdentity",
+            cfg.alert_identity.mode == AlertIdentityMode::OriginatorType,
+        ),
+        (
+            "zones",
+            !cfg.zones.zones.is_empty() || cfg.zones.sync_from_platform,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// Revisión configurada o, si no hay, el modelo que expone el device tree.
+fn hardware_revision(cfg: &AppConfig) -> String {
+    if !cfg.hardware_revision.is_empty() {
+        return cfg.hardware_revision.clone();
+    }
+    fs::read_to_string(DEVICE_TREE_MODEL_PATH)
+        .map(|model| model.trim_end_matches('\0').trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn publish_client_attributes() {
+    let cfg = app_config();
+    let attributes = ClientAttributes {
+        app_version: env!("CARGO_PKG_VERSION"),
+        config_hash: config_hash(cfg),
+        frontend_bundle_hash: env!("FRONTEND_BUNDLE_HASH"),
+        enabled_features: enabled_features(cfg),
+        hardware_revision: hardware_revision(cfg),
+        hardware_profile: hardware_profile().name.as_str(),
+    };
+    match serde_json::to_vec(&attributes) {
+        Ok(payload) => {
+            if mqtt_publish(MQTT_ATTRIBUTES_TOPIC, payload, QoS::AtLeastOnce) {
+                info!(
+                    "[MQTT] Atributos publicados: v{} config {}",
+                    attributes.app_version, attributes.config_hash
+                );
+            }
+        }
+        Err(err) => warn!("[MQTT] No se pudieron serializar atributos: {:?}", err),
+    }
+}
+
+/// El método sólo se acepta firmado con el secreto de `rpc_security`.
+fn rpc_signature_required(method: &str) -> bool {
+    let cfg = &app_config().rpc_security;
+    cfg.enabled
+        && !cfg.secret.is_empty()
+        && cfg
+            .methods
+            .iter()
+            .any(|protected| protected.eq_ignore_ascii_case(method))
+}
+
+/// Rechaza RPC de control repetidos (nonce ya visto), fuera de margen horario o mal firmados.
+fn verify_rpc_request(method: &str, raw: &serde_json::Value) -> Result<(), String> {
+    let cfg = &app_config().rpc_security;
+    if !cfg.enabled
+        || !cfg
+            .methods
+            .iter()
+            .any(|protected| protected.eq_ignore_ascii_case(method))
+    {
+        return Ok(());
+    }
+    let nonces = RPC_NONCES.get_or_init(|| Mutex::new(NonceCache::new(RPC_NONCE_LIMIT)));
+    let mut nonces = nonces
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    // La marca se compara con la hora del servidor; la ventana de nonces, con el reloj monótono
+    // local, para que un ajuste de hora no la acorte ni la alargue.
+    signing::verify_rpc(
+        method,
+        raw,
+        &cfg.secret,
+        Duration::from_secs(cfg.max_skew_seconds),
+        corrected_now().timestamp_millis(),
+        &mut nonces,
+        Instant::now(),
+    )
+}
+
+/// Solicitud RPC ya interpretada, antes de verificar la firma o aplicar efectos.
+#[derive(Debug)]
+enum RpcRequest {
+    GetState,
+    BuzzerInhibit(serde_json::Value),
+    NotificationAction(String),
+    UnlockPanel(String),
+    AudioProfile(serde_json::Value),
+    Alarm(Box<AlarmParams>),
+    Ignored(String),
+}
+
+impl RpcRequest {
+    fn kind(&self) -> &'static str {
+        match self {
+            RpcRequest::GetState => "getState",
+            RpcRequest::BuzzerInhibit(_) => "buzzerInhibit",
+            RpcRequest::NotificationAction(_) => "notificationAction",
+            RpcRequest::UnlockPanel(_) => "unlockPanel",
+            RpcRequest::AudioProfile(_) => "audioProfile",
+            RpcRequest::Alarm(_) => "alarm",
+            RpcRequest::Ignored(_) => "ignored",
+        }
+    }
+}
+
+/// Parseo puro del payload RPC: no toca estado global, así que admite cualquier entrada (fuzzing).
+fn parse_rpc_payload(payload: &[u8]) -> Result<(serde_json::Value, RpcRequest), String> {
+    let raw: serde_json::Value = serde_json::from_slice(payload).map_err(|err| err.to_string())?;
+    let params = raw.get("params").cloned().unwrap_or_default();
+    let request = match raw.get("method").and_then(serde_json::Value::as_str) {
+        Some(method) if method.eq_ignore_ascii_case(GET_STATE_RPC_METHOD) => RpcRequest::GetState,
+        Some(method) if method.eq_ignore_ascii_case(BUZZER_INHIBIT_RPC_METHOD) => {
+            RpcRequest::BuzzerInhibit(params)
+        }
+        Some(method) if method.eq_ignore_ascii_case(NOTIFICATION_ACTION_RPC_METHOD) => {
+            let token = params
+                .get("token")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default();
+            RpcRequest::NotificationAction(token.to_string())
+        }
+        Some(method) if method.eq_ignore_ascii_case(UNLOCK_PANEL_RPC_METHOD) => {
+            let by = params
+                .get("by")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default();
+            RpcRequest::UnlockPanel(by.to_string())
+        }
+        Some(method) if method.eq_ignore_ascii_case(AUDIO_PROFILE_RPC_METHOD) => {
+            RpcRequest::AudioProfile(params)
+        }
+        _ => {
+            let mut envelope: AlarmRpcEnvelope =
+                serde_json::from_value(raw.clone()).map_err(|err| err.to_string())?;
+            if envelope.method.eq_ignore_ascii_case("ALARM") {
+                envelope.params.raw = Some(params);
+                RpcRequest::Alarm(Box::new(envelope.params))
+            } else {
+                RpcRequest::Ignored(envelope.method)
+            }
+        }
+    };
+    Ok((raw, request))
+}
+
+fn handle_rpc_payload(topic: &str, payload: &[u8], app_handle: &EventSink) {
+    let parsed = {
+        let _span = pipeline_span("rpc.parse", None);
+        parse_rpc_payload(payload)
+    };
+    let (raw, request) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            warn!("[MQTT] No se pudo parsear payload RPC: {}", err);
+            dead_letter(topic, payload, err);
+            return;
+        }
+    };
+
+    if let Some(method) = raw.get("method").and_then(serde_json::Value::as_str) {
+        if let Err(err) = verify_rpc_request(method, &raw) {
+            warn!("[MQTT] RPC {} rechazado: {}", method, err);
+            record_audit("platform", "rpc_rejected", method, &err);
+            reply_rpc(topic, &serde_json::json!({ "ok": false, "message": err }));
+            return;
+        }
+    }
+
+    let params = match request {
+        RpcRequest::GetState => {
+            info!("[MQTT] Solicitud GET_STATE en {}", topic);
+            reply_rpc(topic, &snapshot_panel_state());
+            return;
+        }
+        RpcRequest::BuzzerInhibit(params) => {
+            let reply = match handle_buzzer_inhibit_value(&params, "platform", app_handle) {
+                Ok(status) => serde_json::json!({ "ok": true, "status": status }),
+                Err(err) => {
+                    warn!("[BUZZER] Inhibición remota rechazada: {}", err);
+                    serde_json::json!({ "ok": false, "message": err })
+                }
+            };
+            reply_rpc(topic, &reply);
+            return;
+        }
+        RpcRequest::NotificationAction(token) => {
+            let result = apply_remote_action(&token, app_handle);
+            if let Err(err) = &result {
+                warn!("[NOTIFY] Acción remota rechazada: {}", err);
+            }
+            reply_rpc(
+                topic,
+                &serde_json::json!({
+                    "ok": result.is_ok(),
+                    "message": result.unwrap_or_else(|err| err),
+                }),
+            );
+            return;
+        }
+        RpcRequest::UnlockPanel(by) => {
+            if !rpc_signature_required(UNLOCK_PANEL_RPC_METHOD) {
+                let err = "unlockPanel exige rpc_security con secreto";
+                warn!("[BADGE] Desbloqueo remoto rechazado: {}", err);
+                record_audit("platform", "rpc_rejected", UNLOCK_PANEL_RPC_METHOD, err);
+                reply_rpc(topic, &serde_json::json!({ "ok": false, "message": err }));
+                return;
+            }
+            let unlocked = unlock_panel("platform", &by, app_handle);
+            reply_rpc(
+                topic,
+                &serde_json::json!({ "ok": true, "unlocked": unlocked }),
+            );
+            return;
+        }
+        RpcRequest::AudioProfile(params) => {
+            let reply = match handle_audio_profile_value(&params, "platform", app_handle) {
+                Ok(status) => serde_json::json!({ "ok": true, "status": status }),
+                Err(err) => serde_json::json!({ "ok": false, "message": err }),
+            };
+            reply_rpc(topic, &reply);
+            return;
+        }
+        RpcRequest::Ignored(method) => {
+            debug!("[MQTT] Método RPC ignorado: {}", method);
+            return;
+        }
+        RpcRequest::Alarm(params) => *params,
+    };
+
+    match params.status {
+        AlarmStatus::ActiveUnack | AlarmStatus::ActiveAck => {
+            handle_active_alarm(params, app_handle)
+        }
+        AlarmStatus::ClearedUnack | AlarmStatus::ClearedAck => {
+            handle_cleared_alarm(params, app_handle)
+        }
+        AlarmStatus::Unknown => {
+            warn!("[MQTT] Estado de alarma no manejado, se ignora payload.");
+        }
+    }
+}
+
+fn handle_supabase_update(payload: &SupabaseUpdatePayload, app_handle: &EventSink) {
+    match validate_binary_array(&payload.new.message) {
+        Ok(binary_array) => {
+            let commit_ms = payload
+                .commit_timestamp
+                .parse::<DateTime<Utc>>()
+                .map(|commit_time| commit_time.timestamp_millis());
+            let timestamp = parse_supabase_timestamp(&payload.commit_timestamp);
+            let timestamp_ms = commit_ms.unwrap_or_else(|_| corrected_now().timestamp_millis());
+            let update = DeviceStatusUpdate {
+                timestamp: timestamp.clone(),
+                timestamp_iso: iso_utc_ms(timestamp_ms),
+                timestamp_ms,
+                status: binary_array.clone(),
+            };
+
+            info!(
+                "[SUPABASE] Estado actualizado: {:?} en {}",
+                binary_array, timestamp
+            );
+
+            process_refrigerator_alarms(&binary_array, app_handle);
+
+            if let Err(err) = app_handle.emit(DEVICE_STATUS_EVENT, &update) {
+                warn!(
+                    "[SUPABASE] No se pudo emitir evento de actualización: {:?}",
+                    err
+                );
+            }
+        }
+        Err(err) => {
+            error!("[SUPABASE] Validación fallida: {}. Mensaje: {}", err, payload.new.message);
+        }
+    }
+}
+
+fn process_refrigerator_alarms(binary_array: &[u8], app_handle: &EventSink) {
+    let store = REFRIGERATOR_ALARM_STATE.get_or_init(|| Mutex::new(vec![0; BINARY_ARRAY_SIZE]));
+    let mut state_guard = store
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    
+    let previous_state = state_guard.clone();
+    *state_guard = binary_array.to_vec();
+    
+    for (index, (&current_value, &previous_value)) in binary_array.iter().zip(previous_state.iter()).enumerate() {
+        if index >= REFRIGERATOR_NAMES.len() {
+            break;
+        }
+        
+        if current_value == previous_value {
+            continue;
+        }
+        
+        let device_name = REFRIGERATOR_NAMES[index];
+        let alert_id = format!("refrigerator-temp-{}", index);
+        
+        if current_value == 1 && previous_value == 0 {
+            let now_ms = corrected_now().timestamp_millis();
+            let alert = Alert {
+                id: alert_id.clone(),
+                date_time: format_timestamp_ms(now_ms),
+                created_at_iso: iso_utc_ms(now_ms),
+                created_at_ms: now_ms,
+                alert_type: AlertType::TempUp,
+                device: device_name.to_string(),
+                description: TEMPERATURE_ALARM_DESCRIPTION.to_string(),
+                severity: AlertSeverity::Critical,
+                acknowledged: false,
+                details: None,
+                trend: None,
+                eta_to_limit: None,
+                defrost: false,
+                pin_order: pin_position(&alert_id),
+                display: None,
+                raw: Some(serde_json::json!({
+                    "index": index,
+                    "status": binary_array,
+                })),
+                alarm_id: String::new(),
+            };
+            
+            info!(
+                "[REFRIGERATOR] ACTIVADA {} tipo={} dispositivo={}",
+                alert.id, TEMPERATURE_ALARM_TYPE, device_name
+            );
+            cache_alert(&alert);
+            publish_domain_event(app_handle, DomainEvent::AlertAdded(alert));
+        } else if current_value == 0 && previous_value == 1 {
+            if let Some(alert) = remove_alert_by_id(&alert_id) {
+                info!(
+                    "[REFRIGERATOR] LIBERADA {} tipo={} dispositivo={}",
+                    alert_id, TEMPERATURE_ALARM_TYPE, device_name
+                );
+                publish_domain_event(app_handle, DomainEvent::AlertRemoved(alert));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ProblemSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConfigProblem {
+    field: String,
+    severity: ProblemSeverity,
+    message: String,
+}
+
+impl ConfigProblem {
+    fn error(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            severity: ProblemSeverity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            severity: ProblemSeverity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+/// Revisa el archivo de configuración en disco; los campos usan la clave YAML.
+fn validate_config_file(path: &Path) -> Vec<ConfigProblem> {
+    let file = path.display().to_string();
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            return vec![ConfigProblem::error(
+                &file,
+                format!("No se pudo leer: {}", err),
+            )]
+        }
+    };
+
+    match serde_yaml::from_str::<AppConfig>(&contents) {
+        Ok(cfg) => validate_config_values(&cfg),
+        Err(err) => {
+            let message = match err.location() {
+                Some(location) => format!(
+                    "Línea {}, columna {}: {}",
+                    location.line(),
+                    location.column(),
+                    err
+                ),
+                None => err.to_string(),
+            };
+            vec![ConfigProblem::error(&file, message)]
+        }
+    }
+}
+
+fn validate_config_values(cfg: &AppConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+
+    if cfg.mqtt_server.trim().is_empty() {
+        problems.push(ConfigProblem::error("MQTT_SERVER", "Servidor MQTT vacío"));
+    } else {
+        let target = (cfg.mqtt_server.as_str(), cfg.mqtt_port);
+        if let Err(err) = network::resolve(target, cfg.network.ip_preference) {
+            problems.push(ConfigProblem::error(
+                "MQTT_SERVER",
+                format!("No se pudo resolver {}: {}", cfg.mqtt_server, err),
+            ));
+        }
+    }
+
+    if cfg.mqtt_use_secure_client
+        && matches!(
+            cfg.network.ip_preference,
+            IpPreference::Ipv4Only | IpPreference::Ipv6Only
+        )
+    {
+        problems.push(ConfigProblem::warning(
+            "NETWORK",
+            "Con TLS el broker se conecta por nombre; ip_preference sólo ordena sondas y validación",
+        ));
+    }
+    for number in &cfg.modem.sms_recipients {
+        let digits = number.strip_prefix('+').unwrap_or(number);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            problems.push(ConfigProblem::error(
+                "MODEM",
+                format!("Número SMS inválido (formato +56912345678): {}", number),
+            ));
+        }
+    }
+    if !cfg.modem.sms_recipients.is_empty() && !cfg.modem.enabled {
+        problems.push(ConfigProblem::warning(
+            "MODEM",
+            "Hay sms_recipients pero el módem está deshabilitado",
+        ));
+    }
+
+    let portal_url = &cfg.network.captive_portal_url;
+    if !portal_url.is_empty() && !portal_url.starts_with("http://") {
+        problems.push(ConfigProblem::warning(
+            "NETWORK",
+            format!(
+                "captive_portal_url debe ser http://; los portales no pueden interceptar HTTPS: {}",
+                portal_url
+            ),
+        ));
+    }
+    for probe in &cfg.network.connectivity_probes {
+        if probe.to_socket_addrs().is_err() {
+            problems.push(ConfigProblem::warning(
+                "NETWORK",
+                format!(
+                    "Sonda de conectividad sin resolver (host:puerto): {}",
+                    probe
+                ),
+            ));
+        }
+    }
+
+    if !cfg.server_time.rpc_method.is_empty() && cfg.server_time.interval_secs < 10 {
+        problems.push(ConfigProblem::warning(
+            "SERVER_TIME",
+            "interval_secs menor a 10 s; se consultará la hora cada 10 s",
+        ));
+    }
+    let skew_attribute = cfg.server_time.attribute.as_str();
+    if [
+        BUZZER_INHIBIT_ATTRIBUTE,
+        ON_CALL_SCHEDULE_ATTRIBUTE,
+        ZONES_ATTRIBUTE,
+        NOTIFICATION_ROUTES_ATTRIBUTE,
+        AUDIO_PROFILE_ATTRIBUTE,
+    ]
+    .contains(&skew_attribute)
+    {
+        problems.push(ConfigProblem::error(
+            "SERVER_TIME",
+            format!("El atributo {} ya tiene otro uso", skew_attribute),
+        ));
+    }
+
+    if cfg.mqtt_use_secure_client {
+        match cfg.mqtt_ca_source {
+            MqttCaSource::File => check_certificate(
+                &mqtt_ca_path(cfg),
+                "MQTT_USE_SECURE_CLIENT",
+                cfg,
+                &mut problems,
+            ),
+            MqttCaSource::System => {
+                if let Err(err) = system_root_store() {
+                    problems.push(ConfigProblem::error("MQTT_CA_SOURCE", err));
+                }
+            }
+        }
+    }
+
+    if !cfg.mqtt_client_key.is_empty() {
+        let client_cert = mqtt_client_cert_path(cfg);
+        if let Err(err) = check_client_auth(&client_cert, Path::new(&cfg.mqtt_client_key)) {
+            problems.push(ConfigProblem::error("MQTT_CLIENT_KEY", err));
+        }
+    } else if !cfg.mqtt_client_cert.is_empty() {
+        problems.push(ConfigProblem::error(
+            "MQTT_CLIENT_CERT",
+            "Falta MQTT_CLIENT_KEY para autenticar con el certificado",
+        ));
+    }
+
+    let telemetry_topic = cfg.mqtt_telemetry_topic.as_str();
+    if !telemetry_topic.is_empty() && !rumqttc::valid_filter(telemetry_topic) {
+        problems.push(ConfigProblem::error(
+            "MQTT_TELEMETRY_TOPIC",
+            format!("Filtro de topic inválido: {}", telemetry_topic),
+        ));
+    }
+
+    if cfg.mqtt_auth.mode == MqttAuthMode::Oauth2
+        && (cfg.mqtt_auth.token_url.is_empty() || cfg.mqtt_auth.client_id.is_empty())
+    {
+        problems.push(ConfigProblem::error(
+            "MQTT_AUTH",
+            "El modo oauth2 requiere token_url y client_id",
+        ));
+    }
+    if cfg.mqtt_transport == MqttTransport::Wss {
+        if !cfg.mqtt_use_secure_client {
+            problems.push(ConfigProblem::error(
+                "MQTT_TRANSPORT",
+                "wss requiere MQTT_USE_SECURE_CLIENT para la configuración TLS",
+            ));
+        }
+        if !cfg.mqtt_ws_path.starts_with('/') {
+            problems.push(ConfigProblem::error(
+                "MQTT_WS_PATH",
+                format!("La ruta debe empezar con '/': {}", cfg.mqtt_ws_path),
+            ));
+        }
+    }
+    if cfg.mqtt_protocol == MqttProtocol::V4
+        && (cfg.mqtt_v5.session_expiry_secs > 0 || !cfg.mqtt_v5.user_properties.is_empty())
+    {
+        problems.push(ConfigProblem::warning(
+            "MQTT_V5",
+            "Se ignora: MQTT_PROTOCOL es v4",
+        ));
+    }
+
+    if cfg.rpc_security.enabled && cfg.rpc_security.secret.is_empty() {
+        problems.push(ConfigProblem::warning(
+            "RPC_SECURITY",
+            "Sin secreto sólo se valida nonce/ts; un cliente del broker puede generar RPC válidos",
+        ));
+    }
+
+    for (index, entry) in cfg.payload_schemas.iter().enumerate() {
+        if !rumqttc::valid_filter(&entry.topic) {
+            problems.push(ConfigProblem::error(
+                "PAYLOAD_SCHEMAS",
+                format!("Filtro de topic inválido: {}", entry.topic),
+            ));
+        }
+        if let Err(err) = load_payload_schema(entry) {
+            problems.push(ConfigProblem::error(
+                "PAYLOAD_SCHEMAS",
+                format!("Esquema {} ({}): {}", index, entry.topic, err),
+            ));
+        }
+    }
+
+    for mapping in &cfg.payload_mappings {
+        if !rumqttc::valid_filter(&mapping.topic) {
+            problems.push(ConfigProblem::error(
+                "PAYLOAD_MAPPINGS",
+                format!("Filtro de topic inválido: {}", mapping.topic),
+            ));
+        }
+        let required: &[&str] = match mapping.kind {
+            MappingKind::Telemetry => &["device", "value"],
+            MappingKind::Alarm => &["id", "type", "device"],
+        };
+        for field in required {
+            if !mapping.fields.contains_key(*field) {
+                problems.push(ConfigProblem::error(
+                    "PAYLOAD_MAPPINGS",
+                    format!("Falta el campo {} en el mapeo de {}", field, mapping.topic),
+                ));
+            }
+        }
+    }
+
+    if cfg.mqtt_bridge.enabled {
+        if cfg.mqtt_bridge.server.is_empty() {
+            problems.push(ConfigProblem::error(
+                "MQTT_BRIDGE",
+                "Puente MQTT habilitado sin servidor",
+            ));
+        }
+        for rule in &cfg.mqtt_bridge.rules {
+            if !rumqttc::valid_filter(&rule.source) {
+                problems.push(ConfigProblem::error(
+                    "MQTT_BRIDGE",
+                    format!("Filtro de origen inválido: {}", rule.source),
+                ));
+            }
+            if rule.qos > 2 {
+                problems.push(ConfigProblem::error(
+                    "MQTT_BRIDGE",
+                    format!("QoS inválido para {}: {}", rule.source, rule.qos),
+                ));
+            }
+        }
+        let bridge = &cfg.mqtt_bridge;
+        match (bridge.client_cert.is_empty(), bridge.client_key.is_empty()) {
+            (true, true) => {}
+            (false, false) if bridge.ca_path.is_empty() => {
+                problems.push(ConfigProblem::warning(
+                    "MQTT_BRIDGE",
+                    "Se ignora el certificado de cliente: sin ca_path la conexión no usa TLS",
+                ));
+            }
+            (false, false) => {
+                if let Err(err) = check_client_auth(
+                    Path::new(&bridge.client_cert),
+                    Path::new(&bridge.client_key),
+                ) {
+                    problems.push(ConfigProblem::error("MQTT_BRIDGE", err));
+                }
+            }
+            _ => problems.push(ConfigProblem::error(
+                "MQTT_BRIDGE",
+                "mTLS requiere client_cert y client_key",
+            )),
+        }
+    }
+
+    if cfg.system_fault.enabled && cfg.system_fault.on_ms == 0 {
+        problems.push(ConfigProblem::error(
+            "SYSTEM_FAULT",
+            "on_ms = 0: la falla del sistema no se anunciaría",
+        ));
+    }
+
+    let forwarding = &cfg.log_forwarding;
+    if forwarding.enabled {
+        if forwarding.endpoint.is_empty() {
+            problems.push(ConfigProblem::error(
+                "LOG_FORWARDING",
+                "Reenvío de log habilitado sin endpoint",
+            ));
+        } else if forwarding.target == LogForwardTarget::Syslog
+            && forwarding
+                .endpoint
+                .split_once("://")
+                .is_some_and(|(scheme, _)| scheme != "udp" && scheme != "tcp")
+        {
+            problems.push(ConfigProblem::error(
+                "LOG_FORWARDING",
+                format!("Esquema de syslog no soportado: {}", forwarding.endpoint),
+            ));
+        }
+        if forwarding.min_level.parse::<LevelFilter>().is_err() {
+            problems.push(ConfigProblem::error(
+                "LOG_FORWARDING",
+                format!("Nivel inválido: {}", forwarding.min_level),
+            ));
+        }
+    }
+
+    if let Err(err) = schedule::parse_timezone(&cfg.timezone) {
+        problems.push(ConfigProblem::error("TIMEZONE", err));
+    }
+
+    for err in load_holiday_calendar(&cfg.holidays).1 {
+        problems.push(ConfigProblem::error("HOLIDAYS", err));
+    }
+
+    if cfg.display_latency_slo_ms == 0 {
+        problems.push(ConfigProblem::error(
+            "DISPLAY_LATENCY_SLO_MS",
+            "El SLO de latencia debe ser mayor que 0",
+        ));
+    }
+
+    if cfg.otel.enabled {
+        if !cfg!(feature = "otel") {
+            problems.push(ConfigProblem::warning(
+                "OTEL",
+                "OTEL habilitado pero el binario no incluye la feature otel",
+            ));
+        }
+        if !cfg.otel.endpoint.starts_with("http://") && !cfg.otel.endpoint.starts_with("https://") {
+            problems.push(ConfigProblem::error(
+                "OTEL",
+                format!("Endpoint OTLP inválido: {}", cfg.otel.endpoint),
+            ));
+        }
+    }
+
+    if cfg.grpc.enabled {
+        if !cfg!(feature = "grpc") {
+            problems.push(ConfigProblem::warning(
+                "GRPC",
+                "GRPC habilitado pero el binario no incluye la feature grpc",
+            ));
+        }
+        match cfg.grpc.bind.parse::<std::net::SocketAddr>() {
+            Err(err) => problems.push(ConfigProblem::error(
+                "GRPC",
+                format!("bind inválido {:?}: {}", cfg.grpc.bind, err),
+            )),
+            Ok(addr) if !addr.ip().is_loopback() && cfg.grpc.token.is_empty() => {
+                problems.push(ConfigProblem::error(
+                    "GRPC",
+                    format!("{} es accesible desde la red: falta token", addr),
+                ))
+            }
+            Ok(_) => {}
+        }
+    }
+
+    let snapshots = &cfg.alert_snapshots;
+    if snapshots.enabled && (snapshots.max_bytes == 0 || snapshots.max_cache_mb == 0) {
+        problems.push(ConfigProblem::error(
+            "ALERT_SNAPSHOTS",
+            "max_bytes y max_cache_mb deben ser mayores que 0",
+        ));
+    }
+    if snapshots
+        .allowed_hosts
+        .iter()
+        .any(|host| host.trim().is_empty())
+    {
+        problems.push(ConfigProblem::error(
+            "ALERT_SNAPSHOTS",
+            "allowed_hosts no admite entradas vacías",
+        ));
+    }
+    if snapshots.enabled && snapshots.allowed_hosts.is_empty() {
+        problems.push(ConfigProblem::warning(
+            "ALERT_SNAPSHOTS",
+            "allowed_hosts vacío: no se descargará ninguna foto",
+        ));
+    }
+
+    let floorplan = &cfg.floorplan;
+    if !floorplan.image_path.is_empty() {
+        let path = floorplan_image_path(floorplan, &cfg.data_dir);
+        if !path.is_file() {
+            problems.push(ConfigProblem::error(
+                "FLOORPLAN",
+                format!("No existe la imagen del plano: {:?}", path),
+            ));
+        } else if image_mime(&path).is_none() {
+            problems.push(ConfigProblem::error(
+                "FLOORPLAN",
+                format!("Formato de imagen no soportado: {:?}", path),
+            ));
+        }
+    } else if !floorplan.markers.is_empty() {
+        problems.push(ConfigProblem::error(
+            "FLOORPLAN",
+            "Hay marcadores pero falta image_path",
+        ));
+    }
+    for marker in &floorplan.markers {
+        if !(0.0..=1.0).contains(&marker.x) || !(0.0..=1.0).contains(&marker.y) {
+            problems.push(ConfigProblem::error(
+                "FLOORPLAN",
+                format!(
+                    "{}: coordenadas fuera de rango (0-1): {}, {}",
+                    marker.device, marker.x, marker.y
+                ),
+            ));
+        }
+    }
+
+    let identity = &cfg.alert_identity;
+    if identity.mode == AlertIdentityMode::OriginatorType && identity.clear_grace_secs > 300 {
+        problems.push(ConfigProblem::warning(
+            "ALERT_IDENTITY",
+            format!(
+                "clear_grace_secs = {}: las alarmas despejadas siguen visibles todo ese tiempo",
+                identity.clear_grace_secs
+            ),
+        ));
+    }
+
+    let mut zone_ids = HashSet::new();
+    let mut zoned_devices = HashMap::new();
+    for zone in &cfg.zones.zones {
+        if zone.id.trim().is_empty() || !zone_ids.insert(zone.id.as_str()) {
+            problems.push(ConfigProblem::error(
+                "ZONES",
+                format!("Id de zona vacío o repetido: {:?}", zone.id),
+            ));
+        }
+        for device in &zone.devices {
+            if let Some(previous) = zoned_devices.insert(device.as_str(), zone.id.as_str()) {
+                problems.push(ConfigProblem::warning(
+                    "ZONES",
+                    format!(
+                        "{} está en las zonas {} y {}; se usa la primera",
+                        device, previous, zone.id
+                    ),
+                ));
+            }
+        }
+    }
+
+    let audible_test = &cfg.audible_test;
+    if audible_test.enabled {
+        if audible_test_weekday(audible_test).is_none() {
+            problems.push(ConfigProblem::error(
+                "AUDIBLE_TEST",
+                format!("weekday fuera de rango (1-7): {}", audible_test.weekday),
+            ));
+        }
+        if schedule::parse_hhmm(&audible_test.time).is_none() {
+            problems.push(ConfigProblem::error(
+                "AUDIBLE_TEST",
+                format!("Hora inválida (HH:MM): {}", audible_test.time),
+            ));
+        }
+        if audible_test.duration_secs == 0 {
+            problems.push(ConfigProblem::error(
+                "AUDIBLE_TEST",
+                "duration_secs debe ser mayor que 0",
+            ));
+        }
+        let known = signal_outputs_for(cfg, hardware_profile());
+        for name in &audible_test.outputs {
+            if !known.iter().any(|output| &output.name == name) {
+                problems.push(ConfigProblem::error(
+                    "AUDIBLE_TEST",
+                    format!("Salida desconocida: {}", name),
+                ));
+            }
+        }
+        if let Some(feedback) = &audible_test.feedback {
+            if feedback.gpio.is_empty() == feedback.path.is_empty() {
+                problems.push(ConfigProblem::error(
+                    "AUDIBLE_TEST",
+                    "feedback requiere exactamente uno de gpio o path",
+                ));
+            }
+        }
+    }
+
+    let fault_rate = cfg.hardware_fault_injection.failure_rate;
+    if !(0.0..=1.0).contains(&fault_rate) {
+        problems.push(ConfigProblem::error(
+            "HARDWARE_FAULT_INJECTION",
+            format!("failure_rate fuera de rango (0-1): {}", fault_rate),
+        ));
+    }
+    if cfg.hardware_fault_injection.enabled {
+        problems.push(ConfigProblem::warning(
+            "HARDWARE_FAULT_INJECTION",
+            "Inyección de fallos de hardware activa: buzzer y luces fallarán a propósito",
+        ));
+    }
+
+    if cfg.peer_sync_enabled && !rumqttc::valid_topic(&cfg.peer_sync_topic) {
+        problems.push(ConfigProblem::error(
+            "PEER_SYNC_TOPIC",
+            format!(
+                "Topic inválido (no admite comodines): {}",
+                cfg.peer_sync_topic
+            ),
+        ));
+    }
+
+    let mut device_ids = HashSet::new();
+    for device in &cfg.devices {
+        if device.id.trim().is_empty() || !device_ids.insert(device.id.as_str()) {
+            problems.push(ConfigProblem::error(
+                "DEVICES",
+                format!("Id de equipo vacío o repetido: {:?}", device.id),
+            ));
+        }
+        if let (Some(high), Some(low)) = (
+            device.critical_temperature_high,
+            device.critical_temperature_low,
+        ) {
+            if low >= high {
+                problems.push(ConfigProblem::error(
+                    "DEVICES",
+                    format!(
+                        "Umbral bajo ({}) no menor que el alto ({}) para {}",
+                        low, high, device.id
+                    ),
+                ));
+            }
+        }
+    }
+
+    let mut operator_ids = HashSet::new();
+    let mut badge_owners: HashMap<&str, &str> = HashMap::new();
+    for operator in &cfg.operators {
+        if operator.id.trim().is_empty() || !operator_ids.insert(operator.id.as_str()) {
+            problems.push(ConfigProblem::error(
+                "OPERATORS",
+                format!("Id de operador vacío o repetido: {:?}", operator.id),
+            ));
+        }
+        for badge in operator
+            .badges
+            .iter()
+            .filter(|badge| !badge.trim().is_empty())
+        {
+            if let Some(owner) = badge_owners.insert(badge.trim(), operator.id.as_str()) {
+                problems.push(ConfigProblem::error(
+                    "OPERATORS",
+                    format!(
+                        "Credencial {} asignada a {} y a {}",
+                        mask_badge(badge.trim()),
+                        owner,
+                        operator.id
+                    ),
+                ));
+            }
+        }
+    }
+    for operator in &cfg.operators {
+        let pin = &operator.pin_sha256;
+        if !pin.is_empty() && (pin.len() != 64 || from_hex(pin).is_none()) {
+            problems.push(ConfigProblem::error(
+                "OPERATORS",
+                format!("pin_sha256 de {} no es un SHA-256 en hex", operator.id),
+            ));
+        }
+    }
+    if cfg.pin_lockout.max_attempts == 0 || cfg.pin_lockout.lockout_secs == 0 {
+        problems.push(ConfigProblem::error(
+            "PIN_LOCKOUT",
+            "max_attempts y lockout_secs deben ser mayores que 0",
+        ));
+    }
+    if cfg.session_idle.idle_minutes > 0 && cfg.session_idle.grace_secs == 0 {
+        problems.push(ConfigProblem::warning(
+            "SESSION_IDLE",
+            "grace_secs en 0: la sesión se cierra sin aviso previo",
+        ));
+    }
+    if cfg.pin_lockout.max_lockout_secs < cfg.pin_lockout.lockout_secs {
+        problems.push(ConfigProblem::warning(
+            "PIN_LOCKOUT",
+            "max_lockout_secs menor que lockout_secs: se usa lockout_secs",
+        ));
+    }
+    if let Err(err) = checked_audio_profile(cfg, cfg.audio_profile.profile()) {
+        problems.push(ConfigProblem::error("AUDIO_PROFILE", err));
+    }
+    for (name, patterns) in &cfg.audio_profile.pattern_sets {
+        if patterns.iter().any(|pattern| pattern.on_ms == 0) {
+            problems.push(ConfigProblem::warning(
+                "AUDIO_PROFILE",
+                format!("El juego de patrones {} tiene on_ms en 0", name),
+            ));
+        }
+    }
+    if cfg.badge_reader.enabled && badge_owners.is_empty() {
+        problems.push(ConfigProblem::warning(
+            "BADGE_READER",
+            "Lector habilitado sin credenciales en OPERATORS",
+        ));
+    }
+    if !cfg.badge_reader.device.is_empty() && !Path::new(&cfg.badge_reader.device).exists() {
+        problems.push(ConfigProblem::warning(
+            "BADGE_READER",
+            format!("No existe el dispositivo {}", cfg.badge_reader.device),
+        ));
+    }
+
+    for schedule in &cfg.defrost_schedules {
+        if NaiveTime::parse_from_str(schedule.start.trim(), "%H:%M").is_err() {
+            problems.push(ConfigProblem::error(
+                "DEFROST_SCHEDULES",
+                format!(
+                    "Hora de inicio inválida para {}: {} (formato HH:MM)",
+                    schedule.device, schedule.start
+                ),
+            ));
+        }
+    }
+
+    for shift in &cfg.shifts {
+        if NaiveTime::parse_from_str(shift.start.trim(), "%H:%M").is_err() {
+            problems.push(ConfigProblem::error(
+                "SHIFTS",
+                format!(
+                    "Hora de inicio inválida para {}: {} (formato HH:MM)",
+                    shift.name, shift.start
+                ),
+            ));
+        }
+    }
+
+    for shift in &cfg.on_call.schedule {
+        let invalid = [&shift.start, &shift.end].into_iter().any(|value| {
+            !value.trim().is_empty() && NaiveTime::parse_from_str(value.trim(), "%H:%M").is_err()
+        });
+        if invalid || shift.start.trim().is_empty() != shift.end.trim().is_empty() {
+            problems.push(ConfigProblem::error(
+                "ON_CALL",
+                format!(
+                    "Horario inválido para {}: {}-{} (formato HH:MM, ambos o ninguno)",
+                    shift.name, shift.start, shift.end
+                ),
+            ));
+        }
+    }
+
+    problems.extend(notification_route_problems(
+        &cfg.notification_routing.routes,
+    ));
+
+    if cfg.printer.enabled {
+        if !Path::new(&cfg.printer.device).exists() {
+            problems.push(ConfigProblem::warning(
+                "PRINTER",
+                format!("No existe la impresora {}", cfg.printer.device),
+            ));
+        }
+        if !(16..=80).contains(&cfg.printer.width) {
+            problems.push(ConfigProblem::error(
+                "PRINTER",
+                format!(
+                    "Ancho fuera de rango (16-80 caracteres): {}",
+                    cfg.printer.width
+                ),
+            ));
+        }
+    }
+
+    if !cfg.handover_email_to.is_empty() && cfg.smtp.host.is_empty() {
+        problems.push(ConfigProblem::warning(
+            "HANDOVER_EMAIL_TO",
+            "Hay destinatarios de entrega de turno pero SMTP no está configurado",
+        ));
+    }
+    for target in &cfg.webhooks {
+        if !target.url.starts_with("https://") && !target.url.starts_with("http://") {
+            problems.push(ConfigProblem::error(
+                "WEBHOOKS",
+                format!("URL inválida para {}: {}", target.name, target.url),
+            ));
+        }
+        if target.format == WebhookFormat::Json {
+            if let Some(template) = &target.body {
+                // Con valores vacíos una plantilla JSON bien formada debe seguir siéndolo.
+                let body = render_template_with(template, &BTreeMap::new(), json_escape);
+                if let Err(err) = serde_json::from_str::<serde_json::Value>(&body) {
+                    problems.push(ConfigProblem::error(
+                        "WEBHOOKS",
+                        format!("Plantilla JSON inválida en {}: {}", target.name, err),
+                    ));
+                }
+            }
+        }
+    }
+    if cfg.smtp.security() == SmtpTls::None && !cfg.smtp.username.is_empty() {
+        problems.push(ConfigProblem::warning(
+            "SMTP",
+            "Las credenciales SMTP viajarían sin cifrar (tls = none)",
+        ));
+    }
+
+    for (index, rule) in cfg.rate_of_change_rules.iter().enumerate() {
+        if rule.max_rise.is_none() && rule.max_fall.is_none() {
+            problems.push(ConfigProblem::warning(
+                "RATE_OF_CHANGE_RULES",
+                format!("La regla {} no define MAX_RISE ni MAX_FALL", index),
+            ));
+        }
+    }
+
+    let known_profile = cfg.hardware_profile == DEFAULT_HARDWARE_PROFILE
+        || cfg
+            .hardware_profiles
+            .iter()
+            .any(|profile| profile.name == cfg.hardware_profile);
+    if !known_profile {
+        problems.push(ConfigProblem::warning(
+            "HARDWARE_PROFILE",
+            format!(
+                "El perfil {} no está definido en HARDWARE_PROFILES, se usará el perfil por defecto",
+                cfg.hardware_profile
+            ),
+        ));
+    }
+    if !cfg.board_eeprom_path.is_empty() && !Path::new(&cfg.board_eeprom_path).exists() {
+        problems.push(ConfigProblem::warning(
+            "BOARD_EEPROM_PATH",
+            format!("No existe {}", cfg.board_eeprom_path),
+        ));
+    }
+
+    let profile = detect_hardware_profile(cfg);
+    let outputs = signal_outputs_for(cfg, &profile);
+    for (index, output) in outputs.iter().enumerate() {
+        let field = match index {
+            0 => "BUZZER_ENABLED",
+            1 if profile.strobe.is_some() => "HARDWARE_PROFILES",
+            _ => "AUDIBLE_OUTPUTS",
+        };
+        if outputs[..index]
+            .iter()
+            .any(|other| other.name == output.name)
+        {
+            problems.push(ConfigProblem::error(
+                field,
+                format!("Salida audible duplicada: {}", output.name),
+            ));
+        }
+        if !cfg.zones.sync_from_platform {
+            for zone in &output.zones {
+                if !cfg.zones.zones.iter().any(|known| known.id == *zone) {
+                    problems.push(ConfigProblem::warning(
+                        field,
+                        format!("{}: zona desconocida {}", output.name, zone),
+                    ));
+                }
+            }
+        }
+        if !output.enabled {
+            continue;
+        }
+        if let Err(err) = probe_signal_output(output) {
+            problems.push(ConfigProblem::error(
+                field,
+                format!("{}: {}", output.name, err),
+            ));
+        }
+    }
+
+    for (name, feedback) in &cfg.output_verification.feedback {
+        if !outputs.iter().any(|output| output.name == *name) {
+            problems.push(ConfigProblem::warning(
+                "OUTPUT_VERIFICATION",
+                format!("Realimentación para una salida inexistente: {}", name),
+            ));
+        }
+        if feedback.gpio.is_empty() == feedback.path.is_empty() {
+            problems.push(ConfigProblem::error(
+                "OUTPUT_VERIFICATION",
+                format!(
+                    "La realimentación de {} necesita gpio o path (uno solo)",
+                    name
+                ),
+            ));
+        }
+    }
+
+    problems
+}
+
+fn check_certificate(path: &Path, field: &str, cfg: &AppConfig, problems: &mut Vec<ConfigProblem>) {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            problems.push(ConfigProblem::error(
+                field,
+                format!("No se pudo leer {}: {}", path.display(), err),
+            ));
+            return;
+        }
+    };
+
+    match parse_certificate(&bytes) {
+        Ok(info) => {
+            let remaining_days = info.remaining_days();
+            if remaining_days < 0 {
+                problems.push(ConfigProblem::error(
+                    field,
+                    format!("El certificado {} está vencido", path.display()),
+                ));
+            } else if remaining_days < cfg.cert_expiry.warning_days {
+                problems.push(ConfigProblem::warning(
+                    field,
+                    format!(
+                        "El certificado {} vence en {} días",
+                        path.display(),
+                        remaining_days
+                    ),
+                ));
+            }
+        }
+        Err(err) => problems.push(ConfigProblem::error(
+            field,
+            format!("Certificado inválido en {}: {}", path.display(), err),
+        )),
+    }
+}
+
+struct CertificateInfo {
+    not_after: i64,
+    public_key: Vec<u8>,
+}
+
+impl CertificateInfo {
+    fn from_x509(cert: &x509_parser::certificate::X509Certificate) -> Self {
+        Self {
+            not_after: cert.validity().not_after.timestamp(),
+            public_key: cert.public_key().raw.to_vec(),
+        }
+    }
+
+    fn remaining_days(&self) -> i64 {
+        (self.not_after - Utc::now().timestamp()) / 86_400
+    }
+}
+
+/// Acepta certificados en PEM o DER.
+fn parse_certificate(bytes: &[u8]) -> Result<CertificateInfo, String> {
+    match x509_parser::pem::parse_x509_pem(bytes) {
+        Ok((_, pem)) => pem
+            .parse_x509()
+            .map(|cert| CertificateInfo::from_x509(&cert))
+            .map_err(|err| err.to_string()),
+        Err(_) => x509_parser::parse_x509_certificate(bytes)
+            .map(|(_, cert)| CertificateInfo::from_x509(&cert))
+            .map_err(|err| err.to_string()),
+    }
+}
+
+/// El certificado de `cert_path` se puede leer y corresponde a la clave de `key_path`.
+fn check_client_auth(cert_path: &Path, key_path: &Path) -> Result<(), String> {
+    let bytes = fs::read(cert_path)
+        .map_err(|err| format!("No se pudo leer {}: {}", cert_path.display(), err))?;
+    let info = parse_certificate(&bytes)?;
+    private_key_matches(&info.public_key, key_path)
+}
+
+fn private_key_matches(public_key: &[u8], key_path: &Path) -> Result<(), String> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::PrivateKeyDer;
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|err| format!("No se pudo leer la clave {}: {}", key_path.display(), err))?;
+    let signing_key = rustls::crypto::aws_lc_rs::default_provider()
+        .key_provider
+        .load_private_key(key)
+        .map_err(|err| format!("Clave no soportada en {}: {}", key_path.display(), err))?;
+    match signing_key.public_key() {
+        Some(spki) if spki.as_ref() == public_key => Ok(()),
+        Some(_) => Err(format!(
+            "El certificado no corresponde a la clave {}",
+            key_path.display()
+        )),
+        None => Err(format!(
+            "No se pudo derivar la clave pública de {}",
+            key_path.display()
+        )),
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum CertificateKind {
+    #[default]
+    Ca,
+    Client,
+}
+
+fn imported_certificate_path(cfg: &AppConfig, kind: CertificateKind) -> PathBuf {
+    let file_name = match kind {
+        CertificateKind::Ca => "ca.crt",
+        CertificateKind::Client => "client.crt",
+    };
+    Path::new(&cfg.data_dir).join("certs").join(file_name)
+}
+
+fn mqtt_client_cert_path(cfg: &AppConfig) -> PathBuf {
+    if cfg.mqtt_client_cert.is_empty() {
+        imported_certificate_path(cfg, CertificateKind::Client)
+    } else {
+        PathBuf::from(&cfg.mqtt_client_cert)
+    }
+}
+
+/// La CA importada en el directorio de datos tiene prioridad sobre la incluida.
+fn mqtt_ca_path(cfg: &AppConfig) -> PathBuf {
+    let imported = imported_certificate_path(cfg, CertificateKind::Ca);
+    if imported.exists() {
+        imported
+    } else {
+        PathBuf::from(MQTT_CA_PATH)
+    }
+}
+
+/// Sólo https y sin redirecciones: quien esté en el camino no debe poder cambiar la CA.
+fn download_certificate(url: &str) -> Result<Vec<u8>, String> {
+    if !url::Url::parse(url).is_ok_and(|parsed| parsed.scheme() == "https") {
+        return Err(format!(
+            "Los certificados sólo se descargan por https: {}",
+            url
+        ));
+    }
+    let response = ureq::AgentBuilder::new()
+        .timeout(CERT_DOWNLOAD_TIMEOUT)
+        .redirects(0)
+        .build()
+        .get(url)
+        .call()
+        .map_err(|err| format!("No se pudo descargar {}: {}", url, err))?;
+    if response.status() != 200 {
+        return Err(format!(
+            "{} respondió {} (no se siguen redirecciones)",
+            url,
+            response.status()
+        ));
+    }
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(CERT_MAX_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|err| format!("Descarga incompleta de {}: {}", url, err))?;
+    Ok(bytes)
+}
+
+/// Importa un certificado desde una ruta (p. ej. un USB montado) o una URL https.
+fn import_certificate_from(source: &str, kind: CertificateKind) -> Result<String, String> {
+    let is_url = ["https://", "http://"].iter().any(|scheme| {
+        source
+            .get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    });
+    let bytes = if is_url {
+        download_certificate(source)?
+    } else {
+        fs::read(source).map_err(|err| format!("No se pudo leer {}: {}", source, err))?
+    };
+
+    let info = parse_certificate(&bytes).map_err(|err| format!("Certificado inválido: {}", err))?;
+    let remaining_days = info.remaining_days();
+    if remaining_days < 0 {
+        return Err("El certificado está vencido".to_string());
+    }
+
+    let cfg = app_config();
+    if kind == CertificateKind::Client && !cfg.mqtt_client_cert.is_empty() {
+        return Err(format!(
+            "MQTT_CLIENT_CERT apunta a {}; el certificado importado no se usaría",
+            cfg.mqtt_client_cert
+        ));
+    }
+    if kind == CertificateKind::Client && !cfg.mqtt_client_key.is_empty() {
+        private_key_matches(&info.public_key, Path::new(&cfg.mqtt_client_key))?;
+    }
+
+    let target = imported_certificate_path(cfg, kind);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("No se pudo crear {}: {}", parent.display(), err))?;
+    }
+    write_file_atomic(&target, &bytes)
+        .map_err(|err| format!("No se pudo guardar {}: {}", target.display(), err))?;
+
+    info!("[CERT] Certificado {:?} importado desde {}", kind, source);
+    request_mqtt_reconnect();
+    CERT_RECHECK.store(true, Ordering::SeqCst);
+
+    let mut message = format!("Certificado importado en {}", target.display());
+    if remaining_days < cfg.cert_expiry.warning_days {
+        message.push_str(&format!(" (vence en {} días)", remaining_days));
+    }
+    Ok(message)
+}
+
+#[tauri::command]
+async fn import_certificate(
+    window: tauri::Window,
+    source: String,
+    kind: Option<CertificateKind>,
+) -> Result<String, String> {
+    check_write_access(&window)?;
+    async_runtime::spawn_blocking(move || {
+        import_certificate_from(&source, kind.unwrap_or_default())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CertificateStatus {
+    /// `ca`, `client`, `bridgeCa` o `bridgeClient`.
+    name: &'static str,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    not_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remaining_days: Option<i64>,
+    expiring: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CertExpiryStatus {
+    warning_days: i64,
+    certificates: Vec<CertificateStatus>,
+}
+
+/// Certificados que el panel usa con la configuración actual; el almacén del sistema no se revisa.
+fn certificates_in_use(cfg: &AppConfig) -> Vec<(&'static str, PathBuf)> {
+    let mut certificates = Vec::new();
+    if cfg.mqtt_use_secure_client {
+        if cfg.mqtt_ca_source == MqttCaSource::File {
+            certificates.push(("ca", mqtt_ca_path(cfg)));
+        }
+        let client = mqtt_client_cert_path(cfg);
+        if !cfg.mqtt_client_key.is_empty() || client.exists() {
+            certificates.push(("client", client));
+        }
+    }
+    let bridge = &cfg.mqtt_bridge;
+    if bridge.enabled && !bridge.ca_path.is_empty() {
+        certificates.push(("bridgeCa", PathBuf::from(&bridge.ca_path)));
+        if !bridge.client_cert.is_empty() {
+            certificates.push(("bridgeClient", PathBuf::from(&bridge.client_cert)));
+        }
+    }
+    certificates
+}
+
+fn certificate_status(name: &'static str, path: &Path, warning_days: i64) -> CertificateStatus {
+    let parsed = fs::read(path)
+        .map_err(|err| format!("No se pudo leer: {}", err))
+        .and_then(|bytes| parse_certificate(&bytes));
+    match parsed {
+        Ok(info) => {
+            let remaining_days = info.remaining_days();
+            CertificateStatus {
+                name,
+                path: path.display().to_string(),
+                not_after: DateTime::<Utc>::from_timestamp(info.not_after, 0)
+                    .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                remaining_days: Some(remaining_days),
+                expiring: remaining_days < warning_days,
+                error: None,
+            }
+        }
+        Err(err) => CertificateStatus {
+            name,
+            path: path.display().to_string(),
+            not_after: None,
+            remaining_days: None,
+            expiring: false,
+            error: Some(err),
+        },
+    }
+}
+
+fn check_certificate_expiry(app_handle: &EventSink) {
+    let cfg = app_config();
+    let warning_days = cfg.cert_expiry.warning_days;
+    let status = CertExpiryStatus {
+        warning_days,
+        certificates: certificates_in_use(cfg)
+            .iter()
+            .map(|(name, path)| certificate_status(name, path, warning_days))
+            .collect(),
+    };
+    let expiring: Vec<String> = status
+        .certificates
+        .iter()
+        .filter(|cert| cert.expiring)
+        .map(|cert| match cert.remaining_days {
+            Some(days) if days < 0 => format!("{} ({}) vencido", cert.name, cert.path),
+            Some(days) => format!("{} ({}) vence en {} días", cert.name, cert.path, days),
+            None => cert.name.to_string(),
+        })
+        .collect();
+    for cert in &status.certificates {
+        if let Some(err) = &cert.error {
+            warn!("[CERT] {} ({}): {}", cert.name, cert.path, err);
+        }
+    }
+    let description =
+        (!expiring.is_empty()).then(|| format!("Certificados por vencer: {}", expiring.join("; ")));
+    apply_local_alert(CERT_EXPIRY_ALERT_ID, description, app_handle);
+
+    let previous = CERT_STATUS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .replace(status.clone());
+    let expiring_days = |status: &CertExpiryStatus| {
+        status
+            .certificates
+            .iter()
+            .filter(|cert| cert.expiring)
+            .map(|cert| (cert.name, cert.remaining_days))
+            .collect::<Vec<_>>()
+    };
+    // Se avisa al cambiar el conjunto o los días restantes, no en cada revisión.
+    if previous.as_ref().map(expiring_days).unwrap_or_default() != expiring_days(&status) {
+        if let Err(err) = app_handle.emit(CERT_EXPIRING_EVENT, &status) {
+            warn!("[CERT] No se pudo emitir vencimiento: {:?}", err);
+        }
+    }
+}
+
+/// Revisa al arrancar, cada `check_interval_hours` y cuando se importa un certificado.
+fn start_cert_expiry_loop(app_handle: EventSink) {
+    supervise(
+        "cert-expiry",
+        false,
+        Some(CERT_EXPIRY_TICK),
+        RestartPolicy::Always,
+        move |task| {
+            let app_handle = app_handle.clone();
+            async move {
+                let mut last_check: Option<Instant> = None;
+                while !is_shutting_down() {
+                    task.beat();
+                    let interval = Duration::from_secs(
+                        app_config().cert_expiry.check_interval_hours.max(1) * 3600,
+                    );
+                    let due = CERT_RECHECK.swap(false, Ordering::SeqCst)
+                        || last_check.is_none_or(|at| at.elapsed() >= interval);
+                    if due {
+                        last_check = Some(Instant::now());
+                        let app_handle = app_handle.clone();
+                        if let Err(err) = async_runtime::spawn_blocking(move || {
+                            check_certificate_expiry(&app_handle)
+                        })
+                        .await
+                        {
+                            warn!("[CERT] Fallo al revisar certificados: {:?}", err);
+                        }
+                    }
+                    tokio::time::sleep(CERT_EXPIRY_TICK).await;
+                }
+            }
+        },
+    );
+}
+
+#[tauri::command]
+fn get_certificate_status() -> Option<CertExpiryStatus> {
+    CERT_STATUS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+#[tauri::command]
+async fn validate_config() -> Vec<ConfigProblem> {
+    async_runtime::spawn_blocking(|| validate_config_file(Path::new(CONFIG_PATH)))
+        .await
+        .unwrap_or_else(|err| vec![ConfigProblem::error(CONFIG_PATH, format!("{:?}", err))])
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum WizardStep {
+    Network,
+    Broker,
+    Certificate,
+    Buzzer,
+    Commit,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum WizardStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Resultado de un paso del asistente; también se emite como progreso en `wizard://progress`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct WizardStepResult {
+    step: WizardStep,
+    status: WizardStatus,
+    message: String,
+    problems: Vec<ConfigProblem>,
+}
+
+impl WizardStepResult {
+    fn new(step: WizardStep, status: WizardStatus, message: impl Into<String>) -> Self {
+        Self {
+            step,
+            status,
+            message: message.into(),
+            problems: Vec::new(),
+        }
+    }
+
+    fn from_result(step: WizardStep, result: Result<String, String>) -> Self {
+        match result {
+            Ok(message) => Self::new(step, WizardStatus::Succeeded, message),
+            Err(message) => Self::new(step, WizardStatus::Failed, message),
+        }
+    }
+}
+
+fn emit_wizard_progress(sink: &EventSink, result: &WizardStepResult) {
+    if let Err(err) = sink.emit(WIZARD_PROGRESS_EVENT, result) {
+        warn!("[WIZARD] No se pudo emitir progreso: {:?}", err);
+    }
+}
+
+async fn run_wizard_step<F>(
+    app_handle: tauri::AppHandle,
+    step: WizardStep,
+    work: F,
+) -> WizardStepResult
+where
+    F: FnOnce() -> WizardStepResult + Send + 'static,
+{
+    let sink = EventSink::App(app_handle);
+    emit_wizard_progress(
+        &sink,
+        &WizardStepResult::new(step, WizardStatus::Running, ""),
+    );
+
+    let result = async_runtime::spawn_blocking(work)
+        .await
+        .unwrap_or_else(|err| {
+            WizardStepResult::new(step, WizardStatus::Failed, format!("{:?}", err))
+        });
+    info!(
+        "[WIZARD] Paso {:?}: {:?} {}",
+        step, result.status, result.message
+    );
+    emit_wizard_progress(&sink, &result);
+    result
+}
+
+/// Los secretos guardados vuelven como `SECRET_SET_MARKER` (o vacíos si no hay valor).
+#[tauri::command]
+fn wizard_get_config(window: tauri::Window) -> Result<AppConfig, String> {
+    check_write_access(&window)?;
+    let mut draft = config_draft();
+    redact_secrets(&mut draft);
+    Ok(draft)
+}
+
+#[tauri::command]
+async fn wizard_configure_network(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    ssid: String,
+    password: String,
+) -> WizardStepResult {
+    if let Err(err) = check_write_access(&window) {
+        return WizardStepResult::new(WizardStep::Network, WizardStatus::Failed, err);
+    }
+    run_wizard_step(app_handle, WizardStep::Network, move || {
+        WizardStepResult::from_result(WizardStep::Network, connect_wifi(&ssid, &password))
+    })
+    .await
+}
+
+fn connect_wifi(ssid: &str, password: &str) -> Result<String, String> {
+    let mut command = Command::new("nmcli");
+    command.args(["device", "wifi", "connect", ssid]);
+    if !password.is_empty() {
+        command.args(["password", password]);
+    }
+
+    let output = command
+        .output()
+        .map_err(|err| format!("No se pudo ejecutar nmcli: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "nmcli devolvio codigo {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    match internet_state() {
+        ConnectivityState::Online => Ok(format!("Conectado a {}", ssid)),
+        ConnectivityState::CaptivePortal => Err(format!(
+            "Conectado a {} pero la red exige iniciar sesión en un portal",
+            ssid
+        )),
+        _ => Err(format!("Conectado a {} pero sin acceso a internet", ssid)),
+    }
+}
+
+/// Prueba el broker del borrador sin guardar nada ni tocar la conexión activa.
+#[tauri::command]
+async fn wizard_test_broker(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    mut draft: AppConfig,
+) -> WizardStepResult {
+    if let Err(err) = check_write_access(&window) {
+        return WizardStepResult::new(WizardStep::Broker, WizardStatus::Failed, err);
+    }
+    restore_secrets(&mut draft);
+    run_wizard_step(app_handle, WizardStep::Broker, move || {
+        let client_id = format!("{}-wizard", draft.mqtt_client_id);
+        let result = match build_mqtt_options_for(&draft, client_id) {
+            Some(mqttoptions) => test_mqtt_connection(mqttoptions)
+                .map(|()| format!("Conectado a {}:{}", draft.mqtt_server, draft.mqtt_port)),
+            None if draft.mqtt_ca_source == MqttCaSource::System => Err(
+                "No se pudo cargar el almacén de certificados del sistema, el certificado de cliente u obtener el token del broker"
+                    .to_string(),
+            ),
+            None => Err(format!(
+                "No se pudo leer la CA en {}, el certificado de cliente u obtener el token del broker",
+                MQTT_CA_PATH
+            )),
+        };
+        WizardStepResult::from_result(WizardStep::Broker, result)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn wizard_list_usb_certificates() -> Vec<String> {
+    async_runtime::spawn_blocking(|| {
+        let mut found = Vec::new();
+        for root in USB_MOUNT_ROOTS {
+            collect_certificates(Path::new(root), USB_SCAN_DEPTH, &mut found);
+        }
+        found
+    })
+    .await
+    .unwrap_or_default()
+}
+
+fn collect_certificates(dir: &Path, depth: usize, found: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth > 0 {
+                collect_certificates(&path, depth - 1, found);
+            }
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("crt" | "pem" | "cer" | "der")
+        ) {
+            found.push(path.display().to_string());
+        }
+    }
+}
+
+#[tauri::command]
+async fn wizard_import_certificate(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    path: String,
+) -> WizardStepResult {
+    if let Err(err) = check_write_access(&window) {
+        return WizardStepResult::new(WizardStep::Certificate, WizardStatus::Failed, err);
+    }
+    run_wizard_step(app_handle, WizardStep::Certificate, move || {
+        WizardStepResult::from_result(
+            WizardStep::Certificate,
+            import_certificate_from(&path, CertificateKind::Ca),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+async fn wizard_test_buzzer(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> WizardStepResult {
+    if let Err(err) = check_write_access(&window) {
+        return WizardStepResult::new(WizardStep::Buzzer, WizardStatus::Failed, err);
+    }
+    run_wizard_step(app_handle, WizardStep::Buzzer, || {
+        let result = run_buzzer_test().map(|()| "Buzzer OK".to_string());
+        apply_buzzer_policy();
+        WizardStepResult::from_result(WizardStep::Buzzer, result)
+    })
+    .await
+}
+
+/// Valida y escribe el borrador en disco; se aplica al reiniciar la aplicación.
+#[tauri::command]
+async fn wizard_commit_config(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    mut draft: AppConfig,
+) -> WizardStepResult {
+    if let Err(err) = check_write_access(&window) {
+        return WizardStepResult::new(WizardStep::Commit, WizardStatus::Failed, err);
+    }
+    restore_secrets(&mut draft);
+    run_wizard_step(app_handle, WizardStep::Commit, move || {
+        let problems = validate_config_values(&draft);
+        let result = if problems
+            .iter()
+            .any(|problem| problem.severity == ProblemSeverity::Error)
+        {
+            Err("La configuración tiene errores".to_string())
+        } else {
+            write_config_file(Path::new(CONFIG_PATH), &draft)
+                .map(|()| "Configuración guardada; reinicie para aplicarla".to_string())
+        };
+        let mut result = WizardStepResult::from_result(WizardStep::Commit, result);
+        result.problems = problems;
+        result
+    })
+    .await
+}
+
+/// Base para guardar cambios: la configuración cargada con las credenciales MQTT aplicadas en
+/// caliente, para que otro guardado no las revierta.
+fn config_draft() -> AppConfig {
+    let mut draft = app_config().clone();
+    if let Some(credentials) = mqtt_credentials() {
+        credentials.apply_to(&mut draft);
+    }
+    draft
+}
+
+/// Campos que no salen del backend: el asistente sólo ve si están definidos.
+fn config_secrets(cfg: &mut AppConfig) -> [&mut String; 11] {
+    [
+        &mut cfg.mqtt_password,
+        &mut cfg.supabase_anon_key,
+        &mut cfg.site_pack_secret,
+        &mut cfg.notifications.action_secret,
+        &mut cfg.mqtt_auth.client_secret,
+        &mut cfg.rpc_security.secret,
+        &mut cfg.smtp.password,
+        &mut cfg.pagerduty.routing_key,
+        &mut cfg.opsgenie.api_key,
+        &mut cfg.mqtt_bridge.password,
+        &mut cfg.grpc.token,
+    ]
+}
+
+fn redact_secrets(cfg: &mut AppConfig) {
+    let redact = |secret: &mut String| {
+        if !secret.is_empty() {
+            *secret = SECRET_SET_MARKER.to_string();
+        }
+    };
+    config_secrets(cfg).into_iter().for_each(redact);
+    // Las cabeceras de webhooks suelen llevar `Authorization: Bearer …`.
+    cfg.webhooks
+        .iter_mut()
+        .flat_map(|webhook| webhook.headers.values_mut())
+        .for_each(redact);
+}
+
+/// Un secreto que vuelve vacío o con el marcador conserva el valor guardado. Las cabeceras se
+/// emparejan por webhook y nombre, porque el borrador puede agregar o quitar webhooks.
+fn restore_secrets(draft: &mut AppConfig) {
+    let mut stored = config_draft();
+    for (secret, saved) in config_secrets(draft)
+        .into_iter()
+        .zip(config_secrets(&mut stored))
+    {
+        if secret.is_empty() || secret == SECRET_SET_MARKER {
+            *secret = std::mem::take(saved);
+        }
+    }
+    for webhook in &mut draft.webhooks {
+        let saved = stored
+            .webhooks
+            .iter_mut()
+            .find(|saved| saved.name == webhook.name);
+        for (name, value) in &mut webhook.headers {
+            if value != SECRET_SET_MARKER {
+                continue;
+            }
+            match saved.as_ref().and_then(|saved| saved.headers.get(name)) {
+                Some(saved) => *value = saved.clone(),
+                // Sin valor guardado el marcador no debe terminar en la petición.
+                None => value.clear(),
+            }
+        }
+    }
+}
+
+fn write_config_file(path: &Path, cfg: &AppConfig) -> Result<(), String> {
+    let yaml = serde_yaml::to_string(cfg).map_err(|err| err.to_string())?;
+    if path.exists() {
+        let backup = path.with_extension("yaml.bak");
+        fs::copy(path, &backup)
+            .map_err(|err| format!("No se pudo respaldar {:?}: {}", path, err))?;
+    } else if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    write_file_atomic(path, yaml.as_bytes())
+        .map_err(|err| format!("No se pudo escribir {:?}: {}", path, err))
+}
+
+/// Escribe en un temporal hermano, lo lleva a disco y lo renombra: un corte de luz deja el
+/// archivo anterior o el nuevo, nunca uno truncado.
+fn write_file_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let staging = path.with_extension("tmp");
+    let result = fs::File::create(&staging)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&staging, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&staging);
+        return result;
+    }
+    // El renombrado queda en el directorio; sin sincronizarlo podría perderse tras el corte.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Lo que se repite entre tiendas iguales: mapeo y presentación de alarmas, reglas de supresión
+/// por descongelamiento y umbrales locales. Las claves son las mismas del YAML.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct SitePack {
+    #[serde(default)]
+    payload_mappings: Vec<PayloadMapping>,
+    #[serde(default)]
+    payload_schemas: Vec<PayloadSchema>,
+    #[serde(default)]
+    alert_display_rules: Vec<AlertDisplayRule>,
+    #[serde(default)]
+    defrost_schedules: Vec<DefrostSchedule>,
+    #[serde(default)]
+    rate_of_change_rules: Vec<RateOfChangeRule>,
+    #[serde(default)]
+    critical_temperature_high: Option<f64>,
+    #[serde(default)]
+    critical_temperature_low: Option<f64>,
+}
+
+impl SitePack {
+    /// Los esquemas en archivo se incrustan: la ruta no existe en el panel de destino.
+    fn from_config(cfg: &AppConfig) -> Result<Self, String> {
+        let payload_schemas = cfg
+            .payload_schemas
+            .iter()
+            .map(|entry| {
+                let mut entry = entry.clone();
+                if let (None, Some(path)) = (&entry.schema, entry.schema_path.take()) {
+                    let text = fs::read_to_string(&path)
+                        .map_err(|err| format!("No se pudo leer {}: {}", path, err))?;
+                    entry.schema = Some(
+                        serde_json::from_str(&text)
+                            .map_err(|err| format!("Esquema inválido en {}: {}", path, err))?,
+                    );
+                }
+                Ok(entry)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            payload_mappings: cfg.payload_mappings.clone(),
+            payload_schemas,
+            alert_display_rules: cfg.alert_display_rules.clone(),
+            defrost_schedules: cfg.defrost_schedules.clone(),
+            rate_of_change_rules: cfg.rate_of_change_rules.clone(),
+            critical_temperature_high: cfg.critical_temperature_high,
+            critical_temperature_low: cfg.critical_temperature_low,
+        })
+    }
+
+    fn apply_to(self, cfg: &mut AppConfig) {
+        cfg.payload_mappings = self.payload_mappings;
+        cfg.payload_schemas = self.payload_schemas;
+        cfg.alert_display_rules = self.alert_display_rules;
+        cfg.defrost_schedules = self.defrost_schedules;
+        cfg.rate_of_change_rules = self.rate_of_change_rules;
+        cfg.critical_temperature_high = self.critical_temperature_high;
+        cfg.critical_temperature_low = self.critical_temperature_low;
+    }
+}
+
+/// `signature` es el HMAC-SHA256 (hex) del JSON canónico de `pack` con `SITE_PACK_SECRET`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SitePackFile {
+    format: String,
+    version: u32,
+    created_at: String,
+    source_panel: String,
+    pack: serde_json::Value,
+    signature: String,
+}
+
+fn write_site_pack() -> Result<PathBuf, String> {
+    let cfg = app_config();
+    let pack = serde_json::to_value(SitePack::from_config(cfg)?).map_err(|err| err.to_string())?;
+    let signature =
+        sign_json(&cfg.site_pack_secret, &pack).ok_or("SITE_PACK_SECRET no configurado")?;
+    let file = SitePackFile {
+        format: SITE_PACK_FORMAT.to_string(),
+        version: SITE_PACK_VERSION,
+        created_at: corrected_now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        source_panel: panel_id().to_string(),
+        pack,
+        signature,
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|err| err.to_string())?;
+
+    let dir = Path::new(&cfg.data_dir).join(REPORTS_DIR);
+    fs::create_dir_all(&dir).map_err(|err| format!("No se pudo crear {:?}: {:?}", dir, err))?;
+    let path = dir.join(format!(
+        "site-pack-{}.json",
+        corrected_now().format("%Y%m%d-%H%M")
+    ));
+    fs::write(&path, json).map_err(|err| format!("No se pudo escribir {:?}: {:?}", path, err))?;
+    Ok(path)
+}
+
+fn read_site_pack(path: &Path) -> Result<(SitePackFile, SitePack), String> {
+    let text =
+        fs::read_to_string(path).map_err(|err| format!("No se pudo leer {:?}: {}", path, err))?;
+    parse_site_pack(&text, &app_config().site_pack_secret)
+}
+
+/// Sin `secret` no se acepta ningún pack: uno sin firmar o firmado con otra clave se rechaza.
+fn parse_site_pack(text: &str, secret: &str) -> Result<(SitePackFile, SitePack), String> {
+    if secret.is_empty() {
+        return Err("SITE_PACK_SECRET no configurado".to_string());
+    }
+    let file: SitePackFile =
+        serde_json::from_str(text).map_err(|err| format!("Site pack inválido: {}", err))?;
+    if file.format != SITE_PACK_FORMAT {
+        return Err(format!("No es un site pack: {}", file.format));
+    }
+    if file.version > SITE_PACK_VERSION {
+        return Err(format!(
+            "Site pack versión {} no soportada por esta versión del panel",
+            file.version
+        ));
+    }
+    verify_json(secret, &file.pack, &file.signature)
+        .map_err(|err| format!("Site pack rechazado: {}", err))?;
+    let pack = serde_json::from_value(file.pack.clone())
+        .map_err(|err| format!("Contenido del site pack inválido: {}", err))?;
+    Ok((file, pack))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SitePackImport {
+    source_panel: String,
+    created_at: String,
+    saved: bool,
+    problems: Vec<ConfigProblem>,
+}
+
+/// Exporta mapeos, reglas y umbrales firmados y devuelve la ruta del archivo.
+#[tauri::command]
+async fn export_site_pack() -> Result<String, String> {
+    async_runtime::spawn_blocking(|| {
+        let path = write_site_pack()?;
+        record_audit("local", "export_site_pack", &path.to_string_lossy(), "");
+        Ok(path.to_string_lossy().into_owned())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Verifica la firma, reemplaza esas secciones de la configuración y la guarda si valida; se aplica al reiniciar.
+#[tauri::command]
+async fn import_site_pack(window: tauri::Window, path: String) -> Result<SitePackImport, String> {
+    check_write_access(&window)?;
+    async_runtime::spawn_blocking(move || {
+        let (file, pack) = read_site_pack(Path::new(&path))?;
+        let mut draft = config_draft();
+        pack.apply_to(&mut draft);
+        let problems = validate_config_values(&draft);
+        let saved = !problems
+            .iter()
+            .any(|problem| problem.severity == ProblemSeverity::Error);
+        if saved {
+            write_config_file(Path::new(CONFIG_PATH), &draft)?;
+            info!(
+                "[CONFIG] Site pack de {} importado; se aplica al reiniciar",
+                file.source_panel
+            );
+            record_audit("local", "import_site_pack", &path, &file.source_panel);
+        }
+        Ok(SitePackImport {
+            source_panel: file.source_panel,
+            created_at: file.created_at,
+            saved,
+            problems,
+        })
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Resultado de `import_devices`; los problemas de filas usan `línea N` como campo.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceImportReport {
+    source: String,
+    dry_run: bool,
+    saved: bool,
+    rows: usize,
+    created: Vec<String>,
+    updated: Vec<String>,
+    problems: Vec<ConfigProblem>,
+}
+
+fn read_device_csv(source: &str) -> Result<String, String> {
+    if !(source.starts_with("https://") || source.starts_with("http://")) {
+        return fs::read_to_string(source)
+            .map_err(|err| format!("No se pudo leer {}: {}", source, err));
+    }
+    let response = ureq::AgentBuilder::new()
+        .timeout(CERT_DOWNLOAD_TIMEOUT)
+        .build()
+        .get(source)
+        .call()
+        .map_err(|err| format!("No se pudo descargar {}: {}", source, err))?;
+    let mut text = String::new();
+    response
+        .into_reader()
+        .take(DEVICE_CSV_MAX_BYTES)
+        .read_to_string(&mut text)
+        .map_err(|err| format!("Descarga incompleta de {}: {}", source, err))?;
+    Ok(text)
+}
+
+/// Número con punto o coma decimal; `-` borra el umbral y vacío lo conserva.
+fn csv_limit(value: &str, current: Option<f64>) -> Result<Option<f64>, String> {
+    match value {
+        "" => Ok(current),
+        DEVICE_CSV_CLEAR => Ok(None),
+        _ => value
+            .replace(',', ".")
+            .parse::<f64>()
+            .map(Some)
+            .map_err(|_| format!("Umbral inválido: {}", value)),
+    }
+}
+
+/// `06:00+30;18:00` (duración en minutos opcional) para el equipo `device`.
+fn csv_defrost(device: &str, value: &str, action: &str) -> Result<Vec<DefrostSchedule>, String> {
+    let action = match action {
+        "" => DefrostAction::default(),
+        _ => serde_json::from_value(serde_json::Value::String(action.to_lowercase()))
+            .map_err(|_| format!("Acción de descongelamiento inválida: {}", action))?,
+    };
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (start, minutes) = entry.split_once('+').unwrap_or((entry, ""));
+            let start = schedule::parse_hhmm(start)
+                .ok_or_else(|| format!("Descongelamiento inválido (HH:MM+minutos): {}", entry))?;
+            let duration_minutes = match minutes.trim() {
+                "" => default_defrost_duration_minutes(),
+                minutes => minutes
+                    .parse()
+                    .map_err(|_| format!("Duración de descongelamiento inválida: {}", entry))?,
+            };
+            Ok(DefrostSchedule {
+                device: device.to_string(),
+                start: start.format("%H:%M").to_string(),
+                duration_minutes,
+                action,
+            })
+        })
+        .collect()
+}
+
+/// Aplica una fila al borrador: crea o actualiza el equipo, lo mueve de zona y reemplaza sus
+/// descongelamientos. Devuelve si el equipo ya existía.
+fn apply_device_row(
+    draft: &mut AppConfig,
+    table: &csv::CsvTable,
+    row: &csv::CsvRow,
+    device: &str,
+) -> Result<bool, String> {
+    let index = draft.devices.iter().position(|entry| entry.id == device);
+    let mut entry = index
+        .map(|index| draft.devices[index].clone())
+        .unwrap_or_else(|| DeviceEntry {
+            id: device.to_string(),
+            name: String::new(),
+            critical_temperature_high: None,
+            critical_temperature_low: None,
+        });
+    match table.get(row, "name") {
+        "" => {}
+        DEVICE_CSV_CLEAR => entry.name.clear(),
+        name => entry.name = name.to_string(),
+    }
+    entry.critical_temperature_high =
+        csv_limit(table.get(row, "temp_high"), entry.critical_temperature_high)?;
+    entry.critical_temperature_low =
+        csv_limit(table.get(row, "temp_low"), entry.critical_temperature_low)?;
+    if let (Some(high), Some(low)) = (
+        entry.critical_temperature_high,
+        entry.critical_temperature_low,
+    ) {
+        if low >= high {
+            return Err(format!(
+                "temp_low ({}) debe ser menor que temp_high ({})",
+                low, high
+            ));
+        }
+    }
+    let defrost = match table.get(row, "defrost") {
+        "" => None,
+        DEVICE_CSV_CLEAR => Some(Vec::new()),
+        value => Some(csv_defrost(
+            device,
+            value,
+            table.get(row, "defrost_action"),
+        )?),
+    };
+
+    if let Some(defrost) = defrost {
+        draft
+            .defrost_schedules
+            .retain(|schedule| schedule.device != device);
+        draft.defrost_schedules.extend(defrost);
+    }
+    match table.get(row, "zone") {
+        "" => {}
+        zone => {
+            for current in &mut draft.zones.zones {
+                current.devices.retain(|member| member != device);
+            }
+            if zone != DEVICE_CSV_CLEAR {
+                match draft
+                    .zones
+                    .zones
+                    .iter_mut()
+                    .find(|current| current.id == zone)
+                {
+                    Some(current) => current.devices.push(device.to_string()),
+                    None => draft.zones.zones.push(Zone {
+                        id: zone.to_string(),
+                        name: zone.to_string(),
+                        devices: vec![device.to_string()],
+                    }),
+                }
+            }
+        }
+    }
+    match index {
+        Some(index) => draft.devices[index] = entry,
+        None => draft.devices.push(entry),
+    }
+    Ok(index.is_some())
+}
+
+fn import_devices_from(source: &str, dry_run: bool) -> Result<DeviceImportReport, String> {
+    let table = csv::parse(&read_device_csv(source)?)?;
+    if table.column("device").is_none() {
+        return Err(format!(
+            "El CSV no tiene columna device (cabecera: {})",
+            table.headers.join(", ")
+        ));
+    }
+    let mut report = DeviceImportReport {
+        source: source.to_string(),
+        dry_run,
+        saved: false,
+        rows: table.rows.len(),
+        created: Vec::new(),
+        updated: Vec::new(),
+        problems: Vec::new(),
+    };
+    let mut draft = config_draft();
+    let mut seen = HashSet::new();
+    for row in &table.rows {
+        let field = format!("línea {}", row.line);
+        let device = table.get(row, "device");
+        if device.is_empty() {
+            report
+                .problems
+                .push(ConfigProblem::error(&field, "Fila sin device"));
+            continue;
+        }
+        if !seen.insert(device.to_string()) {
+            report.problems.push(ConfigProblem::error(
+                &field,
+                format!("Equipo repetido en el CSV: {}", device),
+            ));
+            continue;
+        }
+        match apply_device_row(&mut draft, &table, row, device) {
+            Ok(true) => report.updated.push(device.to_string()),
+            Ok(false) => report.created.push(device.to_string()),
+            Err(err) => report
+                .problems
+                .push(ConfigProblem::error(&field, format!("{}: {}", device, err))),
+        }
+    }
+    report.problems.extend(validate_config_values(&draft));
+
+    let has_errors = report
+        .problems
+        .iter()
+        .any(|problem| problem.severity == ProblemSeverity::Error);
+    if !dry_run && !has_errors {
+        write_config_file(Path::new(CONFIG_PATH), &draft)?;
+        report.saved = true;
+        info!(
+            "[CONFIG] {} equipos importados desde {} ({} nuevos); se aplica al reiniciar",
+            report.created.len() + report.updated.len(),
+            source,
+            report.created.len()
+        );
+        record_audit(
+            "local",
+            "import_devices",
+            source,
+            &format!(
+                "{} nuevos, {} actualizados",
+                report.created.len(),
+                report.updated.len()
+            ),
+        );
+    }
+    Ok(report)
+}
+
+/// Carga equipos desde un CSV (ruta, p. ej. un USB, o URL http(s)) con columnas `device`, `name`,
+/// `zone`, `temp_high`, `temp_low`, `defrost` y `defrost_action`. Celda vacía conserva el valor y
+/// `-` lo borra. Con `dry_run` sólo devuelve el reporte; si no, guarda si no hay errores y se
+/// aplica al reiniciar.
+#[tauri::command]
+async fn import_devices(
+    window: tauri::Window,
+    csv_path: String,
+    dry_run: Option<bool>,
+) -> Result<DeviceImportReport, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        check_write_access(&window)?;
+    }
+    async_runtime::spawn_blocking(move || import_devices_from(&csv_path, dry_run))
+        .await
+        .map_err(|err| format!("{:?}", err))?
+}
+
+#[tauri::command]
+fn get_devices() -> Vec<DeviceEntry> {
+    app_config().devices.clone()
+}
+
+/// El rol de la ventana puede forzarse a espectador por etiqueta; si no, rige el del panel.
+fn window_role(window: &tauri::Window) -> PanelRole {
+    let cfg = app_config();
+    if cfg
+        .spectator_windows
+        .iter()
+        .any(|label| label == window.label())
+    {
+        PanelRole::Spectator
+    } else {
+        cfg.panel_role
+    }
+}
+
+/// Rechaza comandos que modifican estado desde pantallas en modo espectador.
+fn check_write_access(window: &tauri::Window) -> Result<(), String> {
+    if window_role(window) == PanelRole::Spectator {
+        warn!(
+            "[ROLE] Comando rechazado desde ventana espectador {}",
+            window.label()
+        );
+        return Err(
+            "Pantalla en modo solo lectura: no puede modificar alertas ni silencio".to_string(),
+        );
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_panel_role(window: tauri::Window) -> PanelRole {
+    window_role(&window)
+}
+
+/// Con `zone` sólo las alertas de los equipos de esa zona.
+#[tauri::command]
+fn get_active_alerts(zone: Option<String>) -> Result<Vec<Alert>, String> {
+    let mut alerts = snapshot_alerts();
+    if let Some(zone) = zone {
+        let devices = zone_devices(&zone)?;
+        alerts.retain(|alert| devices.contains(&alert.device));
+    }
+    Ok(alerts)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlertChange {
+    Added,
+    Updated,
+    Removed,
+}
+
+/// Diario de cambios del store para visores remotos; `epoch` distingue cursores de otro arranque.
+struct AlertJournal {
+    epoch: i64,
+    seq: u64,
+    entries: VecDeque<(u64, String, AlertChange)>,
+}
+
+fn with_alert_journal<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut AlertJournal) -> R,
+{
+    let journal = ALERT_JOURNAL.get_or_init(|| {
+        Mutex::new(AlertJournal {
+            epoch: Utc::now().timestamp_millis(),
+            seq: 0,
+            entries: VecDeque::new(),
+        })
+    });
+    let mut guard = journal
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn journal_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    let (alert, change) = match event {
+        DomainEvent::AlertAdded(alert) => (alert, AlertChange::Added),
+        DomainEvent::AlertUpdated(alert) => (alert, AlertChange::Updated),
+        DomainEvent::AlertRemoved(alert) => (alert, AlertChange::Removed),
+        DomainEvent::MuteChanged(_) => return,
+    };
+    with_alert_journal(|journal| {
+        journal.seq += 1;
+        journal
+            .entries
+            .push_back((journal.seq, alert.id.clone(), change));
+        while journal.entries.len() > ALERT_JOURNAL_CAPACITY {
+            journal.entries.pop_front();
+        }
+    });
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AlertDiff {
+    /// Para la próxima consulta.
+    cursor: String,
+    /// Cursor ausente, de otro arranque o demasiado viejo: `added` trae la lista completa y el
+    /// visor debe descartar la suya.
+    full: bool,
+    added: Vec<Alert>,
+    updated: Vec<Alert>,
+    removed: Vec<String>,
+}
+
+/// Cambios desde `cursor` resumidos por alerta. El cursor se toma antes de leer el store, así que
+/// un cambio concurrente puede llegar dos veces; el visor aplica todo como reemplazo.
+fn alerts_since(cursor: Option<&str>) -> AlertDiff {
+    let since = cursor.and_then(|cursor| {
+        let (epoch, seq) = cursor.split_once(':')?;
+        Some((epoch.parse::<i64>().ok()?, seq.parse::<u64>().ok()?))
+    });
+    let (cursor, changes) = with_alert_journal(|journal| {
+        let cursor = format!("{}:{}", journal.epoch, journal.seq);
+        let covered = |seq: u64| {
+            seq <= journal.seq
+                && journal
+                    .entries
+                    .front()
+                    .is_none_or(|(first, _, _)| *first <= seq + 1)
+        };
+        let changes = since
+            .filter(|(epoch, seq)| *epoch == journal.epoch && covered(*seq))
+            .map(|(_, seq)| {
+                // Primer cambio de cada alerta, en orden de llegada; el estado final sale del store.
+                let mut changes: Vec<(String, AlertChange)> = Vec::new();
+                for (_, id, change) in journal.entries.iter().filter(|entry| entry.0 > seq) {
+                    if !changes.iter().any(|(known, _)| known == id) {
+                        changes.push((id.clone(), *change));
+                    }
+                }
+                changes
+            });
+        (cursor, changes)
+    });
+    let Some(changes) = changes else {
+        return AlertDiff {
+            cursor,
+            full: true,
+            added: snapshot_alerts(),
+            updated: Vec::new(),
+            removed: Vec::new(),
+        };
+    };
+    let mut diff = AlertDiff {
+        cursor,
+        full: false,
+        added: Vec::new(),
+        updated: Vec::new(),
+        removed: Vec::new(),
+    };
+    for (id, first) in changes {
+        match with_alert_store(|store| store.get(&id).map(with_display)) {
+            Some(alert) if first == AlertChange::Added => diff.added.push(alert),
+            Some(alert) => diff.updated.push(alert),
+            // Creada y retirada después del cursor: el visor nunca la vio.
+            None if first == AlertChange::Added => {}
+            None => diff.removed.push(id),
+        }
+    }
+    diff
+}
+
+/// Sólo lo que cambió desde `cursor`, para visores remotos sobre enlaces celulares; sin cursor
+/// devuelve la lista completa y el primer cursor.
+#[tauri::command]
+fn get_alerts_since(cursor: Option<String>) -> AlertDiff {
+    alerts_since(cursor.as_deref())
+}
+
+fn with_zones<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Vec<Zone>) -> R,
+{
+    let zones = ZONES.get_or_init(|| Mutex::new(app_config().zones.zones.clone()));
+    let mut guard = zones
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn zone_devices(id: &str) -> Result<Vec<String>, String> {
+    with_zones(|zones| {
+        zones
+            .iter()
+            .find(|zone| zone.id == id)
+            .map(|zone| zone.devices.clone())
+            .ok_or_else(|| format!("Zona desconocida: {}", id))
+    })
+}
+
+fn handle_zones_value(value: &serde_json::Value) {
+    if !app_config().zones.sync_from_platform {
+        return;
+    }
+    match serde_json::from_value::<Vec<Zone>>(value.clone()) {
+        Ok(zones) => {
+            info!(
+                "[ZONES] Zonas sincronizadas desde la plataforma: {}",
+                zones.len()
+            );
+            record_audit(
+                "platform",
+                "zones_synced",
+                "",
+                &format!("{} zonas", zones.len()),
+            );
+            with_zones(|current| *current = zones);
+            apply_buzzer_policy();
+        }
+        Err(err) => warn!("[ZONES] Zonas inválidas desde la plataforma: {:?}", err),
+    }
+}
+
+/// Conteo de alertas activas de un grupo de equipos (zona o marcador del plano).
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct AlertSummary {
+    active_alerts: usize,
+    unacknowledged: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highest_severity: Option<AlertSeverity>,
+}
+
+impl AlertSummary {
+    fn add(&mut self, alert: &Alert) {
+        self.active_alerts += 1;
+        if !alert.acknowledged {
+            self.unacknowledged += 1;
+        }
+        if self
+            .highest_severity
+            .is_none_or(|highest| alert.severity.rank() > highest.rank())
+        {
+            self.highest_severity = Some(alert.severity);
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ZoneStatus {
+    id: String,
+    name: String,
+    devices: Vec<String>,
+    #[serde(flatten)]
+    alerts: AlertSummary,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SiteStatus {
+    site: String,
+    zones: Vec<ZoneStatus>,
+    /// Alertas de equipos que no están en ninguna zona (incluye las del propio panel).
+    unassigned_alerts: usize,
+}
+
+/// Resumen por zona de las alertas activas; un equipo en varias zonas cuenta en la primera.
+#[tauri::command]
+fn get_zone_status() -> SiteStatus {
+    let zones = with_zones(|zones| zones.clone());
+    let alerts = with_alert_store(|store| store.values().cloned().collect::<Vec<_>>());
+    let mut statuses: Vec<ZoneStatus> = zones
+        .iter()
+        .map(|zone| ZoneStatus {
+            id: zone.id.clone(),
+            name: if zone.name.is_empty() {
+                zone.id.clone()
+            } else {
+                zone.name.clone()
+            },
+            devices: zone.devices.clone(),
+            alerts: AlertSummary::default(),
+        })
+        .collect();
+    let mut unassigned_alerts = 0;
+    for alert in &alerts {
+        let Some(status) = statuses
+            .iter_mut()
+            .find(|status| status.devices.contains(&alert.device))
+        else {
+            unassigned_alerts += 1;
+            continue;
+        };
+        status.alerts.add(alert);
+    }
+    let site = &app_config().zones.site;
+    SiteStatus {
+        site: if site.is_empty() {
+            panel_id().to_string()
+        } else {
+            site.clone()
+        },
+        zones: statuses,
+        unassigned_alerts,
+    }
+}
+
+/// `reason` es obligatorio para las severidades que `ACK_POLICY` manda confirmar.
+#[tauri::command]
+async fn remove_alert(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    id: String,
+    reason: Option<String>,
+) -> Result<bool, String> {
+    check_write_access(&window)?;
+    let sink = EventSink::App(app_handle);
+    match async_runtime::spawn_blocking(move || {
+        remove_alert_blocking(&sink, &id, reason.as_deref())
+    })
+    .await
+    {
+        Ok(result) => result,
+        Err(err) => {
+            error!("[ALERT] Fallo al eliminar alerta: {:?}", err);
+            Ok(false)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AckPolicy {
+    confirm_severities: Vec<AlertSeverity>,
+    min_reason_chars: usize,
+}
+
+/// Para que la UI pida el motivo antes de intentar el reconocimiento.
+#[tauri::command]
+fn get_ack_policy() -> AckPolicy {
+    let policy = &app_config().ack_policy;
+    AckPolicy {
+        confirm_severities: policy.confirm_severities.clone(),
+        min_reason_chars: policy.min_reason_chars.max(1),
+    }
+}
+
+fn remove_alert_blocking(
+    app_handle: &EventSink,
+    id: &str,
+    reason: Option<&str>,
+) -> Result<bool, String> {
+    remove_alert_from(app_handle, id, "local", reason)
+}
+
+/// Rechaza el reconocimiento si la severidad de la alerta exige confirmar y falta el motivo.
+fn check_ack_policy(id: &str, reason: Option<&str>) -> Result<(), String> {
+    let policy = &app_config().ack_policy;
+    let Some(severity) = with_alert_store(|store| store.get(id).map(|alert| alert.severity)) else {
+        return Ok(());
+    };
+    if !policy.confirm_severities.contains(&severity) {
+        return Ok(());
+    }
+    let reason = reason.map(str::trim).unwrap_or_default();
+    let min_chars = policy.min_reason_chars.max(1);
+    if reason.chars().count() < min_chars {
+        return Err(format!(
+            "Las alertas {:?} requieren confirmar con un motivo (mínimo {} caracteres)",
+            severity, min_chars
+        ));
+    }
+    Ok(())
+}
+
+fn remove_alert_from(
+    app_handle: &EventSink,
+    id: &str,
+    source: &str,
+    reason: Option<&str>,
+) -> Result<bool, String> {
+    check_ack_policy(id, reason)?;
+    record_acknowledgement(Some(id));
+    let removed = remove_alert_local(app_handle, id);
+    if removed {
+        record_audit(
+            source,
+            "remove_alert",
+            id,
+            reason.unwrap_or_default().trim(),
+        );
+        broadcast_peer_action(PeerAction::Remove, Some(id));
+    }
+    Ok(removed)
+}
+
+fn remove_alert_local(app_handle: &EventSink, id: &str) -> bool {
+    if let Some(alert) = remove_alert_by_id(id) {
+        publish_domain_event(app_handle, DomainEvent::AlertRemoved(alert));
+        true
+    } else {
+        false
+    }
+}
+
+/// Fija la alerta en la parte superior del anunciador.
+#[tauri::command]
+async fn pin_alert(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    id: String,
+) -> Result<(), String> {
+    check_write_access(&window)?;
+    let sink = EventSink::App(app_handle);
+    async_runtime::spawn_blocking(move || {
+        if !with_alert_store(|store| store.contains_key(&id)) {
+            return Err(format!("La alerta {} no está activa", id));
+        }
+        update_pinned_alerts(&sink, |pinned| {
+            pinned.retain(|pinned_id| *pinned_id != id);
+            pinned.insert(0, id.clone());
+        });
+        Ok(())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+#[tauri::command]
+async fn unpin_alert(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    id: String,
+) -> Result<(), String> {
+    check_write_access(&window)?;
+    let sink = EventSink::App(app_handle);
+    if let Err(err) = async_runtime::spawn_blocking(move || {
+        update_pinned_alerts(&sink, |pinned| pinned.retain(|pinned_id| *pinned_id != id));
+    })
+    .await
+    {
+        error!("[PIN] Fallo al desfijar alerta: {:?}", err);
+    }
+    Ok(())
+}
+
+/// Reordena manualmente las alertas fijadas; `ids` debe contener exactamente las fijadas.
+#[tauri::command]
+async fn reorder_pinned_alerts(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    ids: Vec<String>,
+) -> Result<(), String> {
+    check_write_access(&window)?;
+    let sink = EventSink::App(app_handle);
+    async_runtime::spawn_blocking(move || {
+        let mut requested = ids.clone();
+        let mut current = with_pinned_alerts(|pinned| pinned.clone());
+        requested.sort();
+        current.sort();
+        if requested != current {
+            return Err("La lista no coincide con las alertas fijadas".to_string());
+        }
+        update_pinned_alerts(&sink, |pinned| *pinned = ids);
+        Ok(())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Foto adjunta a la alerta como URL `data:` para el `<img>` de la tarjeta.
+/// Usa la copia local si existe, así se ve aunque el panel esté sin red.
+#[tauri::command]
+async fn get_alert_snapshot(id: String) -> Result<String, String> {
+    let url = with_alert_store(|store| {
+        store
+            .get(&id)
+            .and_then(alert_snapshot_url)
+            .map(str::to_string)
+    })
+    .ok_or_else(|| format!("La alerta {} no tiene foto", id))?;
+    async_runtime::spawn_blocking(move || {
+        let (path, mime) = match cached_snapshot(&url, true) {
+            Some(cached) => cached,
+            None => {
+                download_snapshot(&url).or_else(|err| cached_snapshot(&url, false).ok_or(err))?
+            }
+        };
+        let bytes =
+            fs::read(&path).map_err(|err| format!("No se pudo leer {:?}: {:?}", path, err))?;
+        Ok(format!("data:{};base64,{}", mime, BASE64.encode(bytes)))
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+fn floorplan_image_path(cfg: &FloorplanConfig, data_dir: &str) -> PathBuf {
+    Path::new(data_dir).join(&cfg.image_path)
+}
+
+fn image_mime(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "svg" => Some("image/svg+xml"),
+        "jpeg" => Some("image/jpeg"),
+        other => SNAPSHOT_TYPES
+            .iter()
+            .find(|(_, ext)| *ext == other)
+            .map(|(mime, _)| *mime),
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Floorplan {
+    /// Imagen como `data:` URL, lista para usar en `<img>`.
+    image: String,
+    markers: Vec<FloorplanMarker>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct FloorplanOverlay {
+    device: String,
+    x: f64,
+    y: f64,
+    label: String,
+    #[serde(flatten)]
+    alerts: AlertSummary,
+}
+
+fn floorplan_overlay(marker: &FloorplanMarker) -> FloorplanOverlay {
+    let mut alerts = AlertSummary::default();
+    with_alert_store(|store| {
+        store
+            .values()
+            .filter(|alert| alert.device == marker.device)
+            .for_each(|alert| alerts.add(alert));
+    });
+    FloorplanOverlay {
+        device: marker.device.clone(),
+        x: marker.x,
+        y: marker.y,
+        label: if marker.label.is_empty() {
+            marker.device.clone()
+        } else {
+            marker.label.clone()
+        },
+        alerts,
+    }
+}
+
+/// Reenvía a la UI el estado del marcador del equipo afectado para actualizar el plano sin releerlo.
+fn floorplan_side_effect(event: &DomainEvent, app_handle: &EventSink) {
+    let (DomainEvent::AlertAdded(alert)
+    | DomainEvent::AlertUpdated(alert)
+    | DomainEvent::AlertRemoved(alert)) = event
+    else {
+        return;
+    };
+    for marker in app_config()
+        .floorplan
+        .markers
+        .iter()
+        .filter(|marker| marker.device == alert.device)
+    {
+        if let Err(err) = app_handle.emit(FLOORPLAN_OVERLAY_EVENT, floorplan_overlay(marker)) {
+            warn!(
+                "[FLOORPLAN] No se pudo emitir estado de {}: {:?}",
+                marker.device, err
+            );
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_floorplan() -> Result<Floorplan, String> {
+    let cfg = app_config();
+    if cfg.floorplan.image_path.is_empty() {
+        return Err("No hay plano configurado".to_string());
+    }
+    let path = floorplan_image_path(&cfg.floorplan, &cfg.data_dir);
+    async_runtime::spawn_blocking(move || {
+        let mime = image_mime(&path)
+            .ok_or_else(|| format!("Formato de imagen no soportado: {:?}", path))?;
+        let bytes =
+            fs::read(&path).map_err(|err| format!("No se pudo leer {:?}: {:?}", path, err))?;
+        Ok(Floorplan {
+            image: format!("data:{};base64,{}", mime, BASE64.encode(bytes)),
+            markers: app_config().floorplan.markers.clone(),
+        })
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Estado actual de cada marcador del plano.
+#[tauri::command]
+fn get_floorplan_overlay() -> Vec<FloorplanOverlay> {
+    app_config()
+        .floorplan
+        .markers
+        .iter()
+        .map(floorplan_overlay)
+        .collect()
+}
+
+/// Búsqueda de texto completo sobre descripción, dispositivo y notas del historial, opcionalmente de una zona.
+#[tauri::command]
+async fn search_history(
+    query: String,
+    range: Option<HistoryRange>,
+    zone: Option<String>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let devices = zone.as_deref().map(zone_devices).transpose()?;
+    async_runtime::spawn_blocking(move || {
+        search_history_entries(&query, range.unwrap_or_default(), devices.as_deref())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Agrega una nota al último registro de historial de la alerta.
+#[tauri::command]
+async fn add_alert_note(window: tauri::Window, id: String, note: String) -> Result<(), String> {
+    check_write_access(&window)?;
+    async_runtime::spawn_blocking(move || {
+        let updated = with_history_db(|conn| {
+            conn.execute(
+                "UPDATE alert_history SET notes = CASE WHEN notes = '' THEN ?1 ELSE notes || char(10) || ?1 END
+                 WHERE id = (SELECT max(id) FROM alert_history WHERE alert_id = ?2)",
+                params![note.trim(), id],
+            )
+        })?;
+        if updated == 0 {
+            return Err(format!("La alerta {} no tiene historial", id));
+        }
+        record_audit("local", "add_note", &id, note.trim());
+        Ok(())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+#[tauri::command]
+async fn run_maintenance_now(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<MaintenanceReport, String> {
+    check_write_access(&window)?;
+    let report = async_runtime::spawn_blocking(run_maintenance)
+        .await
+        .map_err(|err| format!("{:?}", err))?;
+    record_audit("local", "run_maintenance", "", "");
+    emit_maintenance_report(&EventSink::App(app_handle), &report);
+    Ok(report)
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AuditEntry {
+    ts_ms: i64,
+    source: String,
+    action: String,
+    target: String,
+    detail: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    operator: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HandoverReport {
+    panel_id: String,
+    shift: String,
+    from: String,
+    to: String,
+    alarms_raised: usize,
+    alarms_by_severity: BTreeMap<String, usize>,
+    acknowledgements: usize,
+    mutes: usize,
+    notes: Vec<AuditEntry>,
+    actions: Vec<AuditEntry>,
+    open_items: Vec<Alert>,
+    path: Option<String>,
+    emailed: bool,
+}
+
+fn audit_entries(from_ms: i64, to_ms: i64) -> Result<Vec<AuditEntry>, String> {
+    with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT ts_ms, source, action, target, detail, operator FROM audit_log
+             WHERE ts_ms BETWEEN ?1 AND ?2 ORDER BY ts_ms",
+        )?;
+        let rows = stmt.query_map(params![from_ms, to_ms], |row| {
+            Ok(AuditEntry {
+                ts_ms: row.get(0)?,
+                source: row.get(1)?,
+                action: row.get(2)?,
+                target: row.get(3)?,
+                detail: row.get(4)?,
+                operator: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+fn raised_alarm_severities(from_ms: i64, to_ms: i64) -> Result<Vec<String>, String> {
+    with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT severity FROM alert_history
+             WHERE event = 'added' AND ts_ms BETWEEN ?1 AND ?2",
+        )?;
+        let rows = stmt.query_map(params![from_ms, to_ms], |row| row.get(0))?;
+        rows.collect()
+    })
+}
+
+fn format_local_ms(ts_ms: i64) -> String {
+    Local
+        .timestamp_millis_opt(ts_ms)
+        .single()
+        .map(|ts| ts.format("%d/%m/%Y %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// Resume el turno en curso (que termina) a partir del historial, la auditoría y las notas.
+fn build_handover_report() -> Result<HandoverReport, String> {
+    let now = plant_now();
+    let (shift, start) =
+        current_shift(now, &shift_definitions(app_config())).ok_or("No hay turnos configurados")?;
+    let (from_ms, to_ms) = (start.timestamp_millis(), now.timestamp_millis());
+
+    let severities = raised_alarm_severities(from_ms, to_ms)?;
+    let mut alarms_by_severity = BTreeMap::new();
+    for severity in &severities {
+        *alarms_by_severity.entry(severity.clone()).or_insert(0) += 1;
+    }
+    let (notes, actions): (Vec<AuditEntry>, Vec<AuditEntry>) = audit_entries(from_ms, to_ms)?
+        .into_iter()
+        .partition(|entry| entry.action == "add_note");
+
+    Ok(HandoverReport {
+        panel_id: panel_id().to_string(),
+        shift,
+        from: start.to_rfc3339_opts(SecondsFormat::Secs, false),
+        to: now.to_rfc3339_opts(SecondsFormat::Secs, false),
+        alarms_raised: severities.len(),
+        alarms_by_severity,
+        acknowledgements: actions
+            .iter()
+            .filter(|entry| entry.action == "remove_alert")
+            .count(),
+        mutes: actions
+            .iter()
+            .filter(|entry| entry.action == "mute")
+            .count(),
+        notes,
+        actions,
+        open_items: snapshot_alerts(),
+        path: None,
+        emailed: false,
+    })
+}
+
+fn render_handover_text(report: &HandoverReport) -> String {
+    let mut text = format!(
+        "Entrega de turno {} - panel {}\nDesde {} hasta {}\n\n",
+        report.shift, report.panel_id, report.from, report.to
+    );
+    let by_severity: Vec<String> = report
+        .alarms_by_severity
+        .iter()
+        .map(|(severity, count)| format!("{}: {}", severity, count))
+        .collect();
+    text.push_str(&format!(
+        "Alarmas: {} ({})\nReconocimientos: {}\nSilenciamientos: {}\n",
+        report.alarms_raised,
+        by_severity.join(", "),
+        report.acknowledgements,
+        report.mutes
+    ));
+
+    text.push_str("\nPendientes:\n");
+    for alert in &report.open_items {
+        text.push_str(&format!(
+            "- [{}] {}: {}\n",
+            serde_name(&alert.severity),
+            alert.device,
+            alert.description
+        ));
+    }
+    text.push_str("\nNotas:\n");
+    for note in &report.notes {
+        text.push_str(&format!(
+            "- {} {}: {}\n",
+            format_local_ms(note.ts_ms),
+            note.target,
+            note.detail
+        ));
+    }
+    text.push_str("\nAcciones:\n");
+    for action in &report.actions {
+        let source = match action.operator.as_str() {
+            "" => action.source.clone(),
+            operator => format!("{} ({})", action.source, operator),
+        };
+        text.push_str(&format!(
+            "- {} {} {} {}\n",
+            format_local_ms(action.ts_ms),
+            source,
+            action.action,
+            action.target
+        ));
+    }
+    text
+}
+
+fn store_handover_report(report: &HandoverReport) -> Result<PathBuf, String> {
+    let dir = Path::new(&app_config().data_dir).join(REPORTS_DIR);
+    fs::create_dir_all(&dir).map_err(|err| format!("No se pudo crear {:?}: {:?}", dir, err))?;
+    let path = dir.join(format!(
+        "handover-{}.json",
+        corrected_now().format("%Y%m%d-%H%M")
+    ));
+    let json = serde_json::to_vec_pretty(report).map_err(|err| format!("{:?}", err))?;
+    fs::write(&path, json).map_err(|err| format!("No se pudo escribir {:?}: {:?}", path, err))?;
+    Ok(path)
+}
+
+fn send_email(subject: &str, body: String, to: &[String]) -> Result<(), String> {
+    let smtp = &app_config().smtp;
+    if smtp.host.is_empty() {
+        return Err("SMTP no configurado".to_string());
+    }
+    let from: Mailbox = smtp
+        .from
+        .parse()
+        .map_err(|err| format!("Remitente inválido {}: {:?}", smtp.from, err))?;
+    let mut builder = Message::builder().from(from).subject(subject);
+    for recipient in to {
+        let mailbox: Mailbox = recipient
+            .parse()
+            .map_err(|err| format!("Destinatario inválido {}: {:?}", recipient, err))?;
+        builder = builder.to(mailbox);
+    }
+    let message = builder.body(body).map_err(|err| format!("{:?}", err))?;
+
+    let relay = match smtp.security() {
+        SmtpTls::Starttls => SmtpTransport::starttls_relay(&smtp.host),
+        SmtpTls::Implicit => SmtpTransport::relay(&smtp.host),
+        SmtpTls::None => Ok(SmtpTransport::builder_dangerous(&smtp.host)),
+    };
+    let mut transport = relay
+        .map_err(|err| format!("{:?}", err))?
+        .port(smtp.port)
+        .timeout(Some(SMTP_TIMEOUT));
+    if !smtp.username.is_empty() {
+        transport = transport.credentials(Credentials::new(
+            smtp.username.clone(),
+            smtp.password.clone(),
+        ));
+    }
+    transport
+        .build()
+        .send(&message)
+        .map(|_| ())
+        .map_err(|err| format!("{:?}", err))
+}
+
+/// Sustituye `{{campo}}`; los campos desconocidos quedan vacíos.
+fn render_template(template: &str, fields: &BTreeMap<String, String>) -> String {
+    render_template_with(template, fields, str::to_string)
+}
+
+/// Igual que `render_template`, escapando cada valor para el formato de destino.
+fn render_template_with(
+    template: &str,
+    fields: &BTreeMap<String, String>,
+    escape: fn(&str) -> String,
+) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let key = rest[start + 2..start + end].trim();
+        if let Some(value) = fields.get(key) {
+            rendered.push_str(&escape(value));
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Asunto y cuerpo del correo según `EMAIL_TEMPLATES[event]`, con el pie de identidad del panel.
+fn compose_email(event: &str, fields: &BTreeMap<String, String>) -> (String, String) {
+    let cfg = app_config();
+    let fields = template_fields(event, fields);
+    let template = cfg.email_templates.get(event).cloned().unwrap_or_default();
+    let subject = render_template(
+        template
+            .subject
+            .as_deref()
+            .unwrap_or("[{{panelId}}] {{subject}}"),
+        &fields,
+    );
+    let mut body = render_template(template.body.as_deref().unwrap_or("{{body}}"), &fields);
+    let footer = render_template(&cfg.email_footer, &fields);
+    if !footer.trim().is_empty() {
+        body.push_str("\n\n");
+        body.push_str(footer.trim_end());
+    }
+    (subject, body)
+}
+
+/// Campos comunes a todas las plantillas: evento, identidad del panel y marca de tiempo.
+fn template_fields(event: &str, fields: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let cfg = app_config();
+    let mut fields = fields.clone();
+    fields.insert("event".to_string(), event.to_string());
+    fields.insert("panelId".to_string(), panel_id().to_string());
+    fields.insert(
+        "appVersion".to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+    );
+    fields.insert(
+        "hardwareProfile".to_string(),
+        hardware_profile().name.clone(),
+    );
+    fields.insert("hardwareRevision".to_string(), hardware_revision(cfg));
+    let now = corrected_now();
+    fields
+        .entry("ts".to_string())
+        .or_insert_with(|| now.to_rfc3339_opts(SecondsFormat::Secs, false));
+    fields
+        .entry("tsMs".to_string())
+        .or_insert_with(|| now.timestamp_millis().to_string());
+    fields
+}
+
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn form_escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            b' ' => "+".to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Sin plantilla se envían todos los campos; con plantilla JSON se valida que el resultado lo sea.
+fn render_webhook_body(
+    target: &WebhookTarget,
+    fields: &BTreeMap<String, String>,
+) -> Result<String, String> {
+    match (target.format, target.body.as_deref()) {
+        (WebhookFormat::Json, Some(template)) => {
+            let body = render_template_with(template, fields, json_escape);
+            serde_json::from_str::<serde_json::Value>(&body)
+                .map_err(|err| format!("La plantilla no produce JSON válido: {}", err))?;
+            Ok(body)
+        }
+        (WebhookFormat::Json, None) => {
+            serde_json::to_string(fields).map_err(|err| format!("{:?}", err))
+        }
+        (WebhookFormat::Form, Some(template)) => {
+            Ok(render_template_with(template, fields, form_escape))
+        }
+        (WebhookFormat::Form, None) => Ok(fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", form_escape(key), form_escape(value)))
+            .collect::<Vec<_>>()
+            .join("&")),
+    }
+}
+
+fn post_webhook(target: &WebhookTarget, body: &str) -> Result<(), String> {
+    let content_type = match target.format {
+        WebhookFormat::Json => "application/json",
+        WebhookFormat::Form => "application/x-www-form-urlencoded",
+    };
+    let headers: Vec<(&str, &str)> = target
+        .headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    http_post(&target.url, content_type, &headers, body)
+}
+
+fn http_post(
+    url: &str,
+    content_type: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<(), String> {
+    let mut request = ureq::AgentBuilder::new()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .post(url)
+        .set("Content-Type", content_type);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    request
+        .send_string(body)
+        .map(|_| ())
+        .map_err(|err| format!("{}", err))
+}
+
+fn send_webhooks(event: &str, fields: &BTreeMap<String, String>) {
+    let targets: Vec<WebhookTarget> = app_config()
+        .webhooks
+        .iter()
+        .filter(|target| {
+            target.enabled
+                && (target.events.is_empty() || target.events.iter().any(|name| name == event))
+        })
+        .cloned()
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+    let fields = template_fields(event, fields);
+    async_runtime::spawn_blocking(move || {
+        for target in targets {
+            let result =
+                render_webhook_body(&target, &fields).and_then(|body| post_webhook(&target, &body));
+            if let Err(err) = result {
+                warn!("[WEBHOOK] {}: no se pudo enviar: {}", target.name, err);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct QueuedEmail {
+    id: String,
+    subject: String,
+    body: String,
+    to: Vec<String>,
+    created_ms: i64,
+    attempts: u32,
+    next_attempt_ms: i64,
+    last_error: Option<String>,
+}
+
+fn email_queue_path() -> PathBuf {
+    Path::new(&app_config().data_dir).join(EMAIL_QUEUE_FILE)
+}
+
+fn load_email_queue() -> Vec<QueuedEmail> {
+    let Ok(contents) = fs::read_to_string(email_queue_path()) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&contents).unwrap_or_else(|err| {
+        warn!("[EMAIL] No se pudo leer {}: {:?}", EMAIL_QUEUE_FILE, err);
+        Vec::new()
+    })
+}
+
+fn persist_email_queue(queue: &[QueuedEmail]) {
+    let path = email_queue_path();
+    if let Some(parent) = path.parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            error!("[EMAIL] No se pudo crear carpeta {:?}: {:?}", parent, err);
+            return;
+        }
+    }
+    match serde_json::to_string(queue) {
+        Ok(json) => {
+            if let Err(err) = fs::write(&path, json) {
+                error!("[EMAIL] No se pudo escribir {:?}: {:?}", path, err);
+            }
+        }
+        Err(err) => error!("[EMAIL] No se pudo serializar la cola de correo: {:?}", err),
+    }
+}
+
+fn with_email_queue<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Vec<QueuedEmail>) -> R,
+{
+    let queue = EMAIL_QUEUE.get_or_init(|| Mutex::new(load_email_queue()));
+    let mut guard = queue
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Encola el correo en disco; el envío (con reintentos) lo hace `start_email_queue_loop`.
+fn queue_email(subject: String, body: String, to: &[String]) -> Result<(), String> {
+    if app_config().smtp.host.is_empty() {
+        return Err("SMTP no configurado".to_string());
+    }
+    let now_ms = corrected_now().timestamp_millis();
+    let email = QueuedEmail {
+        id: format!(
+            "mail-{}-{}",
+            now_ms,
+            EMAIL_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ),
+        subject,
+        body,
+        to: to.to_vec(),
+        created_ms: now_ms,
+        attempts: 0,
+        next_attempt_ms: now_ms,
+        last_error: None,
+    };
+    with_email_queue(|queue| {
+        if queue.len() >= EMAIL_QUEUE_LIMIT {
+            let dropped = queue.remove(0);
+            warn!(
+                "[EMAIL] Cola llena, se descarta el correo más antiguo: {}",
+                dropped.subject
+            );
+        }
+        queue.push(email);
+        persist_email_queue(queue);
+    });
+    Ok(())
+}
+
+fn email_retry_delay(attempts: u32) -> Duration {
+    let max = Duration::from_secs(app_config().smtp.retry_max_minutes.max(1) * 60);
+    EMAIL_RETRY_BASE
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1).min(16)))
+        .min(max)
+}
+
+/// Envía los correos vencidos; los fallidos se reprograman con backoff exponencial.
+fn process_email_queue() {
+    let now_ms = corrected_now().timestamp_millis();
+    let due: Vec<QueuedEmail> = with_email_queue(|queue| {
+        queue
+            .iter()
+            .filter(|email| email.next_attempt_ms <= now_ms)
+            .cloned()
+            .collect()
+    });
+    if due.is_empty() {
+        return;
+    }
+
+    let results: Vec<(String, Result<(), String>)> = due
+        .into_iter()
+        .map(|email| {
+            let result = send_email(&email.subject, email.body, &email.to);
+            (email.id, result)
+        })
+        .collect();
+
+    let max_attempts = app_config().smtp.max_attempts.max(1);
+    with_email_queue(|queue| {
+        for (id, result) in results {
+            let Some(index) = queue.iter().position(|email| email.id == id) else {
+                continue;
+            };
+            match result {
+                Ok(()) => {
+                    let email = queue.remove(index);
+                    info!("[EMAIL] Correo enviado: {}", email.subject);
+                }
+                Err(err) => {
+                    let email = &mut queue[index];
+                    email.attempts += 1;
+                    email.last_error = Some(err.clone());
+                    if email.attempts >= max_attempts {
+                        let email = queue.remove(index);
+                        error!(
+                            "[EMAIL] Correo descartado tras {} intentos: {} ({})",
+                            email.attempts, email.subject, err
+                        );
+                        record_audit("local", "email_dropped", &email.id, &err);
+                    } else {
+                        let delay = email_retry_delay(email.attempts);
+                        email.next_attempt_ms = now_ms + delay.as_millis() as i64;
+                        warn!(
+                            "[EMAIL] Falló el envío (intento {}), reintento en {:?}: {}",
+                            email.attempts, delay, err
+                        );
+                    }
+                }
+            }
+        }
+        persist_email_queue(queue);
+    });
+}
+
+fn start_email_queue_loop() {
+    if app_config().smtp.host.is_empty() {
+        return;
+    }
+    supervise(
+        "email-queue",
+        false,
+        Some(EMAIL_QUEUE_TICK),
+        RestartPolicy::Always,
+        move |task| async move {
+            while !is_shutting_down() {
+                let _ = async_runtime::spawn_blocking(process_email_queue).await;
+                tokio::time::sleep(EMAIL_QUEUE_TICK).await;
+                task.beat();
+            }
+        },
+    );
+}
+
+#[tauri::command]
+fn get_email_queue() -> Vec<QueuedEmail> {
+    with_email_queue(|queue| queue.clone())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationPayload {
+    event: String,
+    subject: String,
+    body: String,
+    panel_id: String,
+    ts: String,
+    recipient: Option<String>,
+    actions: Vec<NotificationAction>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fields: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum RemoteAction {
+    Ack,
+    Snooze,
+    AckEscalation,
+}
+
+/// Token firmado que la plataforma (bot, webhook) devuelve por RPC para actuar sobre el panel.
+#[derive(Debug, Serialize, Clone)]
+struct NotificationAction {
+    action: RemoteAction,
+    token: String,
+}
+
+/// `hex(acción|vence_ms|panel|nonce|objetivo).hex(hmac)`; `None` si no hay `ACTION_SECRET`.
+fn sign_action(action: RemoteAction, target: &str) -> Option<NotificationAction> {
+    let cfg = &app_config().notifications;
+    let expires_ms = corrected_now().timestamp_millis()
+        + (cfg.action_ttl_minutes.max(1) as i64).saturating_mul(60_000);
+    let mut nonce = [0u8; 16];
+    if let Err(err) = rustls::crypto::aws_lc_rs::default_provider()
+        .secure_random
+        .fill(&mut nonce)
+    {
+        warn!("[NOTIFY] No se pudo generar nonce de acción: {:?}", err);
+        return None;
+    }
+    let claims = ActionClaims {
+        action: serde_name(&action),
+        expires_ms,
+        panel: panel_id().to_string(),
+        nonce: to_hex(&nonce),
+        target: target.to_string(),
+    };
+    let token = sign_action_token(&cfg.action_secret, &claims)?;
+    Some(NotificationAction { action, token })
+}
+
+fn verify_action_token(token: &str) -> Result<(RemoteAction, String), String> {
+    let now_ms = corrected_now().timestamp_millis();
+    let claims = signing::verify_action_token(
+        &app_config().notifications.action_secret,
+        token,
+        panel_id(),
+        now_ms,
+    )?;
+    let action: RemoteAction =
+        serde_json::from_value(serde_json::Value::String(claims.action.clone()))
+            .map_err(|_| format!("Acción desconocida: {}", claims.action))?;
+    let used = ACTION_NONCES.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut used = used.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    used.retain(|_, expires| *expires >= now_ms);
+    if used.insert(claims.nonce, claims.expires_ms).is_some() {
+        return Err("Token ya usado".to_string());
+    }
+    Ok((action, claims.target))
+}
+
+/// Reconoce o pospone (silencia) la alerta de origen, o cierra la escalación, desde una notificación.
+fn apply_remote_action(token: &str, app_handle: &EventSink) -> Result<String, String> {
+    let (action, target) = verify_action_token(token)?;
+    info!("[NOTIFY] Acción remota {:?} sobre {}", action, target);
+    match action {
+        RemoteAction::Ack => {
+            // Desde la notificación no hay motivo: las severidades con confirmación se rechazan.
+            if remove_alert_from(app_handle, &target, "remote", None)? {
+                Ok(format!("Alerta {} reconocida", target))
+            } else {
+                Err(format!("La alerta {} ya no está activa", target))
+            }
+        }
+        RemoteAction::Snooze => {
+            if !with_alert_store(|store| store.contains_key(&target)) {
+                return Err(format!("La alerta {} ya no está activa", target));
+            }
+            if !with_mute_controller(|ctrl| ctrl.muted) {
+                mute_alerts_internal(app_handle);
+                record_mute_metric();
+                broadcast_peer_action(PeerAction::Mute, None);
+            }
+            record_audit("remote", "mute", &target, "desde notificación");
+            Ok(format!("Alertas silenciadas por {:?}", mute_duration()))
+        }
+        RemoteAction::AckEscalation => {
+            if resolve_escalation(&target, "remote", "desde notificación") {
+                Ok(format!("Escalación {} cerrada", target))
+            } else {
+                Err(format!("La escalación {} ya no está abierta", target))
+            }
+        }
+    }
+}
+
+/// Envía la notificación por todos los canales configurados sin bloquear al llamador.
+fn notify(event: &str, subject: String, body: String) {
+    send_notification(event, subject, body, None, None, &[], BTreeMap::new());
+}
+
+/// Con `recipient` el correo va sólo a ese contacto de guardia en vez de a `NOTIFICATIONS.email_to`.
+/// Con `severity` (notificación de una alerta) cada canal consulta `NOTIFICATION_ROUTING`.
+fn send_notification(
+    event: &str,
+    subject: String,
+    body: String,
+    recipient: Option<&OnCallShift>,
+    severity: Option<AlertSeverity>,
+    actions: &[(RemoteAction, &str)],
+    fields: BTreeMap<String, String>,
+) {
+    let cfg = &app_config().notifications;
+    let routed = |channel| severity.is_none_or(|severity| route_allows(severity, channel));
+    let actions: Vec<NotificationAction> = actions
+        .iter()
+        .filter_map(|(action, target)| sign_action(*action, target))
+        .collect();
+    let mut body = body;
+    for action in &actions {
+        body.push_str(&format!(
+            "\n{}: {}",
+            serde_name(&action.action).to_uppercase(),
+            action.token
+        ));
+    }
+    let payload = NotificationPayload {
+        event: event.to_string(),
+        subject,
+        body,
+        panel_id: panel_id().to_string(),
+        ts: corrected_now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        recipient: recipient.map(|contact| contact.name.clone()),
+        actions,
+        fields,
+    };
+    warn!(
+        "[NOTIFY] {}: {} ({})",
+        payload.event,
+        payload.subject,
+        payload.recipient.as_deref().unwrap_or("todos")
+    );
+    record_audit(
+        "local",
+        "notify",
+        &payload.event,
+        &format!(
+            "{} -> {}",
+            payload.subject,
+            payload.recipient.as_deref().unwrap_or("todos")
+        ),
+    );
+
+    if cfg.mqtt && routed(NotificationChannel::Mqtt) {
+        match serde_json::to_vec(&serde_json::json!({ "notification": &payload })) {
+            Ok(bytes) => {
+                mqtt_publish(MQTT_TELEMETRY_PUBLISH_TOPIC, bytes, QoS::AtLeastOnce);
+            }
+            Err(err) => warn!("[NOTIFY] No se pudo serializar: {:?}", err),
+        }
+    }
+
+    let mut fields = payload.fields;
+    fields.insert("subject".to_string(), payload.subject);
+    fields.insert("body".to_string(), payload.body);
+    fields.insert("ts".to_string(), payload.ts);
+    if let Some(recipient) = payload.recipient {
+        fields.insert("recipient".to_string(), recipient);
+    }
+    if routed(NotificationChannel::Webhook) {
+        send_webhooks(&payload.event, &fields);
+    }
+
+    let recipients = match recipient {
+        _ if !routed(NotificationChannel::Email) => Vec::new(),
+        Some(contact) if !contact.email.is_empty() => vec![contact.email.clone()],
+        Some(_) => Vec::new(),
+        None => cfg.email_to.clone(),
+    };
+    if !recipients.is_empty() {
+        let (subject, body) = compose_email(&payload.event, &fields);
+        if let Err(err) = queue_email(subject, body, &recipients) {
+            warn!("[NOTIFY] No se pudo encolar correo: {}", err);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Escalation {
+    id: String,
+    event: String,
+    subject: String,
+    body: String,
+    chain: Vec<OnCallShift>,
+    level: usize,
+    notified_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct EscalationStatus {
+    id: String,
+    event: String,
+    subject: String,
+    on_call: String,
+    level: usize,
+    notified_at: String,
+}
+
+fn with_on_call_schedule<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Vec<OnCallShift>) -> R,
+{
+    let schedule =
+        ON_CALL_SCHEDULE.get_or_init(|| Mutex::new(app_config().on_call.schedule.clone()));
+    let mut guard = schedule
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn with_escalations<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashMap<String, Escalation>) -> R,
+{
+    let escalations = ESCALATIONS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = escalations
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Primero quienes están de guardia ahora y luego el resto de la lista como respaldo.
+fn on_call_chain(at: &DateTime<Tz>) -> Vec<OnCallShift> {
+    with_on_call_schedule(|schedule| {
+        let (mut chain, backups): (Vec<OnCallShift>, Vec<OnCallShift>) =
+            schedule.iter().cloned().partition(|shift| shift.covers(at));
+        chain.extend(backups);
+        chain
+    })
+}
+
+fn notify_on_call(escalation: &Escalation) {
+    let contact = &escalation.chain[escalation.level % escalation.chain.len()];
+    record_audit("local", "escalation_sent", &escalation.id, &contact.name);
+    send_notification(
+        &escalation.event,
+        escalation.subject.clone(),
+        format!(
+            "{}\n\nEscalamiento {} (id {})",
+            escalation.body,
+            escalation.level + 1,
+            escalation.id
+        ),
+        Some(contact),
+        None,
+        &[(RemoteAction::AckEscalation, escalation.id.as_str())],
+        BTreeMap::from([
+            ("escalationId".to_string(), escalation.id.clone()),
+            ("level".to_string(), (escalation.level + 1).to_string()),
+        ]),
+    );
+}
+
+/// Envíos recientes y supresiones pendientes de resumen para un (dispositivo, tipo).
+#[derive(Debug, Default)]
+struct NotificationThrottle {
+    sent: VecDeque<DateTime<Utc>>,
+    suppressed: u32,
+    first_suppressed: Option<DateTime<Utc>>,
+    last_suppressed: Option<DateTime<Utc>>,
+    last_description: String,
+    worst_severity: Option<AlertSeverity>,
+}
+
+fn with_notification_throttle<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashMap<(String, String), NotificationThrottle>) -> R,
+{
+    let throttle = NOTIFICATION_THROTTLE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = throttle
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Registra el envío si cabe en la ventana; si no, lo acumula para el resumen y devuelve `false`.
+fn admit_notification(alert: &Alert, now: DateTime<Utc>) -> bool {
+    let cfg = &app_config().notifications;
+    if cfg.throttle_max == 0 {
+        return true;
+    }
+    let window = chrono::Duration::minutes(cfg.throttle_window_minutes.max(1) as i64);
+    let key = (
+        alert.device.clone(),
+        serde_name(&alert.alert_type).to_string(),
+    );
+    with_notification_throttle(|throttle| {
+        let entry = throttle.entry(key).or_default();
+        while entry.sent.front().is_some_and(|sent| now - *sent >= window) {
+            entry.sent.pop_front();
+        }
+        // Un duplicado exacto dentro de la ventana nunca se reenvía, aunque quede cupo.
+        let duplicate = !entry.sent.is_empty() && entry.last_description == alert.description;
+        entry.last_description = alert.description.clone();
+        if !duplicate && entry.sent.len() < cfg.throttle_max as usize {
+            entry.sent.push_back(now);
+            return true;
+        }
+        entry.suppressed += 1;
+        entry.first_suppressed.get_or_insert(now);
+        entry.last_suppressed = Some(now);
+        if entry
+            .worst_severity
+            .is_none_or(|worst| alert.severity.rank() > worst.rank())
+        {
+            entry.worst_severity = Some(alert.severity);
+        }
+        false
+    })
+}
+
+/// Vacía las supresiones acumuladas y devuelve una línea por (dispositivo, tipo).
+fn take_notification_digest() -> Vec<String> {
+    with_notification_throttle(|throttle| {
+        let mut lines: Vec<String> = throttle
+            .iter_mut()
+            .filter(|(_, entry)| entry.suppressed > 0)
+            .map(|((device, alert_type), entry)| {
+                let line = format!(
+                    "{} / {}: {} suprimidas entre {} y {} (máx. {}, última: {})",
+                    device,
+                    alert_type,
+                    entry.suppressed,
+                    format_local_ms(entry.first_suppressed.map_or(0, |ts| ts.timestamp_millis())),
+                    format_local_ms(entry.last_suppressed.map_or(0, |ts| ts.timestamp_millis())),
+                    entry
+                        .worst_severity
+                        .map(|severity| serde_name(&severity))
+                        .unwrap_or_default(),
+                    entry.last_description
+                );
+                entry.suppressed = 0;
+                entry.first_suppressed = None;
+                entry.last_suppressed = None;
+                entry.worst_severity = None;
+                line
+            })
+            .collect();
+        throttle.retain(|_, entry| !entry.sent.is_empty());
+        lines.sort();
+        lines
+    })
+}
+
+fn send_notification_digest() {
+    let lines = take_notification_digest();
+    if lines.is_empty() {
+        return;
+    }
+    let total = lines.len();
+    info!(
+        "[NOTIFY] Resumen de notificaciones suprimidas: {} orígenes",
+        total
+    );
+    notify(
+        "digest",
+        format!(
+            "Panel {}: {} alarmas repetitivas suprimidas",
+            panel_id(),
+            total
+        ),
+        lines.join("\n"),
+    );
+}
+
+fn start_notification_digest_loop() {
+    let cfg = &app_config().notifications;
+    if cfg.min_severity.is_none() || cfg.throttle_max == 0 {
+        return;
+    }
+    let interval = Duration::from_secs(cfg.digest_interval_minutes.max(1) * 60);
+    supervise(
+        "notification-digest",
+        false,
+        Some(NOTIFICATION_DIGEST_TICK),
+        RestartPolicy::Always,
+        move |task| async move {
+            let mut last_digest = Instant::now();
+            while !is_shutting_down() {
+                tokio::time::sleep(NOTIFICATION_DIGEST_TICK).await;
+                task.beat();
+                if last_digest.elapsed() < interval {
+                    continue;
+                }
+                last_digest = Instant::now();
+                let _ = async_runtime::spawn_blocking(send_notification_digest).await;
+            }
+        },
+    );
+}
+
+fn notify_alert(alert: &Alert) {
+    if !admit_notification(alert, corrected_now().with_timezone(&Utc)) {
+        debug!(
+            "[NOTIFY] Notificación suprimida por límite: {} / {}",
+            alert.device,
+            serde_name(&alert.alert_type)
+        );
+        return;
+    }
+    send_notification(
+        "alert",
+        format!(
+            "[{}] {}: {}",
+            serde_name(&alert.severity),
+            alert.device,
+            alert.description
+        ),
+        format!(
+            "Panel {}: alerta {} en {} ({})\nAbrir en el panel: {}",
+            panel_id(),
+            serde_name(&alert.alert_type),
+            alert.device,
+            alert.description,
+            alert_deep_link(&alert.id)
+        ),
+        None,
+        Some(alert.severity),
+        &[
+            (RemoteAction::Ack, alert.id.as_str()),
+            (RemoteAction::Snooze, alert.id.as_str()),
+        ],
+        BTreeMap::from([
+            ("alertId".to_string(), alert.id.clone()),
+            ("alertLink".to_string(), alert_deep_link(&alert.id)),
+            ("device".to_string(), alert.device.clone()),
+            ("description".to_string(), alert.description.clone()),
+            (
+                "severity".to_string(),
+                serde_name(&alert.severity).to_string(),
+            ),
+            (
+                "alertType".to_string(),
+                serde_name(&alert.alert_type).to_string(),
+            ),
+        ]),
+    );
+}
+
+fn notification_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    let DomainEvent::AlertAdded(alert) = event else {
+        return;
+    };
+    let Some(min_severity) = app_config().notifications.min_severity else {
+        return;
+    };
+    if alert.severity.rank() >= min_severity.rank() {
+        notify_alert(alert);
+    }
+}
+
+fn pagerduty_severity(severity: AlertSeverity) -> &'static str {
+    match severity {
+        AlertSeverity::Critical => "critical",
+        AlertSeverity::Major => "error",
+        AlertSeverity::Minor | AlertSeverity::Warning => "warning",
+        AlertSeverity::Indeterminate => "info",
+    }
+}
+
+fn opsgenie_priority(severity: AlertSeverity) -> &'static str {
+    match severity {
+        AlertSeverity::Critical => "P1",
+        AlertSeverity::Major => "P2",
+        AlertSeverity::Minor => "P3",
+        AlertSeverity::Warning => "P4",
+        AlertSeverity::Indeterminate => "P5",
+    }
+}
+
+/// Clave de deduplicación compartida por el disparo y la resolución en ambos servicios.
+fn incident_key(alert: &Alert) -> String {
+    format!("{}:{}", panel_id(), alert.id)
+}
+
+fn alert_summary(alert: &Alert) -> String {
+    format!("{} {}: {}", panel_id(), alert.device, alert.description)
+}
+
+fn pagerduty_event(alert: &Alert, resolve: bool) -> Result<(), String> {
+    let cfg = &app_config().pagerduty;
+    let body = if resolve {
+        serde_json::json!({
+            "routing_key": cfg.routing_key,
+            "event_action": "resolve",
+            "dedup_key": incident_key(alert),
+        })
+    } else {
+        serde_json::json!({
+            "routing_key": cfg.routing_key,
+            "event_action": "trigger",
+            "dedup_key": incident_key(alert),
+            "payload": {
+                "summary": alert_summary(alert),
+                "source": panel_id(),
+                "severity": pagerduty_severity(alert.severity),
+                "timestamp": alert.date_time,
+                "component": alert.device,
+                "class": serde_name(&alert.alert_type),
+                "custom_details": alert,
+            },
+        })
+    };
+    http_post(&cfg.url, "application/json", &[], &body.to_string())
+}
+
+fn opsgenie_event(alert: &Alert, resolve: bool) -> Result<(), String> {
+    let cfg = &app_config().opsgenie;
+    let authorization = format!("GenieKey {}", cfg.api_key);
+    let headers = [("Authorization", authorization.as_str())];
+    let base = cfg.url.trim_end_matches('/');
+    if resolve {
+        let url = format!(
+            "{}/v2/alerts/{}/close?identifierType=alias",
+            base,
+            form_escape(&incident_key(alert))
+        );
+        let body =
+            serde_json::json!({ "source": panel_id(), "note": "Alerta normalizada en el panel" });
+        return http_post(&url, "application/json", &headers, &body.to_string());
+    }
+    // Opsgenie limita el mensaje a 130 caracteres; el texto completo va en la descripción.
+    let message: String = alert_summary(alert).chars().take(130).collect();
+    let body = serde_json::json!({
+        "message": message,
+        "alias": incident_key(alert),
+        "description": alert.description,
+        "priority": opsgenie_priority(alert.severity),
+        "source": panel_id(),
+        "entity": alert.device,
+        "tags": cfg.tags,
+        "details": {
+            "alertId": alert.id,
+            "type": serde_name(&alert.alert_type),
+            "severity": serde_name(&alert.severity),
+            "dateTime": alert.date_time,
+        },
+    });
+    http_post(
+        &format!("{}/v2/alerts", base),
+        "application/json",
+        &headers,
+        &body.to_string(),
+    )
+}
+
+fn with_open_incidents<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashMap<String, AlertSeverity>) -> R,
+{
+    let incidents = OPEN_INCIDENTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = incidents
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn meets_severity(severity: AlertSeverity, min_severity: Option<AlertSeverity>) -> bool {
+    min_severity.is_none_or(|min_severity| severity.rank() >= min_severity.rank())
+}
+
+/// Abre el incidente al entrar (o escalar) la alerta y lo resuelve al normalizarse.
+fn incident_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    let cfg = app_config();
+    let pagerduty = !cfg.pagerduty.routing_key.is_empty();
+    let opsgenie = !cfg.opsgenie.api_key.is_empty();
+    if !pagerduty && !opsgenie {
+        return;
+    }
+    let (alert, resolve) = match event {
+        DomainEvent::AlertAdded(alert) | DomainEvent::AlertUpdated(alert) => (alert.clone(), false),
+        DomainEvent::AlertRemoved(alert) => (alert.clone(), true),
+        DomainEvent::MuteChanged(_) => return,
+    };
+    let pagerduty_key = format!("pagerduty:{}", alert.id);
+    let opsgenie_key = format!("opsgenie:{}", alert.id);
+    let (send_pagerduty, send_opsgenie) = with_open_incidents(|opened| {
+        if resolve {
+            return (
+                opened.remove(&pagerduty_key).is_some(),
+                opened.remove(&opsgenie_key).is_some(),
+            );
+        }
+        // PagerDuty acepta re-disparar la misma clave para reflejar un cambio de severidad;
+        // en Opsgenie repetir el alias sólo incrementa el contador, así que se envía una vez.
+        (
+            pagerduty
+                && meets_severity(alert.severity, cfg.pagerduty.min_severity)
+                && route_allows(alert.severity, NotificationChannel::Pagerduty)
+                && opened.insert(pagerduty_key, alert.severity) != Some(alert.severity),
+            opsgenie
+                && meets_severity(alert.severity, cfg.opsgenie.min_severity)
+                && route_allows(alert.severity, NotificationChannel::Opsgenie)
+                && opened.insert(opsgenie_key, alert.severity).is_none(),
+        )
+    });
+    if !send_pagerduty && !send_opsgenie {
+        return;
+    }
+    async_runtime::spawn_blocking(move || {
+        if send_pagerduty {
+            if let Err(err) = pagerduty_event(&alert, resolve) {
+                warn!("[INCIDENT] PagerDuty {}: {}", alert.id, err);
+            }
+        }
+        if send_opsgenie {
+            if let Err(err) = opsgenie_event(&alert, resolve) {
+                warn!("[INCIDENT] Opsgenie {}: {}", alert.id, err);
+            }
+        }
+    });
+}
+
+/// Notifica a la guardia; mientras siga abierta una escalación del mismo evento no se crea otra.
+fn escalate(event: &str, subject: String, body: String) {
+    let now = plant_now();
+    let chain = on_call_chain(&now);
+    if chain.is_empty() {
+        notify(event, subject, body);
+        return;
+    }
+    let created = with_escalations(|escalations| {
+        if escalations.contains_key(event) {
+            return None;
+        }
+        let escalation = Escalation {
+            id: format!("{}-{}", event, now.timestamp_millis()),
+            event: event.to_string(),
+            subject,
+            body,
+            chain,
+            level: 0,
+            notified_at: now.with_timezone(&Utc),
+        };
+        escalations.insert(event.to_string(), escalation.clone());
+        Some(escalation)
+    });
+    if let Some(escalation) = created {
+        notify_on_call(&escalation);
+    }
+}
+
+/// Cierra la escalación por id o por evento; devuelve si había una abierta.
+fn resolve_escalation(key: &str, source: &str, reason: &str) -> bool {
+    let resolved = with_escalations(|escalations| {
+        let event = escalations
+            .values()
+            .find(|escalation| escalation.id == key || escalation.event == key)
+            .map(|escalation| escalation.event.clone())?;
+        escalations.remove(&event)
+    });
+    match resolved {
+        Some(escalation) => {
+            info!(
+                "[ESCALATION] {} cerrada por {}: {}",
+                escalation.id, source, reason
+            );
+            record_audit(source, "escalation_acknowledged", &escalation.id, reason);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Escalaciones sin acuse dentro del plazo pasan al siguiente contacto de la cadena.
+fn advance_escalations(now: DateTime<Utc>) {
+    let timeout = chrono::Duration::minutes(app_config().on_call.ack_timeout_minutes.max(1) as i64);
+    let due: Vec<Escalation> = with_escalations(|escalations| {
+        escalations
+            .values_mut()
+            .filter(|escalation| now - escalation.notified_at >= timeout)
+            .map(|escalation| {
+                escalation.level += 1;
+                escalation.notified_at = now;
+                escalation.clone()
+            })
+            .collect()
+    });
+    for escalation in due {
+        warn!(
+            "[ESCALATION] {} sin acuse, se escala al nivel {}",
+            escalation.id,
+            escalation.level + 1
+        );
+        notify_on_call(&escalation);
+    }
+}
+
+fn start_escalation_loop() {
+    let cfg = &app_config().on_call;
+    if cfg.schedule.is_empty() && !cfg.sync_from_platform {
+        return;
+    }
+    supervise(
+        "escalations",
+        true,
+        Some(ESCALATION_CHECK_TICK),
+        RestartPolicy::Always,
+        move |task| async move {
+            while !is_shutting_down() {
+                tokio::time::sleep(ESCALATION_CHECK_TICK).await;
+                task.beat();
+                let now = corrected_now().with_timezone(&Utc);
+                let _ = async_runtime::spawn_blocking(move || advance_escalations(now)).await;
+            }
+        },
+    );
+}
+
+fn handle_on_call_schedule_value(value: &serde_json::Value) {
+    if !app_config().on_call.sync_from_platform {
+        return;
+    }
+    match serde_json::from_value::<Vec<OnCallShift>>(value.clone()) {
+        Ok(schedule) => {
+            info!(
+                "[ESCALATION] Guardias sincronizadas desde la plataforma: {}",
+                schedule.len()
+            );
+            record_audit(
+                "platform",
+                "on_call_schedule_synced",
+                "",
+                &format!("{} guardias", schedule.len()),
+            );
+            with_on_call_schedule(|current| *current = schedule);
+        }
+        Err(err) => warn!(
+            "[ESCALATION] Guardias inválidas desde la plataforma: {:?}",
+            err
+        ),
+    }
+}
+
+fn with_notification_routes<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Vec<NotificationRoute>) -> R,
+{
+    let routes = NOTIFICATION_ROUTES
+        .get_or_init(|| Mutex::new(app_config().notification_routing.routes.clone()));
+    let mut guard = routes
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Si la matriz de rutas deja notificar `severity` por `channel` ahora mismo.
+fn route_allows(severity: AlertSeverity, channel: NotificationChannel) -> bool {
+    let now = plant_now();
+    let route = with_notification_routes(|routes| {
+        routes
+            .iter()
+            .find(|route| route.matches(severity, channel, &now))
+            .cloned()
+    });
+    match route {
+        Some(route) if route.action == RouteAction::Suppress => {
+            debug!(
+                "[NOTIFY] {} por {} suprimida por la ruta {}",
+                serde_name(&severity),
+                serde_name(&channel),
+                route.name
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
+fn notification_route_problems(routes: &[NotificationRoute]) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    for (index, route) in routes.iter().enumerate() {
+        let name = if route.name.is_empty() {
+            format!("#{}", index + 1)
+        } else {
+            route.name.clone()
+        };
+        let invalid_time = [&route.start, &route.end]
+            .into_iter()
+            .any(|value| !value.trim().is_empty() && schedule::parse_hhmm(value).is_none());
+        if invalid_time || route.start.trim().is_empty() != route.end.trim().is_empty() {
+            problems.push(ConfigProblem::error(
+                "NOTIFICATION_ROUTING",
+                format!(
+                    "Horario inválido en la ruta {}: {}-{} (formato HH:MM, ambos o ninguno)",
+                    name, route.start, route.end
+                ),
+            ));
+        }
+        if route.days.iter().any(|day| !(1..=7).contains(day)) {
+            problems.push(ConfigProblem::error(
+                "NOTIFICATION_ROUTING",
+                format!(
+                    "Días inválidos en la ruta {} (1 = lunes … 7 = domingo)",
+                    name
+                ),
+            ));
+        }
+    }
+    problems
+}
+
+fn handle_notification_routes_value(value: &serde_json::Value) {
+    if !app_config().notification_routing.sync_from_platform {
+        return;
+    }
+    let routes = match serde_json::from_value::<Vec<NotificationRoute>>(value.clone()) {
+        Ok(routes) => routes,
+        Err(err) => {
+            warn!("[NOTIFY] Rutas inválidas desde la plataforma: {:?}", err);
+            return;
+        }
+    };
+    if let Some(problem) = notification_route_problems(&routes).first() {
+        warn!(
+            "[NOTIFY] Rutas rechazadas desde la plataforma: {}",
+            problem.message
+        );
+        return;
+    }
+    info!(
+        "[NOTIFY] Rutas de notificación sincronizadas desde la plataforma: {}",
+        routes.len()
+    );
+    record_audit(
+        "platform",
+        "notification_routes_synced",
+        "",
+        &format!("{} rutas", routes.len()),
+    );
+    with_notification_routes(|current| *current = routes);
+}
+
+#[tauri::command]
+fn get_notification_routes() -> Vec<NotificationRoute> {
+    with_notification_routes(|routes| routes.clone())
+}
+
+/// Reemplaza la matriz en caliente y la guarda en el YAML para el próximo arranque.
+#[tauri::command]
+async fn set_notification_routes(
+    window: tauri::Window,
+    routes: Vec<NotificationRoute>,
+) -> Result<Vec<NotificationRoute>, String> {
+    check_write_access(&window)?;
+    if let Some(problem) = notification_route_problems(&routes).first() {
+        return Err(problem.message.clone());
+    }
+    async_runtime::spawn_blocking(move || {
+        let mut draft = config_draft();
+        draft.notification_routing.routes = routes.clone();
+        write_config_file(Path::new(CONFIG_PATH), &draft)?;
+        info!(
+            "[NOTIFY] Rutas de notificación actualizadas: {}",
+            routes.len()
+        );
+        record_audit(
+            "local",
+            "notification_routes_updated",
+            window.label(),
+            &format!("{} rutas", routes.len()),
+        );
+        with_notification_routes(|current| *current = routes.clone());
+        Ok(routes)
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+#[tauri::command]
+fn get_escalations() -> Vec<EscalationStatus> {
+    with_escalations(|escalations| {
+        escalations
+            .values()
+            .map(|escalation| EscalationStatus {
+                id: escalation.id.clone(),
+                event: escalation.event.clone(),
+                subject: escalation.subject.clone(),
+                on_call: escalation.chain[escalation.level % escalation.chain.len()]
+                    .name
+                    .clone(),
+                level: escalation.level,
+                notified_at: escalation
+                    .notified_at
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+            })
+            .collect()
+    })
+}
+
+/// Guardias en el orden en que se escalaría ahora mismo.
+#[tauri::command]
+fn get_on_call_chain() -> Vec<OnCallShift> {
+    on_call_chain(&plant_now())
+}
+
+#[tauri::command]
+fn acknowledge_escalation(window: tauri::Window, id: String) -> Result<bool, String> {
+    check_write_access(&window)?;
+    Ok(resolve_escalation(&id, "local", window.label()))
+}
+
+fn print_ticket(ticket: escpos::Ticket) -> Result<(), String> {
+    let cfg = &app_config().printer;
+    let _printing = PRINTER_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(baud_rate) = cfg.baud_rate {
+        let output = Command::new("stty")
+            .args(["-F", &cfg.device, &baud_rate.to_string(), "raw", "-echo"])
+            .output()
+            .map_err(|err| format!("No se pudo ejecutar stty: {}", err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "stty devolvio codigo {:?}: {}",
+                output.status.code(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&cfg.device)
+        .and_then(|mut printer| printer.write_all(&ticket.finish()))
+        .map_err(|err| format!("No se pudo imprimir en {}: {}", cfg.device, err))
+}
+
+fn alert_ticket(alert: &Alert) -> escpos::Ticket {
+    let mut ticket = escpos::Ticket::new(app_config().printer.width);
+    ticket
+        .title(&serde_name(&alert.severity).to_uppercase())
+        .bold(&alert.device)
+        .text(&alert.description)
+        .separator()
+        .text(&format!("Tipo: {}", serde_name(&alert.alert_type)))
+        .text(&format!("Fecha: {}", alert.date_time))
+        .text(&format!("Panel: {}", panel_id()))
+        .text(&format!("Id: {}", alert.id));
+    ticket
+}
+
+fn printer_side_effect(event: &DomainEvent, _app_handle: &EventSink) {
+    let cfg = &app_config().printer;
+    let DomainEvent::AlertAdded(alert) = event else {
+        return;
+    };
+    if !cfg.enabled
+        || cfg.min_severity.is_none()
+        || !meets_severity(alert.severity, cfg.min_severity)
+    {
+        return;
+    }
+    let alert = alert.clone();
+    async_runtime::spawn_blocking(move || match print_ticket(alert_ticket(&alert)) {
+        Ok(()) => record_audit("local", "print_ticket", &alert.id, &alert.device),
+        Err(err) => warn!("[PRINTER] Ticket de {}: {}", alert.id, err),
+    });
+}
+
+fn print_handover_report(report: &HandoverReport) {
+    let cfg = &app_config().printer;
+    if !cfg.enabled || !cfg.handover {
+        return;
+    }
+    let mut ticket = escpos::Ticket::new(cfg.width);
+    ticket
+        .title("Entrega de turno")
+        .text(&render_handover_text(report));
+    match print_ticket(ticket) {
+        Ok(()) => record_audit("local", "print_ticket", "handover", &report.shift),
+        Err(err) => warn!("[PRINTER] Informe de turno {}: {}", report.shift, err),
+    }
+}
+
+/// Imprime un ticket de prueba para verificar conexión, página de códigos y ancho del papel.
+#[tauri::command]
+async fn print_test_ticket(window: tauri::Window) -> Result<(), String> {
+    check_write_access(&window)?;
+    if !app_config().printer.enabled {
+        return Err("Impresora deshabilitada (PRINTER.enabled)".to_string());
+    }
+    async_runtime::spawn_blocking(|| {
+        let width = app_config().printer.width;
+        let mut ticket = escpos::Ticket::new(width);
+        ticket
+            .title("Prueba")
+            .text(&format!("Panel {}", panel_id()))
+            .text(&format_local_ms(corrected_now().timestamp_millis()))
+            .text("Acentos: áéíóú ñÑ ¿¡ -18°C")
+            .separator()
+            .text(&"0123456789".repeat(width.div_ceil(10)));
+        print_ticket(ticket)
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OperatorSession {
+    operator_id: String,
+    name: String,
+    started_at_ms: i64,
+    #[serde(skip)]
+    started: Instant,
+    #[serde(skip)]
+    last_activity: Instant,
+    #[serde(skip)]
+    idle_warned: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SessionExpiredPayload {
+    operator_id: String,
+    reason: &'static str,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SessionIdleWarningPayload {
+    operator_id: String,
+    expires_in_secs: u64,
+}
+
+enum SessionTick {
+    IdleWarning(SessionIdleWarningPayload),
+    Expired(OperatorSession, &'static str),
+}
+
+fn with_operator_session<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Option<OperatorSession>) -> R,
+{
+    let session = OPERATOR_SESSION.get_or_init(|| Mutex::new(None));
+    let mut guard = session
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn emit_operator_session(app_handle: &EventSink) {
+    let session = with_operator_session(|session| session.clone());
+    if let Err(err) = app_handle.emit(OPERATOR_SESSION_EVENT, session) {
+        warn!("[BADGE] No se pudo emitir sesión: {:?}", err);
+    }
+}
+
+/// Últimos caracteres de la credencial, para el log sin exponer el código completo.
+fn mask_badge(code: &str) -> String {
+    let tail: Vec<char> = code.chars().rev().take(4).collect();
+    format!("…{}", tail.into_iter().rev().collect::<String>())
+}
+
+/// Una credencial desconocida se rechaza; la del operador en sesión la cierra y la de otro
+/// operador la reemplaza.
+fn handle_badge_scan(code: &str, app_handle: &EventSink) {
+    let code = code.trim();
+    let operator = app_config()
+        .operators
+        .iter()
+        .find(|operator| operator.badges.iter().any(|badge| badge.trim() == code))
+        .cloned();
+    let Some(operator) = operator else {
+        warn!("[BADGE] Credencial desconocida {}", mask_badge(code));
+        record_audit("badge", "badge_rejected", "", &mask_badge(code));
+        return;
+    };
+    if let Some(remaining) = panel_lock_remaining() {
+        warn!(
+            "[BADGE] Panel bloqueado por {:?}, credencial ignorada",
+            remaining
+        );
+        record_audit("badge", "login_rejected", &operator.id, "panel bloqueado");
+        return;
+    }
+    let same_operator = with_operator_session(|session| {
+        session
+            .as_ref()
+            .is_some_and(|session| session.operator_id == operator.id)
+    });
+    if same_operator {
+        end_operator_session("", app_handle);
+    } else {
+        start_operator_session(&operator, "badge", app_handle);
+    }
+}
+
+/// Abre la sesión de `operator`, cerrando antes la de otro operador si la hubiera.
+fn start_operator_session(operator: &OperatorEntry, source: &str, app_handle: &EventSink) {
+    let previous = with_operator_session(|session| {
+        session.replace(OperatorSession {
+            operator_id: operator.id.clone(),
+            name: operator.name.clone(),
+            started_at_ms: corrected_now().timestamp_millis(),
+            started: Instant::now(),
+            last_activity: Instant::now(),
+            idle_warned: false,
+        })
+    });
+    if let Some(previous) = previous {
+        info!("[BADGE] Sesión cerrada: {}", previous.operator_id);
+        record_audit(source, "operator_logout", &previous.operator_id, "");
+    }
+    info!("[BADGE] Sesión iniciada ({}): {}", source, operator.id);
+    record_audit(source, "operator_login", &operator.id, "");
+    emit_operator_session(app_handle);
+}
+
+fn end_operator_session(reason: &str, app_handle: &EventSink) {
+    let Some(previous) = with_operator_session(|session| session.take()) else {
+        return;
+    };
+    close_operator_session(&previous, reason, app_handle);
+}
+
+fn close_operator_session(previous: &OperatorSession, reason: &str, app_handle: &EventSink) {
+    info!(
+        "[BADGE] Sesión cerrada ({}): {}",
+        reason, previous.operator_id
+    );
+    record_audit("badge", "operator_logout", &previous.operator_id, reason);
+    emit_operator_session(app_handle);
+}
+
+/// Un toque en pantalla mantiene viva la sesión y anula el aviso de inactividad.
+fn touch_operator_session() {
+    with_operator_session(|session| {
+        if let Some(session) = session {
+            session.last_activity = Instant::now();
+            session.idle_warned = false;
+        }
+    });
+}
+
+/// Revisa la sesión abierta: la cierra si venció (duración máxima o inactividad tras el aviso) o
+/// marca el aviso de inactividad.
+fn operator_session_tick(now: Instant) -> Option<SessionTick> {
+    let timeout = app_config().badge_reader.session_timeout_minutes;
+    let idle = &app_config().session_idle;
+    with_operator_session(|slot| {
+        let session = slot.as_mut()?;
+        let idle_for = now.saturating_duration_since(session.last_activity);
+        let idle_after = Duration::from_secs(idle.idle_minutes * 60);
+        let grace = Duration::from_secs(idle.grace_secs);
+        let reason = if timeout > 0
+            && now.saturating_duration_since(session.started) >= Duration::from_secs(timeout * 60)
+        {
+            "timeout"
+        } else if idle.idle_minutes > 0 && idle_for >= idle_after + grace {
+            "idle"
+        } else if idle.idle_minutes > 0 && idle_for >= idle_after && !session.idle_warned {
+            session.idle_warned = true;
+            return Some(SessionTick::IdleWarning(SessionIdleWarningPayload {
+                operator_id: session.operator_id.clone(),
+                expires_in_secs: (idle_after + grace).saturating_sub(idle_for).as_secs(),
+            }));
+        } else {
+            return None;
+        };
+        slot.take()
+            .map(|session| SessionTick::Expired(session, reason))
+    })
+}
+
+fn handle_session_tick(tick: SessionTick, app_handle: &EventSink) {
+    match tick {
+        SessionTick::IdleWarning(payload) => {
+            info!(
+                "[BADGE] Sesión de {} inactiva, se cierra en {} s",
+                payload.operator_id, payload.expires_in_secs
+            );
+            if let Err(err) = app_handle.emit(SESSION_IDLE_WARNING_EVENT, payload) {
+                warn!("[BADGE] No se pudo emitir aviso de inactividad: {:?}", err);
+            }
+        }
+        SessionTick::Expired(previous, reason) => {
+            close_operator_session(&previous, reason, app_handle);
+            let payload = SessionExpiredPayload {
+                operator_id: previous.operator_id,
+                reason,
+            };
+            if let Err(err) = app_handle.emit(SESSION_EXPIRED_EVENT, payload) {
+                warn!("[BADGE] No se pudo emitir sesión vencida: {:?}", err);
+            }
+        }
+    }
+}
+
+fn badge_scan_buffer() -> badge::ScanBuffer {
+    let cfg = &app_config().badge_reader;
+    badge::ScanBuffer::new(Duration::from_millis(cfg.max_key_gap_ms), cfg.min_length)
+}
+
+/// Lee el lector por evdev; si el dispositivo falta o se desconecta, reintenta abrirlo.
+fn start_badge_reader(app_handle: EventSink) {
+    let cfg = &app_config().badge_reader;
+    if !cfg.enabled || cfg.device.is_empty() {
+        return;
+    }
+    let device = cfg.device.clone();
+    supervise_thread(
+        "badge-reader",
+        false,
+        RestartPolicy::OnPanic,
+        move |_task| {
+            while !is_shutting_down() {
+                if let Err(err) = read_badge_device(&device, &app_handle) {
+                    warn!("[BADGE] Lector {}: {}", device, err);
+                }
+                sleep_with_shutdown(BADGE_REOPEN_DELAY);
+            }
+        },
+    );
+}
+
+fn read_badge_device(device: &str, app_handle: &EventSink) -> Result<(), String> {
+    let mut file = fs::File::open(device).map_err(|err| format!("No se pudo abrir: {}", err))?;
+    info!("[BADGE] Leyendo credenciales desde {}", device);
+    let mut decoder = badge::KeyDecoder::default();
+    let mut buffer = badge_scan_buffer();
+    let mut raw = [0u8; badge::INPUT_EVENT_SIZE];
+    while !is_shutting_down() {
+        file.read_exact(&mut raw)
+            .map_err(|err| format!("Lectura interrumpida: {}", err))?;
+        let Some(key) = badge::decode_event(&raw).and_then(|event| decoder.feed(&event)) else {
+            continue;
+        };
+        if let Some(code) = buffer.push(key, Instant::now()) {
+            handle_badge_scan(&code, app_handle);
+        }
+    }
+    Ok(())
+}
+
+fn start_operator_session_loop(app_handle: EventSink) {
+    let cfg = app_config();
+    if cfg.operators.is_empty()
+        || (cfg.badge_reader.session_timeout_minutes == 0 && cfg.session_idle.idle_minutes == 0)
+    {
+        return;
+    }
+    supervise(
+        "operator-session",
+        false,
+        Some(OPERATOR_SESSION_TICK),
+        RestartPolicy::Always,
+        move |task| {
+            let app_handle = app_handle.clone();
+            async move {
+                while !is_shutting_down() {
+                    tokio::time::sleep(OPERATOR_SESSION_TICK).await;
+                    task.beat();
+                    if let Some(tick) = operator_session_tick(Instant::now()) {
+                        let app_handle = app_handle.clone();
+                        let _ = async_runtime::spawn_blocking(move || {
+                            handle_session_tick(tick, &app_handle)
+                        })
+                        .await;
+                    }
+                }
+            }
+        },
+    );
+}
+
+/// Modo cuña de teclado: el frontend reenvía cada `KeyboardEvent.key` y el backend junta las
+/// ráfagas del lector.
+#[tauri::command]
+fn badge_key(app_handle: tauri::AppHandle, key: String) {
+    let cfg = &app_config().badge_reader;
+    if !cfg.enabled || !cfg.device.is_empty() {
+        return;
+    }
+    let Some(key) = badge::ScanKey::from_dom(&key) else {
+        return;
+    };
+    let code = BADGE_WEDGE_BUFFER
+        .get_or_init(|| Mutex::new(badge_scan_buffer()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(key, Instant::now());
+    if let Some(code) = code {
+        handle_badge_scan(&code, &EventSink::App(app_handle));
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PanelLockStatus {
+    locked: bool,
+    locked_until_ms: Option<i64>,
+    attempts_left: u32,
+    lockouts: u32,
+}
+
+fn pin_lockout_policy() -> lockout::LockoutPolicy {
+    let cfg = &app_config().pin_lockout;
+    lockout::LockoutPolicy {
+        max_attempts: cfg.max_attempts,
+        base: Duration::from_secs(cfg.lockout_secs),
+        max: Duration::from_secs(cfg.max_lockout_secs),
+    }
+}
+
+fn with_panel_lockout<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut lockout::Lockout) -> R,
+{
+    let mut guard = PANEL_LOCKOUT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn panel_lock_remaining() -> Option<Duration> {
+    with_panel_lockout(|lockout| lockout.remaining(Instant::now()))
+}
+
+fn snapshot_panel_lock() -> PanelLockStatus {
+    let policy = pin_lockout_policy();
+    with_panel_lockout(|lockout| {
+        let remaining = lockout.remaining(Instant::now());
+        PanelLockStatus {
+            locked: remaining.is_some(),
+            locked_until_ms: remaining
+                .map(|remaining| corrected_now().timestamp_millis() + remaining.as_millis() as i64),
+            attempts_left: lockout.attempts_left(&policy),
+            lockouts: lockout.lockouts,
+        }
+    })
+}
+
+fn emit_panel_lock(app_handle: &EventSink) {
+    if let Err(err) = app_handle.emit(PANEL_LOCK_EVENT, snapshot_panel_lock()) {
+        warn!("[BADGE] No se pudo emitir estado de bloqueo: {:?}", err);
+    }
+}
+
+/// Valida el PIN del operador. Los fallos cuentan para el bloqueo aunque el operador no exista,
+/// para no revelar qué ids son válidos.
+fn login_with_pin(operator_id: &str, pin: &str, app_handle: &EventSink) -> Result<(), String> {
+    if let Some(remaining) = panel_lock_remaining() {
+        record_audit("pin", "login_rejected", operator_id, "panel bloqueado");
+        return Err(format!(
+            "Panel bloqueado, reintente en {} s",
+            remaining.as_secs().max(1)
+        ));
+    }
+    let digest = sha256_hex(pin.as_bytes());
+    let operator = app_config()
+        .operators
+        .iter()
+        .find(|operator| {
+            operator.id == operator_id
+                && !operator.pin_sha256.is_empty()
+                && operator.pin_sha256.eq_ignore_ascii_case(&digest)
+        })
+        .cloned();
+    if let Some(operator) = operator {
+        with_panel_lockout(lockout::Lockout::reset);
+        start_operator_session(&operator, "pin", app_handle);
+        emit_panel_lock(app_handle);
+        return Ok(());
+    }
+    let policy = pin_lockout_policy();
+    let (locked, attempts_left) = with_panel_lockout(|lockout| {
+        let locked = lockout.record_failure(Instant::now(), &policy);
+        (locked, lockout.attempts_left(&policy))
+    });
+    warn!("[BADGE] PIN incorrecto para {:?}", operator_id);
+    record_audit("pin", "pin_failed", operator_id, "");
+    emit_panel_lock(app_handle);
+    match locked {
+        Some(cooldown) => {
+            warn!("[BADGE] Panel bloqueado por {:?}", cooldown);
+            record_audit(
+                "pin",
+                "panel_locked",
+                "",
+                &format!("{} s", cooldown.as_secs()),
+            );
+            Err(format!(
+                "Demasiados intentos: panel bloqueado por {} s",
+                cooldown.as_secs()
+            ))
+        }
+        None => Err(format!("PIN incorrecto, quedan {} intentos", attempts_left)),
+    }
+}
+
+/// Levanta el bloqueo y reinicia la espera exponencial; devuelve si el panel estaba bloqueado.
+fn unlock_panel(source: &str, by: &str, app_handle: &EventSink) -> bool {
+    let was_locked = with_panel_lockout(|lockout| {
+        let was_locked = lockout.remaining(Instant::now()).is_some();
+        lockout.reset();
+        was_locked
+    });
+    info!("[BADGE] Panel desbloqueado por {} {:?}", source, by);
+    record_audit(source, "panel_unlocked", "", by);
+    emit_panel_lock(app_handle);
+    was_locked
+}
+
+#[tauri::command]
+fn pin_login(
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+    operator_id: String,
+    pin: String,
+) -> Result<(), String> {
+    check_write_access(&window)?;
+    login_with_pin(operator_id.trim(), &pin, &EventSink::App(app_handle))
+}
+
+#[tauri::command]
+fn get_panel_lock() -> PanelLockStatus {
+    snapshot_panel_lock()
+}
+
+#[tauri::command]
+fn get_operator_session() -> Option<OperatorSession> {
+    with_operator_session(|session| session.clone())
+}
+
+#[tauri::command]
+fn logout_operator(window: tauri::Window, app_handle: tauri::AppHandle) -> Result<(), String> {
+    check_write_access(&window)?;
+    end_operator_session("manual", &EventSink::App(app_handle));
+    Ok(())
+}
+
+/// Genera, guarda y (si se pide y hay destinatarios) envía por correo el informe de entrega de turno.
+#[tauri::command]
+async fn generate_handover_report(email: Option<bool>) -> Result<HandoverReport, String> {
+    async_runtime::spawn_blocking(move || {
+        let mut report = build_handover_report()?;
+        let path = store_handover_report(&report)?;
+        info!(
+            "[HANDOVER] Informe de turno {} guardado en {:?}",
+            report.shift, path
+        );
+        report.path = Some(path.to_string_lossy().into_owned());
+
+        let recipients = &app_config().handover_email_to;
+        if email.unwrap_or(false) && !recipients.is_empty() {
+            let fields = BTreeMap::from([
+                (
+                    "subject".to_string(),
+                    format!("Entrega de turno {}", report.shift),
+                ),
+                ("body".to_string(), render_handover_text(&report)),
+                ("shift".to_string(), report.shift.clone()),
+            ]);
+            let (subject, body) = compose_email("handover", &fields);
+            match queue_email(subject, body, recipients) {
+                Ok(()) => report.emailed = true,
+                Err(err) => warn!(
+                    "[HANDOVER] No se pudo encolar el informe por correo: {}",
+                    err
+                ),
+            }
+        }
+        record_audit("local", "handover_report", &report.shift, "");
+        print_handover_report(&report);
+        Ok(report)
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DeviceStatistics {
+    device: String,
+    from_ms: i64,
+    to_ms: i64,
+    alarm_count: usize,
+    alarms_per_day: f64,
+    out_of_range_ms: i64,
+    longest_excursion_ms: i64,
+    longest_excursion_start_ms: Option<i64>,
+}
+
+fn statistics_bounds(range: HistoryRange) -> (i64, i64) {
+    let to_ms = range
+        .to_ms
+        .unwrap_or_else(|| corrected_now().timestamp_millis());
+    let from_ms = range
+        .from_ms
+        .unwrap_or(to_ms - STATISTICS_DEFAULT_DAYS * 86_400_000);
+    (from_ms, to_ms)
+}
+
+/// Cantidad de alarmas del rango e intervalos de alarmas de temperatura (alta/baja) según el historial.
+fn history_excursions(
+    device: &str,
+    from_ms: i64,
+    to_ms: i64,
+) -> Result<(usize, Vec<(i64, i64)>), String> {
+    let rows: Vec<(String, String, i64, String)> = with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT alert_id, event, ts_ms, alert_type FROM alert_history
+             WHERE device = ?1 AND ts_ms <= ?2 AND event IN ('added', 'removed')
+             ORDER BY ts_ms, id",
+        )?;
+        let rows = stmt.query_map(params![device, to_ms], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?;
+        rows.collect()
+    })?;
+
+    let temperature_types = [
+        serde_name(&AlertType::TempUp),
+        serde_name(&AlertType::TempDown),
+    ];
+    let mut alarm_count = 0;
+    let mut open: HashMap<String, i64> = HashMap::new();
+    let mut intervals = Vec::new();
+    for (alert_id, event, ts_ms, alert_type) in rows {
+        if event == "added" {
+            if ts_ms >= from_ms {
+                alarm_count += 1;
+            }
+            if temperature_types.contains(&alert_type) {
+                open.insert(alert_id, ts_ms);
+            }
+        } else if let Some(start) = open.remove(&alert_id) {
+            intervals.push((start, ts_ms));
+        }
+    }
+    // Las que siguen abiertas sólo cuentan si la alerta continúa activa; si no, se perdió su cierre.
+    let active: Vec<String> = with_alert_store(|store| store.keys().cloned().collect());
+    intervals.extend(
+        open.into_iter()
+            .filter(|(alert_id, _)| active.contains(alert_id))
+            .map(|(_, start)| (start, to_ms)),
+    );
+    Ok((alarm_count, intervals))
+}
+
+/// Tramos de telemetría reciente fuera de los límites críticos, sin contar los deshielos.
+fn telemetry_excursions(device: &str, from_ms: i64, to_ms: i64) -> Vec<(i64, i64)> {
+    let (high, low) = device_limits(device);
+    let out_of_range =
+        |value: f64| high.is_some_and(|high| value > high) || low.is_some_and(|low| value < low);
+    let samples: Vec<TelemetrySample> = with_telemetry_buffer(|buffer| {
+        buffer
+            .get(device)
+            .map(|samples| {
+                samples
+                    .iter()
+                    .filter(|sample| {
+                        sample.ts_ms >= from_ms && sample.ts_ms <= to_ms && !sample.defrost
+                    })
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    });
+
+    let mut intervals = Vec::new();
+    let mut start: Option<i64> = None;
+    let mut last_out = 0;
+    for sample in samples {
+        if out_of_range(sample.value) {
+            start.get_or_insert(sample.ts_ms);
+            last_out = sample.ts_ms;
+        } else if let Some(begin) = start.take() {
+            intervals.push((begin, sample.ts_ms));
+        }
+    }
+    if let Some(begin) = start {
+        intervals.push((begin, last_out));
+    }
+    intervals
+}
+
+/// Une intervalos solapados para no contar dos veces el mismo tramo fuera de rango.
+fn merge_intervals(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::new();
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn compute_device_statistics(
+    device: &str,
+    range: HistoryRange,
+) -> Result<DeviceStatistics, String> {
+    let (from_ms, to_ms) = statistics_bounds(range);
+    let (alarm_count, mut intervals) = history_excursions(device, from_ms, to_ms)?;
+    intervals.extend(telemetry_excursions(device, from_ms, to_ms));
+    let excursions: Vec<(i64, i64)> = merge_intervals(
+        intervals
+            .into_iter()
+            .map(|(start, end)| (start.max(from_ms), end.min(to_ms)))
+            .filter(|(start, end)| end > start)
+            .collect(),
+    );
+    let longest = excursions.iter().max_by_key(|(start, end)| end - start);
+    let days = (to_ms - from_ms).max(1) as f64 / 86_400_000.0;
+
+    Ok(DeviceStatistics {
+        device: device.to_string(),
+        from_ms,
+        to_ms,
+        alarm_count,
+        alarms_per_day: (alarm_count as f64 / days * 100.0).round() / 100.0,
+        out_of_range_ms: excursions.iter().map(|(start, end)| end - start).sum(),
+        longest_excursion_ms: longest.map_or(0, |(start, end)| end - start),
+        longest_excursion_start_ms: longest.map(|(start, _)| *start),
+    })
+}
+
+fn history_devices(from_ms: i64, to_ms: i64) -> Result<Vec<String>, String> {
+    with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT device FROM alert_history WHERE ts_ms BETWEEN ?1 AND ?2 ORDER BY device",
+        )?;
+        let rows = stmt.query_map(params![from_ms, to_ms], |row| row.get(0))?;
+        rows.collect()
+    })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Equipos con alarmas en el rango, ordenados por tiempo fuera de rango (los que más mantenimiento piden primero).
+fn write_device_statistics_csv(range: HistoryRange) -> Result<PathBuf, String> {
+    let (from_ms, to_ms) = statistics_bounds(range);
+    let range = HistoryRange {
+        from_ms: Some(from_ms),
+        to_ms: Some(to_ms),
+    };
+    let mut statistics = history_devices(from_ms, to_ms)?
+        .iter()
+        .map(|device| compute_device_statistics(device, range))
+        .collect::<Result<Vec<_>, String>>()?;
+    statistics.sort_by_key(|stats| Reverse((stats.out_of_range_ms, stats.alarm_count)));
+
+    let mut csv = String::from(
+        "device,alarm_count,alarms_per_day,out_of_range_minutes,longest_excursion_minutes,longest_excursion_start\n",
+    );
+    for stats in &statistics {
+        csv.push_str(&format!(
+            "{},{},{},{:.1},{:.1},{}\n",
+            csv_field(&stats.device),
+            stats.alarm_count,
+            stats.alarms_per_day,
+            stats.out_of_range_ms as f64 / 60_000.0,
+            stats.longest_excursion_ms as f64 / 60_000.0,
+            stats
+                .longest_excursion_start_ms
+                .map(format_local_ms)
+                .unwrap_or_default()
+        ));
+    }
+
+    let dir = Path::new(&app_config().data_dir).join(REPORTS_DIR);
+    fs::create_dir_all(&dir).map_err(|err| format!("No se pudo crear {:?}: {:?}", dir, err))?;
+    let path = dir.join(format!(
+        "device-statistics-{}.csv",
+        corrected_now().format("%Y%m%d-%H%M")
+    ));
+    fs::write(&path, csv).map_err(|err| format!("No se pudo escribir {:?}: {:?}", path, err))?;
+    Ok(path)
+}
+
+/// Frecuencia de alarmas y tiempo fuera de rango de un equipo; por defecto los últimos 30 días.
+#[tauri::command]
+async fn get_device_statistics(
+    device: String,
+    range: Option<HistoryRange>,
+) -> Result<DeviceStatistics, String> {
+    async_runtime::spawn_blocking(move || {
+        compute_device_statistics(&device, range.unwrap_or_default())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Exporta a CSV las estadísticas de todos los equipos y devuelve la ruta del archivo.
+#[tauri::command]
+async fn export_device_statistics(range: Option<HistoryRange>) -> Result<String, String> {
+    async_runtime::spawn_blocking(move || {
+        write_device_statistics_csv(range.unwrap_or_default())
+            .map(|path| path.to_string_lossy().into_owned())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Una activación de alarma con su despeje (si ya ocurrió), para exportar como evento de calendario.
+struct AlarmOccurrence {
+    history_id: i64,
+    alert_id: String,
+    device: String,
+    severity: String,
+    alert_type: String,
+    description: String,
+    notes: Vec<String>,
+    start_ms: i64,
+    end_ms: Option<i64>,
+}
+
+/// Activaciones que se solapan con el rango; las que siguen abiertas quedan sin fin.
+fn alarm_occurrences(from_ms: i64, to_ms: i64) -> Result<Vec<AlarmOccurrence>, String> {
+    type Row = (
+        i64,
+        String,
+        String,
+        i64,
+        String,
+        String,
+        String,
+        String,
+        String,
+    );
+    let rows: Vec<Row> = with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, alert_id, event, ts_ms, alert_type, severity, device, description, notes
+             FROM alert_history WHERE ts_ms <= ?1 AND event IN ('added', 'removed')
+             ORDER BY ts_ms, id",
+        )?;
+        let rows = stmt.query_map(params![to_ms], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+                row.get(7)?,
+                row.get(8)?,
+            ))
+        })?;
+        rows.collect()
+    })?;
+
+    let mut open: HashMap<String, AlarmOccurrence> = HashMap::new();
+    let mut occurrences = Vec::new();
+    for (id, alert_id, event, ts_ms, alert_type, severity, device, description, notes) in rows {
+        let notes = (!notes.is_empty()).then_some(notes);
+        if event == "added" {
+            let occurrence = AlarmOccurrence {
+                history_id: id,
+                alert_id: alert_id.clone(),
+                device,
+                severity,
+                alert_type,
+                description,
+                notes: notes.into_iter().collect(),
+                start_ms: ts_ms,
+                end_ms: None,
+            };
+            // Activación repetida sin despeje registrado: la anterior se cierra donde empieza esta.
+            if let Some(mut previous) = open.insert(alert_id, occurrence) {
+                previous.end_ms = Some(ts_ms);
+                occurrences.push(previous);
+            }
+        } else if let Some(mut occurrence) = open.remove(&alert_id) {
+            occurrence.end_ms = Some(ts_ms);
+            occurrence.notes.extend(notes);
+            occurrences.push(occurrence);
+        }
+    }
+    occurrences.extend(open.into_values());
+    occurrences.retain(|occurrence| occurrence.end_ms.is_none_or(|end| end >= from_ms));
+    occurrences.sort_by_key(|occurrence| (occurrence.start_ms, occurrence.history_id));
+    Ok(occurrences)
+}
+
+/// Escapa un valor TEXT de iCal (RFC 5545 §3.3.11).
+fn ical_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn ical_utc(ts_ms: i64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(ts_ms)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// Agrega la línea plegada a 75 octetos sin partir caracteres UTF-8.
+fn push_ical_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for ch in line.chars() {
+        if width + ch.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(ch);
+        width += ch.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+fn render_alarm_ical(occurrences: &[AlarmOccurrence], generated_ms: i64) -> String {
+    let panel = panel_id();
+    let mut ics = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//nxt-hmi//Alarmas//ES",
+        "CALSCALE:GREGORIAN",
+        "METHOD:PUBLISH",
+    ] {
+        push_ical_line(&mut ics, line);
+    }
+    push_ical_line(
+        &mut ics,
+        &format!("X-WR-CALNAME:{}", ical_text(&format!("Alarmas {}", panel))),
+    );
+    for occurrence in occurrences {
+        let state = if occurrence.end_ms.is_some() {
+            "despejada"
+        } else {
+            "activa"
+        };
+        let mut description = format!(
+            "Alarma {} ({})\nTipo: {}\nSeveridad: {}\nEstado: {}",
+            occurrence.alert_id,
+            occurrence.device,
+            occurrence.alert_type,
+            occurrence.severity,
+            state
+        );
+        for note in &occurrence.notes {
+            description.push_str(&format!("\nNota: {}", note));
+        }
+        push_ical_line(&mut ics, "BEGIN:VEVENT");
+        push_ical_line(
+            &mut ics,
+            &format!("UID:alarm-{}@{}", occurrence.history_id, ical_text(panel)),
+        );
+        push_ical_line(&mut ics, &format!("DTSTAMP:{}", ical_utc(generated_ms)));
+        push_ical_line(
+            &mut ics,
+            &format!("DTSTART:{}", ical_utc(occurrence.start_ms)),
+        );
+        if let Some(end_ms) = occurrence.end_ms {
+            push_ical_line(&mut ics, &format!("DTEND:{}", ical_utc(end_ms)));
+        }
+        push_ical_line(
+            &mut ics,
+            &format!(
+                "SUMMARY:{}",
+                ical_text(&format!(
+                    "[{}] {}: {}",
+                    occurrence.severity.to_uppercase(),
+                    occurrence.device,
+                    occurrence.description
+                ))
+            ),
+        );
+        push_ical_line(
+            &mut ics,
+            &format!("DESCRIPTION:{}", ical_text(&description)),
+        );
+        push_ical_line(
+            &mut ics,
+            &format!("CATEGORIES:{}", ical_text(&occurrence.severity)),
+        );
+        push_ical_line(&mut ics, "TRANSP:TRANSPARENT");
+        push_ical_line(&mut ics, "END:VEVENT");
+    }
+    push_ical_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Activaciones y despejes del rango como eventos iCal, en el directorio de reportes.
+fn write_alarm_ical(range: HistoryRange) -> Result<PathBuf, String> {
+    let (from_ms, to_ms) = statistics_bounds(range);
+    let occurrences = alarm_occurrences(from_ms, to_ms)?;
+    let ics = render_alarm_ical(&occurrences, corrected_now().timestamp_millis());
+
+    let dir = Path::new(&app_config().data_dir).join(REPORTS_DIR);
+    fs::create_dir_all(&dir).map_err(|err| format!("No se pudo crear {:?}: {:?}", dir, err))?;
+    let path = dir.join(format!(
+        "alarmas-{}.ics",
+        corrected_now().format("%Y%m%d-%H%M")
+    ));
+    fs::write(&path, ics).map_err(|err| format!("No se pudo escribir {:?}: {:?}", path, err))?;
+    info!(
+        "[HISTORY] {} alarmas exportadas a {:?}",
+        occurrences.len(),
+        path
+    );
+    Ok(path)
+}
+
+/// Exporta las alarmas del rango (por defecto los últimos 30 días) a un .ics y devuelve la ruta.
+#[tauri::command]
+async fn export_ical(range: Option<HistoryRange>) -> Result<String, String> {
+    async_runtime::spawn_blocking(move || {
+        write_alarm_ical(range.unwrap_or_default()).map(|path| path.to_string_lossy().into_owned())
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))?
+}
+
+/// Cambio histórico reproducido; los eventos `playback://` nunca se mezclan con los de la operación en vivo.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PlaybackFrame {
+    /// Siempre `true`, para que ninguna vista lo confunda con estado real.
+    playback: bool,
+    session: u64,
+    ts_ms: i64,
+    #[serde(flatten)]
+    change: PlaybackChange,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum PlaybackChange {
+    Alert(HistoryEntry),
+    Telemetry {
+        device: String,
+        value: f64,
+        defrost: bool,
+    },
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PlaybackState {
+    playback: bool,
+    session: u64,
+    active: bool,
+    from_ms: i64,
+    to_ms: i64,
+    speed: f64,
+    /// Alertas que ya estaban activas al comienzo del rango.
+    initial_alerts: Vec<HistoryEntry>,
+    frames: usize,
+}
+
+/// Último registro de cada alerta anterior a `ts_ms` que no sea un despeje.
+fn history_state_at(ts_ms: i64) -> Result<Vec<HistoryEntry>, String> {
+    with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, alert_id, event, ts_ms, alert_type, severity,
+                    acknowledged, device, description, notes, alarm_id
+             FROM alert_history
+             WHERE id IN (SELECT max(id) FROM alert_history WHERE ts_ms < ?1 GROUP BY alert_id)
+               AND event != 'removed'
+             ORDER BY ts_ms",
+        )?;
+        let rows = stmt.query_map(params![ts_ms], history_entry)?;
+        rows.collect()
+    })
+}
+
+fn history_entries_between(from_ms: i64, to_ms: i64) -> Result<Vec<HistoryEntry>, String> {
+    with_history_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, alert_id, event, ts_ms, alert_type, severity,
+                    acknowledged, device, description, notes, alarm_id
+             FROM alert_history WHERE ts_ms BETWEEN ?1 AND ?2 ORDER BY ts_ms, id",
+        )?;
+        let rows = stmt.query_map(params![from_ms, to_ms], history_entry)?;
+        rows.collect()
+    })
+}
+
+/// Cambios de alertas del historial y muestras de telemetría aún en memoria, en orden cronológico.
+fn playback_frames(session: u64, from_ms: i64, to_ms: i64) -> Result<Vec<PlaybackFrame>, String> {
+    let frame = |ts_ms, change| PlaybackFrame {
+        playback: true,
+        session,
+        ts_ms,
+        change,
+    };
+    let mut frames: Vec<PlaybackFrame> = history_entries_between(from_ms, to_ms)?
+        .into_iter()
+        .map(|entry| frame(entry.ts_ms, PlaybackChange::Alert(entry)))
+        .collect();
+    with_telemetry_buffer(|buffer| {
+        for (device, samples) in buffer.iter() {
+            for sample in samples
+                .iter()
+                .filter(|sample| sample.ts_ms >= from_ms && sample.ts_ms <= to_ms)
+            {
+                frames.push(frame(
+                    sample.ts_ms,
+                    PlaybackChange::Telemetry {
+                        device: device.clone(),
+                        value: sample.value,
+                        defrost: sample.defrost,
+                    },
+                ));
+            }
+        }
+    });
+    frames.sort_by_key(|frame| frame.ts_ms);
+    Ok(frames)
+}
+
+fn emit_playback_state(app_handle: &EventSink, state: &PlaybackState) {
+    if let Err(err) = app_handle.emit(PLAYBACK_STATE_EVENT, state) {
+        warn!("[PLAYBACK] No se pudo emitir estado: {:?}", err);
+    }
+}
+
+/// Reproduce el rango a `speed` veces el tiempo real como eventos `playback://frame`.
+/// Iniciar otra reproducción o llamar a `stop_playback` corta la anterior.
+#[tauri::command]
+async fn playback(
+    app_handle: tauri::AppHandle,
+    range: HistoryRange,
+    speed: f64,
+) -> Result<PlaybackState, String> {
+    if !(speed > 0.0 && speed <= PLAYBACK_MAX_SPEED) {
+        return Err(format!(
+            "Velocidad fuera de rango (0-{}): {}",
+            PLAYBACK_MAX_SPEED, speed
+        ));
+    }
+    let from_ms = range.from_ms.ok_or("Falta el inicio del rango")?;
+    let to_ms = range
+        .to_ms
+        .unwrap_or_else(|| corrected_now().timestamp_millis());
+    if to_ms <= from_ms {
+        return Err("El rango termina antes de empezar".to_string());
+    }
+
+    let session = PLAYBACK_SESSION.fetch_add(1, Ordering::SeqCst) + 1;
+    let (initial_alerts, frames) = async_runtime::spawn_blocking(move || {
+        Ok::<_, String>((
+            history_state_at(from_ms)?,
+            playback_frames(session, from_ms, to_ms)?,
+        ))
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))??;
+    let mut state = PlaybackState {
+        playback: true,
+        session,
+        active: true,
+        from_ms,
+        to_ms,
+        speed,
+        initial_alerts,
+        frames: frames.len(),
+    };
+    info!(
+        "[PLAYBACK] Sesión {}: {} cambios a x{}",
+        session,
+        frames.len(),
+        speed
+    );
+    record_audit(
+        "local",
+        "playback",
+        "",
+        &format!(
+            "{} - {} x{}",
+            format_local_ms(from_ms),
+            format_local_ms(to_ms),
+            speed
+        ),
+    );
+
+    let sink = EventSink::App(app_handle);
+    emit_playback_state(&sink, &state);
+    let result = state.clone();
+    async_runtime::spawn(async move {
+        let _task = track_task("playback", TaskKind::Timer, false, None);
+        let mut previous_ms = from_ms;
+        for frame in frames {
+            let gap_ms = (frame.ts_ms - previous_ms).max(0) as f64 / speed;
+            tokio::time::sleep(Duration::from_millis(gap_ms as u64).min(PLAYBACK_MAX_GAP)).await;
+            if is_shutting_down() || PLAYBACK_SESSION.load(Ordering::SeqCst) != session {
+                return;
+            }
+            previous_ms = frame.ts_ms;
+            if let Err(err) = sink.emit(PLAYBACK_FRAME_EVENT, &frame) {
+                warn!("[PLAYBACK] No se pudo emitir cuadro: {:?}", err);
+            }
+        }
+        state.active = false;
+        state.initial_alerts.clear();
+        emit_playback_state(&sink, &state);
+    });
+    Ok(result)
+}
+
+#[tauri::command]
+fn stop_playback(app_handle: tauri::AppHandle) {
+    let session = PLAYBACK_SESSION.fetch_add(1, Ordering::SeqCst);
+    emit_playback_state(
+        &EventSink::App(app_handle),
+        &PlaybackState {
+            playback: true,
+            session,
+            active: false,
+            from_ms: 0,
+            to_ms: 0,
+            speed: 0.0,
+            initial_alerts: Vec::new(),
+            frames: 0,
+        },
+    );
+}
+
+/// `due_at` es cuando se pide la confirmación; vencido `GRACE_MINUTES` sin respuesta se escala.
+#[derive(Debug, Default)]
+struct PresenceCheck {
+    due_at: Option<DateTime<Utc>>,
+    prompted: bool,
+    escalations: u32,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct PresenceStatus {
+    required: bool,
+    prompted: bool,
+    due_at: Option<String>,
+    due_at_ms: Option<i64>,
+    deadline: Option<String>,
+    deadline_ms: Option<i64>,
+    escalations: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PresenceTransition {
+    Started,
+    Prompted,
+    Missed,
+    Cleared,
+}
+
+fn with_presence_check<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut PresenceCheck) -> R,
+{
+    let check = PRESENCE_CHECK.get_or_init(|| Mutex::new(PresenceCheck::default()));
+    let mut guard = check
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn presence_interval() -> chrono::Duration {
+    chrono::Duration::minutes(app_config().presence_check.interval_minutes.max(1) as i64)
+}
+
+fn presence_grace() -> chrono::Duration {
+    chrono::Duration::minutes(app_config().presence_check.grace_minutes.max(1) as i64)
+}
+
+fn snapshot_presence_status() -> PresenceStatus {
+    with_presence_check(|check| PresenceStatus {
+        required: check.due_at.is_some(),
+        prompted: check.prompted,
+        due_at: check
+            .due_at
+            .map(|due_at| due_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        due_at_ms: check.due_at.map(|due_at| due_at.timestamp_millis()),
+        deadline: check
+            .due_at
+            .map(|due_at| (due_at + presence_grace()).to_rfc3339_opts(SecondsFormat::Secs, true)),
+        deadline_ms: check
+            .due_at
+            .map(|due_at| (due_at + presence_grace()).timestamp_millis()),
+        escalations: check.escalations,
+    })
+}
+
+fn has_critical_alert() -> bool {
+    with_alert_store(|store| {
+        store
+            .values()
+            .any(|alert| alert.severity == AlertSeverity::Critical)
+    })
+}
+
+fn presence_tick(now: DateTime<Utc>) -> Option<PresenceTransition> {
+    let critical = has_critical_alert();
+    with_presence_check(|check| match check.due_at {
+        None if critical => {
+            check.due_at = Some(now + presence_interval());
+            Some(PresenceTransition::Started)
+        }
+        None => None,
+        Some(_) if !critical => {
+            *check = PresenceCheck::default();
+            Some(PresenceTransition::Cleared)
+        }
+        Some(due_at) if !check.prompted && now >= due_at => {
+            check.prompted = true;
+            Some(PresenceTransition::Prompted)
+        }
+        Some(due_at) if check.prompted && now >= due_at + presence_grace() => {
+            // Se reinicia el plazo de gracia: mientras nadie confirme, se vuelve a escalar.
+            check.due_at = Some(now);
+            check.escalations += 1;
+            Some(PresenceTransition::Missed)
+        }
+        _ => None,
+    })
+}
+
+fn emit_presence_status(app_handle: &EventSink) {
+    if let Err(err) = app_handle.emit(PRESENCE_EVENT, snapshot_presence_status()) {
+        warn!("[PRESENCE] No se pudo emitir estado: {:?}", err);
+    }
+}
+
+fn handle_presence_transition(transition: PresenceTransition, app_handle: &EventSink) {
+    let status = snapshot_presence_status();
+    match transition {
+        PresenceTransition::Started => {
+            info!("[PRESENCE] Alertas CRITICAL activas, confirmación requerida");
+            record_audit("local", "presence_check_started", "", "");
+        }
+        PresenceTransition::Prompted => {
+            info!("[PRESENCE] Solicitando confirmación de presencia");
+            record_audit("local", "presence_prompt", "", "");
+        }
+        PresenceTransition::Missed => {
+            record_audit(
+                "local",
+                "presence_missed",
+                "",
+                &format!("escalamiento {}", status.escalations),
+            );
+            escalate(
+                "presence_missed",
+                "Sin confirmación de presencia con alertas CRITICAL activas".to_string(),
+                format!(
+                    "Panel {}: nadie confirmó presencia (escalamiento {}). Alertas CRITICAL activas: {}",
+                    panel_id(),
+                    status.escalations,
+                    snapshot_alerts()
+                        .iter()
+                        .filter(|alert| alert.severity == AlertSeverity::Critical)
+                        .map(|alert| format!("{} ({})", alert.device, alert.description))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+        PresenceTransition::Cleared => {
+            info!("[PRESENCE] Sin alertas CRITICAL, confirmación no requerida");
+            record_audit("local", "presence_check_cleared", "", "");
+            resolve_escalation("presence_missed", "local", "sin alertas CRITICAL");
+        }
+    }
+    emit_presence_status(app_handle);
+}
+
+fn start_presence_loop(app_handle: EventSink) {
+    if !app_config().presence_check.enabled {
+        return;
+    }
+    supervise(
+        "presence-check",
+        true,
+        Some(PRESENCE_CHECK_TICK),
+        RestartPolicy::Always,
+        move |task| {
+            let app_handle = app_handle.clone();
+            async move {
+                while !is_shutting_down() {
+                    tokio::time::sleep(PRESENCE_CHECK_TICK).await;
+                    task.beat();
+                    let now = corrected_now().with_timezone(&Utc);
+                    if let Some(transition) = presence_tick(now) {
+                        let app_handle = app_handle.clone();
+                        let _ = async_runtime::spawn_blocking(move || {
+                            handle_presence_transition(transition, &app_handle)
+                        })
+                        .await;
+                    }
+                }
+            }
+        },
+    );
+}
+
+#[tauri::command]
+fn get_presence_status() -> PresenceStatus {
+    snapshot_presence_status()
+}
+
+/// El operador confirma que está presente; reinicia el plazo y cancela el escalamiento en curso.
+#[tauri::command]
+fn confirm_presence(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<PresenceStatus, String> {
+    check_write_access(&window)?;
+    let now = corrected_now().with_timezone(&Utc);
+    let previous = with_presence_check(|check| {
+        let due_at = check.due_at?;
+        let previous = (due_at, check.prompted, check.escalations);
+        check.due_at = Some(now + presence_interval());
+        check.prompted = false;
+        check.escalations = 0;
+        Some(previous)
+    });
+    if let Some((due_at, prompted, escalations)) = previous {
+        let late_secs = (now - due_at).num_seconds().max(0);
+        record_audit(
+            "local",
+            "presence_confirmed",
+            window.label(),
+            &format!(
+                "solicitada: {}, escalamientos: {}, demora: {} s",
+                prompted, escalations, late_secs
+            ),
+        );
+        resolve_escalation("presence_missed", "local", "presencia confirmada");
+        emit_presence_status(&EventSink::App(app_handle));
+    }
+    Ok(snapshot_presence_status())
+}
+
+/// El frontend informa toques de pantalla (agrupados) para las métricas de uso y para mantener
+/// abierta la sesión del operador.
+#[tauri::command]
+fn report_interaction(count: Option<u32>) {
+    with_interaction_metrics(|metrics| {
+        metrics.touches += u64::from(count.unwrap_or(1));
+    });
+    touch_operator_session();
+}
+
+#[tauri::command]
+fn get_buzzer_inhibit() -> BuzzerInhibitStatus {
+    snapshot_buzzer_inhibit()
+}
+
+#[tauri::command]
+fn get_audio_profile() -> AudioProfileStatus {
+    snapshot_audio_profile()
+}
+
+/// Fija (o con `null` retira) el perfil de audio local; sólo para supervisores.
+#[tauri::command]
+async fn set_audio_profile_override(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+    profile: Option<AudioProfile>,
+) -> Result<AudioProfileStatus, String> {
+    check_write_access(&window)?;
+    let sink = EventSink::App(app_handle);
+    async_runtime::spawn_blocking(move || set_local_audio_profile(profile, &sink))
+        .await
+        .map_err(|err| format!("{:?}", err))?
+}
+
+/// Anulación local: el operador puede cancelar una inhibición remota.
+#[tauri::command]
+async fn clear_buzzer_inhibit_local(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<BuzzerInhibitStatus, String> {
+    check_write_access(&window)?;
+    let sink = EventSink::App(app_handle);
+    async_runtime::spawn_blocking(move || {
+        clear_buzzer_inhibit("local", &sink);
+        snapshot_buzzer_inhibit()
+    })
+    .await
+    .map_err(|err| format!("{:?}", err))
+}
+
+#[tauri::command]
+async fn check_internet_connection() -> bool {
+    async_runtime::spawn_blocking(has_internet)
+        .await
+        .unwrap_or(false)
+}
+
+fn has_internet() -> bool {
+    internet_state() == ConnectivityState::Online
+}
+
+/// Carrera entre todas las sondas TCP (en una red sólo IPv6 gana la IPv6) y luego, si está
+/// configurada, la URL de portal: detrás de muchos portales el puerto 53 igual conecta.
+fn internet_state() -> ConnectivityState {
+    let cfg = &app_config().network;
+    let addrs: Vec<_> = cfg
+        .connectivity_probes
+        .iter()
+        .filter_map(|probe| match probe.to_socket_addrs() {
+            Ok(addrs) => Some(addrs),
+            Err(err) => {
+                debug!("[NET] Sonda {} no resuelve: {}", probe, err);
+                None
+            }
+        })
+        .flatten()
+        .collect();
+    let addrs = network::order_addresses(addrs, cfg.ip_preference);
+    if network::connect_first(&addrs, CONNECTIVITY_PROBE_TIMEOUT).is_err() {
+        return ConnectivityState::NoInternet;
+    }
+    if cfg.captive_portal_url.is_empty() {
+        return ConnectivityState::Online;
+    }
+    check_captive_portal(&cfg.captive_portal_url).0
+}
+
+#[tauri::command]
+fn get_mute_status() -> MuteStatePayload {
+    snapshot_mute_state()
+}
+
+#[tauri::command]
+fn get_clock_skew() -> ClockSkewStatus {
+    snapshot_clock_skew()
+}
+
+#[tauri::command]
+async fn toggle_alerts_mute(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<MuteStatePayload, String> {
+    check_write_access(&window)?;
+    let sink = EventSink::App(app_handle);
+    match async_runtime::spawn_blocking(move || toggle_alerts_mute_blocking(&sink, "local")).await {
+        Ok(payload) => Ok(payload),
+        Err(err) => {
+            error!("[MUTE] Fallo al alternar silencio: {:?}", err);
+            Ok(snapshot_mute_state())
+        }
+    }
+}
+
+/// `source` es el origen que queda en la auditoría (`local`, `grpc`).
+fn toggle_alerts_mute_blocking(app_handle: &EventSink, source: &str) -> MuteStatePayload {
+    let currently_muted = with_mute_controller(|ctrl| ctrl.muted);
+
+    if currently_muted {
+        force_unmute(app_handle);
+        record_audit(source, "unmute", "", "");
+        broadcast_peer_action(PeerAction::Unmute, None);
+        snapshot_mute_state()
+    } else {
+        if !has_active_alerts() {
+            return snapshot_mute_state();
+        }
+        let payload = mute_alerts_internal(app_handle);
+        record_audit(source, "mute", "", "");
+        record_mute_metric();
+        record_acknowledgement(None);
+        broadcast_peer_action(PeerAction::Mute, None);
+        payload
+    }
+}
+
+fn invalidate_buzzer_line(gpio_name: &str) {
+    if let Some(cache) = BUZZER_GPIO_CACHE.get() {
+        let mut guard = cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard.remove(gpio_name);
+    }
+}
+
+/// Compatibles del devicetree más los textos imprimibles de la EEPROM de la placa, si está configurada.
+fn board_identifiers(cfg: &AppConfig) -> Vec<String> {
+    let mut ids: Vec<String> = fs::read(DEVICE_TREE_COMPATIBLE_PATH)
+        .map(|bytes| {
+            bytes
+                .split(|byte| *byte == 0)
+                .filter(|id| !id.is_empty())
+                .map(|id| String::from_utf8_lossy(id).into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if !cfg.board_eeprom_path.is_empty() {
+        let mut bytes = Vec::new();
+        match fs::File::open(&cfg.board_eeprom_path)
+            .and_then(|file| file.take(BOARD_EEPROM_READ_BYTES).read_to_end(&mut bytes))
+        {
+            Ok(_) => ids.extend(
+                bytes
+                    .split(|byte| !byte.is_ascii_graphic() && *byte != b' ')
+                    .map(|run| String::from_utf8_lossy(run).trim().to_string())
+                    .filter(|run| run.len() >= BOARD_EEPROM_MIN_STRING),
+            ),
+            Err(err) => warn!(
+                "[HW] No se pudo leer EEPROM {}: {:?}",
+                cfg.board_eeprom_path, err
+            ),
+        }
+    }
+
+    ids
+}
+
+fn detect_hardware_profile(cfg: &AppConfig) -> HardwareProfile {
+    let ids = board_identifiers(cfg);
+    if let Some(profile) = cfg.hardware_profiles.iter().find(|profile| {
+        profile
+            .match_ids
+            .iter()
+            .any(|match_id| ids.iter().any(|id| id == match_id))
+    }) {
+        return profile.clone();
+    }
+
+    cfg.hardware_profiles
+        .iter()
+        .find(|profile| profile.name == cfg.hardware_profile)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn hardware_profile() -> &'static HardwareProfile {
+    HARDWARE_PROFILE.get_or_init(|| {
+        let profile = detect_hardware_profile(app_config());
+        info!(
+            "[HW] Perfil de hardware: {} (buzzer {})",
+            profile.name, profile.buzzer_gpio
+        );
+        profile
+    })
+}
+
+/// La línea GPIO o el chip PWM de la salida existen en esta placa.
+fn probe_signal_output(output: &SignalOutput) -> Result<(), String> {
+    match &output.pwm {
+        Some(pwm) if !pwm.chip.is_dir() => Err(format!("No existe el chip PWM {:?}", pwm.chip)),
+        Some(_) => Ok(()),
+        None => find_buzzer_line(&output.gpio).map(|_| ()),
+    }
+}
+
+/// Paso de arranque del hardware: alcanza con que responda una salida habilitada.
+fn probe_hardware() -> Result<(), String> {
+    let outputs: Vec<SignalOutput> = signal_outputs_for(app_config(), hardware_profile())
+        .into_iter()
+        .filter(|output| output.enabled)
+        .collect();
+    let mut errors = Vec::new();
+    for output in &outputs {
+        match probe_signal_output(output) {
+            Ok(()) => return Ok(()),
+            Err(err) => errors.push(format!("{}: {}", output.name, err)),
+        }
+    }
+    if outputs.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+fn find_buzzer_line(gpio_name: &str) -> Result<(String, String), String> {
+    let gpiofind_output = Command::new("gpiofind")
+        .arg(gpio_name)
+        .output()
+        .map_err(|err| format!("No se pudo ejecutar gpiofind: {:?}", err))?;
+
+    if !gpiofind_output.status.success() {
+        return Err(format!(
+            "gpiofind devolvio codigo {:?}: {}",
+            gpiofind_output.status.code(),
+            String::from_utf8_lossy(&gpiofind_output.stderr).trim()
+        ));
+    }
+
+    let location = String::from_utf8_lossy(&gpiofind_output.stdout).to_string();
+    let mut parts = location.split_whitespace();
+    let chip = parts
+        .next()
+        .map(|chip| chip.trim().to_string())
+        .ok_or("gpiofind no entrego chip valido")?;
+    let line = parts
+        .next()
+        .map(|line| line.trim().to_string())
+        .ok_or("gpiofind no entrego linea valida")?;
+
+    Ok((chip, line))
+}
+
+fn resolve_buzzer_line(gpio_name: &str) -> Option<(String, String)> {
+    let cache = buzzer_gpio_cache();
+    if let Some(pair) = cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(gpio_name)
+        .cloned()
+    {
+        return Some(pair);
+    }
+
+    let pair = match find_buzzer_line(gpio_name) {
+        Ok(pair) => pair,
+        Err(err) => {
+            error!("[BUZZER] {}: {}", gpio_name, err);
+            return None;
+        }
+    };
+
+    let mut guard = cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    guard.insert(gpio_name.to_string(), pair.clone());
+    Some(pair)
+}
+
+fn severity_pattern(severity: AlertSeverity) -> BuzzerPattern {
+    let (on_ms, off_ms) = match severity {
+        AlertSeverity::Critical => (1000, 1000),
+        AlertSeverity::Major => (1000, 2000),
+        AlertSeverity::Minor | AlertSeverity::Indeterminate => (500, 4500),
+        AlertSeverity::Warning => (200, 9800),
+    };
+    BuzzerPattern::Blink {
+        on: Duration::from_millis(on_ms),
+        off: Duration::from_millis(off_ms),
+    }
+}
+
+/// Alerta que gobierna el buzzer: la de mayor severidad sin reconocer.
+fn highest_audible_severity() -> Option<AlertSeverity> {
+    with_alert_store(|store| {
+        store
+            .values()
+            .filter(|alert| !alert.acknowledged)
+            .map(|alert| alert.severity)
+            .max_by_key(|severity| severity.rank())
+    })
+}
+
+/// Como `highest_audible_severity` pero sólo con alertas de equipos de `zones` o sin zona; con
+/// `CRITICAL_SOUNDS_ALL_ZONES` las CRITICAL cuentan en cualquier zona.
+fn highest_audible_severity_in(zones: &[String]) -> Option<AlertSeverity> {
+    if zones.is_empty() {
+        return highest_audible_severity();
+    }
+    let (local, zoned) = with_zones(|all| {
+        let mut local = HashSet::new();
+        let mut zoned = HashSet::new();
+        for zone in all.iter() {
+            if zones.contains(&zone.id) {
+                local.extend(zone.devices.iter().cloned());
+            }
+            zoned.extend(zone.devices.iter().cloned());
+        }
+        (local, zoned)
+    });
+    let critical_everywhere = app_config().critical_sounds_all_zones;
+    with_alert_store(|store| {
+        store
+            .values()
+            .filter(|alert| !alert.acknowledged)
+            .filter(|alert| {
+                (critical_everywhere && alert.severity == AlertSeverity::Critical)
+                    || local.contains(&alert.device)
+                    || !zoned.contains(&alert.device)
+            })
+            .map(|alert| alert.severity)
+            .max_by_key(|severity| severity.rank())
+    })
+}
+
+/// Severidad que debe anunciar una salida audible de `zones` tras aplicar el mute y la
+/// inhibición remota.
+fn resolve_audible_severity(zones: &[String]) -> Option<AlertSeverity> {
+    if with_mute_controller(|ctrl| ctrl.muted) {
+        return None;
+    }
+    match highest_audible_severity_in(zones) {
+        // La inhibición remota nunca silencia alertas CRITICAL.
+        Some(severity) if severity != AlertSeverity::Critical && buzzer_inhibit_active() => None,
+        severity => severity,
+    }
+}
+
+/// Patrón de una salida para `severity` según el perfil de audio; la baliza es visual y lo ignora.
+fn output_pattern(output: &SignalOutput, severity: Option<AlertSeverity>) -> BuzzerPattern {
+    let threshold = output.min_severity.map_or(0, AlertSeverity::rank);
+    let Some(severity) = severity.filter(|severity| severity.rank() >= threshold) else {
+        return BuzzerPattern::Off;
+    };
+    let profile = active_audio_profile();
+    let mut patterns = &output.patterns;
+    if output.name != STROBE_OUTPUT {
+        match profile.mode {
+            AudioMode::Continuous => return BuzzerPattern::Steady,
+            AudioMode::Chime => {
+                return BuzzerPattern::Blink {
+                    on: AUDIO_CHIME_ON,
+                    off: AUDIO_CHIME_PERIOD - AUDIO_CHIME_ON,
+                }
+            }
+            AudioMode::Pattern => {}
+        }
+        if let Some(set) = app_config()
+            .audio_profile
+            .pattern_sets
+            .get(&profile.pattern_set)
+        {
+            patterns = set;
+        }
+    }
+    patterns
+        .iter()
+        .find(|pattern| pattern.severity == Some(severity))
+        .or_else(|| patterns.iter().find(|pattern| pattern.severity.is_none()))
+        .map(|pattern| BuzzerPattern::Blink {
+            on: Duration::from_millis(pattern.on_ms),
+            off: Duration::from_millis(pattern.off_ms),
+        })
+        .unwrap_or_else(|| severity_pattern(severity))
+}
+
+#[derive(Debug, Clone)]
+struct BuzzerInhibit {
+    until: DateTime<Utc>,
+    reason: String,
+    source: String,
+    generation: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct BuzzerInhibitStatus {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    until: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    until_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BuzzerInhibitRequest {
+    enabled: bool,
+    #[serde(default)]
+    duration_minutes: Option<u64>,
+    #[serde(default)]
+    reason: String,
+}
+
+fn with_buzzer_inhibit<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Option<BuzzerInhibit>) -> R,
+{
+    let inhibit = BUZZER_INHIBIT.get_or_init(|| Mutex::new(None));
+    let mut guard = inhibit
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn buzzer_inhibit_active() -> bool {
+    with_buzzer_inhibit(|inhibit| {
+        inhibit
+            .as_ref()
+            .is_some_and(|inhibit| inhibit.until > corrected_now())
+    })
+}
+
+fn snapshot_buzzer_inhibit() -> BuzzerInhibitStatus {
+    with_buzzer_inhibit(|inhibit| match inhibit {
+        Some(inhibit) if inhibit.until > corrected_now() => BuzzerInhibitStatus {
+            active: true,
+            until: Some(inhibit.until.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            until_ms: Some(inhibit.until.timestamp_millis()),
+            reason: Some(inhibit.reason.clone()),
+            source: Some(inhibit.source.clone()),
+        },
+        _ => BuzzerInhibitStatus {
+            active: false,
+            until: None,
+            until_ms: None,
+            reason: None,
+            source: None,
+        },
+    })
+}
+
+fn emit_buzzer_inhibit(app_handle: &EventSink) {
+    if let Err(err) = app_handle.emit(BUZZER_INHIBIT_EVENT, snapshot_buzzer_inhibit()) {
+        warn!("[BUZZER] No se pudo emitir estado de inhibición: {:?}", err);
+    }
+}
+
+/// Inhibe el buzzer hasta `BUZZER_INHIBIT_MAX_MINUTES` como máximo; se rehabilita solo al vencer.
+fn start_buzzer_inhibit(request: &BuzzerInhibitRequest, source: &str, app_handle: &EventSink) {
+    let max_minutes = app_config().buzzer_inhibit_max_minutes.max(1);
+    let minutes = request
+        .duration_minutes
+        .unwrap_or(max_minutes)
+        .clamp(1, max_minutes);
+    let duration = Duration::from_secs(minutes * 60);
+    let generation = BUZZER_INHIBIT_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let until = corrected_now().with_timezone(&Utc) + chrono::Duration::minutes(minutes as i64);
+
+    with_buzzer_inhibit(|inhibit| {
+        *inhibit = Some(BuzzerInhibit {
+            until,
+            reason: request.reason.clone(),
+            source: source.to_string(),
+            generation,
+        });
+    });
+    warn!(
+        "[BUZZER] Inhibido por {} durante {} min: {}",
+        source, minutes, request.reason
+    );
+    record_audit(
+        source,
+        "buzzer_inhibit",
+        "",
+        &format!("{} min: {}", minutes, request.reason),
+    );
+    apply_buzzer_policy();
+    emit_buzzer_inhibit(app_handle);
+    apply_visual_alarm(app_handle);
+
+    let app_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        let _task = track_task("buzzer-inhibit-timer", TaskKind::Timer, true, None);
+        tokio::time::sleep(duration).await;
+        let expired = with_buzzer_inhibit(|inhibit| {
+            if inhibit
+                .as_ref()
+                .is_some_and(|inhibit| inhibit.generation == generation)
+            {
+                *inhibit = None;
+                true
+            } else {
+                false
+            }
+        });
+        if expired {
+            info!("[BUZZER] Inhibición vencida, buzzer rehabilitado");
+            record_audit("local", "buzzer_inhibit_expired", "", "");
+            let _ = async_runtime::spawn_blocking(apply_buzzer_policy).await;
+            emit_buzzer_inhibit(&app_handle);
+            apply_visual_alarm(&app_handle);
+        }
+    });
+}
+
+fn clear_buzzer_inhibit(source: &str, app_handle: &EventSink) {
+    if with_buzzer_inhibit(|inhibit| inhibit.take()).is_none() {
+        return;
+    }
+    info!("[BUZZER] Inhibición cancelada por {}", source);
+    record_audit(source, "buzzer_inhibit_cleared", "", "");
+    apply_buzzer_policy();
+    emit_buzzer_inhibit(app_handle);
+    apply_visual_alarm(app_handle);
+}
+
+/// Inhibición remota (RPC o atributo); devuelve el estado resultante para la respuesta del RPC.
+fn handle_buzzer_inhibit_value(
+    value: &serde_json::Value,
+    source: &str,
+    app_handle: &EventSink,
+) -> Result<BuzzerInhibitStatus, String> {
+    if !app_config().remote_buzzer_inhibit_enabled {
+        return Err("Inhibición remota del buzzer deshabilitada".to_string());
+    }
+    let request = match value {
+        serde_json::Value::Bool(enabled) => BuzzerInhibitRequest {
+            enabled: *enabled,
+            duration_minutes: None,
+            reason: String::new(),
+        },
+        other => BuzzerInhibitRequest::deserialize(other)
+            .map_err(|err| format!("Solicitud de inhibición inválida: {}", err))?,
+    };
+    if request.enabled {
+        start_buzzer_inhibit(&request, source, app_handle);
+    } else {
+        clear_buzzer_inhibit(source, app_handle);
+    }
+    Ok(snapshot_buzzer_inhibit())
+}
+
+/// Perfil de audio fijado fuera de la configuración; el local gana sobre el remoto.
+#[derive(Debug)]
+struct AudioProfileState {
+    remote: Option<AudioProfile>,
+    /// Perfil del supervisor y su id de operador.
+    local: Option<(AudioProfile, String)>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AudioProfileStatus {
+    profile: AudioProfile,
+    /// `local`, `remote` o `config`.
+    source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote: Option<AudioProfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overridden_by: Option<String>,
+}
+
+fn with_audio_profile_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut AudioProfileState) -> R,
+{
+    let mut guard = AUDIO_PROFILE_STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn snapshot_audio_profile() -> AudioProfileStatus {
+    with_audio_profile_state(|state| {
+        let (profile, source) = match (&state.local, &state.remote) {
+            (Some((profile, _)), _) => (profile.clone(), "local"),
+            (None, Some(profile)) => (profile.clone(), "remote"),
+            (None, None) => (app_config().audio_profile.profile(), "config"),
+        };
+        AudioProfileStatus {
+            profile,
+            source,
+            remote: state.remote.clone(),
+            overridden_by: state.local.as_ref().map(|(_, by)| by.clone()),
+        }
+    })
+}
+
+fn active_audio_profile() -> AudioProfile {
+    snapshot_audio_profile().profile
+}
+
+/// Rechaza juegos de patrones desconocidos y sube el volumen a `AUDIO_MIN_VOLUME` como mínimo.
+fn checked_audio_profile(
+    cfg: &AppConfig,
+    mut profile: AudioProfile,
+) -> Result<AudioProfile, String> {
+    if !profile.pattern_set.is_empty()
+        && !cfg
+            .audio_profile
+            .pattern_sets
+            .contains_key(&profile.pattern_set)
+    {
+        return Err(format!(
+            "Juego de patrones desconocido: {}",
+            profile.pattern_set
+        ));
+    }
+    profile.volume = profile.volume.clamp(AUDIO_MIN_VOLUME, 100);
+    Ok(profile)
+}
+
+fn describe_audio_profile(profile: Option<&AudioProfile>) -> String {
+    match profile {
+        Some(profile) => format!(
+            "volumen {} modo {:?} patrones {}",
+            profile.volume,
+            profile.mode,
+            if profile.pattern_set.is_empty() {
+                "por salida"
+            } else {
+                profile.pattern_set.as_str()
+            }
+        ),
+        None => "retirado".to_string(),
+    }
+}
+
+/// Recalcula las salidas con el perfil vigente y lo avisa al frontend.
+fn apply_audio_profile(app_handle: &EventSink) -> AudioProfileStatus {
+    apply_buzzer_policy();
+    let status = snapshot_audio_profile();
+    if let Err(err) = app_handle.emit(AUDIO_PROFILE_EVENT, &status) {
+        warn!("[AUDIO] No se pudo emitir perfil de audio: {:?}", err);
+    }
+    status
+}
+
+/// Perfil remoto (RPC o atributo); `null` lo retira. Con un perfil local vigente queda guardado
+/// y se aplica cuando el supervisor retire el suyo.
+fn handle_audio_profile_value(
+    value: &serde_json::Value,
+    source: &str,
+    app_handle: &EventSink,
+) -> Result<AudioProfileStatus, String> {
+    let cfg = app_config();
+    if !cfg.audio_profile.remote_control {
+        return Err("Control remoto del perfil de audio deshabilitado".to_string());
+    }
+    let profile = match value {
+        serde_json::Value::Null => None,
+        other => Some(checked_audio_profile(
+            cfg,
+            AudioProfile::deserialize(other).map_err(|err| err.to_string())?,
+        )?),
+    };
+    let detail = describe_audio_profile(profile.as_ref());
+    let overridden = with_audio_profile_state(|state| {
+        state.remote = profile;
+        state.local.is_some()
+    });
+    if overridden {
+        info!(
+            "[AUDIO] Perfil remoto de {} en espera (hay perfil local): {}",
+            source, detail
+        );
+    } else {
+        info!("[AUDIO] Perfil remoto de {}: {}", source, detail);
+    }
+    record_audit(source, "audio_profile_remote", "", &detail);
+    Ok(apply_audio_profile(app_handle))
+}
+
+/// Perfil local de un supervisor con sesión abierta; `None` lo retira y vuelve el remoto.
+fn set_local_audio_profile(
+    profile: Option<AudioProfile>,
+    app_handle: &EventSink,
+) -> Result<AudioProfileStatus, String> {
+    let cfg = app_config();
+    let supervisor = with_operator_session(|session| {
+        session.as_ref().map(|session| session.operator_id.clone())
+    })
+    .filter(|id| {
+        cfg.operators
+            .iter()
+            .any(|operator| operator.id == *id && operator.supervisor)
+    })
+    .ok_or("Se requiere la sesión de un supervisor")?;
+    let profile = profile
+        .map(|profile| checked_audio_profile(cfg, profile))
+        .transpose()?;
+    let detail = describe_audio_profile(profile.as_ref());
+    with_audio_profile_state(|state| {
+        state.local = profile.map(|profile| (profile, supervisor));
+    });
+    info!("[AUDIO] Perfil local: {}", detail);
+    record_audit("local", "audio_profile_override", "", &detail);
+    Ok(apply_audio_profile(app_handle))
+}
+
+/// Atributos compartidos: llegan planos en actualizaciones o bajo `shared` en respuestas.
+fn handle_attributes_payload(payload: &[u8], app_handle: &EventSink) {
+    let value: serde_json::Value = match serde_json::from_slice(payload) {
+        Ok(value) => value,
+        Err(err) => {
+            warn!("[MQTT] No se pudo parsear atributos: {:?}", err);
+            return;
+        }
+    };
+    let attributes = value.get("shared").unwrap_or(&value);
+    if let Some(inhibit) = attributes.get(BUZZER_INHIBIT_ATTRIBUTE) {
+        if let Err(err) = handle_buzzer_inhibit_value(inhibit, "platform", app_handle) {
+            warn!("[BUZZER] Inhibición remota rechazada: {}", err);
+        }
+    }
+    if let Some(schedule) = attributes.get(ON_CALL_SCHEDULE_ATTRIBUTE) {
+        handle_on_call_schedule_value(schedule);
+    }
+    if let Some(zones) = attributes.get(ZONES_ATTRIBUTE) {
+        handle_zones_value(zones);
+    }
+    if let Some(routes) = attributes.get(NOTIFICATION_ROUTES_ATTRIBUTE) {
+        handle_notification_routes_value(routes);
+    }
+    if let Some(profile) = attributes.get(AUDIO_PROFILE_ATTRIBUTE) {
+        if let Err(err) = handle_audio_profile_value(profile, "platform", app_handle) {
+            warn!("[AUDIO] Perfil remoto rechazado: {}", err);
+        }
+    }
+    let server_time_attribute = &app_config().server_time.attribute;
+    if let Some(time) = attributes.get(server_time_attribute.as_str()) {
+        handle_server_time_attribute(time, app_handle);
+    }
+}
+
+/// Punto único de arbitraje: recalcula el patrón de cada salida a partir de las alertas de sus
+/// zonas y el mute. Una salida sin alerta que anunciar marca la falla del sistema, si la hay.
+fn apply_buzzer_policy() -> bool {
+    let fault = &app_config().system_fault;
+    let fault_pattern = (system_fault_active() && !with_mute_controller(|ctrl| ctrl.muted))
+        .then_some(BuzzerPattern::Blink {
+            on: Duration::from_millis(fault.on_ms),
+            off: Duration::from_millis(fault.off_ms),
+        });
+    let mut result = true;
+    for output in signal_outputs() {
+        let severity = resolve_audible_severity(&output.zones);
+        let pattern = match (output_pattern(output, severity), fault_pattern) {
+            (BuzzerPattern::Off, Some(fault_pattern)) => fault_pattern,
+            (pattern, _) => pattern,
+        };
+        result &= set_buzzer_pattern(output, pattern);
+    }
+    result
+}
+
+/// Controla el estado de una salida audible según el patrón de parpadeo solicitado.
+fn set_buzzer_pattern(output: &'static SignalOutput, pattern: BuzzerPattern) -> bool {
+    if with_buzzer_controller(&output.name, |ctrl| ctrl.pattern == Some(pattern)) {
+        return true;
+    }
+
+    if !output.enabled {
+        debug!(
+            "[BUZZER] {}: cambio de estado ignorado (deshabilitado)",
+            output.name
+        );
+        if pattern == BuzzerPattern::Off {
+            let _ = stop_buzzer_blinking(output);
+        }
+        return true;
+    }
+
+    let result = match pattern {
+        BuzzerPattern::Off => {
+            info!("[BUZZER] {}: desactivado", output.name);
+            stop_buzzer_blinking(output)
+        }
+        BuzzerPattern::Blink { on, off } => {
+            info!(
+                "[BUZZER] {}: activado (on={:?}, off={:?})",
+                output.name, on, off
+            );
+            let anchor = output.sync_with.as_deref().and_then(|leader| {
+                with_buzzer_controller(leader, |ctrl| {
+                    ctrl.started_at.filter(|_| ctrl.pattern == Some(pattern))
+                })
+            });
+            start_buzzer_blinking(output, on, off, anchor)
+        }
+        BuzzerPattern::Steady => {
+            info!("[BUZZER] {}: activado (continuo)", output.name);
+            if let Some(handle) = with_buzzer_controller(&output.name, |ctrl| {
+                ctrl.started_at = None;
+                ctrl.handle.take()
+            }) {
+                handle.abort();
+            }
+            set_output_level(output, true)
+        }
+    };
+
+    if result {
+        with_buzzer_controller(&output.name, |ctrl| ctrl.pattern = Some(pattern));
+    } else {
+        error!(
+            "[BUZZER] {}: no se pudo cambiar estado a {:?}",
+            output.name, pattern
+        );
+    }
+
+    result
+}
+
+/// Nivel actual y próximo cambio dentro del ciclo que arrancó en `anchor`.
+fn blink_phase(anchor: Instant, on: Duration, off: Duration) -> (bool, Instant) {
+    let now = Instant::now();
+    let period = (on + off).as_nanos().max(1);
+    let position = Duration::from_nanos((now.duration_since(anchor).as_nanos() % period) as u64);
+    if position < on {
+        (true, now + (on - position))
+    } else {
+        (false, now + (on + off).saturating_sub(position))
+    }
+}
+
+/// Con `anchor` la salida entra en fase con otra que ya parpadea; sin él arranca encendida.
+fn start_buzzer_blinking(
+    output: &'static SignalOutput,
+    on: Duration,
+    off: Duration,
+    anchor: Option<Instant>,
+) -> bool {
+    if let Some(handle) = with_buzzer_controller(&output.name, |ctrl| ctrl.handle.take()) {
+        handle.abort();
+    }
+
+    let anchor = anchor.unwrap_or_else(Instant::now);
+    let (mut level, mut next_toggle) = blink_phase(anchor, on, off);
+    if !set_output_level(output, level) {
+        return false;
+    }
+
+    let handle = async_runtime::spawn(async move {
+        let _task = track_task(
+            &format!("buzzer-blink:{}", output.name),
+            TaskKind::Timer,
+            true,
+            None,
+        );
+        let mut consecutive_failures: u8 = 0;
+        loop {
+            // Plazos absolutos: la latencia de gpioset no acumula desfase entre salidas sincronizadas.
+            tokio::time::sleep_until(tokio::time::Instant::from_std(next_toggle)).await;
+            if is_shutting_down() {
+                break;
+            }
+            level = !level;
+            next_toggle += if level { on } else { off };
+            let toggled = async_runtime::spawn_blocking(move || set_output_level(output, level))
+                .await
+                .unwrap_or(false);
+            if toggled {
+                consecutive_failures = 0;
+            } else {
+                consecutive_failures = consecutive_failures.saturating_add(1);
+                warn!(
+                    "[BUZZER] {}: fallo al alternar nivel {}",
+                    output.name, level as u8
+                );
+                if consecutive_failures >= BUZZER_FAILURE_LIMIT {
+                    error!(
+                        "[BUZZER] {}: se desactiva parpadeo tras {} errores consecutivos",
+                        output.name, BUZZER_FAILURE_LIMIT
+                    );
+                    // Sin patrón registrado, la próxima evaluación de la política vuelve a intentarlo.
+                    with_buzzer_controller(&output.name, |ctrl| {
+                        ctrl.pattern = None;
+                        ctrl.started_at = None;
+                    });
+                    break;
+                }
+            }
+        }
+
+        let _ = async_runtime::spawn_blocking(move || set_output_level(output, false)).await;
+    });
+
+    let mut handle_slot = Some(handle);
+    let was_set = with_buzzer_controller(&output.name, |ctrl| {
+        if ctrl.handle.is_none() {
+            ctrl.handle = handle_slot.take();
+            ctrl.started_at = Some(anchor);
+            true
+        } else {
+            false
+        }
+    });
+
+    if !was_set {
+        if let Some(handle) = handle_slot {
+            handle.abort();
+        }
+    }
+
+    true
+}
+
+fn stop_buzzer_blinking(output: &SignalOutput) -> bool {
+    if let Some(handle) = with_buzzer_controller(&output.name, |ctrl| {
+        ctrl.pattern = None;
+        ctrl.started_at = None;
+        ctrl.handle.take()
+    }) {
+        handle.abort();
+    }
+
+    set_output_level(output, false)
+}
+
+fn stop_all_buzzers() {
+    for output in signal_outputs() {
+        let _ = stop_buzzer_blinking(output);
+    }
+}
+
+fn set_pwm_level(pwm: &PwmLine, on: bool) -> std::io::Result<()> {
+    let channel_dir = pwm.chip.join(format!("pwm{}", pwm.channel));
+    if !channel_dir.exists() {
+        fs::write(pwm.chip.join("export"), pwm.channel.to_string())?;
+    }
+    if on {
+        let duty = PWM_PERIOD_NS * u64::from(pwm.duty_percent) / 100;
+        fs::write(channel_dir.join("period"), PWM_PERIOD_NS.to_string())?;
+        fs::write(channel_dir.join("duty_cycle"), duty.to_string())?;
+    }
+    fs::write(channel_dir.join("enable"), if on { "1" } else { "0" })
+}
+
+fn set_output_level(output: &SignalOutput, on: bool) -> bool {
+    let (result, injected) = match injected_fault(&output.name) {
+        Some(err) => (Err(err), true),
+        None => (
+            write_output_level(output, on).and_then(|()| verify_output_level(output, on)),
+            false,
+        ),
+    };
+    if let Err(err) = &result {
+        error!("[BUZZER] {}: {}", output.name, err);
+    }
+    record_output_result(&output.name, result.as_ref().err(), injected);
+    result.is_ok()
+}
+
+fn write_output_level(output: &SignalOutput, on: bool) -> Result<(), String> {
+    if let Some(pwm) = &output.pwm {
+        return set_pwm_level(pwm, on).map_err(|err| {
+            format!(
+                "no se pudo escribir PWM {:?}/pwm{}: {:?}",
+                pwm.chip, pwm.channel, err
+            )
+        });
+    }
+
+    let level = if on { "1" } else { "0" };
+
+    let (chip, line) = match resolve_buzzer_line(&output.gpio) {
+        Some(pair) => pair,
+        None => return Err(format!("línea GPIO {} no disponible", output.gpio)),
+    };
+
+    match Command::new("gpioset")
+        .arg(&chip)
+        .arg(format!("{}={}", line, level))
+        .status()
+    {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => {
+            invalidate_buzzer_line(&output.gpio);
+            Err(format!("gpioset termino con codigo {:?}", status.code()))
+        }
+        Err(err) => {
+            invalidate_buzzer_line(&output.gpio);
+            Err(format!("no se pudo ejecutar gpioset: {:?}", err))
+        }
+    }
+}
+
+/// Relee la salida si toca; ante una discrepancia la reescribe una vez antes de dar el fallo.
+fn verify_output_level(output: &SignalOutput, on: bool) -> Result<(), String> {
+    let cfg = &app_config().output_verification;
+    if !cfg.enabled {
+        return Ok(());
+    }
+    let interval = Duration::from_secs(cfg.min_interval_secs);
+    let due = with_output_verified_at(|verified| {
+        let due = verified
+            .get(&output.name)
+            .is_none_or(|at| at.elapsed() >= interval);
+        if due {
+            verified.insert(output.name.clone(), Instant::now());
+        }
+        due
+    });
+    if !due {
+        return Ok(());
+    }
+
+    let settle = Duration::from_millis(cfg.settle_ms);
+    thread::sleep(settle);
+    match read_output_level(output) {
+        Ok(level) if level == on => return Ok(()),
+        Ok(level) => warn!(
+            "[BUZZER] {}: se escribió {} y se leyó {}, se reintenta",
+            output.name,
+            u8::from(on),
+            u8::from(level)
+        ),
+        Err(err) => warn!(
+            "[BUZZER] {}: no se pudo verificar ({}), se reintenta",
+            output.name, err
+        ),
+    }
+    write_output_level(output, on)?;
+    thread::sleep(settle);
+    let level = read_output_level(output).map_err(|err| format!("verificación: {}", err))?;
+    if level != on {
+        return Err(format!(
+            "verificación: se escribió {} y se leyó {} tras reintentar",
+            u8::from(on),
+            u8::from(level)
+        ));
+    }
+    info!("[BUZZER] {}: verificada al segundo intento", output.name);
+    Ok(())
+}
+
+/// Nivel real de la salida: la realimentación configurada o, si no hay, la propia línea.
+fn read_output_level(output: &SignalOutput) -> Result<bool, String> {
+    if let Some(feedback) = app_config().output_verification.feedback.get(&output.name) {
+        return read_audible_feedback(feedback);
+    }
+    if let Some(pwm) = &output.pwm {
+        let path = pwm.chip.join(format!("pwm{}", pwm.channel)).join("enable");
+        return fs::read_to_string(&path)
+            .map(|value| value.trim() == "1")
+            .map_err(|err| format!("no se pudo leer {:?}: {:?}", path, err));
+    }
+
+    let (chip, line) = resolve_buzzer_line(&output.gpio)
+        .ok_or_else(|| format!("línea GPIO {} no disponible", output.gpio))?;
+    // `--as-is` lee sin reconfigurar la línea como entrada, lo que apagaría la salida.
+    match Command::new("gpioget")
+        .args(["--as-is", "--numeric", "-c"])
+        .arg(&chip)
+        .arg(&line)
+        .output()
+    {
+        Ok(result) if result.status.success() => {
+            Ok(String::from_utf8_lossy(&result.stdout).trim() == "1")
+        }
+        Ok(result) => Err(format!(
+            "gpioget termino con codigo {:?}: {}",
+            result.status.code(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        )),
+        Err(err) => Err(format!("no se pudo ejecutar gpioget: {:?}", err)),
+    }
+}
+
+fn with_output_verified_at<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HashMap<String, Instant>) -> R,
+{
+    let verified = OUTPUT_VERIFIED_AT.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = verified
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn with_fault_injection<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut FaultInjectionConfig) -> R,
+{
+    let config =
+        FAULT_INJECTION.get_or_init(|| Mutex::new(app_config().hardware_fault_injection.clone()));
+    let mut guard = config
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Con la inyección activa decide al azar si la operación sobre `output` falla.
+fn injected_fault(output: &str) -> Option<String> {
+    let rate = with_fault_injection(|cfg| {
+        let applies = cfg.outputs.is_empty() || cfg.outputs.iter().any(|name| name == output);
+        (cfg.enabled && applies).then_some(cfg.failure_rate)
+    })?;
+    (next_fault_sample() < rate).then(|| "fallo inyectado (simulación)".to_string())
+}
+
+/// xorshift64 sembrado con el reloj: basta para repartir fallos, no es aleatoriedad criptográfica.
+fn next_fault_sample() -> f64 {
+    let mut state = FAULT_INJECTION_STATE.load(Ordering::Relaxed);
+    if state == 0 {
+        state = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(1)
+            | 1;
+    }
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    FAULT_INJECTION_STATE.store(state, Ordering::Relaxed);
+    (state >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct OutputHealth {
+    name: String,
+    failures: u64,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    last_failure_at: Option<String>,
+    /// El último fallo fue simulado por la inyección de fallos.
+    injected: bool,
+}
+
+#[derive(Debug, Default)]
+struct HardwareHealth {
+    outputs: BTreeMap<String, OutputHealth>,
+    /// Salidas con fallo ya informadas a la UI y la telemetría.
+    reported: Vec<String>,
+}
+
+fn with_hardware_health<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut HardwareHealth) -> R,
+{
+    let health = HARDWARE_HEALTH.get_or_init(|| Mutex::new(HardwareHealth::default()));
+    let mut guard = health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn record_output_result(name: &str, error: Option<&String>, injected: bool) {
+    with_hardware_health(|health| {
+        let entry = health
+            .outputs
+            .entry(name.to_string())
+            .or_insert_with(|| OutputHealth {
+                name: name.to_string(),
+                ..OutputHealth::default()
+            });
+        match error {
+            Some(err) => {
+                entry.failures += 1;
+                entry.consecutive_failures = entry.consecutive_failures.saturating_add(1);
+                entry.last_error = Some(err.clone());
+                entry.last_failure_at =
+                    Some(corrected_now().to_rfc3339_opts(SecondsFormat::Secs, false));
+                entry.injected = injected;
+            }
+            None => entry.consecutive_failures = 0,
+        }
+    });
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct HardwareStatus {
+    fault_injection_enabled: bool,
+    fault_injection_rate: f64,
+    fault_injection_outputs: Vec<String>,
+    failing_outputs: Vec<String>,
+    outputs: Vec<OutputHealth>,
+}
+
+fn hardware_status() -> HardwareStatus {
+    let (enabled, rate, targets) =
+        with_fault_injection(|cfg| (cfg.enabled, cfg.failure_rate, cfg.outputs.clone()));
+    with_hardware_health(|health| HardwareStatus {
+        fault_injection_enabled: enabled,
+        fault_injection_rate: rate,
+        fault_injection_outputs: targets,
+        failing_outputs: failing_outputs(health),
+        outputs: health.outputs.values().cloned().collect(),
+    })
+}
+
+fn failing_outputs(health: &HardwareHealth) -> Vec<String> {
+    health
+        .outputs
+        .values()
+        .filter(|output| output.consecutive_failures > 0)
+        .map(|output| output.name.clone())
+        .collect()
+}
+
+/// Informa a la UI, la telemetría y la lista de alertas cuando cambia el conjunto de salidas con fallo.
+fn apply_hardware_status(app_handle: &EventSink) {
+    let changed = with_hardware_health(|health| {
+        let failing = failing_outputs(health);
+        (failing != health.reported).then(|| std::mem::replace(&mut health.reported, failing))
+    });
+    if changed.is_none() {
+        return;
+    }
+    let status = hardware_status();
+    let failing = &status.failing_outputs;
+    let description =
+        (!failing.is_empty()).then(|| format!("Fallo de hardware en: {}", failing.join(", ")));
+    apply_local_alert(HARDWARE_FAULT_ALERT_ID, description, app_handle);
+
+    let telemetry = serde_json::json!({
+        "hardwareFaults": failing.len(),
+        "hardwareFaultOutputs": failing.join(","),
+        "hardwareFaultInjection": status.fault_injection_enabled,
+    });
+    if let Ok(bytes) = serde_json::to_vec(&telemetry) {
+        mqtt_publish(MQTT_TELEMETRY_PUBLISH_TOPIC, bytes, QoS::AtLeastOnce);
+    }
+    if let Err(err) = app_handle.emit(HARDWARE_STATUS_EVENT, &status) {
+        warn!("[HARDWARE] No se pudo emitir estado de hardware: {:?}", err);
+    }
+}
+
+#[tauri::command]
+fn get_hardware_status() -> HardwareStatus {
+    hardware_status()
+}
+
+/// Activa o ajusta la inyección de fallos hasta el próximo reinicio (no modifica el YAML).
+#[tauri::command]
+fn set_fault_injection(
+    window: tauri::Window,
+    enabled: bool,
+    failure_rate: Option<f64>,
+    outputs: Option<Vec<String>>,
+) -> Result<HardwareStatus, String> {
+    check_write_access(&window)?;
+    if let Some(rate) = failure_rate.filter(|rate| !(0.0..=1.0).contains(rate)) {
+        return Err(format!("failure_rate fuera de rango (0-1): {}", rate));
+    }
+    let summary = with_fault_injection(|cfg| {
+        cfg.enabled = enabled;
+        if let Some(rate) = failure_rate {
+            cfg.failure_rate = rate;
+        }
+        if let Some(outputs) = outputs {
+            cfg.outputs = outputs;
+        }
+        format!(
+            "enabled={} rate={} outputs={:?}",
+            cfg.enabled, cfg.failure_rate, cfg.outputs
+        )
+    });
+    warn!("[HARDWARE] Inyección de fallos: {}", summary);
+    record_audit("local", "fault_injection", "hardware", &summary);
+    Ok(hardware_status())
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum VisualAlarmReason {
+    Settings,
+    Muted,
+}
+
+/// Estado que el frontend usa para invertir/parpadear la pantalla con el mismo ritmo que el buzzer.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct VisualAlarmState {
+    active: bool,
+    severity: Option<AlertSeverity>,
+    on_ms: u64,
+    off_ms: u64,
+    reason: Option<VisualAlarmReason>,
+}
+
+#[derive(Default)]
+struct VisualAlarmController {
+    state: VisualAlarmState,
+    pulse: Option<JoinHandle<()>>,
+    restore_brightness: Option<(PathBuf, u32)>,
+}
+
+fn with_visual_alarm<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut VisualAlarmController) -> R,
+{
+    let controller = VISUAL_ALARM.get_or_init(|| Mutex::new(VisualAlarmController::default()));
+    let mut guard = controller
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Activa si el panel lo tiene habilitado o, sin configurarlo, mientras el buzzer está silenciado.
+fn resolve_visual_alarm() -> VisualAlarmState {
+    let silenced = with_mute_controller(|ctrl| ctrl.muted) || buzzer_inhibit_active();
+    let reason = if app_config().visual_alarm_enabled {
+        VisualAlarmReason::Settings
+    } else if silenced {
+        VisualAlarmReason::Muted
+    } else {
+        return VisualAlarmState::default();
+    };
+    let Some(severity) = highest_audible_severity() else {
+        return VisualAlarmState::default();
+    };
+    let BuzzerPattern::Blink { on, off } = severity_pattern(severity) else {
+        return VisualAlarmState::default();
+    };
+    VisualAlarmState {
+        active: true,
+        severity: Some(severity),
+        on_ms: on.as_millis() as u64,
+        off_ms: off.as_millis() as u64,
+        reason: Some(reason),
+    }
+}
+
+fn read_sysfs_u32(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn write_backlight(dir: &Path, brightness: u32) {
+    let (result, injected) = match injected_fault(BACKLIGHT_OUTPUT) {
+        Some(err) => (Err(err), true),
+        None => (
+            fs::write(dir.join("brightness"), brightness.to_string())
+                .map_err(|err| format!("{:?}", err)),
+            false,
+        ),
+    };
+    if let Err(err) = &result {
+        warn!("[VISUAL] No se pudo escribir brillo en {:?}: {}", dir, err);
+    }
+    record_output_result(BACKLIGHT_OUTPUT, result.as_ref().err(), injected);
+}
+
+/// Alterna el backlight entre el máximo y un nivel atenuado; el brillo previo se restaura al detener.
+fn start_backlight_pulse(on: Duration, off: Duration) {
+    let backlight_path = hardware_profile().backlight_path.as_str();
+    if backlight_path.is_empty() {
+        debug!("[VISUAL] Perfil de hardware sin backlight, solo parpadeo en pantalla");
+        return;
+    }
+    let dir = PathBuf::from(backlight_path);
+    let (Some(max), Some(current)) = (
+        read_sysfs_u32(&dir.join("max_brightness")),
+        read_sysfs_u32(&dir.join("brightness")),
+    ) else {
+        warn!("[VISUAL] No se pudo leer el backlight en {:?}", dir);
+        return;
+    };
+    let dim = max * VISUAL_ALARM_DIM_PERCENT / 100;
+
+    let pulse_dir = dir.clone();
+    let handle = async_runtime::spawn(async move {
+        let _task = track_task("backlight-pulse", TaskKind::Timer, false, None);
+        let mut bright = true;
+        loop {
+            let dir = pulse_dir.clone();
+            let level = if bright { max } else { dim };
+            let _ = async_runtime::spawn_blocking(move || write_backlight(&dir, level)).await;
+            tokio::time::sleep(if bright { on } else { off }).await;
+            if is_shutting_down() {
+                break;
+            }
+            bright = !bright;
+        }
+    });
+    with_visual_alarm(|ctrl| {
+        ctrl.pulse = Some(handle);
+        ctrl.restore_brightness = Some((dir, current));
+    });
+}
+
+fn stop_backlight_pulse() {
+    let (pulse, restore) =
+        with_visual_alarm(|ctrl| (ctrl.pulse.take(), ctrl.restore_brightness.take()));
+    if let Some(handle) = pulse {
+        handle.abort();
+    }
+    if let Some((dir, brightness)) = restore {
+        write_backlight(&dir, brightness);
+    }
+}
+
+fn apply_visual_alarm(app_handle: &EventSink) {
+    let state = resolve_visual_alarm();
+    let previous = with_visual_alarm(|ctrl| std::mem::replace(&mut ctrl.state, state.clone()));
+    if previous == state {
+        return;
+    }
+
+    stop_backlight_pulse();
+    if state.active {
+        info!(
+            "[VISUAL] Alarma visual activa ({:?}, {:?})",
+            state.severity, state.reason
+        );
+        if app_config().visual_alarm_backlight_pulse {
+            start_backlight_pulse(
+                Duration::from_millis(state.on_ms),
+                Duration::from_millis(state.off_ms),
+            );
+        }
+    } else {
+        info!("[VISUAL] Alarma visual desactivada");
+    }
+
+    if let Err(err) = app_handle.emit(VISUAL_ALARM_EVENT, state) {
+        warn!(
+            "[VISUAL] No se pudo emitir estado de alarma visual: {:?}",
+            err
+        );
+    }
+}
+
+fn visual_alarm_side_effect(_event: &DomainEvent, app_handle: &EventSink) {
+    apply_visual_alarm(app_handle);
+}
+
+#[tauri::command]
+fn get_visual_alarm() -> VisualAlarmState {
+    with_visual_alarm(|ctrl| ctrl.state.clone())
+}
+
+fn request_shutdown() {
+    if !SHUTDOWN.swap(true, Ordering::SeqCst) {
+        info!("[CORE] Shutdown solicitado");
+    }
+    SHUTDOWN_NOTIFY.notify_waiters();
+    MQTT_CONNECTED.store(false, Ordering::SeqCst);
+    SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
+    stop_all_buzzers();
+    stop_backlight_pulse();
+    stop_mdns_advertisement();
+    shutdown_tracing();
+}
+
+fn mdns_host_label(name: &str) -> String {
+    let label: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    label.trim_matches('-').to_ascii_lowercase()
+}
+
+/// Anuncia el panel en la LAN como `_nxt-hmi._tcp` con nombre, versión y endpoints.
+fn start_mdns_advertisement() {
+    let cfg = app_config();
+    if !cfg.mdns_enabled {
+        return;
+    }
+
+    let instance = panel_id();
+    let host_label = mdns_host_label(instance);
+    let host_name = format!(
+        "{}.local.",
+        if host_label.is_empty() {
+            "nxt-hmi"
+        } else {
+            host_label.as_str()
+        }
+    );
+
+    let mut properties = cfg.mdns_endpoints.clone();
+    properties.insert("name".to_string(), instance.to_string());
+    properties.insert("version".to_string(), env!("CARGO_PKG_VERSION").to_string());
+    if cfg.peer_sync_enabled {
+        properties.insert("peerTopic".to_string(), cfg.peer_sync_topic.clone());
+    }
+
+    let service = match ServiceInfo::new(
+        MDNS_SERVICE_TYPE,
+        instance,
+        &host_name,
+        "",
+        cfg.mdns_port,
+        properties,
+    ) {
+        Ok(service) => service.enable_addr_auto(),
+        Err(err) => {
+            error!("[MDNS] Datos de servicio inválidos: {:?}", err);
+            return;
+        }
+    };
+
+    let daemon = match ServiceDaemon::new() {
+        Ok(daemon) => daemon,
+        Err(err) => {
+            error!("[MDNS] No se pudo iniciar daemon: {:?}", err);
+            return;
+        }
+    };
+
+    if let Err(err) = daemon.register(service) {
+        error!("[MDNS] No se pudo registrar servicio: {:?}", err);
+        let _ = daemon.shutdown();
+        return;
+    }
+
+    info!(
+        "[MDNS] Panel anunciado como {}.{} en {}",
+        instance, MDNS_SERVICE_TYPE, host_name
+    );
+    let slot = MDNS_DAEMON.get_or_init(|| Mutex::new(None));
+    let mut guard = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(previous) = guard.replace(daemon) {
+        let _ = previous.shutdown();
+    }
+}
+
+fn stop_mdns_advertisement() {
+    let Some(slot) = MDNS_DAEMON.get() else {
+        return;
+    };
+    let daemon = slot
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+    if let Some(daemon) = daemon {
+        if let Err(err) = daemon.shutdown() {
+            warn!("[MDNS] Error al detener daemon: {:?}", err);
+        }
+    }
+}
+
+
+fn log_mqtt_ping(kind: &str) {
+    let count = MQTT_PING_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    if count % MQTT_PING_LOG_EVERY == 1 {
+        debug!(
+            "[MQTT] {} (muestreo 1 de {}, total {})",
+            kind, MQTT_PING_LOG_EVERY, count
+        );
+    }
+}
+
+fn log_mqtt_incoming(pkt: &Packet) {
+    match pkt {
+        Packet::PingResp => log_mqtt_ping("PingResp"),
+        Packet::Publish(publish) => debug!(
+            "[MQTT] Publish entrante topic={} bytes={}",
+            publish.topic,
+            publish.payload.len()
+        ),
+        other => debug!("[MQTT] Evento entrante: {:?}", other),
+    }
+}
+
+fn log_mqtt_outgoing(pkt: &Outgoing) {
+    match pkt {
+        Outgoing::PingReq => log_mqtt_ping("PingReq"),
+        other => trace!("[MQTT] Evento saliente: {:?}", other),
+    }
+}
+
+fn log_mqtt_incoming_v5(pkt: &v5::Incoming) {
+    match pkt {
+        v5::Incoming::PingResp(_) => log_mqtt_ping("PingResp"),
+        v5::Incoming::Publish(publish) => debug!(
+            "[MQTT] Publish entrante topic={} bytes={} propiedades={:?}",
+            String::from_utf8_lossy(&publish.topic),
+            publish.payload.len(),
+            publish
+                .properties
+                .as_ref()
+                .map(|properties| &properties.user_properties)
+        ),
+        other => debug!("[MQTT] Evento entrante: {:?}", other),
+    }
+}
+
+fn v5_qos(qos: QoS) -> v5::mqttbytes::QoS {
+    match qos {
+        QoS::AtMostOnce => v5::mqttbytes::QoS::AtMostOnce,
+        QoS::AtLeastOnce => v5::mqttbytes::QoS::AtLeastOnce,
+        QoS::ExactlyOnce => v5::mqttbytes::QoS::ExactlyOnce,
+    }
+}
+
+/// Opciones de conexión en la versión de `MQTT_PROTOCOL`.
+enum MqttConnectOptions {
+    V4(Box<MqttOptions>),
+    V5(Box<v5::MqttOptions>),
+}
+
+impl MqttConnectOptions {
+    fn into_async(self, cap: usize) -> (MqttClient, MqttEventLoop) {
+        match self {
+            MqttConnectOptions::V4(options) => {
+                let (client, eventloop) = AsyncClient::new(*options, cap);
+                (
+                    MqttClient::V4(client),
+                    MqttEventLoop::V4(Box::new(eventloop)),
+                )
+            }
+            MqttConnectOptions::V5(options) => {
+                let (client, eventloop) = v5::AsyncClient::new(*options, cap);
+                (
+                    MqttClient::V5(client),
+                    MqttEventLoop::V5(Box::new(eventloop)),
+                )
+            }
+        }
+    }
+}
+
+/// Cliente de la conexión activa; los errores vuelven como texto en ambas versiones.
+#[derive(Clone)]
+enum MqttClient {
+    V4(AsyncClient),
+    V5(v5::AsyncClient),
+}
+
+impl MqttClient {
+    async fn subscribe(&self, topic: &str, qos: QoS) -> Result<(), String> {
+        match self {
+            MqttClient::V4(client) => client
+                .subscribe(topic, qos)
+                .await
+                .map_err(|err| format!("{:?}", err)),
+            MqttClient::V5(client) => client
+                .subscribe(topic, v5_qos(qos))
+                .await
+                .map_err(|err| format!("{:?}", err)),
+        }
+    }
+
+    /// En v5 los mensajes RPC llevan `MQTT_V5.user_properties`.
+    fn try_publish(&self, topic: &str, qos: QoS, payload: Vec<u8>) -> Result<(), String> {
+        match self {
+            MqttClient::V4(client) => client
+                .try_publish(topic, qos, false, payload)
+                .map_err(|err| format!("{:?}", err)),
+            MqttClient::V5(client) => {
+                let user_properties: Vec<(String, String)> = if topic
+                    .starts_with(MQTT_RPC_REQUEST_PREFIX)
+                    || topic.starts_with(MQTT_RPC_RESPONSE_PREFIX)
+                {
+                    app_config()
+                        .mqtt_v5
+                        .user_properties
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                let properties = v5::mqttbytes::v5::PublishProperties {
+                    user_properties,
+                    ..Default::default()
+                };
+                client
+                    .try_publish_with_properties(topic, v5_qos(qos), false, payload, properties)
+                    .map_err(|err| format!("{:?}", err))
+            }
+        }
+    }
+
+    fn try_disconnect(&self) -> Result<(), String> {
+        match self {
+            MqttClient::V4(client) => client.try_disconnect().map_err(|err| format!("{:?}", err)),
+            MqttClient::V5(client) => client.try_disconnect().map_err(|err| format!("{:?}", err)),
+        }
+    }
+}
+
+enum MqttEventLoop {
+    V4(Box<EventLoop>),
+    V5(Box<v5::EventLoop>),
+}
+
+/// Lo que el loop necesita de un paquete entrante, igual en ambas versiones.
+enum MqttIncoming {
+    ConnAck,
+    Publish { topic: String, payload: Vec<u8> },
+    Other,
+}
+
+impl MqttEventLoop {
+    /// Siguiente evento; `None` para los salientes. En v5 el error incluye el código de motivo
+    /// con que el broker cerró la sesión.
+    async fn poll(&mut self) -> Result<Option<MqttIncoming>, String> {
+        match self {
+            MqttEventLoop::V4(eventloop) => match eventloop.poll().await {
+                Ok(Event::Incoming(pkt)) => {
+                    log_mqtt_incoming(&pkt);
+                    Ok(Some(match pkt {
+                        Packet::ConnAck(_) => MqttIncoming::ConnAck,
+                        Packet::Publish(publish) => MqttIncoming::Publish {
+                            topic: publish.topic,
+                            payload: publish.payload.to_vec(),
+                        },
+                        _ => MqttIncoming::Other,
+                    }))
+                }
+                Ok(Event::Outgoing(pkt)) => {
+                    log_mqtt_outgoing(&pkt);
+                    Ok(None)
+                }
+                Err(err) => Err(err.to_string()),
+            },
+            MqttEventLoop::V5(eventloop) => match eventloop.poll().await {
+                Ok(v5::Event::Incoming(pkt)) => {
+                    log_mqtt_incoming_v5(&pkt);
+                    Ok(Some(match pkt {
+                        v5::Incoming::ConnAck(_) => MqttIncoming::ConnAck,
+                        v5::Incoming::Publish(publish) => MqttIncoming::Publish {
+                            topic: String::from_utf8_lossy(&publish.topic).into_owned(),
+                            payload: publish.payload.to_vec(),
+                        },
+                        _ => MqttIncoming::Other,
+                    }))
+                }
+                Ok(v5::Event::Outgoing(pkt)) => {
+                    log_mqtt_outgoing(&pkt);
+                    Ok(None)
+                }
+                Err(v5::ConnectionError::MqttState(v5::StateError::ServerDisconnect {
+                    reason_code,
+                    reason_string,
+                })) => Err(format!(
+                    "El broker cerró la sesión: {:?}{}",
+                    reason_code,
+                    reason_string
+                        .map(|reason| format!(" ({})", reason))
+                        .unwrap_or_default()
+                )),
+                Err(err) => Err(err.to_string()),
+            },
+        }
+    }
+}
+
+fn set_mqtt_client(client: Option<MqttClient>) {
+    let slot = MQTT_CLIENT.get_or_init(|| Mutex::new(None));
+    let mut guard = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    *guard = client;
+}
+
+/// Publica sin bloquear usando el cliente de la conexión MQTT activa.
+fn mqtt_publish(topic: &str, payload: Vec<u8>, qos: QoS) -> bool {
+    let Some(slot) = MQTT_CLIENT.get() else {
+        return false;
+    };
+    let guard = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(client) = guard.as_ref() else {
+        debug!("[MQTT] Publicación descartada, sin conexión: {}", topic);
+        return false;
+    };
+    match client.try_publish(topic, qos, payload) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("[MQTT] No se pudo publicar en {}: {}", topic, err);
+            false
+        }
+    }
+}
+
+fn panel_id() -> &'static str {
+    let cfg = app_config();
+    if cfg.panel_id.is_empty() {
+        cfg.mqtt_client_id.as_str()
+    } else {
+        cfg.panel_id.as_str()
+    }
+}
+
+fn peer_sync_key(action: PeerAction, alert_id: Option<&str>) -> String {
+    match action {
+        PeerAction::Remove => format!("alert:{}", alert_id.unwrap_or_default()),
+        PeerAction::Mute | PeerAction::Unmute => PEER_SYNC_MUTE_KEY.to_string(),
+    }
+}
+
+/// Registra la marca de la acción; devuelve false si ya hay una más reciente para la misma clave.
+fn claim_peer_sync_slot(key: String, ts: i64) -> bool {
+    let clock = PEER_SYNC_CLOCK.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut guard = clock
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match guard.get(&key) {
+        Some(last) if *last >= ts => false,
+        _ => {
+            guard.insert(key, ts);
+            true
+        }
+    }
+}
+
+fn broadcast_peer_action(action: PeerAction, alert_id: Option<&str>) {
+    let cfg = app_config();
+    let ts = corrected_now().timestamp_millis();
+    claim_peer_sync_slot(peer_sync_key(action, alert_id), ts);
+    if !cfg.peer_sync_enabled {
+        return;
+    }
+
+    let message = PeerSyncMessage {
+        panel: panel_id().to_string(),
+        action,
+        alert_id: alert_id.map(str::to_string),
+        ts,
+    };
+    match serde_json::to_vec(&message) {
+        Ok(payload) => {
+            if mqtt_publish(&cfg.peer_sync_topic, payload, QoS::AtLeastOnce) {
+                debug!("[PEER] Acción {:?} publicada", action);
+            }
+        }
+        Err(err) => warn!("[PEER] No se pudo serializar acción: {:?}", err),
+    }
+}
+
+fn handle_peer_sync_payload(payload: &[u8], app_handle: &EventSink) {
+    let message: PeerSyncMessage = match serde_json::from_slice(payload) {
+        Ok(data) => data,
+        Err(err) => {
+            warn!("[PEER] No se pudo parsear mensaje: {:?}", err);
+            return;
+        }
+    };
+
+    if message.panel == panel_id() {
+        return;
+    }
+
+    let key = peer_sync_key(message.action, message.alert_id.as_deref());
+    if !claim_peer_sync_slot(key, message.ts) {
+        debug!(
+            "[PEER] Acción {:?} de {} descartada por ser más antigua",
+            message.action, message.panel
+        );
+        return;
+    }
+
+    info!(
+        "[PEER] Aplicando {:?} desde panel {}",
+        message.action, message.panel
+    );
+    match message.action {
+        PeerAction::Remove => {
+            if let Some(id) = message.alert_id.as_deref() {
+                remove_alert_local(app_handle, id);
+            }
+        }
+        PeerAction::Mute => {
+            if has_active_alerts() && !with_mute_controller(|ctrl| ctrl.muted) {
+                mute_alerts_internal(app_handle);
+            }
+        }
+        PeerAction::Unmute => {
+            force_unmute(app_handle);
+        }
+    }
+}
+
+fn handle_incoming_publish(topic: &str, payload: &[u8], app_handle: &EventSink) {
+    let _span = pipeline_span("mqtt.publish", Some(("mqtt.topic", topic)));
+    let cfg = app_config();
+    if let Err(err) = validate_incoming_payload(topic, payload) {
+        warn!("[SCHEMA] Mensaje de {} rechazado: {}", topic, err);
+        dead_letter(topic, payload, err);
+        return;
+    }
+    if let Some(mapping) = cfg
+        .payload_mappings
+        .iter()
+        .find(|mapping| rumqttc::matches(topic, &mapping.topic))
+    {
+        handle_mapped_payload(mapping, topic, payload, app_handle);
+    } else if cfg.peer_sync_enabled && topic == cfg.peer_sync_topic {
+        handle_peer_sync_payload(payload, app_handle);
+    } else if is_telemetry_topic(topic) {
+        handle_telemetry_payload(topic, payload, app_handle);
+    } else if topic == MQTT_ATTRIBUTES_TOPIC {
+        handle_attributes_payload(payload, app_handle);
+    } else if topic.starts_with(MQTT_RPC_RESPONSE_PREFIX) {
+        handle_rpc_response_payload(topic, payload, app_handle);
+    } else {
+        handle_rpc_payload(topic, payload, app_handle);
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetter {
+    ts: String,
+    topic: String,
+    payload: String,
+    error: String,
+}
+
+fn with_dead_letters<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut VecDeque<DeadLetter>) -> R,
+{
+    let letters = DEAD_LETTERS.get_or_init(|| Mutex::new(VecDeque::new()));
+    let mut guard = letters
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+/// Guarda el mensaje descartado con el motivo; conserva los últimos `DEAD_LETTER_CAPACITY`.
+fn dead_letter(topic: &str, payload: &[u8], error: String) {
+    let payload = &payload[..payload.len().min(DEAD_LETTER_PAYLOAD_LIMIT)];
+    let letter = DeadLetter {
+        ts: corrected_now().to_rfc3339_opts(SecondsFormat::Millis, false),
+        topic: topic.to_string(),
+        payload: String::from_utf8_lossy(payload).into_owned(),
+        error,
+    };
+    with_dead_letters(|letters| {
+        if letters.len() >= DEAD_LETTER_CAPACITY {
+            letters.pop_front();
+        }
+        letters.push_back(letter);
+    });
+}
+
+#[tauri::command]
+fn get_dead_letters() -> Vec<DeadLetter> {
+    with_dead_letters(|letters| letters.iter().cloned().collect())
+}
+
+#[tauri::command]
+fn clear_dead_letters(window: tauri::Window) -> Result<(), String> {
+    check_write_access(&window)?;
+    let cleared = with_dead_letters(|letters| {
+        let cleared = letters.len();
+        letters.clear();
+        cleared
+    });
+    record_audit(
+        "local",
+        "clear_dead_letters",
+        "",
+        &format!("{} mensajes", cleared),
+    );
+    Ok(())
+}
+
+fn load_payload_schema(entry: &PayloadSchema) -> Result<serde_json::Value, String> {
+    match (&entry.schema, &entry.schema_path) {
+        (Some(schema), _) => Ok(schema.clone()),
+        (None, Some(path)) => {
+            let contents = fs::read_to_string(path)
+                .map_err(|err| format!("No se pudo leer {}: {}", path, err))?;
+            serde_json::from_str(&contents)
+                .map_err(|err| format!("JSON inválido en {}: {}", path, err))
+        }
+        (None, None) => Err("Sin schema ni schema_path".to_string()),
+    }
+}
+
+fn payload_schemas() -> &'static [Option<serde_json::Value>] {
+    PAYLOAD_SCHEMA_CACHE.get_or_init(|| {
+        app_config()
+            .payload_schemas
+            .iter()
+            .map(|entry| {
+                load_payload_schema(entry)
+                    .inspect_err(|err| error!("[SCHEMA] {}: {}", entry.topic, err))
+                    .ok()
+            })
+            .collect()
+    })
+}
+
+/// Valida contra el primer esquema cuyo filtro coincida; sin esquema el mensaje pasa.
+fn validate_incoming_payload(topic: &str, payload: &[u8]) -> Result<(), String> {
+    let entries = &app_config().payload_schemas;
+    let Some(index) = entries
+        .iter()
+        .position(|entry| rumqttc::matches(topic, &entry.topic))
+    else {
+        return Ok(());
+    };
+    let Some(schema) = payload_schemas().get(index).and_then(Option::as_ref) else {
+        return Ok(());
+    };
+    let value: serde_json::Value =
+        serde_json::from_slice(payload).map_err(|err| format!("JSON inválido: {}", err))?;
+    let mut errors = Vec::new();
+    validate_schema(schema, &value, "$", &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+fn json_type_matches(expected: &str, value: &serde_json::Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn validate_schema(
+    schema: &serde_json::Value,
+    value: &serde_json::Value,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            serde_json::Value::String(name) => vec![name.as_str()],
+            serde_json::Value::Array(names) => {
+                names.iter().filter_map(|name| name.as_str()).collect()
+            }
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| json_type_matches(name, value)) {
+            errors.push(format!(
+                "{}: se esperaba {}, llegó {}",
+                path,
+                types.join("|"),
+                value
+            ));
+            return;
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: debe ser {}", path, expected));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(|options| options.as_array()) {
+        if !options.contains(value) {
+            errors.push(format!(
+                "{}: {} no está entre los valores permitidos",
+                path, value
+            ));
+        }
+    }
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(|limit| limit.as_f64()) {
+            if number < minimum {
+                errors.push(format!("{}: {} menor que {}", path, number, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(|limit| limit.as_f64()) {
+            if number > maximum {
+                errors.push(format!("{}: {} mayor que {}", path, number, maximum));
+            }
+        }
+    }
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        if schema
+            .get("minLength")
+            .and_then(|limit| limit.as_u64())
+            .is_some_and(|limit| length < limit)
+        {
+            errors.push(format!("{}: texto demasiado corto", path));
+        }
+        if schema
+            .get("maxLength")
+            .and_then(|limit| limit.as_u64())
+            .is_some_and(|limit| length > limit)
+        {
+            errors.push(format!("{}: texto demasiado largo", path));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema
+            .get("required")
+            .and_then(|required| required.as_array())
+        {
+            for name in required.iter().filter_map(|name| name.as_str()) {
+                if !object.contains_key(name) {
+                    errors.push(format!("{}: falta {}", path, name));
+                }
+            }
+        }
+        let properties = schema
+            .get("properties")
+            .and_then(|properties| properties.as_object());
+        for (name, child) in object {
+            let child_path = format!("{}.{}", path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(child_schema) => validate_schema(child_schema, child, &child_path, errors),
+                None => match schema.get("additionalProperties") {
+                    Some(serde_json::Value::Bool(false)) => {
+                        errors.push(format!("{}: propiedad no permitida", child_path))
+                    }
+                    Some(extra @ serde_json::Value::Object(_)) => {
+                        validate_schema(extra, child, &child_path, errors)
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+    if let (Some(items), Some(item_schema)) = (value.as_array(), schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_schema(item_schema, item, &format!("{}[{}]", path, index), errors);
+        }
+    }
+}
+
+/// Evalúa una ruta `a.b[0].c` (índices negativos cuentan desde el final, `.` es la raíz).
+fn eval_path<'a>(path: &str, root: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+    let path = path.trim().trim_start_matches('$');
+    let mut current = root;
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (name, mut indexes) = match segment.find('[') {
+            Some(start) => (&segment[..start], &segment[start..]),
+            None => (segment, ""),
+        };
+        if !name.is_empty() {
+            current = current.get(name)?;
+        }
+        while let Some(rest) = indexes.strip_prefix('[') {
+            let end = rest.find(']')?;
+            let index: i64 = rest[..end].trim().parse().ok()?;
+            let items = current.as_array()?;
+            let index = if index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)?
+            } else {
+                index as usize
+            };
+            current = items.get(index)?;
+            indexes = &rest[end + 1..];
+        }
+    }
+    Some(current)
+}
+
+/// Expresión de extracción: rutas alternativas separadas por `||` (gana la primera no nula)
+/// y literales entre comillas simples, p. ej. `data.temp || values.temperature || '0'`.
+fn eval_expression(expression: &str, root: &serde_json::Value) -> Option<serde_json::Value> {
+    expression.split("||").find_map(|alternative| {
+        let alternative = alternative.trim();
+        if let Some(literal) = alternative
+            .strip_prefix('\'')
+            .and_then(|rest| rest.strip_suffix('\''))
+        {
+            return Some(serde_json::Value::String(literal.to_string()));
+        }
+        eval_path(alternative, root)
+            .filter(|value| !value.is_null())
+            .cloned()
+    })
+}
+
+fn json_to_f64(value: &serde_json::Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|text| text.trim().parse().ok()))
+}
+
+fn json_to_i64(value: &serde_json::Value) -> Option<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_f64().map(|number| number as i64))
+        .or_else(|| value.as_str().and_then(|text| text.trim().parse().ok()))
+}
+
+fn json_to_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn mapped_telemetry(
+    mapping: &PayloadMapping,
+    item: &serde_json::Value,
+) -> Result<TelemetryPayload, String> {
+    let field = |name: &str| {
+        mapping
+            .fields
+            .get(name)
+            .and_then(|expression| eval_expression(expression, item))
+    };
+    Ok(TelemetryPayload {
+        device: field("device")
+            .map(|value| json_to_text(&value))
+            .ok_or("Sin dispositivo")?,
+        value: field("value")
+            .as_ref()
+            .and_then(json_to_f64)
+            .ok_or("Sin valor numérico")?,
+        ts: field("ts").as_ref().and_then(json_to_i64),
+    })
+}
+
+/// Arma la alarma con la forma de ThingsBoard para reutilizar el flujo RPC existente.
+fn mapped_alarm(mapping: &PayloadMapping, item: &serde_json::Value) -> Result<AlarmParams, String> {
+    let field = |name: &str| {
+        mapping
+            .fields
+            .get(name)
+            .and_then(|expression| eval_expression(expression, item))
+    };
+    let text = |name: &str| field(name).map(|value| json_to_text(&value));
+    // Un booleano se interpreta como "activa"; un texto debe ser un estado de ThingsBoard.
+    let status = match field("status") {
+        Some(serde_json::Value::Bool(active)) => if active {
+            "ACTIVE_UNACK"
+        } else {
+            "CLEARED_UNACK"
+        }
+        .to_string(),
+        Some(value) => json_to_text(&value).to_uppercase(),
+        None => "ACTIVE_UNACK".to_string(),
+    };
+    let mut raw = serde_json::json!({
+        "id": { "id": text("id").ok_or("Sin id de alarma")? },
+        "createdTime": field("createdTime")
+            .as_ref()
+            .and_then(json_to_i64)
+            .unwrap_or_else(|| corrected_now().timestamp_millis()),
+        "type": text("type").ok_or("Sin tipo de alarma")?,
+        "originatorName": text("device").ok_or("Sin dispositivo")?,
+        "status": status,
+        "acknowledged": field("acknowledged").and_then(|value| value.as_bool()).unwrap_or(false),
+        "details": text("data").map(|data| serde_json::json!({ "data": data })),
+    });
+    // Sin severidad se omite la clave para que aplique el valor por defecto.
+    if let Some(severity) = text("severity") {
+        raw["severity"] = serde_json::Value::String(severity.to_uppercase());
+    }
+    let mut params: AlarmParams = serde_json::from_value(raw).map_err(|err| format!("{}", err))?;
+    params.raw = Some(item.clone());
+    Ok(params)
+}
+
+fn handle_mapped_payload(
+    mapping: &PayloadMapping,
+    topic: &str,
+    payload: &[u8],
+    app_handle: &EventSink,
+) {
+    let root: serde_json::Value = match serde_json::from_slice(payload) {
+        Ok(value) => value,
+        Err(err) => {
+            warn!("[MAPPING] Payload no JSON en {}: {:?}", topic, err);
+            return;
+        }
+    };
+    let items = match &mapping.each {
+        Some(path) => match eval_path(path, &root).and_then(serde_json::Value::as_array) {
+            Some(items) => items.clone(),
+            None => {
+                warn!("[MAPPING] {} no es un arreglo en {}", path, topic);
+                return;
+            }
+        },
+        None => vec![root],
+    };
+    for item in &items {
+        let result = match mapping.kind {
+            MappingKind::Telemetry => mapped_telemetry(mapping, item)
+                .map(|sample| handle_telemetry_sample(sample, app_handle)),
+            MappingKind::Alarm => mapped_alarm(mapping, item).map(|params| match params.status {
+                AlarmStatus::ActiveUnack | AlarmStatus::ActiveAck => {
+                    handle_active_alarm(params, app_handle)
+                }
+                AlarmStatus::ClearedUnack | AlarmStatus::ClearedAck => {
+                    handle_cleared_alarm(params, app_handle)
+                }
+                AlarmStatus::Unknown => {
+                    warn!("[MAPPING] Estado de alarma desconocido en {}", topic)
+                }
+            }),
+        };
+        if let Err(err) = result {
+            warn!("[MAPPING] No se pudo mapear mensaje de {}: {}", topic, err);
+        }
+    }
+}
+
+/// Resultado de pasar un payload por el pipeline sin aplicarlo.
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PayloadEvaluation {
+    topic: String,
+    /// `mapping`, `peerSync`, `telemetry`, `attributes` o `rpc`.
+    route: &'static str,
+    decisions: Vec<String>,
+    /// Alertas que se crearían o actualizarían, ya con su presentación.
+    alerts: Vec<Alert>,
+    cleared: Vec<String>,
+    telemetry: Vec<EvaluatedSample>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EvaluatedSample {
+    device: String,
+    value: f64,
+    ts_ms: i64,
+    defrost: bool,
+}
+
+impl PayloadEvaluation {
+    fn fail(&mut self, error: String) {
+        self.decisions.push(format!("Descartado: {}", error));
+        self.error = Some(error);
+    }
+
+    fn sample(&mut self, sample: TelemetryPayload) {
+        let cfg = app_config();
+        let ts_ms = sample.ts_ms();
+        let defrost = active_defrost_at(&sample.device, ts_ms).is_some();
+        self.decisions.push(format!(
+            "Telemetría {} = {}{}",
+            sample.device,
+            sample.value,
+            if defrost {
+                " (en descongelamiento, no cuenta para tendencias)"
+            } else {
+                ""
+            }
+        ));
+        let (high, low) = device_limits(&sample.device);
+        if let Some(high) = high.filter(|high| sample.value > *high) {
+            self.decisions
+                .push(format!("Sobre el umbral crítico alto ({})", high));
+        }
+        if let Some(low) = low.filter(|low| sample.value < *low) {
+            self.decisions
+                .push(format!("Bajo el umbral crítico bajo ({})", low));
+        }
+        let rules = cfg
+            .rate_of_change_rules
+            .iter()
+            .filter(|rule| {
+                rule.device
+                    .as_deref()
+                    .is_none_or(|device| device == sample.device)
+            })
+            .count();
+        if rules > 0 {
+            self.decisions.push(format!(
+                "Se evaluarían {} reglas de velocidad de cambio con el historial del equipo",
+                rules
+            ));
+        }
+        self.telemetry.push(EvaluatedSample {
+            device: sample.device,
+            value: sample.value,
+            ts_ms,
+            defrost,
+        });
+    }
+
+    fn alarm(&mut self, params: AlarmParams) {
+        let id = panel_alert_id(&params);
+        match params.status {
+            AlarmStatus::ActiveUnack | AlarmStatus::ActiveAck => {
+                let mut alert = alert_from_params(&params);
+                apply_projection(&mut alert);
+                match apply_defrost_rules(&mut alert) {
+                    Some(DefrostAction::Suppress) => {
+                        self.decisions.push(format!(
+                            "{}: suprimida por descongelamiento de {}",
+                            id, alert.device
+                        ));
+                        return;
+                    }
+                    Some(DefrostAction::Downgrade) => self.decisions.push(format!(
+                        "{}: degradada a WARNING por descongelamiento de {}",
+                        id, alert.device
+                    )),
+                    None => {}
+                }
+                let is_update = with_alert_store(|store| store.contains_key(&id));
+                self.decisions.push(format!(
+                    "{}: {} ({:?}, {:?}, {})",
+                    id,
+                    if is_update {
+                        "se actualizaría"
+                    } else {
+                        "se activaría"
+                    },
+                    alert.alert_type,
+                    alert.severity,
+                    alert.device
+                ));
+                self.alerts.push(with_display(&alert));
+            }
+            AlarmStatus::ClearedUnack | AlarmStatus::ClearedAck => {
+                let current = with_alert_store(|store| store.get(&id).map(|a| a.alarm_id.clone()));
+                let identity = &app_config().alert_identity;
+                self.decisions.push(match current {
+                    None => format!("{}: despeje sin alerta activa, se ignoraría", id),
+                    Some(current) if current != params.id.value => format!(
+                        "{}: despeje de {} ignorado, la alerta sigue a la alarma {}",
+                        id, params.id.value, current
+                    ),
+                    Some(_)
+                        if identity.mode == AlertIdentityMode::OriginatorType
+                            && identity.clear_grace_secs > 0 =>
+                    {
+                        format!(
+                            "{}: se retiraría si no se re-crea en {} s",
+                            id, identity.clear_grace_secs
+                        )
+                    }
+                    Some(_) => format!("{}: se retiraría", id),
+                });
+                self.cleared.push(id);
+            }
+            AlarmStatus::Unknown => self.decisions.push(format!(
+                "{}: estado de alarma desconocido, se ignoraría",
+                id
+            )),
+        }
+    }
+}
+
+/// Misma ruta que `handle_incoming_publish`, sin tocar el store, la telemetría ni las salidas.
+fn evaluate_payload_on(topic: &str, payload: &[u8]) -> PayloadEvaluation {
+    let cfg = app_config();
+    let mut evaluation = PayloadEvaluation {
+        topic: topic.to_string(),
+        ..PayloadEvaluation::default()
+    };
+    if let Err(err) = validate_incoming_payload(topic, payload) {
+        evaluation.fail(format!("no cumple el esquema: {}", err));
+        return evaluation;
+    }
+
+    if let Some(mapping) = cfg
+        .payload_mappings
+        .iter()
+        .find(|mapping| rumqttc::matches(topic, &mapping.topic))
+    {
+        evaluation.route = "mapping";
+        evaluation
+            .decisions
+            .push(format!("Mapeo {} ({:?})", mapping.topic, mapping.kind));
+        let root: serde_json::Value = match serde_json::from_slice(payload) {
+            Ok(value) => value,
+            Err(err) => {
+                evaluation.fail(format!("payload no JSON: {}", err));
+                return evaluation;
+            }
+        };
+        let items = match &mapping.each {
+            Some(path) => match eval_path(path, &root).and_then(serde_json::Value::as_array) {
+                Some(items) => items.clone(),
+                None => {
+                    evaluation.fail(format!("{} no es un arreglo", path));
+                    return evaluation;
+                }
+            },
+            None => vec![root],
+        };
+        for (index, item) in items.iter().enumerate() {
+            let result = match mapping.kind {
+                MappingKind::Telemetry => {
+                    mapped_telemetry(mapping, item).map(|sample| evaluation.sample(sample))
+                }
+                MappingKind::Alarm => {
+                    mapped_alarm(mapping, item).map(|params| evaluation.alarm(params))
+                }
+            };
+            if let Err(err) = result {
+                evaluation
+                    .decisions
+                    .push(format!("Elemento {}: no se pudo mapear: {}", index, err));
+            }
+        }
+    } else if cfg.peer_sync_enabled && topic == cfg.peer_sync_topic {
+        evaluation.route = "peerSync";
+        evaluation
+            .decisions
+            .push("Acción de otro panel: no se simula".to_string());
+    } else if is_telemetry_topic(topic) {
+        evaluation.route = "telemetry";
+        match serde_json::from_slice::<TelemetryPayload>(payload) {
+            Ok(sample) => evaluation.sample(sample),
+            Err(err) => evaluation.fail(format!("telemetría inválida: {}", err)),
+        }
+    } else if topic == MQTT_ATTRIBUTES_TOPIC {
+        evaluation.route = "attributes";
+        let value: serde_json::Value = serde_json::from_slice(payload).unwrap_or_default();
+        let attributes = value.get("shared").unwrap_or(&value);
+        for key in [
+            BUZZER_INHIBIT_ATTRIBUTE,
+            ON_CALL_SCHEDULE_ATTRIBUTE,
+            ZONES_ATTRIBUTE,
+            NOTIFICATION_ROUTES_ATTRIBUTE,
+            AUDIO_PROFILE_ATTRIBUTE,
+            cfg.server_time.attribute.as_str(),
+        ] {
+            if !key.is_empty() && attributes.get(key).is_some() {
+                evaluation
+                    .decisions
+                    .push(format!("Atributo compartido {}: se aplicaría", key));
+            }
+        }
+    } else if topic.starts_with(MQTT_RPC_RESPONSE_PREFIX) {
+        evaluation.route = "rpcResponse";
+        evaluation.decisions.push(
+            "Respuesta a un RPC del panel: sólo se usa si es la hora del servidor pendiente"
+                .to_string(),
+        );
+    } else {
+        evaluation.route = "rpc";
+        let (raw, request) = match parse_rpc_payload(payload) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                evaluation.fail(format!("RPC inválido: {}", err));
+                return evaluation;
+            }
+        };
+        let protected = raw
+            .get("method")
+            .and_then(serde_json::Value::as_str)
+            .filter(|method| {
+                cfg.rpc_security.enabled
+                    && cfg
+                        .rpc_security
+                        .methods
+                        .iter()
+                        .any(|protected| protected.eq_ignore_ascii_case(method))
+            });
+        if let Some(method) = protected {
+            evaluation.decisions.push(format!(
+                "RPC {} protegido: en vivo se exigen nonce, ts y firma (no se verifican aquí)",
+                method
+            ));
+        }
+        match request {
+            RpcRequest::Alarm(params) => evaluation.alarm(*params),
+            other => evaluation
+                .decisions
+                .push(format!("RPC {}: no genera alertas", other.kind())),
+        }
+    }
+    evaluation
+}
+
+/// Simula un payload (pegado o copiado de `get_dead_letters`) para depurar mapeos y reglas en el panel.
+#[tauri::command]
+fn evaluate_payload(sample_json: String, topic: Option<String>) -> PayloadEvaluation {
+    let topic = topic
+        .filter(|topic| !topic.trim().is_empty())
+        .unwrap_or_else(|| MQTT_RPC_REQUEST_TOPIC.replace('+', "0"));
+    evaluate_payload_on(&topic, sample_json.as_bytes())
+}
+
+/// Desde la CLI se usa un client id distinto para no desconectar al panel en marcha.
+fn mqtt_client_id() -> String {
+    let id = app_config().mqtt_client_id.as_str();
+    if CLI_MODE.load(Ordering::SeqCst) {
+        format!("{}-cli", id)
+    } else {
+        id.to_string()
+    }
+}
+
+fn build_mqtt_options() -> Option<MqttConnectOptions> {
+    build_mqtt_options_for(&config_draft(), mqtt_client_id())
+}
+
+/// Sin `Debug`: la contraseña no debe terminar en el log.
+#[derive(Clone)]
+struct MqttCredentials {
+    host: String,
+    port: u16,
+    username: String,
+    password: String,
+}
+
+impl MqttCredentials {
+    fn apply_to(&self, cfg: &mut AppConfig) {
+        cfg.mqtt_server = self.host.clone();
+        cfg.mqtt_port = self.port;
+        cfg.mqtt_username = self.username.clone();
+        cfg.mqtt_password = self.password.clone();
+    }
+}
+
+fn mqtt_credentials() -> Option<MqttCredentials> {
+    MQTT_CREDENTIALS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+fn validate_mqtt_credentials(cfg: &AppConfig, credentials: &MqttCredentials) -> Result<(), String> {
+    let host = credentials.host.as_str();
+    if host.is_empty() || host.contains(char::is_whitespace) || host.contains("://") {
+        return Err(format!(
+            "Servidor MQTT inválido: {:?} (sólo nombre o IP, sin esquema)",
+            host
+        ));
+    }
+    if credentials.port == 0 {
+        return Err("Puerto MQTT inválido: 0".to_string());
+    }
+    if credentials.username.is_empty() {
+        return Err("Usuario MQTT vacío".to_string());
+    }
+    if cfg.mqtt_auth.mode != MqttAuthMode::Static && !credentials.password.is_empty() {
+        return Err(
+            "MQTT_AUTH no es static: la contraseña la emite el servidor de tokens".to_string(),
+        );
+    }
+    network::resolve((host, credentials.port), cfg.network.ip_preference)
+        .map(|_| ())
+        .map_err(|err| format!("No se pudo resolver {}: {}", host, err))
+}
+
+/// Guarda las credenciales en el YAML y reconecta el loop MQTT con ellas sin reiniciar.
+fn apply_mqtt_credentials(credentials: MqttCredentials) -> Result<(), String> {
+    let mut draft = config_draft();
+    validate_mqtt_credentials(&draft, &credentials)?;
+    credentials.apply_to(&mut draft);
+    write_config_file(Path::new(CONFIG_PATH), &draft)?;
+    info!(
+        "[MQTT] Credenciales actualizadas: {}@{}:{}",
+        credentials.username, credentials.host, credentials.port
+    );
+    record_audit(
+        "local",
+        "mqtt_credentials_updated",
+        &format!("{}:{}", credentials.host, credentials.port),
+        &credentials.username,
+    );
+    *MQTT_CREDENTIALS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(credentials);
+    MQTT_CREDENTIALS_GENERATION.fetch_add(1, Ordering::SeqCst);
+    request_mqtt_reconnect();
+    Ok(())
+}
+
+/// Aprovisionamiento en campo: cambia broker y credenciales sin reiniciar la aplicación.
+#[tauri::command]
+async fn set_mqtt_credentials(
+    window: tauri::Window,
+    host: String,
+    port: u16,
+    username: String,
+    password: String,
+) -> Result<(), String> {
+    check_write_access(&window)?;
+    let credentials = MqttCredentials {
+        host: host.trim().to_string(),
+        port,
+        username: username.trim().to_string(),
+        password,
+    };
+    async_runtime::spawn_blocking(move || apply_mqtt_credentials(credentials))
+        .await
+        .map_err(|err| format!("{:?}", err))?
+}
+
+#[derive(Debug, Clone)]
+struct BrokerToken {
+    /// `token_url` y `client_id` con los que se obtuvo; otro par (p. ej. el asistente) no lo reutiliza.
+    source: (String, String),
+    access_token: String,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+fn fetch_broker_token(auth: &MqttAuthConfig) -> Result<BrokerToken, String> {
+    let mut form = vec![
+        ("grant_type", "client_credentials"),
+        ("client_id", auth.client_id.as_str()),
+        ("client_secret", auth.client_secret.as_str()),
+    ];
+    if !auth.scope.is_empty() {
+        form.push(("scope", auth.scope.as_str()));
+    }
+    if !auth.audience.is_empty() {
+        form.push(("audience", auth.audience.as_str()));
+    }
+    let body = ureq::AgentBuilder::new()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .post(&auth.token_url)
+        .send_form(&form)
+        .map_err(|err| format!("No se pudo obtener token de {}: {}", auth.token_url, err))?
+        .into_string()
+        .map_err(|err| format!("Respuesta de token incompleta: {}", err))?;
+    let response: TokenResponse = serde_json::from_str(&body)
+        .map_err(|err| format!("Respuesta de token inválida: {}", err))?;
+    let ttl = response
+        .expires_in
+        .map(Duration::from_secs)
+        .unwrap_or(MQTT_TOKEN_DEFAULT_TTL);
+    info!("[MQTT] Token de broker obtenido, vence en {:?}", ttl);
+    Ok(BrokerToken {
+        source: (auth.token_url.clone(), auth.client_id.clone()),
+        access_token: response.access_token,
+        expires_at: Instant::now() + ttl,
+    })
+}
+
+fn with_broker_token<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Option<BrokerToken>) -> R,
+{
+    let token = MQTT_TOKEN.get_or_init(|| Mutex::new(None));
+    let mut guard = token
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn token_refresh_margin(auth: &MqttAuthConfig) -> Duration {
+    Duration::from_secs(auth.refresh_margin_seconds)
+}
+
+/// Contraseña para conectar: la estática o un token vigente (renovado si está por vencer).
+fn mqtt_password(cfg: &AppConfig) -> Result<String, String> {
+    let auth = &cfg.mqtt_auth;
+    if auth.mode == MqttAuthMode::Static {
+        return Ok(cfg.mqtt_password.clone());
+    }
+    let source = (auth.token_url.clone(), auth.client_id.clone());
+    let cached = with_broker_token(|token| {
+        token
+            .as_ref()
+            .filter(|token| {
+                token.source == source
+                    && token.expires_at > Instant::now() + token_refresh_margin(auth)
+            })
+            .map(|token| token.access_token.clone())
+    });
+    if let Some(access_token) = cached {
+        return Ok(access_token);
+    }
+    let token = fetch_broker_token(auth)?;
+    let access_token = token.access_token.clone();
+    with_broker_token(|slot| *slot = Some(token));
+    Ok(access_token)
+}
+
+/// Renueva el token antes de que venza y fuerza la reconexión para presentar el nuevo.
+fn start_mqtt_token_refresh_loop() {
+    let auth = &app_config().mqtt_auth;
+    if auth.mode != MqttAuthMode::Oauth2 {
+        return;
+    }
+    supervise(
+        "mqtt-token-refresh",
+        true,
+        Some(MQTT_TOKEN_CHECK_TICK),
+        RestartPolicy::Always,
+        move |task| async move {
+            while !is_shutting_down() {
+                tokio::time::sleep(MQTT_TOKEN_CHECK_TICK).await;
+                task.beat();
+                let due = with_broker_token(|token| {
+                    token.as_ref().is_some_and(|token| {
+                        token.expires_at <= Instant::now() + token_refresh_margin(auth)
+                    })
+                });
+                if !due {
+                    continue;
+                }
+                let refreshed =
+                    async_runtime::spawn_blocking(|| mqtt_password(app_config()).is_ok()).await;
+                if matches!(refreshed, Ok(true)) {
+                    info!("[MQTT] Token renovado, reconectando");
+                    request_mqtt_reconnect();
+                }
+            }
+        },
+    );
+}
+
+/// Host para rumqttc, que prueba las direcciones de a una y en el orden del resolver. Sin TLS se
+/// le pasa la dirección que ganó la carrera; con TLS se conserva el nombre.
+fn broker_connect_host(cfg: &AppConfig) -> String {
+    let network = &cfg.network;
+    if !network.happy_eyeballs
+        || cfg.mqtt_use_secure_client
+        || cfg.mqtt_server.parse::<IpAddr>().is_ok()
+    {
+        return cfg.mqtt_server.clone();
+    }
+    let target = (cfg.mqtt_server.as_str(), cfg.mqtt_port);
+    match network::resolve(target, network.ip_preference)
+        .and_then(|addrs| network::connect_first(&addrs, BROKER_RACE_TIMEOUT))
+    {
+        Ok((addr, _)) => {
+            debug!("[MQTT] {} responde en {}", cfg.mqtt_server, addr);
+            addr.ip().to_string()
+        }
+        Err(err) => {
+            warn!(
+                "[MQTT] Ninguna dirección de {} respondió ({}); se deja resolver a rumqttc",
+                cfg.mqtt_server, err
+            );
+            cfg.mqtt_server.clone()
+        }
+    }
+}
+
+/// Opciones de conexión con la plataforma en la versión de `MQTT_PROTOCOL`.
+fn build_mqtt_options_for(cfg: &AppConfig, client_id: String) -> Option<MqttConnectOptions> {
+    let password = match mqtt_password(cfg) {
+        Ok(password) => password,
+        Err(err) => {
+            error!("[MQTT] {}", err);
+            return None;
+        }
+    };
+    let wss = cfg.mqtt_transport == MqttTransport::Wss;
+    // Con WebSocket rumqttc toma host, puerto y ruta de la URL e ignora el puerto aparte.
+    let host = if wss {
+        format!(
+            "wss://{}:{}{}",
+            cfg.mqtt_server, cfg.mqtt_port, cfg.mqtt_ws_path
+        )
+    } else {
+        broker_connect_host(cfg)
+    };
+    let keep_alive = Duration::from_secs(60);
+    let mut transport = Transport::Tcp;
+
+    if cfg.mqtt_use_secure_client {
+        // Detrás de un proxy 443 el handshake WebSocket va por HTTP/1.1, no por ALPN "mqtt".
+        let alpn = if wss {
+            b"http/1.1".to_vec()
+        } else {
+            b"mqtt".to_vec()
+        };
+        let client_auth = match mqtt_client_auth(cfg) {
+            Ok(client_auth) => client_auth,
+            Err(err) => {
+                error!("[MQTT] {}", err);
+                return None;
+            }
+        };
+        let tls_cfg = match cfg.mqtt_ca_source {
+            MqttCaSource::File => {
+                let ca_path = mqtt_ca_path(cfg);
+                let ca_bytes = match fs::read(&ca_path) {
+                    Ok(b) => b,
+                    Err(e) => {
+                        error!("[MQTT] No se pudo leer CA en {:?}: {:?}", ca_path, e);
+                        return None;
+                    }
+                };
+                TlsConfiguration::Simple {
+                    ca: ca_bytes,
+                    alpn: Some(vec![alpn]),
+                    client_auth,
+                }
+            }
+            MqttCaSource::System => match system_tls_config(alpn, client_auth) {
+                Ok(config) => TlsConfiguration::Rustls(Arc::new(config)),
+                Err(err) => {
+                    error!("[MQTT] {}", err);
+                    return None;
+                }
+            },
+        };
+        transport = if wss {
+            Transport::wss_with_config(tls_cfg)
+        } else {
+            Transport::tls_with_config(tls_cfg)
+        };
+    } else if wss {
+        error!("[MQTT] MQTT_TRANSPORT wss requiere MQTT_USE_SECURE_CLIENT");
+        return None;
+    }
+
+    match cfg.mqtt_protocol {
+        MqttProtocol::V4 => {
+            let mut mqttoptions = MqttOptions::new(client_id, host, cfg.mqtt_port);
+            mqttoptions
+                .set_credentials(cfg.mqtt_username.as_str(), password)
+                .set_keep_alive(keep_alive)
+                .set_transport(transport);
+            Some(MqttConnectOptions::V4(Box::new(mqttoptions)))
+        }
+        MqttProtocol::V5 => {
+            let mut mqttoptions = v5::MqttOptions::new(client_id, host, cfg.mqtt_port);
+            mqttoptions
+                .set_credentials(cfg.mqtt_username.as_str(), password)
+                .set_keep_alive(keep_alive)
+                .set_transport(transport);
+            let expiry = cfg.mqtt_v5.session_expiry_secs;
+            if expiry > 0 {
+                // Retoma la sesión que el broker guardó en lugar de empezar limpia.
+                mqttoptions
+                    .set_clean_start(false)
+                    .set_session_expiry_interval(Some(expiry));
+            }
+            Some(MqttConnectOptions::V5(Box::new(mqttoptions)))
+        }
+    }
+}
+
+/// Con clave configurada el certificado es obligatorio: sin él el error corta la conexión, igual
+/// que en el puente, en lugar de conectar sin autenticación mutua.
+fn mqtt_client_auth(cfg: &AppConfig) -> Result<Option<ClientAuth>, String> {
+    if cfg.mqtt_client_key.is_empty() {
+        return Ok(None);
+    }
+    let cert_path = mqtt_client_cert_path(cfg);
+    read_client_auth(&cert_path, Path::new(&cfg.mqtt_client_key)).map(Some)
+}
+
+/// Raíces del sistema operativo; los certificados ilegibles se ignoran con un aviso.
+fn system_root_store() -> Result<rustls::RootCertStore, String> {
+    let native = rustls_native_certs::load_native_certs();
+    for err in &native.errors {
+        warn!("[MQTT] Almacén de certificados del sistema: {}", err);
+    }
+    let mut roots = rustls::RootCertStore::empty();
+    let (added, ignored) = roots.add_parsable_certificates(native.certs);
+    if added == 0 {
+        return Err(format!(
+            "El almacén del sistema no tiene certificados raíz utilizables ({} ignorados)",
+            ignored
+        ));
+    }
+    debug!(
+        "[MQTT] {} certificados raíz del sistema ({} ignorados)",
+        added, ignored
+    );
+    Ok(roots)
+}
+
+/// Configuración rustls para `MQTT_CA_SOURCE: system`, con el mismo certificado de cliente.
+fn system_tls_config(
+    alpn: Vec<u8>,
+    client_auth: Option<ClientAuth>,
+) -> Result<rustls::ClientConfig, String> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|err| format!("Configuración TLS inválida: {}", err))?
+    .with_root_certificates(system_root_store()?);
+    let mut config = match client_auth {
+        Some((cert, key)) => {
+            let chain = CertificateDer::pem_slice_iter(&cert)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("Certificado de cliente inválido: {}", err))?;
+            let key = PrivateKeyDer::from_pem_slice(&key)
+                .map_err(|err| format!("Clave de cliente inválida: {}", err))?;
+            builder
+                .with_client_auth_cert(chain, key)
+                .map_err(|err| format!("Certificado de cliente rechazado: {}", err))?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = vec![alpn];
+    Ok(config)
+}
+
+/// Certificado y clave PEM tal como los espera `TlsConfiguration::Simple`.
+type ClientAuth = (Vec<u8>, Vec<u8>);
+
+fn read_client_auth(cert_path: &Path, key_path: &Path) -> Result<ClientAuth, String> {
+    let cert = fs::read(cert_path).map_err(|err| {
+        format!(
+            "Sin certificado de cliente en {}: {:?}",
+            cert_path.display(),
+            err
+        )
+    })?;
+    let key = fs::read(key_path).map_err(|err| {
+        format!(
+            "No se pudo leer clave de cliente {}: {:?}",
+            key_path.display(),
+            err
+        )
+    })?;
+    Ok((cert, key))
+}
+
+/// Cierra la sesión activa para que el loop reconecte con las opciones actualizadas.
+fn request_mqtt_reconnect() {
+    let Some(slot) = MQTT_CLIENT.get() else {
+        return;
+    };
+    let guard = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(client) = guard.as_ref() {
+        match client.try_disconnect() {
+            Ok(()) => info!("[MQTT] Reconexión solicitada"),
+            Err(err) => warn!("[MQTT] No se pudo solicitar reconexión: {}", err),
+        }
+    }
+}
+
+fn start_mqtt_loop(app_handle: EventSink) {
+    supervise(
+        "mqtt-loop",
+        true,
+        None,
+        RestartPolicy::OnPanic,
+        move |task| {
+            let app_handle = app_handle.clone();
+            async move {
+                let _task = task;
+                run_mqtt_loop(app_handle).await;
+            }
+        },
+    );
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MqttStatus {
+    connected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_error: Option<String>,
+    /// Intentos desde la última conexión; 0 mientras se está conectado.
+    attempts: u64,
+    reconnects: u64,
+}
+
+fn snapshot_mqtt_status() -> MqttStatus {
+    MqttStatus {
+        connected: MQTT_CONNECTED.load(Ordering::SeqCst),
+        last_error: MQTT_LAST_ERROR
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone(),
+        attempts: MQTT_ATTEMPTS.load(Ordering::SeqCst),
+        reconnects: MQTT_RECONNECTS.load(Ordering::Relaxed),
+    }
+}
+
+/// Emite `mqtt://status` cuando cambia la conexión o se registra un error nuevo.
+fn set_mqtt_connected(connected: bool, error: Option<String>, app_handle: &EventSink) {
+    let changed = MQTT_CONNECTED.swap(connected, Ordering::SeqCst) != connected;
+    if !changed && error.is_none() {
+        return;
+    }
+    {
+        let mut last_error = MQTT_LAST_ERROR
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if connected {
+            MQTT_ATTEMPTS.store(0, Ordering::SeqCst);
+            *last_error = None;
+        } else if error.is_some() {
+            *last_error = error;
+        }
+    }
+    if let Err(err) = app_handle.emit(MQTT_STATUS_EVENT, snapshot_mqtt_status()) {
+        warn!("[MQTT] No se pudo emitir estado de conexión: {:?}", err);
+    }
+    refresh_degradation(app_handle);
+}
+
+async fn run_mqtt_loop(app_handle: EventSink) {
+    let mut retry_delay = MQTT_RETRY_DELAY;
+    while !is_shutting_down() {
+        set_mqtt_connected(false, None, &app_handle);
+
+        // Resolver el broker o pedir un token bloquea: se arma fuera del runtime.
+        let built = async_runtime::spawn_blocking(|| {
+            let target = config_draft();
+            let options = build_mqtt_options_for(&target, mqtt_client_id());
+            (target, options)
+        })
+        .await;
+        let Ok((target, Some(mqttoptions))) = built else {
+            error!(
+                "[MQTT] No se pudieron construir las opciones MQTT. Reintentando en {:?}...",
+                retry_delay
+            );
+            MQTT_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+            set_mqtt_connected(
+                false,
+                Some("No se pudieron construir las opciones MQTT".to_string()),
+                &app_handle,
+            );
+            wait_mqtt_retry(&mut retry_delay).await;
+            continue;
+        };
+
+        info!(
+            "[MQTT] Intentando conectar ({}, {:?}) con {}:{} como {}",
+            if target.mqtt_transport == MqttTransport::Wss {
+                "WSS"
+            } else if target.mqtt_use_secure_client {
+                "TLS"
+            } else {
+                "TCP"
+            },
+            target.mqtt_protocol,
+            target.mqtt_server.as_str(),
+            target.mqtt_port,
+            mqtt_client_id()
+        );
+
+        let (client, mut eventloop) = mqttoptions.into_async(10);
+        set_mqtt_client(Some(client.clone()));
+        MQTT_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+
+        loop {
+            let event = tokio::select! {
+                event = eventloop.poll() => event,
+                _ = shutdown_requested() => {
+                    info!("[MQTT] Loop detenido por shutdown");
+                    let _ = client.try_disconnect();
+                    break;
+                }
+            };
+
+            match event {
+                Ok(Some(incoming)) => {
+                    set_mqtt_connected(true, None, &app_handle);
+                    match incoming {
+                        MqttIncoming::ConnAck => {
+                            retry_delay = MQTT_RETRY_DELAY;
+                            MQTT_SESSION_START_MS
+                                .store(corrected_now().timestamp_millis(), Ordering::SeqCst);
+                            // En otra tarea: el canal de solicitudes se vacía con `poll`.
+                            let client = client.clone();
+                            async_runtime::spawn(async move {
+                                subscribe_mqtt_topics(&client).await;
+                                let _ =
+                                    async_runtime::spawn_blocking(publish_client_attributes).await;
+                            });
+                        }
+                        MqttIncoming::Publish { topic, payload } => {
+                            let app_handle = app_handle.clone();
+                            let handled = async_runtime::spawn_blocking(move || {
+                                bridge_forward(&topic, &payload);
+                                handle_incoming_publish(&topic, &payload, &app_handle);
+                            })
+                            .await;
+                            if let Err(err) = handled {
+                                error!("[MQTT] Falló el manejo de un mensaje: {:?}", err);
+                            }
+                        }
+                        MqttIncoming::Other => {}
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!("[MQTT] Error en loop: {}", e);
+                    MQTT_RECONNECTS.fetch_add(1, Ordering::Relaxed);
+                    set_mqtt_connected(false, Some(e), &app_handle);
+                    break;
+                }
+            }
+        }
+
+        set_mqtt_client(None);
+
+        if is_shutting_down() {
+            break;
+        }
+
+        warn!(
+            "[MQTT] Loop MQTT finalizado. Reintentando en {:?}...",
+            retry_delay
+        );
+
+        wait_mqtt_retry(&mut retry_delay).await;
+    }
+
+    info!("[MQTT] Loop terminado");
+}
+
+/// Suscripciones de la sesión; se repiten en cada CONNACK por si el broker no guardó la sesión.
+async fn subscribe_mqtt_topics(client: &MqttClient) {
+    let cfg = app_config();
+    match client
+        .subscribe(MQTT_RPC_REQUEST_TOPIC, QoS::AtLeastOnce)
+        .await
+    {
+        Ok(()) => info!(
+            "[MQTT] Suscrito a solicitudes RPC en {}",
+            MQTT_RPC_REQUEST_TOPIC
+        ),
+        Err(err) => {
+            // Sin RPC el panel no recibe alarmas: se fuerza la reconexión.
+            error!(
+                "[MQTT] No se pudo suscribir a {}: {}. Reconectando...",
+                MQTT_RPC_REQUEST_TOPIC, err
+            );
+            let _ = client.try_disconnect();
+            return;
+        }
+    }
+
+    let telemetry_topic = cfg.mqtt_telemetry_topic.as_str();
+    if !telemetry_topic.is_empty() {
+        if !rumqttc::valid_filter(telemetry_topic) {
+            error!("[MQTT] Topic de telemetría inválido: {}", telemetry_topic);
+        } else if let Err(err) = client.subscribe(telemetry_topic, QoS::AtMostOnce).await {
+            warn!(
+                "[MQTT] No se pudo suscribir a telemetría {}: {}",
+                telemetry_topic, err
+            );
+        } else {
+            info!("[MQTT] Suscrito a telemetría en {}", telemetry_topic);
+        }
+    }
+
+    if cfg.peer_sync_enabled {
+        match client
+            .subscribe(cfg.peer_sync_topic.as_str(), QoS::AtLeastOnce)
+            .await
+        {
+            Ok(()) => info!(
+                "[PEER] Sincronización entre paneles en {}",
+                cfg.peer_sync_topic
+            ),
+            Err(err) => warn!(
+                "[PEER] No se pudo suscribir a {}: {}",
+                cfg.peer_sync_topic, err
+            ),
+        }
+    }
+
+    if !cfg.server_time.rpc_method.is_empty() {
+        if let Err(err) = client
+            .subscribe(MQTT_RPC_RESPONSE_TOPIC, QoS::AtLeastOnce)
+            .await
+        {
+            warn!(
+                "[MQTT] No se pudo suscribir a respuestas RPC {}: {}",
+                MQTT_RPC_RESPONSE_TOPIC, err
+            );
+        }
+    }
+    if cfg.remote_buzzer_inhibit_enabled
+        || cfg.on_call.sync_from_platform
+        || cfg.zones.sync_from_platform
+        || cfg.notification_routing.sync_from_platform
+        || cfg.audio_profile.remote_control
+        || !cfg.server_time.attribute.is_empty()
+    {
+        if let Err(err) = client
+            .subscribe(MQTT_ATTRIBUTES_TOPIC, QoS::AtLeastOnce)
+            .await
+        {
+            warn!(
+                "[MQTT] No se pudo suscribir a atributos {}: {}",
+                MQTT_ATTRIBUTES_TOPIC, err
+            );
+        }
+    }
+    for mapping in &cfg.payload_mappings {
+        if let Err(err) = client
+            .subscribe(mapping.topic.as_str(), QoS::AtLeastOnce)
+            .await
+        {
+            warn!(
+                "[MAPPING] No se pudo suscribir a {}: {}",
+                mapping.topic, err
+            );
+        }
+    }
+    if cfg.mqtt_bridge.enabled {
+        for rule in &cfg.mqtt_bridge.rules {
+            if let Err(err) = client
+                .subscribe(rule.source.as_str(), QoS::AtLeastOnce)
+                .await
+            {
+                warn!("[BRIDGE] No se pudo suscribir a {}: {}", rule.source, err);
+            }
+        }
+    }
+}
+
+/// Segmentos capturados por `+` y `#` en el orden del filtro; `None` si el topic no coincide.
+fn topic_captures(filter: &str, topic: &str) -> Option<Vec<String>> {
+    let mut captures = Vec::new();
+    let mut levels = topic.split('/');
+    for pattern in filter.split('/') {
+        match pattern {
+            "#" => {
+                captures.push(levels.by_ref().collect::<Vec<_>>().join("/"));
+                return Some(captures);
+            }
+            "+" => captures.push(levels.next()?.to_string()),
+            literal => {
+                if levels.next()? != literal {
+                    return None;
+                }
+            }
+        }
+    }
+    levels.next().is_none().then_some(captures)
+}
+
+fn flatten_json(prefix: &str, value: &serde_json::Value, fields: &mut BTreeMap<String, String>) {
+    let key = |name: &str| {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", prefix, name)
+        }
+    };
+    match value {
+        serde_json::Value::Object(map) => {
+            for (name, child) in map {
+                flatten_json(&key(name), child, fields);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                flatten_json(&key(&index.to_string()), child, fields);
+            }
+        }
+        serde_json::Value::String(text) => {
+            fields.insert(prefix.to_string(), text.clone());
+        }
+        other => {
+            fields.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+fn bridge_qos(qos: u8) -> QoS {
+    match qos {
+        2 => QoS::ExactlyOnce,
+        1 => QoS::AtLeastOnce,
+        _ => QoS::AtMostOnce,
+    }
+}
+
+/// Republica en el broker puente el mensaje recibido según la primera regla que coincida.
+fn bridge_forward(topic: &str, payload: &[u8]) {
+    let cfg = &app_config().mqtt_bridge;
+    if !cfg.enabled {
+        return;
+    }
+    let Some((rule, captures)) = cfg
+        .rules
+        .iter()
+        .find_map(|rule| topic_captures(&rule.source, topic).map(|captures| (rule, captures)))
+    else {
+        return;
+    };
+
+    let text = String::from_utf8_lossy(payload);
+    let mut fields = BTreeMap::new();
+    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(payload) {
+        flatten_json("", &json, &mut fields);
+    }
+    for (index, capture) in captures.into_iter().enumerate() {
+        fields.insert((index + 1).to_string(), capture);
+    }
+    fields.insert("topic".to_string(), topic.to_string());
+    fields.insert("panelId".to_string(), panel_id().to_string());
+    fields.insert("payload".to_string(), text.into_owned());
+
+    let target = render_template(&rule.target, &fields);
+    if !rumqttc::valid_topic(&target) {
+        warn!("[BRIDGE] Topic destino inválido para {}: {}", topic, target);
+        return;
+    }
+    let body = match &rule.payload {
+        Some(template) => render_template_with(template, &fields, json_escape).into_bytes(),
+        None => payload.to_vec(),
+    };
+
+    let Some(slot) = BRIDGE_CLIENT.get() else {
+        return;
+    };
+    let guard = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(client) = guard.as_ref() else {
+        debug!("[BRIDGE] Reenvío descartado, sin conexión: {}", target);
+        return;
+    };
+    match client.try_publish(target.as_str(), bridge_qos(rule.qos), rule.retain, body) {
+        Ok(()) => {
+            BRIDGE_FORWARDED.fetch_add(1, Ordering::Relaxed);
+            trace!("[BRIDGE] {} -> {}", topic, target);
+        }
+        Err(err) => warn!("[BRIDGE] No se pudo publicar en {}: {:?}", target, err),
+    }
+}
+
+fn set_bridge_client(client: Option<Client>) {
+    let slot = BRIDGE_CLIENT.get_or_init(|| Mutex::new(None));
+    let mut guard = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    *guard = client;
+}
+
+fn build_bridge_options(cfg: &MqttBridgeConfig) -> Option<MqttOptions> {
+    let client_id = if cfg.client_id.is_empty() {
+        format!("{}-bridge", mqtt_client_id())
+    } else {
+        cfg.client_id.clone()
+    };
+    let mut options = MqttOptions::new(client_id, cfg.server.as_str(), cfg.port);
+    if !cfg.username.is_empty() {
+        options.set_credentials(cfg.username.as_str(), cfg.password.as_str());
+    }
+    options.set_keep_alive(Duration::from_secs(60));
+    if !cfg.ca_path.is_empty() {
+        let ca = match fs::read(&cfg.ca_path) {
+            Ok(ca) => ca,
+            Err(err) => {
+                error!("[BRIDGE] No se pudo leer CA en {}: {:?}", cfg.ca_path, err);
+                return None;
+            }
+        };
+        let client_auth = if cfg.client_cert.is_empty() || cfg.client_key.is_empty() {
+            None
+        } else {
+            // Un broker con mTLS rechazaría la conexión: mejor no intentarla sin certificado.
+            match read_client_auth(Path::new(&cfg.client_cert), Path::new(&cfg.client_key)) {
+                Ok(client_auth) => Some(client_auth),
+                Err(err) => {
+                    error!("[BRIDGE] {}", err);
+                    return None;
+                }
+            }
+        };
+        options.set_transport(Transport::tls_with_config(TlsConfiguration::Simple {
+            ca,
+            alpn: None,
+            client_auth,
+        }));
+    }
+    Some(options)
+}
+
+/// Conexión de sólo publicación con el broker puente; se reconecta igual que la principal.
+fn start_bridge_loop() {
+    let cfg = &app_config().mqtt_bridge;
+    if !cfg.enabled || cfg.server.is_empty() || cfg.rules.is_empty() {
+        return;
+    }
+    supervise_thread("mqtt-bridge", false, RestartPolicy::OnPanic, move |_task| {
+        let mut retry_delay = MQTT_RETRY_DELAY;
+        while !is_shutting_down() {
+            let Some(options) = build_bridge_options(cfg) else {
+                sleep_mqtt_retry(&mut retry_delay);
+                continue;
+            };
+            info!(
+                "[BRIDGE] Conectando con {}:{} ({} reglas)",
+                cfg.server,
+                cfg.port,
+                cfg.rules.len()
+            );
+            let (client, mut connection) = Client::new(options, 100);
+            set_bridge_client(Some(client));
+            for event in connection.iter() {
+                if is_shutting_down() {
+                    break;
+                }
+                match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("[BRIDGE] Conectado");
+                        retry_delay = MQTT_RETRY_DELAY;
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        warn!("[BRIDGE] Error en loop: {:?}", err);
+                        break;
+                    }
+                }
+            }
+            set_bridge_client(None);
+            if is_shutting_down() {
+                break;
+            }
+            sleep_mqtt_retry(&mut retry_delay);
+        }
+        info!(
+            "[BRIDGE] Loop terminado ({} mensajes reenviados)",
+            BRIDGE_FORWARDED.load(Ordering::Relaxed)
+        );
+    });
+}
+
+#[tauri::command]
+fn is_mqtt_connected() -> bool {
+    MQTT_CONNECTED.load(Ordering::SeqCst)
+}
+
+/// Estado inicial del banner de conectividad; los cambios llegan por `mqtt://status`.
+#[tauri::command]
+fn get_mqtt_status() -> MqttStatus {
+    snapshot_mqtt_status()
+}
+
+#[tauri::command]
+fn is_supabase_connected() -> bool {
+    SUPABASE_CONNECTED.load(Ordering::SeqCst)
+}
+
+#[tauri::command]
+fn get_log_level() -> String {
+    log::max_level().to_string()
+}
+
+#[tauri::command]
+fn set_log_level(window: tauri::Window, level: String) -> Result<String, String> {
+    check_write_access(&window)?;
+    let filter = level
+        .trim()
+        .parse::<LevelFilter>()
+        .map_err(|_| format!("Nivel de log inválido: {}", level))?;
+    info!("[LOG] Nivel de log: {} -> {}", log::max_level(), filter);
+    log::set_max_level(filter);
+    Ok(filter.to_string())
+}
+
+fn start_supabase_loop(app_handle: EventSink) {
+    let cfg = app_config();
+    
+    if cfg.supabase_url.is_empty() || cfg.supabase_anon_key.is_empty() {
+        info!("[SUPABASE] No configurado, omitiendo inicialización");
+        return;
+    }
+
+    let supabase_url = cfg.supabase_url.clone();
+    let supabase_key = cfg.supabase_anon_key.clone();
+
+    supervise_thread(
+        "supabase-loop",
+        false,
+        RestartPolicy::OnPanic,
+        move |_task| {
+            let rt = tokio::runtime::Runtime::new().unwrap_or_else(|e| {
+                error!("[SUPABASE] No se pudo crear runtime: {:?}", e);
+                panic!("Runtime error");
+            });
+
+            rt.block_on(async {
+                let mut retry_delay = SUPABASE_RETRY_DELAY;
+
+                while !is_shutting_down() {
+                    SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
 
                     let realtime_url = supabase_url
                         .replace("https://", "wss://")
                         .replace("http://", "ws://");
                     let realtime_url = format!("{}/realtime/v1", realtime_url);
 
-                    info!(
-                        "[SUPABASE] Conectando a {}",
-                        realtime_url
-                    );
+                    info!("[SUPABASE] Conectando a {}", realtime_url);
+
+                    let client = match RealtimeClient::new(
+                        &realtime_url,
+                        RealtimeClientOptions {
+                            api_key: supabase_key.clone(),
+                            ..Default::default()
+                        },
+                    ) {
+                        Ok(c) => c,
+                        Err(err) => {
+                            error!(
+                            "[SUPABASE] No se pudo crear cliente: {:?}. Reintentando en {:?}...",
+                            err, retry_delay
+                        );
+                            tokio::time::sleep(retry_delay).await;
+                            retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
+                            continue;
+                        }
+                    };
+
+                    if let Err(err) = client.connect().await {
+                        error!(
+                            "[SUPABASE] No se pudo conectar: {:?}. Reintentando en {:?}...",
+                            err, retry_delay
+                        );
+                        tokio::time::sleep(retry_delay).await;
+                        retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
+                        continue;
+                    }
+
+                    info!("[SUPABASE] Conectado exitosamente");
+                    SUPABASE_CONNECTED.store(true, Ordering::SeqCst);
+                    retry_delay = SUPABASE_RETRY_DELAY;
+
+                    let channel = client
+                        .channel(SUPABASE_CHANNEL_NAME, Default::default())
+                        .await;
+                    let filter =
+                        PostgresChangesFilter::new(PostgresChangeEvent::Update, SUPABASE_DB_SCHEMA);
+                    let mut rx = channel.on_postgres_changes(filter).await;
+
+                    if let Err(err) = channel.subscribe().await {
+                        error!("[SUPABASE] Error al suscribirse: {:?}", err);
+                        SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
+                        tokio::time::sleep(retry_delay).await;
+                        retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
+                        continue;
+                    }
+
+                    info!(
+                        "[SUPABASE] Suscrito a cambios UPDATE en {}",
+                        SUPABASE_DB_SCHEMA
+                    );
+
+                    let mut should_reconnect = false;
+                    while !is_shutting_down() {
+                        match tokio::time::timeout(Duration::from_secs(2), rx.recv()).await {
+                            Ok(Some(change)) => {
+                                if let Ok(json_str) = serde_json::to_string(&change) {
+                                    if let Ok(payload) =
+                                        serde_json::from_str::<SupabaseUpdatePayload>(&json_str)
+                                    {
+                                        handle_supabase_update(&payload, &app_handle);
+                                    } else {
+                                        debug!("[SUPABASE] Payload deserializado incorrectamente");
+                                    }
+                                }
+                            }
+                            Ok(None) => {
+                                warn!("[SUPABASE] Conexión cerrada por servidor");
+                                should_reconnect = true;
+                                break;
+                            }
+                            Err(_) => {
+                                if is_shutting_down() {
+                                    should_reconnect = false;
+                                    break;
+                                }
+                            }
+                        }
+                    }
+
+                    if is_shutting_down() {
+                        SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
+                        info!("[SUPABASE] Shutdown finalizado");
+                        break;
+                    }
+
+                    if should_reconnect {
+                        warn!("[SUPABASE] Reconectando en {:?}...", retry_delay);
+                        tokio::time::sleep(retry_delay).await;
+                        retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
+                    }
+                }
+
+                info!("[SUPABASE] Loop terminado");
+                SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
+            });
+
+            rt.shutdown_timeout(Duration::from_secs(1));
+        },
+    );
+}
+
+/// Subcomandos de consola para scripts de aprovisionamiento y health checks.
+/// Devuelve `None` si los argumentos no corresponden a ningún subcomando.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let run: fn(&[&str]) -> i32 = match args.as_slice() {
+        ["alerts", "list"] => |_| cli_alerts_list(),
+        ["mqtt", "test"] => |_| cli_mqtt_test(),
+        ["buzzer", "test"] => |_| cli_buzzer_test(),
+        ["config", "validate"] => |_| cli_config_validate(),
+        ["alerts" | "mqtt" | "buzzer" | "config", ..] => {
+            eprintln!("{}", CLI_USAGE);
+            return Some(2);
+        }
+        _ => return None,
+    };
+
+    CLI_MODE.store(true, Ordering::SeqCst);
+    init_logging();
+    install_crypto_provider();
+    Some(run(&args[2..]))
+}
+
+/// Imprime en JSON las alertas activas del panel en marcha, las mismas que muestra la UI.
+fn cli_alerts_list() -> i32 {
+    let alerts = match query_control_socket("alerts list") {
+        Ok(reply) => reply.get("alerts").cloned().unwrap_or_default(),
+        Err(err) => {
+            eprintln!("No se pudo consultar el panel en marcha: {}", err);
+            return 1;
+        }
+    };
+    match serde_json::to_string_pretty(&alerts) {
+        Ok(json) => {
+            println!("{}", json);
+            0
+        }
+        Err(err) => {
+            eprintln!("No se pudieron serializar las alertas: {}", err);
+            1
+        }
+    }
+}
+
+#[cfg(unix)]
+fn control_socket_path() -> PathBuf {
+    Path::new(&app_config().data_dir).join(CONTROL_SOCKET_FILE)
+}
+
+/// Respuesta del panel a una orden del socket local, con la forma `{ok, ...}` de los RPC.
+#[cfg(unix)]
+fn control_reply(request: &str) -> serde_json::Value {
+    match request.trim() {
+        "alerts list" => serde_json::json!({ "ok": true, "alerts": snapshot_alerts() }),
+        other => serde_json::json!({
+            "ok": false,
+            "message": format!("Orden desconocida: {}", other),
+        }),
+    }
+}
+
+#[cfg(unix)]
+fn query_control_socket(request: &str) -> Result<serde_json::Value, String> {
+    use std::os::unix::net::UnixStream;
+
+    let path = control_socket_path();
+    let mut stream = UnixStream::connect(&path).map_err(|err| {
+        format!(
+            "{:?} no responde ({}); ¿está el panel en marcha?",
+            path, err
+        )
+    })?;
+    stream
+        .set_read_timeout(Some(CONTROL_SOCKET_TIMEOUT))
+        .and_then(|_| writeln!(stream, "{}", request))
+        .and_then(|_| stream.shutdown(std::net::Shutdown::Write))
+        .map_err(|err| format!("No se pudo enviar la orden: {}", err))?;
+    let mut body = String::new();
+    stream
+        .read_to_string(&mut body)
+        .map_err(|err| format!("Respuesta incompleta: {}", err))?;
+    let reply: serde_json::Value =
+        serde_json::from_str(&body).map_err(|err| format!("Respuesta inválida: {}", err))?;
+    if reply.get("ok").and_then(|ok| ok.as_bool()) == Some(true) {
+        Ok(reply)
+    } else {
+        Err(reply
+            .get("message")
+            .and_then(|message| message.as_str())
+            .unwrap_or("el panel rechazó la orden")
+            .to_string())
+    }
+}
+
+#[cfg(not(unix))]
+fn query_control_socket(_request: &str) -> Result<serde_json::Value, String> {
+    Err("el socket de control sólo existe en Linux".to_string())
+}
+
+/// Socket local (sólo accesible para el usuario del panel) que atiende las consultas de la CLI.
+#[cfg(unix)]
+fn start_control_socket() -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = control_socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        return Err(format!("{:?} ya lo atiende otra instancia", path));
+    }
+    // Restos de una ejecución anterior que no se cerró bien.
+    let _ = fs::remove_file(&path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("No se pudo crear {:?}: {}", dir, err))?;
+    }
+    let listener =
+        UnixListener::bind(&path).map_err(|err| format!("No se pudo abrir {:?}: {}", path, err))?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+        .map_err(|err| format!("No se pudo proteger {:?}: {}", path, err))?;
+    thread::Builder::new()
+        .name("control-socket".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                if is_shutting_down() {
+                    break;
+                }
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        warn!("[CLI] Conexión fallida en el socket de control: {}", err);
+                        continue;
+                    }
+                };
+                let mut request = String::new();
+                let read = stream
+                    .set_read_timeout(Some(CONTROL_SOCKET_TIMEOUT))
+                    .and_then(|_| {
+                        (&mut stream)
+                            .take(CONTROL_MAX_REQUEST)
+                            .read_to_string(&mut request)
+                    });
+                let reply = match read {
+                    Ok(_) => control_reply(&request),
+                    Err(err) => serde_json::json!({ "ok": false, "message": err.to_string() }),
+                };
+                if let Err(err) = writeln!(stream, "{}", reply) {
+                    debug!(
+                        "[CLI] No se pudo responder por el socket de control: {}",
+                        err
+                    );
+                }
+            }
+        })
+        .map_err(|err| format!("No se pudo crear el hilo del socket de control: {}", err))?;
+    info!("[CLI] Socket de control en {:?}", path);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn start_control_socket() -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(feature = "grpc")]
+fn start_grpc_server(sink: EventSink) -> Result<(), String> {
+    grpc::start(sink)
+}
+
+#[cfg(not(feature = "grpc"))]
+fn start_grpc_server(_sink: EventSink) -> Result<(), String> {
+    if app_config().grpc.enabled {
+        warn!("[GRPC] GRPC habilitado pero el binario se compiló sin la feature grpc");
+    }
+    Ok(())
+}
+
+/// Conecta con el broker y espera el CONNACK sin suscribirse a nada.
+fn test_mqtt_connection(options: MqttConnectOptions) -> Result<(), String> {
+    let deadline = Instant::now() + MQTT_TEST_TIMEOUT;
+    let no_answer = || format!("sin respuesta en {:?}", MQTT_TEST_TIMEOUT);
+    match options {
+        MqttConnectOptions::V4(mqttoptions) => {
+            let (client, mut connection) = Client::new(*mqttoptions, 10);
+            let result = loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match connection.recv_timeout(remaining) {
+                    Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => break Ok(()),
+                    Ok(Ok(_)) => continue,
+                    Ok(Err(err)) => break Err(err.to_string()),
+                    Err(_) => break Err(no_answer()),
+                }
+            };
+            let _ = client.disconnect();
+            result
+        }
+        MqttConnectOptions::V5(mqttoptions) => {
+            let (client, mut connection) = v5::Client::new(*mqttoptions, 10);
+            let result = loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match connection.recv_timeout(remaining) {
+                    Ok(Ok(v5::Event::Incoming(v5::Incoming::ConnAck(_)))) => break Ok(()),
+                    Ok(Ok(_)) => continue,
+                    Ok(Err(err)) => break Err(err.to_string()),
+                    Err(_) => break Err(no_answer()),
+                }
+            };
+            let _ = client.disconnect();
+            result
+        }
+    }
+}
+
+/// Prueba cada salida audible habilitada, una tras otra, para poder identificar cuál falla.
+fn run_buzzer_test() -> Result<(), String> {
+    let mut tested = 0;
+    let mut failures = Vec::new();
+    for output in signal_outputs().iter().filter(|output| output.enabled) {
+        tested += 1;
+        if let Err(err) = test_signal_output(output) {
+            failures.push(format!("{}: {}", output.name, err));
+        }
+    }
+
+    if tested == 0 {
+        Err("no hay salidas de señalización habilitadas".to_string())
+    } else if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+fn test_signal_output(output: &'static SignalOutput) -> Result<(), String> {
+    if !start_buzzer_blinking(
+        output,
+        Duration::from_millis(500),
+        Duration::from_millis(500),
+        None,
+    ) {
+        return Err("no se pudo activar la línea GPIO".to_string());
+    }
+    thread::sleep(BUZZER_TEST_DURATION);
+
+    if stop_buzzer_blinking(output) {
+        Ok(())
+    } else {
+        Err("no se pudo apagar la línea GPIO".to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AudibleTestResult {
+    started_at: String,
+    scheduled: bool,
+    outputs: Vec<String>,
+    passed: bool,
+    /// La entrada de realimentación confirmó el encendido y el apagado.
+    verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AudibleTestStatus {
+    enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_run: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last: Option<AudibleTestResult>,
+}
+
+fn with_last_audible_test<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Option<AudibleTestResult>) -> R,
+{
+    let last = LAST_AUDIBLE_TEST.get_or_init(|| Mutex::new(None));
+    let mut guard = last.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard)
+}
+
+fn audible_test_weekday(cfg: &AudibleTestConfig) -> Option<Weekday> {
+    let index = u8::try_from(cfg.weekday.checked_sub(1)?).ok()?;
+    Weekday::try_from(index).ok()
+}
+
+/// Próxima prueba programada; `None` si está deshabilitada o mal configurada.
+fn next_audible_test(cfg: &AudibleTestConfig, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+    if !cfg.enabled {
+        return None;
+    }
+    let time = schedule::parse_hhmm(&cfg.time)?;
+    Some(schedule::next_weekly(
+        after,
+        audible_test_weekday(cfg)?,
+        time,
+    ))
+}
+
+fn audible_test_outputs(cfg: &AudibleTestConfig) -> Vec<&'static SignalOutput> {
+    signal_outputs()
+        .iter()
+        .filter(|output| {
+            output.enabled && (cfg.outputs.is_empty() || cfg.outputs.contains(&output.name))
+        })
+        .collect()
+}
+
+/// `true` si la entrada indica que la sirena está energizada.
+fn read_audible_feedback(feedback: &AudibleFeedbackConfig) -> Result<bool, String> {
+    if !feedback.path.is_empty() {
+        let raw = fs::read_to_string(&feedback.path)
+            .map_err(|err| format!("no se pudo leer {}: {:?}", feedback.path, err))?;
+        let value: f64 = raw
+            .trim()
+            .parse()
+            .map_err(|_| format!("valor no numérico en {}: {}", feedback.path, raw.trim()))?;
+        return Ok(value >= feedback.threshold);
+    }
+
+    let (chip, line) = resolve_buzzer_line(&feedback.gpio)
+        .ok_or_else(|| format!("línea GPIO {} no disponible", feedback.gpio))?;
+    match Command::new("gpioget").arg(&chip).arg(&line).output() {
+        Ok(output) if output.status.success() => {
+            Ok(String::from_utf8_lossy(&output.stdout).trim() == "1")
+        }
+        Ok(output) => {
+            invalidate_buzzer_line(&feedback.gpio);
+            Err(format!(
+                "gpioget termino con codigo {:?}",
+                output.status.code()
+            ))
+        }
+        Err(err) => {
+            invalidate_buzzer_line(&feedback.gpio);
+            Err(format!("no se pudo ejecutar gpioget: {:?}", err))
+        }
+    }
+}
+
+/// Energiza las salidas de forma continua, lee la realimentación a mitad de la prueba y otra vez
+/// tras apagarlas; devuelve si la realimentación confirmó ambos estados.
+fn audible_test_sequence(
+    cfg: &AudibleTestConfig,
+    outputs: &[&'static SignalOutput],
+) -> Result<bool, String> {
+    if outputs.is_empty() {
+        return Err("no hay salidas de señalización habilitadas".to_string());
+    }
 
-                    let client = match RealtimeClient::new(
-                        &realtime_url,
-                        RealtimeClientOptions {
-                            api_key: supabase_key.clone(),
-                            ..Default::default()
-                        },
-                    ) {
-                        Ok(c) => c,
-                        Err(err) => {
-                            error!(
-                                "[SUPABASE] No se pudo crear cliente: {:?}. Reintentando en {:?}...",
-                                err, retry_delay
-                            );
-                            tokio::time::sleep(retry_delay).await;
-                            retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
-                            continue;
-                        }
-                    };
+    let mut failures = Vec::new();
+    let mut energized = false;
+    for output in outputs {
+        if set_output_level(output, true) {
+            energized = true;
+        } else {
+            failures.push(format!("{}: no se pudo activar", output.name));
+        }
+    }
 
-                    if let Err(err) = client.connect().await {
-                        error!(
-                            "[SUPABASE] No se pudo conectar: {:?}. Reintentando en {:?}...",
-                            err, retry_delay
-                        );
-                        tokio::time::sleep(retry_delay).await;
-                        retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
-                        continue;
-                    }
+    let duration = Duration::from_secs(cfg.duration_secs);
+    thread::sleep(duration / 2);
+    let on_reading = cfg.feedback.as_ref().map(read_audible_feedback);
+    thread::sleep(duration - duration / 2);
 
-                    info!("[SUPABASE] Conectado exitosamente");
-                    SUPABASE_CONNECTED.store(true, Ordering::SeqCst);
-                    retry_delay = SUPABASE_RETRY_DELAY;
+    for output in outputs {
+        if !stop_buzzer_blinking(output) {
+            failures.push(format!("{}: no se pudo apagar", output.name));
+        }
+    }
 
-                    let channel = client.channel(SUPABASE_CHANNEL_NAME, Default::default()).await;
-                    let filter = PostgresChangesFilter::new(PostgresChangeEvent::Update, SUPABASE_DB_SCHEMA);
-                    let mut rx = channel.on_postgres_changes(filter).await;
+    let Some(feedback) = &cfg.feedback else {
+        return if failures.is_empty() {
+            Ok(false)
+        } else {
+            Err(failures.join("; "))
+        };
+    };
+    thread::sleep(AUDIBLE_FEEDBACK_SETTLE);
+    let off_reading = read_audible_feedback(feedback);
+    match on_reading {
+        Some(Ok(true)) => {}
+        Some(Ok(false)) if energized => {
+            failures.push("la realimentación no detectó la sirena encendida".to_string())
+        }
+        Some(Err(err)) => failures.push(format!("realimentación: {}", err)),
+        _ => {}
+    }
+    match off_reading {
+        Ok(false) => {}
+        Ok(true) => failures.push("la realimentación sigue activa tras apagar".to_string()),
+        Err(err) => failures.push(format!("realimentación: {}", err)),
+    }
 
-                    if let Err(err) = channel.subscribe().await {
-                        error!("[SUPABASE] Error al suscribirse: {:?}", err);
-                        SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
-                        tokio::time::sleep(retry_delay).await;
-                        retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
-                        continue;
-                    }
+    if failures.is_empty() {
+        Ok(true)
+    } else {
+        Err(failures.join("; "))
+    }
+}
 
-                    info!(
-                        "[SUPABASE] Suscrito a cambios UPDATE en {}",
-                        SUPABASE_DB_SCHEMA
-                    );
+/// Ejecuta la prueba, restaura el estado de las salidas y deja el resultado en el audit log y,
+/// si falló, como alerta local hasta la próxima prueba correcta.
+fn run_audible_test(scheduled: bool, app_handle: &EventSink) -> AudibleTestResult {
+    let cfg = &app_config().audible_test;
+    let started_at = corrected_now().to_rfc3339_opts(SecondsFormat::Secs, false);
+    let outputs = audible_test_outputs(cfg);
+    let result = audible_test_sequence(cfg, &outputs);
+    apply_buzzer_policy();
 
-                    let mut should_reconnect = false;
-                    while !is_shutting_down() {
-                        match tokio::time::timeout(Duration::from_secs(2), rx.recv()).await {
-                            Ok(Some(change)) => {
-                                if let Ok(json_str) = serde_json::to_string(&change) {
-                                    if let Ok(payload) = serde_json::from_str::<SupabaseUpdatePayload>(&json_str) {
-                                        handle_supabase_update(&payload, &app_handle);
-                                    } else {
-                                        debug!("[SUPABASE] Payload deserializado incorrectamente");
-                                    }
-                                }
-                            }
-                            Ok(None) => {
-                                warn!("[SUPABASE] Conexión cerrada por servidor");
-                                should_reconnect = true;
-                                break;
-                            }
-                            Err(_) => {
-                                if is_shutting_down() {
-                                    should_reconnect = false;
-                                    break;
-                                }
-                            }
-                        }
-                    }
+    let result = AudibleTestResult {
+        started_at,
+        scheduled,
+        outputs: outputs.iter().map(|output| output.name.clone()).collect(),
+        passed: result.is_ok(),
+        verified: result.as_ref().is_ok_and(|verified| *verified),
+        error: result.err(),
+    };
+    let kind = if scheduled { "programada" } else { "manual" };
+    let targets = result.outputs.join(",");
+    match &result.error {
+        None => {
+            info!(
+                "[BUZZER] Prueba audible {} OK: {} (verificada: {})",
+                kind, targets, result.verified
+            );
+            record_audit("local", "audible_test", &targets, &format!("{}: OK", kind));
+        }
+        Some(err) => {
+            warn!("[BUZZER] Prueba audible {} FALLO: {}", kind, err);
+            record_audit(
+                "local",
+                "audible_test",
+                &targets,
+                &format!("{}: {}", kind, err),
+            );
+        }
+    }
+    apply_local_alert(
+        AUDIBLE_TEST_ALERT_ID,
+        result
+            .error
+            .as_ref()
+            .map(|err| format!("Prueba audible fallida: {}", err)),
+        app_handle,
+    );
+    with_last_audible_test(|last| *last = Some(result.clone()));
+    result
+}
 
+/// Prueba audible semanal a la hora `AUDIBLE_TEST` (hora de la planta).
+fn start_audible_test_loop(app_handle: EventSink) {
+    let cfg = &app_config().audible_test;
+    if next_audible_test(cfg, &plant_now()).is_none() {
+        if cfg.enabled {
+            warn!("[BUZZER] Prueba audible sin horario válido; no se programa");
+        }
+        return;
+    }
+    supervise(
+        "audible-test",
+        false,
+        None,
+        RestartPolicy::Always,
+        move |_task| {
+            let app_handle = app_handle.clone();
+            async move {
+                while !is_shutting_down() {
+                    let now = plant_now();
+                    let Some(next) = next_audible_test(&app_config().audible_test, &now) else {
+                        break;
+                    };
+                    info!("[BUZZER] Próxima prueba audible: {}", next.to_rfc3339());
+                    let wait = (next - now).to_std().unwrap_or(Duration::from_secs(3600));
+                    tokio::time::sleep(wait).await;
+                    while !is_shutting_down() && highest_audible_severity().is_some() {
+                        info!("[BUZZER] Prueba audible pospuesta: hay una alarma sonando");
+                        tokio::time::sleep(AUDIBLE_TEST_RETRY).await;
+                    }
                     if is_shutting_down() {
-                        SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
-                        info!("[SUPABASE] Shutdown finalizado");
                         break;
                     }
+                    let app_handle = app_handle.clone();
+                    if let Err(err) =
+                        async_runtime::spawn_blocking(move || run_audible_test(true, &app_handle))
+                            .await
+                    {
+                        warn!("[BUZZER] Fallo en prueba audible: {:?}", err);
+                    }
+                }
+            }
+        },
+    );
+}
 
-                    if should_reconnect {
+#[tauri::command]
+fn get_audible_test_status() -> AudibleTestStatus {
+    let cfg = &app_config().audible_test;
+    AudibleTestStatus {
+        enabled: cfg.enabled,
+        next_run: next_audible_test(cfg, &plant_now()).map(|next| next.to_rfc3339()),
+        last: with_last_audible_test(|last| last.clone()),
+    }
+}
+
+/// Ejecuta la prueba audible ahora, con la configuración de `AUDIBLE_TEST`.
+#[tauri::command]
+async fn run_audible_test_now(
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<AudibleTestResult, String> {
+    check_write_access(&window)?;
+    if highest_audible_severity().is_some() {
+        return Err("Hay una alarma sonando; pruebe cuando esté reconocida".to_string());
+    }
+    let sink = EventSink::App(app_handle);
+    async_runtime::spawn_blocking(move || run_audible_test(false, &sink))
+        .await
+        .map_err(|err| format!("{:?}", err))
+}
+
+fn cli_mqtt_test() -> i32 {
+    let cfg = app_config();
+    let Some(mqttoptions) = build_mqtt_options() else {
+        eprintln!("No se pudieron construir las opciones MQTT");
+        return 1;
+    };
+
+    match test_mqtt_connection(mqttoptions) {
+        Ok(()) => {
+            println!("MQTT OK: {}:{}", cfg.mqtt_server, cfg.mqtt_port);
+            0
+        }
+        Err(err) => {
+            eprintln!("MQTT FALLO: {}:{}: {}", cfg.mqtt_server, cfg.mqtt_port, err);
+            1
+        }
+    }
+}
+
+fn cli_buzzer_test() -> i32 {
+    if !signal_outputs().iter().any(|output| output.enabled) {
+        eprintln!("Salidas audibles deshabilitadas en configuración");
+        return 1;
+    }
+
+    match run_buzzer_test() {
+        Ok(()) => {
+            println!("Buzzer OK");
+            0
+        }
+        Err(err) => {
+            eprintln!("Buzzer FALLO: {}", err);
+            1
+        }
+    }
+}
+
+fn cli_config_validate() -> i32 {
+    let problems = validate_config_file(Path::new(CONFIG_PATH));
+    for problem in &problems {
+        let label = match problem.severity {
+            ProblemSeverity::Error => "ERROR",
+            ProblemSeverity::Warning => "AVISO",
+        };
+        println!("{} {}: {}", label, problem.field, problem.message);
+    }
+
+    if problems
+        .iter()
+        .any(|problem| problem.severity == ProblemSeverity::Error)
+    {
+        1
+    } else {
+        println!("{}: OK", CONFIG_PATH);
+        0
+    }
+}
+
+/// Subsistemas del backend con sus dependencias; el orden declarado desempata.
+fn startup_steps(sink: &EventSink) -> Vec<Step> {
+    let with_sink = |name: &'static str, start: fn(EventSink)| {
+        let sink = sink.clone();
+        Step::new(name, move || {
+            start(sink.clone());
+            Ok(())
+        })
+    };
+    let plain = |name: &'static str, start: fn()| {
+        Step::new(name, move || {
+            start();
+            Ok(())
+        })
+    };
+    vec![
+        plain("events", register_default_side_effects),
+        // La placa se detecta al arranque para que el perfil elegido quede en el log.
+        Step::new("hardware", probe_hardware).retries(2, STARTUP_RETRY_DELAY),
+        Step::new("db", || {
+            if !app_config().history_enabled {
+                return Ok(());
+            }
+            with_history_db(|_| Ok(()))
+        })
+        .timeout(Duration::from_secs(15))
+        .retries(3, STARTUP_RETRY_DELAY),
+        with_sink("network", start_network_monitor),
+        with_sink("mqtt", start_mqtt_loop).after(&["events", "network"]),
+        plain("mqtt-token", start_mqtt_token_refresh_loop).after(&["mqtt"]),
+        plain("bridge", start_bridge_loop).after(&["mqtt"]),
+        with_sink("supabase", start_supabase_loop).after(&["events", "network"]),
+        with_sink("projections", start_projection_loop).after(&["events"]),
+        with_sink("maintenance", start_maintenance_loop).after(&["db"]),
+        with_sink("audible-test", start_audible_test_loop).after(&["hardware"]),
+        with_sink("presence", start_presence_loop).after(&["events"]),
+        with_sink("badge-reader", start_badge_reader).after(&["events"]),
+        with_sink("operator-session", start_operator_session_loop),
+        plain("escalation", start_escalation_loop).after(&["mqtt"]),
+        plain("server-time", start_server_time_loop).after(&["mqtt"]),
+        plain("notification-digest", start_notification_digest_loop).after(&["mqtt"]),
+        plain("email-queue", start_email_queue_loop).after(&["network"]),
+        plain("metrics", start_metrics_loop).after(&["mqtt"]),
+        plain("modem", start_modem_loop),
+        with_sink("runtime-health", start_runtime_health_loop),
+        with_sink("cert-expiry", start_cert_expiry_loop),
+        plain("log-forward", start_log_forward_loop),
+        plain("tracing", init_tracing),
+        plain("mdns", start_mdns_advertisement).after(&["network"]),
+        Step::new("control-socket", start_control_socket),
+        {
+            let sink = sink.clone();
+            Step::new("grpc", move || start_grpc_server(sink.clone())).after(&["events"])
+        },
+    ]
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct StartupStepStatus {
+    name: &'static str,
+    /// `pending`, `started`, `failed`, `timedOut` o `skipped`.
+    state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    attempts: u32,
+    elapsed_ms: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct StartupStatus {
+    complete: bool,
+    /// Algún subsistema no arrancó y el panel funciona en arranque parcial.
+    partial: bool,
+    steps: Vec<StartupStepStatus>,
+}
+
+fn startup_step_status(report: &startup::StepReport) -> StartupStepStatus {
+    let (state, detail) = match &report.state {
+        StepState::Started => ("started", None),
+        StepState::Failed(err) => ("failed", Some(err.clone())),
+        StepState::TimedOut => ("timedOut", None),
+        StepState::Skipped(dep) => ("skipped", Some(format!("Sin {}", dep))),
+    };
+    StartupStepStatus {
+        name: report.name,
+        state,
+        detail,
+        attempts: report.attempts,
+        elapsed_ms: report.elapsed.as_millis() as u64,
+    }
+}
+
+fn update_startup_status(sink: &EventSink, update: impl FnOnce(&mut StartupStatus)) {
+    let status = {
+        let mut guard = STARTUP_STATUS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(status) = guard.as_mut() else {
+            return;
+        };
+        update(status);
+        status.partial = status
+            .steps
+            .iter()
+            .any(|step| !matches!(step.state, "pending" | "started"));
+        status.clone()
+    };
+    if let Err(err) = sink.emit(STARTUP_EVENT, status) {
+        warn!("[CORE] No se pudo emitir estado de arranque: {:?}", err);
+    }
+}
+
+/// Arranca los subsistemas en un hilo propio para no demorar la ventana mientras se reintenta.
+fn start_backend(sink: EventSink) {
+    let steps = startup_steps(&sink);
+    let pending = steps
+        .iter()
+        .map(|step| StartupStepStatus {
+            name: step.name,
+            state: "pending",
+            detail: None,
+            attempts: 0,
+            elapsed_ms: 0,
+        })
+        .collect();
+    *STARTUP_STATUS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(StartupStatus {
+        complete: false,
+        partial: false,
+        steps: pending,
+    });
+    let spawned = thread::Builder::new()
+        .name("startup".to_string())
+        .spawn(move || {
+            let result = startup::run(&steps, |report| {
+                match &report.state {
+                    StepState::Started => {
+                        debug!("[CORE] {} iniciado en {:?}", report.name, report.elapsed)
+                    }
+                    StepState::Failed(err) => error!(
+                        "[CORE] {} no arrancó tras {} intentos: {}",
+                        report.name, report.attempts, err
+                    ),
+                    StepState::TimedOut => error!(
+                        "[CORE] {} no respondió en {:?}; se sigue sin esperarlo",
+                        report.name, report.elapsed
+                    ),
+                    StepState::Skipped(dep) => {
+                        warn!("[CORE] {} omitido: {} no arrancó", report.name, dep)
+                    }
+                }
+                let step = startup_step_status(report);
+                update_startup_status(&sink, |status| {
+                    if let Some(slot) = status.steps.iter_mut().find(|slot| slot.name == step.name)
+                    {
+                        *slot = step;
+                    }
+                });
+            });
+            match result {
+                Ok(reports) => {
+                    let failed = reports
+                        .iter()
+                        .filter(|report| report.state != StepState::Started)
+                        .count();
+                    if failed == 0 {
+                        info!("[CORE] {} subsistemas iniciados", reports.len());
+                    } else {
                         warn!(
-                            "[SUPABASE] Reconectando en {:?}...",
-                            retry_delay
+                            "[CORE] Arranque parcial: {} de {} subsistemas sin iniciar",
+                            failed,
+                            reports.len()
                         );
-                        tokio::time::sleep(retry_delay).await;
-                        retry_delay = (retry_delay * 2).min(SUPABASE_MAX_RETRY_DELAY);
                     }
                 }
+                Err(err) => error!("[CORE] Orden de arranque inválido: {}", err),
+            }
+            update_startup_status(&sink, |status| status.complete = true);
+        });
+    if let Err(err) = spawned {
+        error!("[CORE] No se pudo crear el hilo de arranque: {:?}", err);
+    }
+}
 
-                info!("[SUPABASE] Loop terminado");
-                SUPABASE_CONNECTED.store(false, Ordering::SeqCst);
-            });
+#[tauri::command]
+fn get_startup_status() -> Option<StartupStatus> {
+    STARTUP_STATUS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
 
-            rt.shutdown_timeout(Duration::from_secs(1));
-        })
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
     {
-        error!("[SUPABASE] No se pudo iniciar hilo de conexión: {:?}", err);
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+                return;
+            }
+            Err(err) => warn!("[CORE] No se pudo escuchar SIGTERM: {:?}", err),
+        }
+    }
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        error!("[CORE] No se pudo escuchar Ctrl+C: {:?}", err);
+    }
+}
+
+/// Destino de un enlace `nxthmi://alert/<id>` resuelto contra el estado del panel.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AlertFocus {
+    id: String,
+    active: bool,
+    alert: Option<Alert>,
+    /// Último registro del historial cuando la alerta ya no está activa.
+    history: Option<HistoryEntry>,
+}
+
+/// Enlace para correos y códigos QR que abre el panel sobre la alerta.
+fn alert_deep_link(id: &str) -> String {
+    let encoded: String = id
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                char::from(byte).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect();
+    format!("{}://alert/{}", DEEP_LINK_SCHEME, encoded)
+}
+
+/// Id de alerta de `nxthmi://alert/<id>`; se ignoran query y fragmento.
+fn parse_alert_deep_link(url: &str) -> Option<String> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    if !scheme.eq_ignore_ascii_case(DEEP_LINK_SCHEME) {
+        return None;
+    }
+    let path = rest.split(['?', '#']).next()?.trim_end_matches('/');
+    let encoded = path.strip_prefix("alert/")?;
+    if encoded.is_empty() || encoded.contains('/') {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded;
+    while let Some(index) = rest.find('%') {
+        bytes.extend_from_slice(&rest.as_bytes()[..index]);
+        bytes.extend(from_hex(rest.get(index + 1..index + 3)?)?);
+        rest = &rest[index + 3..];
+    }
+    bytes.extend_from_slice(rest.as_bytes());
+    String::from_utf8(bytes).ok()
+}
+
+fn resolve_alert_focus(id: &str) -> AlertFocus {
+    let alert = with_alert_store(|store| store.get(id).map(with_display));
+    let history = if alert.is_none() {
+        last_history_entry(id).unwrap_or_else(|err| {
+            warn!(
+                "[DEEPLINK] No se pudo consultar historial de {}: {}",
+                id, err
+            );
+            None
+        })
+    } else {
+        None
+    };
+    AlertFocus {
+        id: id.to_string(),
+        active: alert.is_some(),
+        alert,
+        history,
+    }
+}
+
+fn focus_main_window(app_handle: &EventSink) {
+    let EventSink::App(app) = app_handle else {
+        return;
+    };
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let _ = window.unminimize();
+    let _ = window.show();
+    if let Err(err) = window.set_focus() {
+        warn!("[DEEPLINK] No se pudo enfocar la ventana: {:?}", err);
+    }
+}
+
+/// Enfoca el panel y avisa al frontend; queda pendiente por si la UI aún no cargó (arranque por enlace).
+fn open_deep_link(url: &str, app_handle: &EventSink) {
+    let Some(id) = parse_alert_deep_link(url) else {
+        warn!("[DEEPLINK] Enlace no reconocido: {}", url);
+        return;
+    };
+    let focus = resolve_alert_focus(&id);
+    info!(
+        "[DEEPLINK] Abriendo alerta {} (activa: {})",
+        id, focus.active
+    );
+    record_audit("local", "deep_link", &id, url);
+    focus_main_window(app_handle);
+    let slot = PENDING_ALERT_FOCUS.get_or_init(|| Mutex::new(None));
+    *slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(focus.clone());
+    if let Err(err) = app_handle.emit(ALERT_FOCUS_EVENT, &focus) {
+        warn!("[DEEPLINK] No se pudo emitir foco de alerta: {:?}", err);
     }
 }
 
+/// Enlace con el que se abrió el panel, si el frontend todavía no lo atendió.
+#[tauri::command]
+fn take_alert_focus() -> Option<AlertFocus> {
+    let slot = PENDING_ALERT_FOCUS.get_or_init(|| Mutex::new(None));
+    slot.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+}
+
+fn register_deep_links(app: &tauri::App) {
+    use tauri_plugin_deep_link::DeepLinkExt;
+
+    // En Linux y Windows el esquema se registra al arrancar (AppImage, instalaciones sin bundle).
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Err(err) = app.deep_link().register_all() {
+        warn!(
+            "[DEEPLINK] No se pudo registrar {}://: {:?}",
+            DEEP_LINK_SCHEME, err
+        );
+    }
+    let app_handle = EventSink::App(app.handle().clone());
+    if let Ok(Some(urls)) = app.deep_link().get_current() {
+        for url in urls {
+            open_deep_link(url.as_str(), &app_handle);
+        }
+    }
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            open_deep_link(url.as_str(), &app_handle);
+        }
+    });
+}
+
+/// Ejecuta el backend completo sin ventana, para gateways sin pantalla.
+pub fn run_headless() {
+    init_logging();
+    install_crypto_provider();
+    info!("[CORE] Iniciando en modo headless");
+    start_backend(EventSink::Headless);
+    async_runtime::block_on(wait_for_shutdown_signal());
+    request_shutdown();
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     init_logging();
-    tauri::Builder::default()
+    install_crypto_provider();
+    let builder = tauri::Builder::default();
+    // Un segundo lanzamiento (p. ej. al abrir un enlace nxthmi://) se reenvía a esta instancia.
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, _argv, _cwd| {
+        focus_main_window(&EventSink::App(app.clone()));
+    }));
+    builder
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_opener::init())
         .on_window_event(|_, event| match event {
             WindowEvent::CloseRequested { .. } | WindowEvent::Destroyed => {
@@ -1262,19 +18175,10 @@ pub fn run() {
             }
             _ => {}
         })
-        .invoke_handler(tauri::generate_handler![
-            get_active_alerts,
-            remove_alert,
-            check_internet_connection,
-            get_mute_status,
-            toggle_alerts_mute,
-            is_mqtt_connected,
-            is_supabase_connected
-        ])
+        .invoke_handler(app_commands!(command_handler))
         .setup(|app| {
-            let app_handle = app.handle();
-            start_mqtt_loop(app_handle.clone());
-            start_supabase_loop(app_handle.clone());
+            start_backend(EventSink::App(app.handle().clone()));
+            register_deep_links(app);
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,64 @@
+//! Bloqueo del panel tras intentos de PIN fallidos.
+//!
+//! Cada `max_attempts` fallos seguidos el panel se bloquea; el primer bloqueo dura `base` y cada
+//! uno siguiente el doble, hasta `max`. Un ingreso correcto o un desbloqueo remoto reinician la
+//! cuenta.
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub max_attempts: u32,
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl LockoutPolicy {
+    /// Duración del bloqueo número `lockouts` (desde 1).
+    pub fn cooldown(&self, lockouts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(lockouts.saturating_sub(1));
+        self.base
+            .saturating_mul(factor)
+            .min(self.max.max(self.base))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Lockout {
+    /// Fallos desde el último bloqueo o ingreso correcto.
+    pub failures: u32,
+    /// Bloqueos seguidos sin un ingreso correcto en medio.
+    pub lockouts: u32,
+    pub locked_until: Option<Instant>,
+}
+
+impl Lockout {
+    /// Tiempo restante de bloqueo en `now`, si lo hay.
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.locked_until
+            .map(|until| until.saturating_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Registra un PIN incorrecto; devuelve la duración del bloqueo si este fallo lo provoca.
+    pub fn record_failure(&mut self, now: Instant, policy: &LockoutPolicy) -> Option<Duration> {
+        self.failures += 1;
+        if self.failures < policy.max_attempts.max(1) {
+            return None;
+        }
+        self.failures = 0;
+        self.lockouts = self.lockouts.saturating_add(1);
+        let cooldown = policy.cooldown(self.lockouts);
+        self.locked_until = Some(now + cooldown);
+        Some(cooldown)
+    }
+
+    /// Intentos que quedan antes del próximo bloqueo.
+    pub fn attempts_left(&self, policy: &LockoutPolicy) -> u32 {
+        policy.max_attempts.max(1).saturating_sub(self.failures)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
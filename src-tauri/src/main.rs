@@ -2,5 +2,14 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-    nxt_hmi_lib::run()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = nxt_hmi_lib::run_cli(&args) {
+        std::process::exit(code);
+    }
+
+    if args.iter().any(|arg| arg == "--headless") {
+        nxt_hmi_lib::run_headless()
+    } else {
+        nxt_hmi_lib::run()
+    }
 }
@@ -0,0 +1,127 @@
+//! Conexiones TCP con preferencia de familia IP y "happy eyeballs" (RFC 8305).
+//!
+//! Las direcciones se intentan alternando familias a partir de la preferida. Cada intento arranca
+//! `HAPPY_EYEBALLS_DELAY` después del anterior, o apenas falla el anterior, y gana la primera
+//! conexión establecida: una ruta IPv6 rota no demora la IPv4 ni al revés.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Espera recomendada por RFC 8305 antes de lanzar el siguiente intento.
+pub const HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IpPreference {
+    /// Empieza por la familia que el resolver del sistema devuelve primero.
+    #[default]
+    Auto,
+    PreferIpv4,
+    PreferIpv6,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl IpPreference {
+    pub fn allows(self, addr: &SocketAddr) -> bool {
+        match self {
+            IpPreference::Ipv4Only => addr.is_ipv4(),
+            IpPreference::Ipv6Only => addr.is_ipv6(),
+            _ => true,
+        }
+    }
+}
+
+/// Filtra por `preference` y alterna familias empezando por la preferida; dentro de cada familia
+/// se respeta el orden recibido.
+pub fn order_addresses(addrs: Vec<SocketAddr>, preference: IpPreference) -> Vec<SocketAddr> {
+    let ipv6_first = match preference {
+        IpPreference::Auto => addrs.first().is_some_and(SocketAddr::is_ipv6),
+        IpPreference::PreferIpv6 | IpPreference::Ipv6Only => true,
+        IpPreference::PreferIpv4 | IpPreference::Ipv4Only => false,
+    };
+    let (ipv6, ipv4): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .filter(|addr| preference.allows(addr))
+        .partition(SocketAddr::is_ipv6);
+    let (first, second) = if ipv6_first {
+        (ipv6, ipv4)
+    } else {
+        (ipv4, ipv6)
+    };
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    let mut second = second.into_iter();
+    for addr in first {
+        ordered.push(addr);
+        ordered.extend(second.next());
+    }
+    ordered.extend(second);
+    ordered
+}
+
+/// Resuelve `target` y ordena el resultado; error si no queda ninguna dirección permitida.
+pub fn resolve<A: ToSocketAddrs>(
+    target: A,
+    preference: IpPreference,
+) -> io::Result<Vec<SocketAddr>> {
+    let addrs = order_addresses(target.to_socket_addrs()?.collect(), preference);
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!("sin direcciones permitidas por {:?}", preference),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Intenta `addrs` en orden y escalonadas; devuelve la primera conexión establecida antes de
+/// `timeout`. Los intentos que quedan en curso se cierran solos al terminar.
+pub fn connect_first(
+    addrs: &[SocketAddr],
+    timeout: Duration,
+) -> io::Result<(SocketAddr, TcpStream)> {
+    let deadline = Instant::now() + timeout;
+    let (tx, rx) = mpsc::channel();
+    let mut next = addrs.iter();
+    let mut pending = 0;
+    let mut last_err = None;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        if let Some(addr) = next.next().copied() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let _ = tx.send((addr, TcpStream::connect_timeout(&addr, remaining)));
+            });
+            pending += 1;
+        } else if pending == 0 {
+            break;
+        }
+        let wait = if next.len() > 0 {
+            HAPPY_EYEBALLS_DELAY.min(remaining)
+        } else {
+            remaining
+        };
+        match rx.recv_timeout(wait) {
+            Ok((addr, Ok(stream))) => return Ok((addr, stream)),
+            Ok((_, Err(err))) => {
+                pending -= 1;
+                last_err = Some(err);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("ninguna de {} direcciones respondió", addrs.len()),
+        )
+    }))
+}
@@ -0,0 +1,241 @@
+//! Horarios diarios en la zona horaria de la planta, correctos en los cambios de hora.
+//!
+//! Una hora de pared puede no existir (adelanto de primavera) o repetirse (atraso de otoño).
+//! Se resuelve igual que el modo "compatible" de los calendarios: en el hueco se avanza la
+//! duración del salto (02:30 → 03:30) y en la hora repetida se toma la primera ocurrencia.
+//!
+//! Los feriados (`HolidayCalendar`) permiten que un horario se comporte distinto esos días.
+
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Weekday,
+};
+use chrono_tz::Tz;
+use std::collections::BTreeSet;
+
+/// Parsea `HH:MM`; `None` si está vacío o mal formado.
+pub fn parse_hhmm(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+/// Zona IANA (`America/Santiago`); vacío = la zona del sistema, o UTC si no se puede detectar.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Ok(system_timezone().unwrap_or(Tz::UTC));
+    }
+    name.parse::<Tz>()
+        .map_err(|_| format!("Zona horaria desconocida: {}", name))
+}
+
+/// Zona configurada en el sistema operativo, si tiene nombre IANA.
+pub fn system_timezone() -> Option<Tz> {
+    iana_time_zone::get_timezone().ok()?.parse().ok()
+}
+
+/// Instante en que el reloj de pared marca `date` `time`, con la política de cambio de hora del módulo.
+pub fn resolve_local(tz: Tz, date: NaiveDate, time: NaiveTime) -> DateTime<Tz> {
+    let naive = date.and_time(time);
+    match tz.from_local_datetime(&naive) {
+        chrono::LocalResult::Single(at) => at,
+        chrono::LocalResult::Ambiguous(earliest, _) => earliest,
+        chrono::LocalResult::None => resolve_gap(tz, naive),
+    }
+}
+
+/// Hora inexistente: se interpreta con el desfase previo al salto, lo que la corre hacia adelante.
+fn resolve_gap(tz: Tz, naive: NaiveDateTime) -> DateTime<Tz> {
+    // Ningún salto de la base de zonas dura un día; 24 h antes la hora existe.
+    let before = tz
+        .from_local_datetime(&(naive - Duration::days(1)))
+        .earliest()
+        .map(|at| at.offset().fix())
+        .unwrap_or_else(|| tz.offset_from_utc_datetime(&naive).fix());
+    let utc = naive - Duration::seconds(i64::from(before.local_minus_utc()));
+    tz.from_utc_datetime(&utc)
+}
+
+/// Fecha de pared de inicio de la ocurrencia de `[start, end)` que contiene `at`.
+///
+/// Si `end <= start` la franja cruza la medianoche y termina al día siguiente; con `end == start`
+/// dura un día completo. La duración real sigue al reloj de pared: una guardia de 22:00 a 06:00
+/// dura 9 h la noche en que se atrasa la hora y 7 h cuando se adelanta.
+pub fn daily_range_start(at: &DateTime<Tz>, start: NaiveTime, end: NaiveTime) -> Option<NaiveDate> {
+    let tz = at.timezone();
+    let today = at.date_naive();
+    [today.pred_opt()?, today].into_iter().rev().find(|day| {
+        let end_day = if end <= start {
+            day.succ_opt()
+        } else {
+            Some(*day)
+        };
+        let Some(end_day) = end_day else {
+            return false;
+        };
+        let from = resolve_local(tz, *day, start);
+        let to = resolve_local(tz, end_day, end);
+        from <= *at && *at < to
+    })
+}
+
+/// Si `at` cae en la ventana que arranca a las `start` de cada día y dura `length` de tiempo real.
+pub fn in_daily_window(at: &DateTime<Tz>, start: NaiveTime, length: Duration) -> bool {
+    let tz = at.timezone();
+    let today = at.date_naive();
+    [today.pred_opt(), Some(today)]
+        .into_iter()
+        .flatten()
+        .any(|day| {
+            let from = resolve_local(tz, day, start);
+            from <= *at && *at < from + length
+        })
+}
+
+/// Próxima vez, estrictamente posterior a `after`, en que el reloj de pared marca `time`.
+pub fn next_daily(after: &DateTime<Tz>, time: NaiveTime) -> DateTime<Tz> {
+    let tz = after.timezone();
+    let mut day = after.date_naive();
+    loop {
+        let at = resolve_local(tz, day, time);
+        if at > *after {
+            return at;
+        }
+        match day.succ_opt() {
+            Some(next) => day = next,
+            None => return at,
+        }
+    }
+}
+
+/// Próxima vez, estrictamente posterior a `after`, en que es `weekday` y el reloj marca `time`.
+pub fn next_weekly(after: &DateTime<Tz>, weekday: Weekday, time: NaiveTime) -> DateTime<Tz> {
+    let mut at = next_daily(after, time);
+    for _ in 0..7 {
+        if at.weekday() == weekday {
+            break;
+        }
+        at = next_daily(&at, time);
+    }
+    at
+}
+
+/// Última vez, no posterior a `at`, en que el reloj de pared marcó `time` (hoy o ayer).
+pub fn last_daily(at: &DateTime<Tz>, time: NaiveTime) -> Option<DateTime<Tz>> {
+    let tz = at.timezone();
+    let today = at.date_naive();
+    [Some(today), today.pred_opt()]
+        .into_iter()
+        .flatten()
+        .map(|day| resolve_local(tz, day, time))
+        .find(|start| start <= at)
+}
+
+/// Feriados de la planta: fechas puntuales y fechas que se repiten todos los años.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HolidayCalendar {
+    dates: BTreeSet<NaiveDate>,
+    yearly: BTreeSet<(u32, u32)>,
+}
+
+impl HolidayCalendar {
+    /// Agrega `AAAA-MM-DD` (una vez) o `MM-DD` (todos los años).
+    pub fn add_entry(&mut self, entry: &str) -> Result<(), String> {
+        let entry = entry.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(entry, "%Y-%m-%d") {
+            self.dates.insert(date);
+            return Ok(());
+        }
+        // 2024 es bisiesto: así `02-29` también es válido.
+        let yearly = NaiveDate::parse_from_str(&format!("2024-{}", entry), "%Y-%m-%d")
+            .map_err(|_| format!("Feriado inválido (AAAA-MM-DD o MM-DD): {}", entry))?;
+        self.yearly.insert((yearly.month(), yearly.day()));
+        Ok(())
+    }
+
+    /// Importa los `VEVENT` de un calendario iCal (RFC 5545); devuelve cuántos se leyeron.
+    ///
+    /// Sólo se usa la fecha de `DTSTART`/`DTEND` (fin exclusivo) y `RRULE:FREQ=YEARLY`; los
+    /// eventos con hora cuentan como feriado el día completo.
+    pub fn add_ical(&mut self, text: &str) -> Result<usize, String> {
+        let mut events = 0;
+        let mut event: Option<IcalEvent> = None;
+        for line in unfold_ical(text) {
+            let (name, value) = line.split_once(':').unwrap_or((line.as_str(), ""));
+            let property = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+            match (property.as_str(), event.as_mut()) {
+                ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                    event = Some(IcalEvent::default());
+                }
+                ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                    if let Some(done) = event.take() {
+                        self.add_ical_event(done)?;
+                        events += 1;
+                    }
+                }
+                ("DTSTART", Some(current)) => current.start = Some(ical_date(value)?),
+                ("DTEND", Some(current)) => current.end = Some(ical_date(value)?),
+                ("RRULE", Some(current)) => {
+                    current.yearly = value
+                        .split(';')
+                        .any(|part| part.eq_ignore_ascii_case("FREQ=YEARLY"));
+                }
+                _ => {}
+            }
+        }
+        if events == 0 {
+            return Err("El calendario iCal no contiene eventos".to_string());
+        }
+        Ok(events)
+    }
+
+    fn add_ical_event(&mut self, event: IcalEvent) -> Result<(), String> {
+        let start = event.start.ok_or("Evento iCal sin DTSTART".to_string())?;
+        let end = event
+            .end
+            .filter(|end| *end > start)
+            .unwrap_or(start + Duration::days(1));
+        for day in start.iter_days().take_while(|day| *day < end).take(366) {
+            if event.yearly {
+                self.yearly.insert((day.month(), day.day()));
+            } else {
+                self.dates.insert(day);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.dates.contains(&date) || self.yearly.contains(&(date.month(), date.day()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty() && self.yearly.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct IcalEvent {
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    yearly: bool,
+}
+
+/// Une las líneas plegadas de iCal (continuaciones que empiezan con espacio o tab).
+fn unfold_ical(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.lines() {
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(raw.trim_end().to_string()),
+        }
+    }
+    lines
+}
+
+/// `20241225` o `20241225T090000Z`: sólo interesa la fecha.
+fn ical_date(value: &str) -> Result<NaiveDate, String> {
+    value
+        .trim()
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| format!("Fecha iCal inválida: {}", value))
+}
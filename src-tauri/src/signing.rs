@@ -0,0 +1,257 @@
+//! Firmas HMAC-SHA256 de los RPC de control y de los tokens de acción de las notificaciones.
+//!
+//! Un RPC protegido lleva `ts` (ms epoch), `nonce` y, si hay secreto, `signature` =
+//! `hex(hmac(método|ts|nonce|params canónicos))`. Se rechaza si la marca se aleja de la hora del
+//! servidor más que el margen, si la firma no coincide o si el nonce ya se vio dentro de la
+//! ventana. La hora y el instante local los pasa quien llama, así no hay reloj oculto.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub type HmacSha256 = Hmac<Sha256>;
+
+/// `None` con el secreto vacío: sin secreto no se firma ni se verifica.
+pub fn keyed_mac(secret: &str) -> Option<HmacSha256> {
+    if secret.is_empty() {
+        return None;
+    }
+    HmacSha256::new_from_slice(secret.as_bytes()).ok()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// JSON con claves ordenadas, para que la firma no dependa del orden de serialización.
+pub fn canonical_json(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<(&String, &serde_json::Value)> = map.iter().collect();
+            entries.sort_by(|left, right| left.0.cmp(right.0));
+            let fields: Vec<String> = entries
+                .into_iter()
+                .map(|(key, child)| {
+                    format!(
+                        "{}:{}",
+                        serde_json::Value::String(key.clone()),
+                        canonical_json(child)
+                    )
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        serde_json::Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(canonical_json)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        other => other.to_string(),
+    }
+}
+
+/// Firma que espera el panel para un RPC, en hex.
+pub fn rpc_signature(
+    secret: &str,
+    method: &str,
+    ts: i64,
+    nonce: &str,
+    params: &serde_json::Value,
+) -> Option<String> {
+    let mut mac = keyed_mac(secret)?;
+    mac.update(rpc_message(method, ts, nonce, params).as_bytes());
+    Some(to_hex(&mac.finalize().into_bytes()))
+}
+
+fn rpc_message(method: &str, ts: i64, nonce: &str, params: &serde_json::Value) -> String {
+    format!("{}|{}|{}|{}", method, ts, nonce, canonical_json(params))
+}
+
+/// Nonces ya vistos con el instante local en que llegaron. Con el tope lleno se olvida el más
+/// viejo: sin secreto cualquiera puede enviar RPC y, si no, el conjunto crecería sin límite.
+#[derive(Debug)]
+pub struct NonceCache {
+    seen: HashMap<String, Instant>,
+    limit: usize,
+}
+
+impl NonceCache {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            seen: HashMap::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    pub fn contains(&self, nonce: &str) -> bool {
+        self.seen.contains_key(nonce)
+    }
+
+    /// Registra `nonce` en `now`; error si ya se vio dentro de `window`.
+    pub fn insert(&mut self, nonce: &str, now: Instant, window: Duration) -> Result<(), String> {
+        self.seen
+            .retain(|_, seen_at| now.saturating_duration_since(*seen_at) <= window);
+        if self.seen.contains_key(nonce) {
+            return Err(format!("nonce repetido: {}", nonce));
+        }
+        if self.seen.len() >= self.limit {
+            let oldest = self
+                .seen
+                .iter()
+                .min_by_key(|(_, seen_at)| **seen_at)
+                .map(|(nonce, _)| nonce.clone());
+            if let Some(oldest) = oldest {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen.insert(nonce.to_string(), now);
+        Ok(())
+    }
+}
+
+/// Verifica un RPC protegido (`raw` es el mensaje completo con `ts`, `nonce`, `signature` y
+/// `params`). `server_now_ms` es la hora corregida con el servidor, contra la que se mide `ts`;
+/// `now` es el reloj monótono local que mide la ventana de nonces (el doble del margen).
+pub fn verify_rpc(
+    method: &str,
+    raw: &serde_json::Value,
+    secret: &str,
+    max_skew: Duration,
+    server_now_ms: i64,
+    nonces: &mut NonceCache,
+    now: Instant,
+) -> Result<(), String> {
+    let nonce = raw
+        .get("nonce")
+        .and_then(serde_json::Value::as_str)
+        .filter(|nonce| !nonce.is_empty())
+        .ok_or("Falta nonce")?;
+    let ts = raw
+        .get("ts")
+        .and_then(serde_json::Value::as_i64)
+        .ok_or("Falta ts")?;
+    let max_skew = max_skew.max(Duration::from_secs(1));
+    let max_skew_ms = i64::try_from(max_skew.as_millis()).unwrap_or(i64::MAX);
+    if server_now_ms.saturating_sub(ts).saturating_abs() > max_skew_ms {
+        return Err(format!(
+            "ts fuera de margen ({} ms)",
+            server_now_ms.saturating_sub(ts)
+        ));
+    }
+
+    if let Some(mut mac) = keyed_mac(secret) {
+        let signature = raw
+            .get("signature")
+            .and_then(serde_json::Value::as_str)
+            .and_then(from_hex)
+            .ok_or("Falta firma")?;
+        let params = raw.get("params").unwrap_or(&serde_json::Value::Null);
+        mac.update(rpc_message(method, ts, nonce, params).as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| "Firma inválida".to_string())?;
+    }
+
+    // El nonce se registra después de verificar la firma para que un atacante no pueda "quemarlo".
+    nonces.insert(nonce, now, max_skew.saturating_mul(2))
+}
+
+/// Lo que firma un token de acción de notificación: qué hacer, hasta cuándo, en qué panel y
+/// sobre qué alerta o escalación. El nonce lo hace de un solo uso.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionClaims {
+    pub action: String,
+    pub expires_ms: i64,
+    pub panel: String,
+    pub nonce: String,
+    pub target: String,
+}
+
+/// `hex(acción|vence_ms|panel|nonce|objetivo).hex(hmac)`; `None` sin secreto.
+pub fn sign_action_token(secret: &str, claims: &ActionClaims) -> Option<String> {
+    let mut mac = keyed_mac(secret)?;
+    let text = format!(
+        "{}|{}|{}|{}|{}",
+        claims.action, claims.expires_ms, claims.panel, claims.nonce, claims.target
+    );
+    mac.update(text.as_bytes());
+    Some(format!(
+        "{}.{}",
+        to_hex(text.as_bytes()),
+        to_hex(&mac.finalize().into_bytes())
+    ))
+}
+
+/// Comprueba firma (en tiempo constante), vencimiento y panel. Que el nonce no se haya usado
+/// ya lo controla quien guarda los tokens atendidos.
+pub fn verify_action_token(
+    secret: &str,
+    token: &str,
+    panel: &str,
+    now_ms: i64,
+) -> Result<ActionClaims, String> {
+    let mut mac = keyed_mac(secret).ok_or("Acciones remotas deshabilitadas")?;
+    let (claims_hex, signature_hex) = token.trim().split_once('.').ok_or("Token mal formado")?;
+    let text = from_hex(claims_hex).ok_or("Token mal formado")?;
+    let signature = from_hex(signature_hex).ok_or("Token mal formado")?;
+    mac.update(&text);
+    mac.verify_slice(&signature)
+        .map_err(|_| "Firma de token inválida".to_string())?;
+
+    let text = String::from_utf8(text).map_err(|_| "Token mal formado")?;
+    let parts: Vec<&str> = text.splitn(5, '|').collect();
+    let [action, expires_ms, token_panel, nonce, target] = parts[..] else {
+        return Err("Token mal formado".to_string());
+    };
+    let expires_ms: i64 = expires_ms.parse().map_err(|_| "Token mal formado")?;
+    if now_ms > expires_ms {
+        return Err("Token vencido".to_string());
+    }
+    if token_panel != panel {
+        return Err(format!("Token emitido para otro panel: {}", token_panel));
+    }
+    Ok(ActionClaims {
+        action: action.to_string(),
+        expires_ms,
+        panel: token_panel.to_string(),
+        nonce: nonce.to_string(),
+        target: target.to_string(),
+    })
+}
+
+/// HMAC (hex) del JSON canónico de `value`; `None` sin secreto.
+pub fn sign_json(secret: &str, value: &serde_json::Value) -> Option<String> {
+    let mut mac = keyed_mac(secret)?;
+    mac.update(canonical_json(value).as_bytes());
+    Some(to_hex(&mac.finalize().into_bytes()))
+}
+
+/// Verifica en tiempo constante una firma de [`sign_json`].
+pub fn verify_json(secret: &str, value: &serde_json::Value, signature: &str) -> Result<(), String> {
+    let mut mac = keyed_mac(secret).ok_or("Sin secreto para verificar la firma")?;
+    let signature = from_hex(signature).ok_or("Firma mal formada")?;
+    mac.update(canonical_json(value).as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| "Firma inválida".to_string())
+}
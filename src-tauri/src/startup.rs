@@ -0,0 +1,185 @@
+//! Arranque ordenado de subsistemas.
+//!
+//! Cada paso declara de qué pasos depende y se ejecuta después de ellos, con su propio tiempo
+//! máximo y reintentos. Si un paso no arranca, los que dependen de él se saltan y el resto sigue:
+//! el panel queda en arranque parcial y el informe dice qué falta, en lugar de quedar a medias
+//! sin avisar.
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+type StepFn = Arc<dyn Fn() -> Result<(), String> + Send + Sync>;
+
+pub struct Step {
+    pub name: &'static str,
+    pub depends_on: Vec<&'static str>,
+    pub timeout: Duration,
+    pub retries: u32,
+    pub retry_delay: Duration,
+    run: StepFn,
+}
+
+impl Step {
+    /// Paso sin dependencias, un solo intento y 10 s de tiempo máximo.
+    pub fn new(
+        name: &'static str,
+        run: impl Fn() -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            depends_on: Vec::new(),
+            timeout: Duration::from_secs(10),
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            run: Arc::new(run),
+        }
+    }
+
+    pub fn after(mut self, names: &[&'static str]) -> Self {
+        self.depends_on.extend_from_slice(names);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepState {
+    Started,
+    Failed(String),
+    /// Sigue corriendo en su hilo; no se reintenta para no arrancarlo dos veces.
+    TimedOut,
+    /// No se ejecutó porque no arrancó la dependencia indicada.
+    Skipped(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    pub name: &'static str,
+    pub state: StepState,
+    pub attempts: u32,
+    pub elapsed: Duration,
+}
+
+/// Orden de ejecución respetando dependencias y, entre pasos libres, el orden declarado.
+pub fn order(steps: &[Step]) -> Result<Vec<usize>, String> {
+    let mut index = HashMap::new();
+    for (position, step) in steps.iter().enumerate() {
+        if index.insert(step.name, position).is_some() {
+            return Err(format!("Paso duplicado: {}", step.name));
+        }
+    }
+    for step in steps {
+        if let Some(missing) = step.depends_on.iter().find(|dep| !index.contains_key(*dep)) {
+            return Err(format!(
+                "{} depende de {}, que no existe",
+                step.name, missing
+            ));
+        }
+    }
+    let mut placed = vec![false; steps.len()];
+    let mut sequence = Vec::with_capacity(steps.len());
+    while sequence.len() < steps.len() {
+        let next = (0..steps.len()).find(|&position| {
+            !placed[position]
+                && steps[position]
+                    .depends_on
+                    .iter()
+                    .all(|dep| placed[index[dep]])
+        });
+        let Some(position) = next else {
+            let pending: Vec<&str> = (0..steps.len())
+                .filter(|&position| !placed[position])
+                .map(|position| steps[position].name)
+                .collect();
+            return Err(format!(
+                "Dependencias circulares entre {}",
+                pending.join(", ")
+            ));
+        };
+        placed[position] = true;
+        sequence.push(position);
+    }
+    Ok(sequence)
+}
+
+/// Ejecuta los pasos en orden y avisa de cada resultado a `on_report` a medida que termina.
+pub fn run(
+    steps: &[Step],
+    mut on_report: impl FnMut(&StepReport),
+) -> Result<Vec<StepReport>, String> {
+    let sequence = order(steps)?;
+    let mut reports: Vec<StepReport> = Vec::with_capacity(steps.len());
+    for position in sequence {
+        let step = &steps[position];
+        let blocked = step.depends_on.iter().copied().find(|dep| {
+            reports
+                .iter()
+                .any(|report| report.name == *dep && report.state != StepState::Started)
+        });
+        let report = match blocked {
+            Some(dep) => StepReport {
+                name: step.name,
+                state: StepState::Skipped(dep),
+                attempts: 0,
+                elapsed: Duration::ZERO,
+            },
+            None => run_step(step),
+        };
+        on_report(&report);
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+fn run_step(step: &Step) -> StepReport {
+    let start = Instant::now();
+    let mut attempts = 0;
+    let state = loop {
+        attempts += 1;
+        let state = attempt(step);
+        if !matches!(state, StepState::Failed(_)) || attempts > step.retries {
+            break state;
+        }
+        thread::sleep(step.retry_delay);
+    };
+    StepReport {
+        name: step.name,
+        state,
+        attempts,
+        elapsed: start.elapsed(),
+    }
+}
+
+fn attempt(step: &Step) -> StepState {
+    let (tx, rx) = mpsc::channel();
+    let run = step.run.clone();
+    let spawned = thread::Builder::new()
+        .name(format!("startup-{}", step.name))
+        .spawn(move || {
+            let _ = tx.send(run());
+        });
+    if let Err(err) = spawned {
+        return StepState::Failed(format!("No se pudo crear el hilo: {}", err));
+    }
+    match rx.recv_timeout(step.timeout) {
+        Ok(Ok(())) => StepState::Started,
+        Ok(Err(err)) => StepState::Failed(err),
+        Err(mpsc::RecvTimeoutError::Timeout) => StepState::TimedOut,
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            StepState::Failed("El paso terminó con pánico".to_string())
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Lectores de credenciales: `cargo test --test badge`.
+
+use nxt_hmi_lib::badge::{
+    decode_event, InputEvent, KeyDecoder, ScanBuffer, ScanKey, EV_KEY, INPUT_EVENT_SIZE,
+};
+use std::time::{Duration, Instant};
+
+fn raw_event(kind: u16, code: u16, value: i32) -> Vec<u8> {
+    let mut bytes = vec![0xaa; INPUT_EVENT_SIZE - 8];
+    bytes.extend(kind.to_ne_bytes());
+    bytes.extend(code.to_ne_bytes());
+    bytes.extend(value.to_ne_bytes());
+    bytes
+}
+
+fn key(code: u16, value: i32) -> InputEvent {
+    decode_event(&raw_event(EV_KEY, code, value)).unwrap()
+}
+
+#[test]
+fn decodifica_eventos_evdev() {
+    assert_eq!(
+        decode_event(&raw_event(EV_KEY, 30, 1)),
+        Some(InputEvent {
+            kind: EV_KEY,
+            code: 30,
+            value: 1
+        })
+    );
+    assert_eq!(decode_event(&[0; 8]), None);
+}
+
+#[test]
+fn traduce_teclas_con_mayusculas() {
+    let mut decoder = KeyDecoder::default();
+    let events = [
+        key(42, 1),
+        key(30, 1),
+        key(30, 0),
+        key(42, 0),
+        key(2, 1),
+        key(11, 2),
+        key(12, 1),
+        key(28, 1),
+    ];
+    let keys: Vec<ScanKey> = events.iter().filter_map(|e| decoder.feed(e)).collect();
+    assert_eq!(
+        keys,
+        [
+            ScanKey::Char('A'),
+            ScanKey::Char('1'),
+            ScanKey::Char('-'),
+            ScanKey::Enter
+        ]
+    );
+}
+
+#[test]
+fn solo_acepta_rafagas_de_lector() {
+    let mut buffer = ScanBuffer::new(Duration::from_millis(50), 4);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    // Un operador tecleando despacio: cada tecla reinicia el código.
+    for (index, c) in "12".chars().enumerate() {
+        assert_eq!(buffer.push(ScanKey::Char(c), at(index as u64 * 300)), None);
+    }
+    for (index, c) in "0042A".chars().enumerate() {
+        buffer.push(ScanKey::Char(c), at(1000 + index as u64 * 10));
+    }
+    assert_eq!(
+        buffer.push(ScanKey::Enter, at(1060)),
+        Some("0042A".to_string())
+    );
+    buffer.push(ScanKey::Char('7'), at(2000));
+    assert_eq!(buffer.push(ScanKey::Enter, at(2010)), None);
+}
+
+#[test]
+fn teclas_del_frontend() {
+    assert_eq!(ScanKey::from_dom("Enter"), Some(ScanKey::Enter));
+    assert_eq!(ScanKey::from_dom("ñ"), Some(ScanKey::Char('ñ')));
+    assert_eq!(ScanKey::from_dom("Shift"), None);
+}
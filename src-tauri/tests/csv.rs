@@ -0,0 +1,35 @@
+//! Planillas de puesta en marcha: `cargo test --test csv`.
+
+use nxt_hmi_lib::csv::parse;
+
+#[test]
+fn separador_punto_y_coma_de_excel() {
+    let table = parse("\u{feff}Device;Name;Temp_High\r\ncam-01;Cámara 1;-18,5\r\n").unwrap();
+    assert_eq!(table.headers, ["Device", "Name", "Temp_High"]);
+    assert_eq!(table.rows.len(), 1);
+    let row = &table.rows[0];
+    assert_eq!(table.get(row, "device"), "cam-01");
+    assert_eq!(table.get(row, "temp_high"), "-18,5");
+    assert_eq!(table.get(row, "zone"), "");
+}
+
+#[test]
+fn comillas_con_separadores_y_saltos() {
+    let text = "device,name,defrost\n\
+                \"cam-01\",\"Cámara \"\"A\"\", pasillo 2\",\"06:00+30;18:00+30\"\n\
+                cam-02,\"dos\nlíneas\",\n\
+                \n\
+                cam-03,tres,\n";
+    let table = parse(text).unwrap();
+    let lines: Vec<usize> = table.rows.iter().map(|row| row.line).collect();
+    assert_eq!(lines, [2, 3, 6]);
+    assert_eq!(table.get(&table.rows[0], "name"), "Cámara \"A\", pasillo 2");
+    assert_eq!(table.get(&table.rows[0], "defrost"), "06:00+30;18:00+30");
+    assert_eq!(table.get(&table.rows[1], "name"), "dos\nlíneas");
+}
+
+#[test]
+fn comilla_sin_cerrar_es_un_error() {
+    assert!(parse("device,name\ncam-01,\"sin cerrar\n").is_err());
+    assert!(parse("").is_err());
+}
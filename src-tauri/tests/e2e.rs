@@ -0,0 +1,185 @@
+//! Flujo completo: payload MQTT -> loop de conexión -> store de alertas -> eventos hacia la UI.
+
+use nxt_hmi_lib::e2e::{
+    alarm_rpc, alerts_since, command_schema, harness, registered_commands, registered_events,
+};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const RPC_TOPIC: &str = "v1/devices/me/rpc/request";
+
+#[test]
+fn active_alarm_reaches_store_and_frontend() {
+    let harness = harness();
+    harness.publish(
+        &format!("{}/1", RPC_TOPIC),
+        &alarm_rpc("e2e-active", "Cámara 1", "CRITICAL", "ACTIVE_UNACK"),
+    );
+
+    let added = harness.wait_for_event("alerts://added", TIMEOUT, |payload| {
+        payload["id"] == "e2e-active"
+    });
+    let added = added.expect("no se emitió alerts://added");
+    assert_eq!(added.payload["device"], "Cámara 1");
+    assert!(harness
+        .active_alerts()
+        .iter()
+        .any(|alert| alert.id == "e2e-active"));
+}
+
+#[test]
+fn cleared_alarm_is_removed() {
+    let harness = harness();
+    let topic = format!("{}/2", RPC_TOPIC);
+    harness.publish(
+        &topic,
+        &alarm_rpc("e2e-cleared", "Cámara 2", "MAJOR", "ACTIVE_UNACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://added", TIMEOUT, |payload| payload["id"]
+            == "e2e-cleared")
+        .is_some());
+
+    harness.publish(
+        &topic,
+        &alarm_rpc("e2e-cleared", "Cámara 2", "MAJOR", "CLEARED_UNACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://removed", TIMEOUT, |payload| payload["id"]
+            == "e2e-cleared")
+        .is_some());
+    assert!(harness.wait_until(TIMEOUT, || harness
+        .active_alerts()
+        .iter()
+        .all(|alert| alert.id != "e2e-cleared")));
+}
+
+#[test]
+fn alert_diff_returns_only_changes_since_cursor() {
+    let harness = harness();
+    let topic = format!("{}/7", RPC_TOPIC);
+    let start = alerts_since(None);
+    assert_eq!(start["full"], true);
+    let cursor = start["cursor"].as_str().unwrap().to_string();
+
+    harness.publish(
+        &topic,
+        &alarm_rpc("e2e-diff", "Cámara 7", "MINOR", "ACTIVE_UNACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://added", TIMEOUT, |payload| payload["id"]
+            == "e2e-diff")
+        .is_some());
+    let diff = alerts_since(Some(&cursor));
+    assert_eq!(diff["full"], false);
+    assert!(diff["added"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|alert| alert["id"] == "e2e-diff"));
+    let cursor = diff["cursor"].as_str().unwrap().to_string();
+
+    harness.publish(
+        &topic,
+        &alarm_rpc("e2e-diff", "Cámara 7", "MINOR", "CLEARED_UNACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://removed", TIMEOUT, |payload| payload["id"]
+            == "e2e-diff")
+        .is_some());
+    let diff = alerts_since(Some(&cursor));
+    assert!(diff["removed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|id| id == "e2e-diff"));
+    assert!(diff["added"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|alert| alert["id"] != "e2e-diff"));
+    assert_eq!(alerts_since(Some("0:0"))["full"], true);
+}
+
+#[test]
+fn get_state_is_answered_on_response_topic() {
+    let harness = harness();
+    harness.publish(
+        &format!("{}/3", RPC_TOPIC),
+        br#"{"method":"GET_STATE","params":{}}"#,
+    );
+    assert!(harness.wait_until(TIMEOUT, || !harness
+        .broker
+        .received("v1/devices/me/rpc/response/3")
+        .is_empty()));
+}
+
+#[test]
+fn malformed_payload_keeps_connection_alive() {
+    let harness = harness();
+    harness.publish(&format!("{}/4", RPC_TOPIC), b"{not json");
+    harness.publish(
+        &format!("{}/5", RPC_TOPIC),
+        &alarm_rpc("e2e-after-garbage", "Cámara 3", "WARNING", "ACTIVE_UNACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://added", TIMEOUT, |payload| {
+            payload["id"] == "e2e-after-garbage"
+        })
+        .is_some());
+}
+
+#[test]
+fn connection_status_is_pushed_to_frontend() {
+    let harness = harness();
+    let status = harness
+        .wait_for_event("mqtt://status", TIMEOUT, |payload| {
+            payload["connected"] == true
+        })
+        .expect("no se emitió mqtt://status al conectar");
+    assert_eq!(status.payload["attempts"], 0);
+    assert!(status.payload.get("lastError").is_none());
+}
+
+#[test]
+fn command_schema_matches_registry() {
+    let schema = command_schema();
+    let names = |key: &str| -> Vec<String> {
+        schema[key]
+            .as_array()
+            .expect("esquema sin lista")
+            .iter()
+            .filter_map(|entry| entry["name"].as_str().map(str::to_string))
+            .collect()
+    };
+    assert_eq!(names("commands"), registered_commands());
+    assert_eq!(names("events"), registered_events());
+    for key in ["commands", "events"] {
+        let mut unique = names(key);
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names(key).len(), "{} repetidos", key);
+    }
+}
+
+#[test]
+fn emitted_events_are_declared_in_schema() {
+    let harness = harness();
+    harness.publish(
+        &format!("{}/9", RPC_TOPIC),
+        &alarm_rpc("e2e-schema", "Cámara 9", "MAJOR", "ACTIVE_UNACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://added", TIMEOUT, |payload| payload["id"]
+            == "e2e-schema")
+        .is_some());
+
+    let declared = registered_events();
+    for event in harness.events() {
+        assert!(
+            declared.contains(&event.name.as_str()),
+            "{} se emite pero no está en el esquema",
+            event.name
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Silencio frente a alarmas repetidas: `cargo test --features e2e --test e2e_mute`.
+//!
+//! Binario aparte: cualquier alta en paralelo de `tests/e2e.rs` levantaría el silencio.
+
+use nxt_hmi_lib::e2e::{alarm_rpc, harness};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const TOPIC: &str = "v1/devices/me/rpc/request/1";
+
+#[test]
+fn ack_and_refresh_keep_mute_but_reactivation_lifts_it() {
+    let harness = harness();
+    harness.publish(
+        TOPIC,
+        &alarm_rpc("e2e-mute", "Cámara 1", "CRITICAL", "ACTIVE_UNACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://added", TIMEOUT, |payload| payload["id"]
+            == "e2e-mute")
+        .is_some());
+    harness.mute();
+    assert!(harness.muted());
+
+    harness.publish(
+        TOPIC,
+        &alarm_rpc("e2e-mute", "Cámara 1", "CRITICAL", "ACTIVE_ACK"),
+    );
+    assert!(harness
+        .wait_for_event("alerts://updated", TIMEOUT, |payload| {
+            payload["id"] == "e2e-mute" && payload["acknowledged"] == true
+        })
+        .is_some());
+    assert!(harness.muted(), "el ACK no debe quitar el silencio");
+
+    harness.publish(
+        TOPIC,
+        &alarm_rpc("e2e-mute", "Cámara 1", "CRITICAL", "ACTIVE_UNACK"),
+    );
+    let updates = |harness: &nxt_hmi_lib::e2e::Harness| {
+        harness
+            .events()
+            .iter()
+            .filter(|event| event.name == "alerts://updated" && event.payload["id"] == "e2e-mute")
+            .count()
+    };
+    assert!(harness.wait_until(TIMEOUT, || updates(harness) >= 2));
+    assert!(
+        harness.muted(),
+        "un refresco de la misma alarma no debe quitar el silencio"
+    );
+
+    harness.publish(
+        TOPIC,
+        &alarm_rpc("e2e-mute", "Cámara 1", "CRITICAL", "CLEARED_UNACK"),
+    );
+    harness.publish(
+        TOPIC,
+        &alarm_rpc("e2e-mute", "Cámara 1", "CRITICAL", "ACTIVE_UNACK"),
+    );
+    assert!(
+        harness.wait_until(TIMEOUT, || !harness.muted()),
+        "una reactivación tras un CLEAR debe quitar el silencio"
+    );
+}
@@ -0,0 +1,36 @@
+//! Tickets ESC/POS: `cargo test --test escpos`.
+
+use nxt_hmi_lib::escpos::{encode_pc850, wrap, Ticket};
+
+#[test]
+fn ticket_inicializa_y_corta() {
+    let mut ticket = Ticket::new(32);
+    ticket.bold("CRITICAL").text("Cámara 1");
+    let bytes = ticket.finish();
+    assert_eq!(bytes[..5], [0x1b, b'@', 0x1b, b't', 2]);
+    assert!(bytes.ends_with(&[0x1b, b'd', 4, 0x1d, b'V', 1]));
+    let text = [b"C".as_slice(), &[0xa0], b"mara 1\n"].concat();
+    assert!(bytes.windows(text.len()).any(|window| window == text));
+}
+
+#[test]
+fn acentos_en_pc850() {
+    assert_eq!(
+        encode_pc850("¿Señal -18°C?\t€"),
+        [
+            0xa8, b'S', b'e', 0xa4, b'a', b'l', b' ', b'-', b'1', b'8', 0xf8, b'C', b'?', b' ',
+            b'?'
+        ]
+    );
+    assert_eq!(encode_pc850("a\u{7}b"), b"ab");
+}
+
+#[test]
+fn parte_por_palabras_al_ancho() {
+    assert_eq!(
+        wrap("Temperatura alta en cámara de congelados", 16),
+        ["Temperatura alta", "en cámara de", "congelados"]
+    );
+    assert_eq!(wrap("ABCDEFGHIJ", 4), ["ABCD", "EFGH", "IJ"]);
+    assert_eq!(wrap("", 10), [""]);
+}
@@ -0,0 +1,44 @@
+//! Bloqueo por PIN: `cargo test --test lockout`.
+
+use nxt_hmi_lib::lockout::{Lockout, LockoutPolicy};
+use std::time::{Duration, Instant};
+
+const POLICY: LockoutPolicy = LockoutPolicy {
+    max_attempts: 3,
+    base: Duration::from_secs(30),
+    max: Duration::from_secs(100),
+};
+
+#[test]
+fn bloquea_al_agotar_intentos() {
+    let now = Instant::now();
+    let mut lockout = Lockout::default();
+    assert_eq!(lockout.record_failure(now, &POLICY), None);
+    assert_eq!(lockout.record_failure(now, &POLICY), None);
+    assert_eq!(lockout.attempts_left(&POLICY), 1);
+    assert_eq!(
+        lockout.record_failure(now, &POLICY),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(lockout.remaining(now), Some(Duration::from_secs(30)));
+    assert_eq!(lockout.remaining(now + Duration::from_secs(30)), None);
+    assert_eq!(lockout.attempts_left(&POLICY), 3);
+}
+
+#[test]
+fn espera_exponencial_con_tope() {
+    let now = Instant::now();
+    let mut lockout = Lockout::default();
+    let cooldowns: Vec<Duration> = (0..9)
+        .filter_map(|_| lockout.record_failure(now, &POLICY))
+        .collect();
+    assert_eq!(
+        cooldowns,
+        [30, 60, 100].map(Duration::from_secs),
+        "el tercer bloqueo queda en el tope"
+    );
+    lockout.reset();
+    assert_eq!(lockout.remaining(now), None);
+    assert_eq!(POLICY.cooldown(1), Duration::from_secs(30));
+    assert_eq!(POLICY.cooldown(u32::MAX), Duration::from_secs(100));
+}
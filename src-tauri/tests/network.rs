@@ -0,0 +1,82 @@
+//! Orden de direcciones y carrera de conexiones: `cargo test --test network`.
+
+use nxt_hmi_lib::network::{connect_first, order_addresses, resolve, IpPreference};
+use std::net::{SocketAddr, TcpListener};
+use std::time::{Duration, Instant};
+
+fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+    list.iter().map(|addr| addr.parse().unwrap()).collect()
+}
+
+/// Puerto local sin nadie escuchando: la conexión se rechaza al instante.
+fn closed_port() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+#[test]
+fn alterna_familias_desde_la_preferida() {
+    let resolved = addrs(&[
+        "10.0.0.1:1883",
+        "10.0.0.2:1883",
+        "[fd00::1]:1883",
+        "[fd00::2]:1883",
+    ]);
+    assert_eq!(
+        order_addresses(resolved.clone(), IpPreference::PreferIpv6),
+        addrs(&[
+            "[fd00::1]:1883",
+            "10.0.0.1:1883",
+            "[fd00::2]:1883",
+            "10.0.0.2:1883"
+        ])
+    );
+    assert_eq!(
+        order_addresses(resolved.clone(), IpPreference::Auto),
+        addrs(&[
+            "10.0.0.1:1883",
+            "[fd00::1]:1883",
+            "10.0.0.2:1883",
+            "[fd00::2]:1883"
+        ])
+    );
+    assert_eq!(
+        order_addresses(resolved, IpPreference::Ipv6Only),
+        addrs(&["[fd00::1]:1883", "[fd00::2]:1883"])
+    );
+}
+
+#[test]
+fn auto_sigue_al_resolver() {
+    let resolved = addrs(&["[fd00::1]:53", "10.0.0.1:53", "10.0.0.2:53"]);
+    assert_eq!(
+        order_addresses(resolved, IpPreference::Auto),
+        addrs(&["[fd00::1]:53", "10.0.0.1:53", "10.0.0.2:53"])
+    );
+}
+
+#[test]
+fn familia_excluida_es_un_error() {
+    assert!(resolve("127.0.0.1:1883", IpPreference::Ipv6Only).is_err());
+    assert_eq!(
+        resolve("127.0.0.1:1883", IpPreference::PreferIpv6).unwrap(),
+        addrs(&["127.0.0.1:1883"])
+    );
+}
+
+#[test]
+fn gana_la_primera_que_conecta() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap();
+    let started = Instant::now();
+    let (winner, _stream) = connect_first(&[closed_port(), open], Duration::from_secs(2)).unwrap();
+    assert_eq!(winner, open);
+    // El rechazo del primero adelanta el segundo intento.
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn sin_respuesta_devuelve_el_ultimo_error() {
+    assert!(connect_first(&[closed_port(), closed_port()], Duration::from_secs(1)).is_err());
+    assert!(connect_first(&[], Duration::from_secs(1)).is_err());
+}
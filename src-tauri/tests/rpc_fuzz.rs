@@ -0,0 +1,119 @@
+//! Propiedades del parser RPC: ninguna entrada, por rara que sea, puede provocar un pánico.
+
+use nxt_hmi_lib::fuzzing::rpc_payload;
+use proptest::prelude::*;
+use serde_json::{json, Value};
+
+fn arbitrary_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(|number| json!(number)),
+        any::<f64>().prop_map(|number| json!(number)),
+        ".*".prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+            prop::collection::hash_map(".*", inner, 0..8)
+                .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+/// Envoltorio con forma de alarma de ThingsBoard y campos arbitrarios en cada posición.
+fn alarm_like() -> impl Strategy<Value = Value> {
+    (
+        prop_oneof![Just(json!("ALARM")), Just(json!("alarm")), arbitrary_json()],
+        arbitrary_json(),
+        prop_oneof![any::<i64>().prop_map(|ts| json!(ts)), arbitrary_json()],
+        prop_oneof![
+            Just(json!("ACTIVE_UNACK")),
+            Just(json!("CLEARED_ACK")),
+            arbitrary_json()
+        ],
+        prop_oneof![Just(json!("CRITICAL")), arbitrary_json()],
+        arbitrary_json(),
+    )
+        .prop_map(|(method, id, created, status, severity, details)| {
+            json!({
+                "method": method,
+                "params": {
+                    "id": { "id": id },
+                    "createdTime": created,
+                    "type": "Temperature out of range",
+                    "originatorName": "fuzz",
+                    "status": status,
+                    "severity": severity,
+                    "details": details,
+                }
+            })
+        })
+}
+
+/// Métodos reconocidos con mayúsculas/minúsculas mezcladas (la comparación ignora el caso ASCII).
+fn known_method() -> impl Strategy<Value = String> {
+    (
+        prop::sample::select(vec!["GET_STATE", "setBuzzerInhibit", "notificationAction"]),
+        any::<u64>(),
+    )
+        .prop_map(|(method, mask)| {
+            method
+                .chars()
+                .enumerate()
+                .map(|(index, c)| {
+                    if mask >> (index % 64) & 1 == 1 {
+                        c.to_ascii_uppercase()
+                    } else {
+                        c.to_ascii_lowercase()
+                    }
+                })
+                .collect()
+        })
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic(data in prop::collection::vec(any::<u8>(), 0..512)) {
+        let _ = rpc_payload(&data);
+    }
+
+    #[test]
+    fn arbitrary_json_never_panics(value in arbitrary_json()) {
+        let _ = rpc_payload(value.to_string().as_bytes());
+    }
+
+    #[test]
+    fn alarm_shaped_payloads_never_panic(value in alarm_like()) {
+        let _ = rpc_payload(value.to_string().as_bytes());
+    }
+
+    #[test]
+    fn known_methods_are_recognised(method in known_method(), params in arbitrary_json()) {
+        let payload = json!({ "method": method, "params": params });
+        prop_assert!(rpc_payload(payload.to_string().as_bytes()).is_ok());
+    }
+}
+
+#[test]
+fn deeply_nested_payload_is_rejected() {
+    let payload = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+    assert!(rpc_payload(payload.as_bytes()).is_err());
+}
+
+#[test]
+fn sample_alarm_is_mapped() {
+    let payload = json!({
+        "method": "ALARM",
+        "params": {
+            "id": { "entityType": "ALARM", "id": "39a4ca65" },
+            "createdTime": 1763658232107i64,
+            "type": "Temperature out of range",
+            "originatorName": "Sensor push AE53",
+            "severity": "CRITICAL",
+            "status": "ACTIVE_UNACK",
+            "details": { "data": "Temperatura actual = 4.79" }
+        }
+    });
+    assert_eq!(rpc_payload(payload.to_string().as_bytes()), Ok("alarm"));
+}
@@ -0,0 +1,195 @@
+//! Horarios diarios frente a los cambios de hora: `cargo test --test schedule`.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Weekday};
+use chrono_tz::America::Santiago;
+use chrono_tz::Europe::Madrid;
+use chrono_tz::Tz;
+use nxt_hmi_lib::schedule::{
+    daily_range_start, in_daily_window, last_daily, next_daily, next_weekly, parse_timezone,
+    resolve_local, HolidayCalendar,
+};
+
+fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+fn time(h: u32, m: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(h, m, 0).unwrap()
+}
+
+/// Instante a partir de UTC, para no depender de la misma resolución que se está probando.
+fn utc(tz: Tz, y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Tz> {
+    tz.from_utc_datetime(&date(y, mo, d).and_time(time(h, mi)))
+}
+
+#[test]
+fn hora_inexistente_avanza_lo_que_dura_el_salto() {
+    // Madrid, 31/03/2024: 02:00 → 03:00.
+    let at = resolve_local(Madrid, date(2024, 3, 31), time(2, 30));
+    assert_eq!(at, utc(Madrid, 2024, 3, 31, 1, 30));
+    assert_eq!(at.format("%H:%M %:z").to_string(), "03:30 +02:00");
+}
+
+#[test]
+fn hora_repetida_toma_la_primera_ocurrencia() {
+    // Madrid, 27/10/2024: 03:00 → 02:00.
+    let at = resolve_local(Madrid, date(2024, 10, 27), time(2, 30));
+    assert_eq!(at, utc(Madrid, 2024, 10, 27, 0, 30));
+}
+
+#[test]
+fn medianoche_inexistente() {
+    // Santiago, 08/09/2024: 00:00 → 01:00.
+    let at = resolve_local(Santiago, date(2024, 9, 8), time(0, 0));
+    assert_eq!(at.format("%H:%M %:z").to_string(), "01:00 -03:00");
+    let now = utc(Santiago, 2024, 9, 8, 4, 10);
+    assert_eq!(last_daily(&now, time(0, 0)), Some(at));
+}
+
+#[test]
+fn siguiente_ejecucion_respeta_el_adelanto() {
+    let after = resolve_local(Madrid, date(2024, 3, 30), time(3, 0));
+    let next = next_daily(&after, time(3, 0));
+    assert_eq!(next, utc(Madrid, 2024, 3, 31, 1, 0));
+    assert_eq!(next - after, Duration::hours(23));
+}
+
+#[test]
+fn siguiente_ejecucion_dentro_del_hueco_no_se_salta() {
+    let after = resolve_local(Madrid, date(2024, 3, 30), time(12, 0));
+    let next = next_daily(&after, time(2, 0));
+    assert_eq!(next.date_naive(), date(2024, 3, 31));
+    assert_eq!(next.format("%H:%M").to_string(), "03:00");
+}
+
+#[test]
+fn hora_repetida_ejecuta_una_sola_vez() {
+    let first = resolve_local(Madrid, date(2024, 10, 27), time(2, 30));
+    let next = next_daily(&first, time(2, 30));
+    assert_eq!(next.date_naive(), date(2024, 10, 28));
+    assert_eq!(next - first, Duration::hours(25));
+}
+
+#[test]
+fn semanal_cae_en_el_dia_pedido_aunque_cambie_la_hora() {
+    // Viernes 29/03/2024; el domingo 31 se adelanta la hora.
+    let after = resolve_local(Madrid, date(2024, 3, 29), time(9, 0));
+    let next = next_weekly(&after, Weekday::Sun, time(2, 30));
+    assert_eq!(next.date_naive(), date(2024, 3, 31));
+    assert_eq!(next.format("%H:%M").to_string(), "03:30");
+
+    let same_day = resolve_local(Madrid, date(2024, 3, 29), time(10, 0));
+    assert_eq!(next_weekly(&after, Weekday::Fri, time(10, 0)), same_day);
+    let week_later = next_weekly(&same_day, Weekday::Fri, time(10, 0));
+    assert_eq!(week_later.date_naive(), date(2024, 4, 5));
+}
+
+#[test]
+fn ventana_dura_tiempo_real_al_atrasar_la_hora() {
+    // Descongelamiento 01:30 + 60 min: termina a las 02:30 de la primera pasada.
+    let start = time(1, 30);
+    let length = Duration::minutes(60);
+    let first_pass = utc(Madrid, 2024, 10, 27, 0, 15);
+    let second_pass = utc(Madrid, 2024, 10, 27, 1, 15);
+    assert_eq!(first_pass.format("%H:%M").to_string(), "02:15");
+    assert_eq!(second_pass.format("%H:%M").to_string(), "02:15");
+    assert!(in_daily_window(&first_pass, start, length));
+    assert!(!in_daily_window(&second_pass, start, length));
+}
+
+#[test]
+fn ventana_que_cruza_la_medianoche() {
+    let at = resolve_local(Madrid, date(2024, 6, 2), time(0, 10));
+    assert!(in_daily_window(&at, time(23, 50), Duration::minutes(30)));
+    assert!(!in_daily_window(&at, time(23, 50), Duration::minutes(15)));
+}
+
+#[test]
+fn guardia_nocturna_pertenece_al_dia_en_que_empieza() {
+    // Sábado 22:00 a domingo 06:00, la noche en que se atrasa la hora (9 h reales).
+    let start = time(22, 0);
+    let end = time(6, 0);
+    let morning = resolve_local(Madrid, date(2024, 10, 27), time(5, 30));
+    let day = daily_range_start(&morning, start, end).unwrap();
+    assert_eq!(day, date(2024, 10, 26));
+    assert_eq!(day.weekday(), Weekday::Sat);
+
+    let from = resolve_local(Madrid, date(2024, 10, 26), start);
+    let to = resolve_local(Madrid, date(2024, 10, 27), end);
+    assert_eq!(to - from, Duration::hours(9));
+    assert_eq!(daily_range_start(&to, start, end), None);
+}
+
+#[test]
+fn franja_diurna() {
+    let at = resolve_local(Madrid, date(2024, 3, 31), time(8, 0));
+    assert_eq!(
+        daily_range_start(&at, time(7, 0), time(15, 0)),
+        Some(date(2024, 3, 31))
+    );
+    assert_eq!(daily_range_start(&at, time(9, 0), time(15, 0)), None);
+}
+
+#[test]
+fn zona_horaria_configurada() {
+    assert_eq!(parse_timezone("Europe/Madrid"), Ok(Madrid));
+    assert!(parse_timezone("").is_ok());
+    assert!(parse_timezone("Marte/Olimpo").is_err());
+}
+
+#[test]
+fn feriados_puntuales_y_anuales() {
+    let mut calendar = HolidayCalendar::default();
+    calendar.add_entry("2024-05-21").unwrap();
+    calendar.add_entry("12-25").unwrap();
+    calendar.add_entry("02-29").unwrap();
+    assert!(calendar.contains(date(2024, 5, 21)));
+    assert!(!calendar.contains(date(2025, 5, 21)));
+    assert!(calendar.contains(date(2031, 12, 25)));
+    assert!(calendar.contains(date(2028, 2, 29)));
+    assert!(calendar.add_entry("25/12").is_err());
+    assert!(calendar.add_entry("13-01").is_err());
+}
+
+#[test]
+fn feriados_desde_ical() {
+    let ics = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VTIMEZONE\r\n\
+DTSTART:19700101T000000\r\n\
+END:VTIMEZONE\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:Navidad\r\n\
+DTSTART;VALUE=DATE:20241225\r\n\
+RRULE:FREQ=YEARLY\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:Fiestas Patrias\r\n\
+DTSTART;VALUE=DATE:20240918\r\n\
+DTEND;VALUE=DATE:2024\r\n\
+\x200921\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+DTSTART:20241101T090000Z\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+    let mut calendar = HolidayCalendar::default();
+    assert_eq!(calendar.add_ical(ics), Ok(3));
+    assert!(calendar.contains(date(2027, 12, 25)));
+    assert!(calendar.contains(date(2024, 9, 18)));
+    assert!(calendar.contains(date(2024, 9, 20)));
+    assert!(!calendar.contains(date(2024, 9, 21)));
+    assert!(calendar.contains(date(2024, 11, 1)));
+    assert!(!calendar.contains(date(1970, 1, 1)));
+}
+
+#[test]
+fn ical_sin_eventos_es_un_error() {
+    let mut calendar = HolidayCalendar::default();
+    assert!(calendar
+        .add_ical("BEGIN:VCALENDAR\nEND:VCALENDAR\n")
+        .is_err());
+    assert!(calendar
+        .add_ical("BEGIN:VEVENT\nDTSTART:2024-12-25\nEND:VEVENT\n")
+        .is_err());
+}
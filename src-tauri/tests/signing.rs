@@ -0,0 +1,195 @@
+//! Firmas de RPC de control y tokens de acción: `cargo test --test signing`.
+
+use nxt_hmi_lib::signing::{
+    rpc_signature, sign_action_token, to_hex, verify_action_token, verify_rpc, ActionClaims,
+    NonceCache,
+};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+const SECRET: &str = "s3cr3t";
+const NOW_MS: i64 = 1_700_000_000_000;
+const SKEW: Duration = Duration::from_secs(30);
+
+fn signed(nonce: &str, ts: i64) -> serde_json::Value {
+    let params = json!({ "inhibit": true, "minutes": 10 });
+    let signature = rpc_signature(SECRET, "setBuzzerInhibit", ts, nonce, &params).unwrap();
+    json!({
+        "method": "setBuzzerInhibit",
+        "params": params,
+        "ts": ts,
+        "nonce": nonce,
+        "signature": signature,
+    })
+}
+
+fn verify(raw: &serde_json::Value, nonces: &mut NonceCache, now: Instant) -> Result<(), String> {
+    verify_rpc("setBuzzerInhibit", raw, SECRET, SKEW, NOW_MS, nonces, now)
+}
+
+#[test]
+fn acepta_firma_valida_con_params_en_otro_orden() {
+    let mut nonces = NonceCache::new(16);
+    let mut raw = signed("n-1", NOW_MS);
+    raw["params"] = json!({ "minutes": 10, "inhibit": true });
+    assert_eq!(verify(&raw, &mut nonces, Instant::now()), Ok(()));
+    assert!(nonces.contains("n-1"));
+}
+
+#[test]
+fn rechaza_firma_invalida_sin_quemar_el_nonce() {
+    let mut nonces = NonceCache::new(16);
+    let mut raw = signed("n-2", NOW_MS);
+    raw["params"]["minutes"] = json!(600);
+    assert_eq!(
+        verify(&raw, &mut nonces, Instant::now()),
+        Err("Firma inválida".to_string())
+    );
+    raw["signature"] = json!("zz");
+    assert_eq!(
+        verify(&raw, &mut nonces, Instant::now()),
+        Err("Falta firma".to_string())
+    );
+    assert!(nonces.is_empty());
+    assert_eq!(
+        verify(&signed("n-2", NOW_MS), &mut nonces, Instant::now()),
+        Ok(())
+    );
+}
+
+#[test]
+fn rechaza_nonce_repetido_dentro_de_la_ventana() {
+    let mut nonces = NonceCache::new(16);
+    let start = Instant::now();
+    let raw = signed("n-3", NOW_MS);
+    assert_eq!(verify(&raw, &mut nonces, start), Ok(()));
+    assert_eq!(
+        verify(&raw, &mut nonces, start + Duration::from_secs(59)),
+        Err("nonce repetido: n-3".to_string())
+    );
+    // Pasada la ventana (dos veces el margen) el nonce se olvida; el ts ya lo rechazaría.
+    assert_eq!(
+        verify(&raw, &mut nonces, start + Duration::from_secs(61)),
+        Ok(())
+    );
+}
+
+#[test]
+fn rechaza_marcas_viejas_y_futuras() {
+    let mut nonces = NonceCache::new(16);
+    let now = Instant::now();
+    let stale = signed("n-4", NOW_MS - 31_000);
+    let future = signed("n-5", NOW_MS + 31_000);
+    assert!(verify(&stale, &mut nonces, now)
+        .unwrap_err()
+        .starts_with("ts fuera de margen"));
+    assert!(verify(&future, &mut nonces, now)
+        .unwrap_err()
+        .starts_with("ts fuera de margen"));
+    assert_eq!(
+        verify(&signed("n-6", NOW_MS - 29_000), &mut nonces, now),
+        Ok(())
+    );
+    assert_eq!(
+        verify(&json!({ "ts": NOW_MS }), &mut nonces, now),
+        Err("Falta nonce".to_string())
+    );
+}
+
+#[test]
+fn sin_secreto_no_exige_firma_pero_si_nonce() {
+    let mut nonces = NonceCache::new(16);
+    let raw = json!({ "params": {}, "ts": NOW_MS, "nonce": "n-7" });
+    let now = Instant::now();
+    assert_eq!(
+        verify_rpc("unlockPanel", &raw, "", SKEW, NOW_MS, &mut nonces, now),
+        Ok(())
+    );
+    assert!(verify_rpc("unlockPanel", &raw, "", SKEW, NOW_MS, &mut nonces, now).is_err());
+}
+
+#[test]
+fn con_el_tope_lleno_olvida_el_mas_viejo() {
+    let mut nonces = NonceCache::new(3);
+    let start = Instant::now();
+    for (index, nonce) in ["a", "b", "c", "d"].into_iter().enumerate() {
+        let at = start + Duration::from_millis(index as u64);
+        nonces.insert(nonce, at, SKEW * 2).unwrap();
+    }
+    assert_eq!(nonces.len(), 3);
+    assert!(!nonces.contains("a"));
+    assert!(nonces.contains("b") && nonces.contains("d"));
+    assert!(nonces
+        .insert("d", start + Duration::from_millis(5), SKEW * 2)
+        .is_err());
+}
+
+fn ack_claims() -> ActionClaims {
+    ActionClaims {
+        action: "ack".to_string(),
+        expires_ms: NOW_MS + 60_000,
+        panel: "panel-1".to_string(),
+        nonce: "00ff".to_string(),
+        target: "cam-01|temp".to_string(),
+    }
+}
+
+#[test]
+fn token_de_accion_valido() {
+    let token = sign_action_token(SECRET, &ack_claims()).unwrap();
+    assert_eq!(
+        verify_action_token(SECRET, &token, "panel-1", NOW_MS),
+        Ok(ack_claims())
+    );
+    assert_eq!(
+        verify_action_token("", &token, "panel-1", NOW_MS),
+        Err("Acciones remotas deshabilitadas".to_string())
+    );
+}
+
+#[test]
+fn token_de_accion_alterado_o_con_otra_accion() {
+    let token = sign_action_token(SECRET, &ack_claims()).unwrap();
+    let (_, signature) = token.split_once('.').unwrap();
+    let invalid = Err("Firma de token inválida".to_string());
+
+    // Misma firma sobre otro objetivo o sobre otra acción.
+    let mut other_target = ack_claims();
+    other_target.target = "cam-02|temp".to_string();
+    let mut snooze = ack_claims();
+    snooze.action = "snooze".to_string();
+    for claims in [other_target, snooze] {
+        let text = format!(
+            "{}|{}|{}|{}|{}",
+            claims.action, claims.expires_ms, claims.panel, claims.nonce, claims.target
+        );
+        let forged = format!("{}.{}", to_hex(text.as_bytes()), signature);
+        assert_eq!(
+            verify_action_token(SECRET, &forged, "panel-1", NOW_MS),
+            invalid
+        );
+    }
+
+    let other_secret = sign_action_token("otro", &ack_claims()).unwrap();
+    assert_eq!(
+        verify_action_token(SECRET, &other_secret, "panel-1", NOW_MS),
+        invalid
+    );
+    assert_eq!(
+        verify_action_token(SECRET, "sin-punto", "panel-1", NOW_MS),
+        Err("Token mal formado".to_string())
+    );
+}
+
+#[test]
+fn token_de_accion_vencido_o_de_otro_panel() {
+    let token = sign_action_token(SECRET, &ack_claims()).unwrap();
+    assert_eq!(
+        verify_action_token(SECRET, &token, "panel-1", NOW_MS + 60_001),
+        Err("Token vencido".to_string())
+    );
+    assert_eq!(
+        verify_action_token(SECRET, &token, "panel-2", NOW_MS),
+        Err("Token emitido para otro panel: panel-1".to_string())
+    );
+}
@@ -0,0 +1,71 @@
+//! Site packs firmados: `cargo test --features fuzzing --test site_pack`.
+
+use nxt_hmi_lib::fuzzing::site_pack;
+use nxt_hmi_lib::signing::sign_json;
+use serde_json::{json, Value};
+
+const SECRET: &str = "clave-de-la-cadena";
+
+fn pack_file(pack: Value, signature: Option<String>) -> String {
+    let mut file = json!({
+        "format": "nxt-hmi-site-pack",
+        "version": 1,
+        "createdAt": "2026-10-16T12:00:00-03:00",
+        "sourcePanel": "tienda-01",
+        "pack": pack,
+    });
+    if let Some(signature) = signature {
+        file["signature"] = json!(signature);
+    }
+    file.to_string()
+}
+
+fn pack() -> Value {
+    json!({
+        "CRITICAL_TEMPERATURE_HIGH": 8.0,
+        "DEFROST_SCHEDULES": [],
+    })
+}
+
+#[test]
+fn acepta_pack_firmado() {
+    let signature = sign_json(SECRET, &pack());
+    assert_eq!(
+        site_pack(&pack_file(pack(), signature), SECRET),
+        Ok("tienda-01".to_string())
+    );
+}
+
+#[test]
+fn rechaza_pack_alterado() {
+    let signature = sign_json(SECRET, &pack());
+    let mut tampered = pack();
+    tampered["CRITICAL_TEMPERATURE_HIGH"] = json!(30.0);
+    assert_eq!(
+        site_pack(&pack_file(tampered, signature), SECRET),
+        Err("Site pack rechazado: Firma inválida".to_string())
+    );
+}
+
+#[test]
+fn rechaza_pack_sin_firma_o_con_otra_clave() {
+    assert!(site_pack(&pack_file(pack(), None), SECRET)
+        .unwrap_err()
+        .starts_with("Site pack inválido"));
+    assert_eq!(
+        site_pack(&pack_file(pack(), Some(String::new())), SECRET),
+        Err("Site pack rechazado: Firma inválida".to_string())
+    );
+    assert_eq!(
+        site_pack(&pack_file(pack(), Some("no-es-hex".to_string())), SECRET),
+        Err("Site pack rechazado: Firma mal formada".to_string())
+    );
+    assert_eq!(
+        site_pack(&pack_file(pack(), sign_json("otra", &pack())), SECRET),
+        Err("Site pack rechazado: Firma inválida".to_string())
+    );
+    assert_eq!(
+        site_pack(&pack_file(pack(), sign_json(SECRET, &pack())), ""),
+        Err("SITE_PACK_SECRET no configurado".to_string())
+    );
+}
@@ -0,0 +1,73 @@
+//! Orden de arranque: `cargo test --test startup`.
+
+use nxt_hmi_lib::startup::{order, run, Step, StepState};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn ok(name: &'static str) -> Step {
+    Step::new(name, || Ok(()))
+}
+
+#[test]
+fn ordena_por_dependencias() {
+    let steps = [
+        ok("mqtt").after(&["network"]),
+        ok("db"),
+        ok("network"),
+        ok("bridge").after(&["mqtt", "db"]),
+    ];
+    let names: Vec<&str> = order(&steps)
+        .unwrap()
+        .into_iter()
+        .map(|index| steps[index].name)
+        .collect();
+    assert_eq!(names, ["db", "network", "mqtt", "bridge"]);
+    assert!(order(&[ok("a").after(&["b"]), ok("b").after(&["a"])]).is_err());
+    assert!(order(&[ok("a").after(&["x"])]).is_err());
+    assert!(order(&[ok("a"), ok("a")]).is_err());
+}
+
+#[test]
+fn arranque_parcial_salta_dependientes() {
+    let steps = [
+        Step::new("db", || Err("disco de solo lectura".to_string())),
+        ok("maintenance").after(&["db"]),
+        ok("network"),
+    ];
+    let mut seen = Vec::new();
+    let reports = run(&steps, |report| seen.push(report.name)).unwrap();
+    assert_eq!(seen, ["db", "maintenance", "network"]);
+    assert_eq!(
+        reports[0].state,
+        StepState::Failed("disco de solo lectura".to_string())
+    );
+    assert_eq!(reports[1].state, StepState::Skipped("db"));
+    assert_eq!(reports[1].attempts, 0);
+    assert_eq!(reports[2].state, StepState::Started);
+}
+
+#[test]
+fn reintenta_y_respeta_tiempo_maximo() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let counter = calls.clone();
+    let flaky = Step::new("db", move || {
+        if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+            Err("ocupada".to_string())
+        } else {
+            Ok(())
+        }
+    })
+    .retries(3, Duration::from_millis(1));
+    let slow = Step::new("gpio", || {
+        std::thread::sleep(Duration::from_millis(500));
+        Ok(())
+    })
+    .timeout(Duration::from_millis(20))
+    .retries(3, Duration::ZERO);
+    let reports = run(&[flaky, slow], |_| {}).unwrap();
+    assert_eq!(reports[0].state, StepState::Started);
+    assert_eq!(reports[0].attempts, 3);
+    assert_eq!(reports[1].state, StepState::TimedOut);
+    assert_eq!(reports[1].attempts, 1, "un paso colgado no se relanza");
+}